@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use exposrog::{fast_sin, generate_wave_table_with, SineMode, WaveShape};
+use std::f32::consts::TAU;
+
+const TABLE_SIZE: usize = 1 << 20;
+
+/// A million-point sine table, filled by `f32::sin` and by `fast_sin`.
+fn sine_table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("1M-point sine table");
+    group.throughput(Throughput::Elements(TABLE_SIZE as u64));
+    let phases: Vec<f32> = (0..TABLE_SIZE).map(|i| TAU * i as f32 / TABLE_SIZE as f32).collect();
+    let mut buf = vec![0.0; TABLE_SIZE];
+    group.bench_function("f32::sin", |b| {
+        b.iter(|| {
+            for (sample, &phase) in buf.iter_mut().zip(&phases) {
+                *sample = phase.sin();
+            }
+            black_box(&buf);
+        });
+    });
+    group.bench_function("fast_sin", |b| {
+        b.iter(|| {
+            for (sample, &phase) in buf.iter_mut().zip(&phases) {
+                *sample = fast_sin(phase);
+            }
+            black_box(&buf);
+        });
+    });
+    for (name, mode) in [("table Exact", SineMode::Exact), ("table Fast", SineMode::Fast)] {
+        group.bench_function(name, |b| {
+            b.iter(|| generate_wave_table_with(WaveShape::Sine, TABLE_SIZE, black_box(mode)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sine_table);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use exposrog::{mix_voices_simd, Mixer};
+use rodio::source::Zero;
+
+const FRAMES: usize = 44_100;
+
+/// A second of eight-voice lanes, as `PolyphonicEngine` gathers them.
+fn lanes() -> (Vec<[f32; 8]>, Vec<[f32; 8]>) {
+    let samples = (0..FRAMES).map(|i| std::array::from_fn(|v| ((i * 8 + v) as f32 * 0.01).sin())).collect();
+    let gains = (0..FRAMES).map(|i| std::array::from_fn(|v| ((i + v) % 100) as f32 / 100.0)).collect();
+    (samples, gains)
+}
+
+/// A second of eight voices mixed through `mix_voices_simd` against a plain loop.
+fn mix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mix eight voices, one second");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    let (samples, gains) = lanes();
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            let mut total = 0.0;
+            for (samples, gains) in samples.iter().zip(&gains) {
+                let samples = black_box(samples);
+                total += samples.iter().zip(gains).map(|(sample, gain)| sample * gain).sum::<f32>();
+            }
+            black_box(total)
+        });
+    });
+    group.bench_function("mix_voices_simd", |b| {
+        b.iter(|| {
+            let mut total = 0.0;
+            for (samples, gains) in samples.iter().zip(&gains) {
+                total += mix_voices_simd(black_box(samples), gains);
+            }
+            black_box(total)
+        });
+    });
+    group.finish();
+}
+
+/// A second of `Mixer<8>` output from eight silent sources, so the mixing itself is
+/// most of what's timed.
+fn mixer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Mixer<8>, one second");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    let mut mixer = Mixer::<8>::new(44100);
+    for voice in 0..8 {
+        mixer.add_source(Zero::<f32>::new(1, 44100), 0.5, voice as f32 / 4.0 - 1.0);
+    }
+    group.bench_function("next", |b| {
+        b.iter(|| {
+            for _ in 0..FRAMES * 2 {
+                black_box(mixer.next());
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mix, mixer);
+criterion_main!(benches);
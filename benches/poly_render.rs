@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use exposrog::{generate_wave_table, PolyphonicEngine, WaveShape};
+
+const SAMPLES: usize = 44_100;
+
+/// Eight voices holding a chord.
+fn engine() -> PolyphonicEngine {
+    let engine = PolyphonicEngine::new(44_100, generate_wave_table(WaveShape::Sawtooth, 2048), 8);
+    let pool = engine.get_voice_pool_control();
+    for i in 0..8 {
+        pool.lock().unwrap().note_on(220.0 * 2.0_f32.powf(i as f32 * 3.0 / 12.0));
+    }
+    engine
+}
+
+/// A second of eight voices through `get_sample` against the same second in blocks.
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("eight voices, one second");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("get_sample", |b| {
+        let mut engine = engine();
+        let mut buf = vec![0.0; SAMPLES];
+        b.iter(|| {
+            for sample in buf.iter_mut() {
+                *sample = engine.get_sample();
+            }
+            black_box(&buf);
+        });
+    });
+    for block_size in [64, 128, 512] {
+        group.bench_with_input(BenchmarkId::new("render_block", block_size), &block_size, |b, &block_size| {
+            let mut engine = engine();
+            let mut buf = vec![0.0; SAMPLES];
+            b.iter(|| {
+                for block in buf.chunks_mut(block_size) {
+                    engine.render_block(block);
+                }
+                black_box(&buf);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
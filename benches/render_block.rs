@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use exposrog::{generate_wave_table, WaveShape, WaveTableOscillator};
+
+const SAMPLES: usize = 44_100;
+
+fn oscillator() -> WaveTableOscillator {
+    let mut oscillator = WaveTableOscillator::new(44_100, generate_wave_table(WaveShape::Sawtooth, 2048));
+    oscillator.set_frequency_direct(440.0);
+    oscillator
+}
+
+/// A second of audio through `next` against the same second in blocks.
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("one second at 44.1 kHz");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("next", |b| {
+        let mut oscillator = oscillator();
+        let mut buf = vec![0.0; SAMPLES];
+        b.iter(|| {
+            for sample in buf.iter_mut() {
+                *sample = oscillator.next().unwrap_or(0.0);
+            }
+            black_box(&buf);
+        });
+    });
+    for block_size in [64, 256, 512] {
+        group.bench_with_input(BenchmarkId::new("render_block", block_size), &block_size, |b, &block_size| {
+            let mut oscillator = oscillator();
+            let mut buf = vec![0.0; SAMPLES];
+            b.iter(|| {
+                for block in buf.chunks_mut(block_size) {
+                    oscillator.render_block(block);
+                }
+                black_box(&buf);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The most harmonics an [`AdditiveSynthesizer`] sums.
+pub const MAX_PARTIALS: usize = 32;
+
+/// Harmonic spectra for an [`AdditiveSynthesizer`], after the instruments they're named for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AdditivePreset {
+    /// A drawbar organ with the 8', 4', 2 2/3', 2' and 1' bars out: harmonics 1, 2, 3,
+    /// 4 and 8, as on a Hammond.
+    #[default]
+    Organ,
+    /// A trumpet's spectrum, strongest around the third harmonic and falling off slowly
+    /// above it, which is what gives brass its bright edge.
+    Brass,
+    /// A clarinet in its low register: a closed pipe, so nearly all odd harmonics.
+    Clarinet,
+}
+
+impl AdditivePreset {
+    pub const ALL: [AdditivePreset; 3] = [AdditivePreset::Organ, AdditivePreset::Brass, AdditivePreset::Clarinet];
+
+    /// Amplitudes by harmonic, the fundamental first.
+    pub fn partials(self) -> Vec<f32> {
+        match self {
+            // Drawbars at 8, 8, 6, 6 and 6, each step down being 3 dB quieter
+            AdditivePreset::Organ => vec![1.0, 1.0, 0.5, 0.5, 0.0, 0.0, 0.0, 0.5],
+            AdditivePreset::Brass => {
+                vec![0.55, 0.8, 1.0, 0.9, 0.75, 0.58, 0.42, 0.3, 0.2, 0.13, 0.08, 0.05, 0.03]
+            }
+            AdditivePreset::Clarinet => {
+                vec![1.0, 0.02, 0.75, 0.02, 0.5, 0.02, 0.14, 0.01, 0.5, 0.01, 0.12, 0.01, 0.17, 0.0, 0.04]
+            }
+        }
+    }
+}
+
+impl fmt::Display for AdditivePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AdditivePreset::Organ => "organ",
+            AdditivePreset::Brass => "brass",
+            AdditivePreset::Clarinet => "clarinet",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Sums sine partials at whole multiples of the note's frequency, each with its own
+/// amplitude and phase.
+///
+/// Unlike a wave table, the amplitudes can be changed one at a time while it plays,
+/// through [`get_partials_control`](Self::get_partials_control), with nothing to
+/// regenerate. Partials above Nyquist are left out, and the sum is scaled by the
+/// total amplitude so it never clips.
+pub struct AdditiveSynthesizer {
+    sample_rate: u32,
+    frequency: Arc<Mutex<f32>>,
+    /// Amplitude by harmonic: `partials[0]` is the fundamental.
+    partials: Arc<Mutex<Vec<f32>>>,
+    phases: [f32; MAX_PARTIALS],
+}
+
+impl AdditiveSynthesizer {
+    pub fn new(sample_rate: u32, frequency: Arc<Mutex<f32>>, preset: AdditivePreset) -> AdditiveSynthesizer {
+        AdditiveSynthesizer {
+            sample_rate,
+            frequency,
+            partials: Arc::new(Mutex::new(preset.partials())),
+            phases: [0.0; MAX_PARTIALS],
+        }
+    }
+
+    /// Sets the amplitude of `harmonic`, counting the fundamental as 1. Harmonics past
+    /// [`MAX_PARTIALS`] are ignored.
+    pub fn set_partial(&mut self, harmonic: usize, amplitude: f32) {
+        if !(1..=MAX_PARTIALS).contains(&harmonic) {
+            return;
+        }
+        if let Ok(mut partials) = self.partials.lock() {
+            if partials.len() < harmonic {
+                partials.resize(harmonic, 0.0);
+            }
+            partials[harmonic - 1] = amplitude;
+        }
+    }
+
+    pub fn set_preset(&mut self, preset: AdditivePreset) {
+        if let Ok(mut partials) = self.partials.lock() {
+            *partials = preset.partials();
+        }
+    }
+
+    pub fn get_partials_control(&self) -> Arc<Mutex<Vec<f32>>> {
+        self.partials.clone()
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let freq = self.frequency.lock().map_or(0.0, |f| *f);
+        if freq <= 0.0 {
+            return 0.0;
+        }
+        let Ok(partials) = self.partials.lock() else {
+            return 0.0;
+        };
+        let step = freq / self.sample_rate as f32;
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        for (i, (&amplitude, phase)) in partials.iter().zip(self.phases.iter_mut()).enumerate() {
+            let harmonic = (i + 1) as f32;
+            if harmonic * freq >= nyquist {
+                break;
+            }
+            sum += amplitude * (*phase * TAU).sin();
+            total_amplitude += amplitude.abs();
+            *phase = (*phase + step * harmonic).fract();
+        }
+        // About as loud as the FM voice
+        sum / total_amplitude.max(1.0) * 0.3
+    }
+}
+
+impl Source for AdditiveSynthesizer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for AdditiveSynthesizer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
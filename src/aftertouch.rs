@@ -0,0 +1,72 @@
+use crate::pan::pan_indicator;
+use crate::voice::VoicePool;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Vibrato depth in semitones that one notch of the mouse wheel adds or takes away.
+pub const SCROLL_DEPTH_STEP: f32 = 0.05;
+/// The deepest vibrato aftertouch can reach, in semitones.
+pub const MAX_VIBRATO_DEPTH: f32 = 1.0;
+
+/// Simulated polyphonic aftertouch: the mouse wheel deepens or eases the vibrato of
+/// one held voice, the way pressing harder would on a poly-pressure keyboard.
+///
+/// Each voice's depth is shared as `f32` semitones in an `AtomicU32`, from
+/// [`PolyphonicEngine::get_lfo_depth_controls`](crate::PolyphonicEngine::get_lfo_depth_controls).
+pub struct PolyAftertouch {
+    depth_controls: Vec<Arc<AtomicU32>>,
+}
+
+impl PolyAftertouch {
+    pub fn new(depth_controls: Vec<Arc<AtomicU32>>) -> PolyAftertouch {
+        PolyAftertouch { depth_controls }
+    }
+
+    /// Starts `voice_index` over with no vibrato, for a fresh key press.
+    pub fn note_on(&self, voice_index: usize) {
+        if let Some(control) = self.depth_controls.get(voice_index) {
+            control.store(0.0_f32.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Applies `scroll_delta` wheel notches (positive is up) to `voice_index`'s depth
+    /// and returns the change actually made, which is smaller once the depth reaches 0
+    /// or [`MAX_VIBRATO_DEPTH`].
+    pub fn scroll(&self, scroll_delta: i32, voice_index: usize) -> f32 {
+        let Some(control) = self.depth_controls.get(voice_index) else {
+            return 0.0;
+        };
+        let old = f32::from_bits(control.load(Ordering::Relaxed));
+        let new = (old + scroll_delta as f32 * SCROLL_DEPTH_STEP).clamp(0.0, MAX_VIBRATO_DEPTH);
+        control.store(new.to_bits(), Ordering::Relaxed);
+        new - old
+    }
+
+    pub fn depth(&self, voice_index: usize) -> f32 {
+        self.depth_controls
+            .get(voice_index)
+            .map_or(0.0, |control| f32::from_bits(control.load(Ordering::Relaxed)))
+    }
+
+    /// One line per voice with its note, envelope phase, vibrato depth and pan.
+    pub fn render_voice_list(&self, pool: &VoicePool) -> Vec<String> {
+        pool.slots()
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                if slot.is_idle() {
+                    format!("voice {:>2}  idle", index + 1)
+                } else {
+                    format!(
+                        "voice {:>2}  {:>7.1} Hz  {:<7}  vibrato {:.2} st  {}",
+                        index + 1,
+                        slot.frequency,
+                        format!("{:?}", slot.phase),
+                        self.depth(index),
+                        pan_indicator(slot.pan, 9)
+                    )
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,165 @@
+use crossterm::event::KeyCode;
+use rand::Rng;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+/// The order an [`Arpeggiator`] plays its held notes in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArpPattern {
+    /// Lowest to highest, then around again.
+    #[default]
+    Up,
+    Down,
+    /// Up and back down, without repeating the top and bottom notes.
+    UpDown,
+    /// Any held note, never the same one twice running if there's a choice.
+    Random,
+}
+
+impl ArpPattern {
+    pub const ALL: [ArpPattern; 4] = [ArpPattern::Up, ArpPattern::Down, ArpPattern::UpDown, ArpPattern::Random];
+
+    pub fn next(self) -> ArpPattern {
+        let index = ArpPattern::ALL.iter().position(|pattern| *pattern == self).unwrap_or(0);
+        ArpPattern::ALL[(index + 1) % ArpPattern::ALL.len()]
+    }
+}
+
+impl fmt::Display for ArpPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ArpPattern::Up => "up",
+            ArpPattern::Down => "down",
+            ArpPattern::UpDown => "up-down",
+            ArpPattern::Random => "random",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Plays the keys held down one at a time in an [`ArpPattern`], `steps_per_beat` notes
+/// to each beat at `bpm`.
+///
+/// Time comes in through [`tick`](Self::tick), which says when the next note is due.
+/// The first note of a chord plays at once, and the beat runs on from there. With
+/// `latch` on, released notes stay in the pattern until a key is pressed with none
+/// held, which starts a new chord.
+pub struct Arpeggiator {
+    /// The held notes in pitch order, keyed by their frequency's bits: positive floats
+    /// sort the same way as their bit patterns.
+    notes: BTreeMap<u32, KeyCode>,
+    /// Keys physically down, which latch mode keeps apart from the notes playing.
+    held_keys: HashSet<KeyCode>,
+    pub pattern: ArpPattern,
+    pub bpm: f32,
+    pub steps_per_beat: u8,
+    latch: bool,
+    /// How far through the current step, in steps; a note is due at 1.
+    phase: f32,
+    step: usize,
+    last_note: Option<u32>,
+}
+
+impl Arpeggiator {
+    pub fn new(bpm: f32, steps_per_beat: u8) -> Arpeggiator {
+        Arpeggiator {
+            notes: BTreeMap::new(),
+            held_keys: HashSet::new(),
+            pattern: ArpPattern::default(),
+            bpm,
+            steps_per_beat: steps_per_beat.max(1),
+            latch: false,
+            phase: 1.0,
+            step: 0,
+            last_note: None,
+        }
+    }
+
+    pub fn latch(&self) -> bool {
+        self.latch
+    }
+
+    /// Turning latch off lets go of the notes whose keys are already up.
+    pub fn set_latch(&mut self, latch: bool) {
+        self.latch = latch;
+        if !latch {
+            self.notes.retain(|_, key| self.held_keys.contains(key));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Adds `key`'s note at `frequency`; the first of a chord plays on the next tick.
+    pub fn press(&mut self, key: KeyCode, frequency: f32) {
+        // Nothing held means a new chord, dropping any latched one
+        if self.held_keys.is_empty() {
+            self.notes.clear();
+            self.phase = 1.0;
+            self.step = 0;
+        }
+        self.held_keys.insert(key);
+        self.notes.retain(|_, held| *held != key);
+        self.notes.insert(frequency.abs().to_bits(), key);
+    }
+
+    /// Takes `key`'s note out of the pattern, unless latched.
+    pub fn release(&mut self, key: KeyCode) {
+        self.held_keys.remove(&key);
+        if !self.latch {
+            self.notes.retain(|_, held| *held != key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+        self.held_keys.clear();
+        self.last_note = None;
+    }
+
+    /// Moves the beat on by `elapsed`, returning the frequency of the note that falls
+    /// due in it, if one does.
+    pub fn tick(&mut self, elapsed: Duration) -> Option<f32> {
+        if self.notes.is_empty() {
+            return None;
+        }
+        let steps_per_sec = self.bpm.max(1.0) / 60.0 * self.steps_per_beat as f32;
+        self.phase += elapsed.as_secs_f32() * steps_per_sec;
+        if self.phase < 1.0 {
+            return None;
+        }
+        // A stall longer than a step skips the notes it missed rather than rushing them
+        self.phase = self.phase.fract();
+        let note = self.next_note();
+        self.last_note = Some(note);
+        Some(f32::from_bits(note))
+    }
+
+    fn next_note(&mut self) -> u32 {
+        let notes: Vec<u32> = self.notes.keys().copied().collect();
+        let count = notes.len();
+        let step = self.step;
+        self.step += 1;
+        let index = match self.pattern {
+            ArpPattern::Up => step % count,
+            ArpPattern::Down => count - 1 - step % count,
+            ArpPattern::UpDown if count < 2 => 0,
+            ArpPattern::UpDown => {
+                let position = step % (2 * count - 2);
+                if position < count {
+                    position
+                } else {
+                    2 * count - 2 - position
+                }
+            }
+            ArpPattern::Random => {
+                let fresh: Vec<u32> = notes.iter().copied().filter(|note| Some(*note) != self.last_note).collect();
+                let choices = if fresh.is_empty() { &notes } else { &fresh };
+                return choices[rand::thread_rng().gen_range(0..choices.len())];
+            }
+        };
+        notes[index]
+    }
+}
@@ -0,0 +1,17 @@
+use rodio::Source;
+
+/// A source that can also render many samples in one call, for callers that work
+/// in blocks, such as an offline render or a buffer-filling audio thread.
+///
+/// The block path and the per-sample `Iterator` path advance the same state, so the two
+/// can be mixed freely. By default a block is just that many calls to `next`. A source
+/// whose per-sample cost is mostly fixed, like locking its controls, overrides
+/// [`render_block`](Self::render_block) to pay that cost once per block.
+pub trait BlockSource: Source<Item = f32> {
+    /// Fills `buf` with the next samples, with silence for any past the source's end.
+    fn render_block(&mut self, buf: &mut [f32]) {
+        for sample in buf {
+            *sample = self.next().unwrap_or(0.0);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crossbeam::queue::SegQueue;
+use rodio::Source;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Renders a source on its own thread ahead of the audio callback.
+///
+/// The worker keeps the queue topped up to twice `callback_frames` frames, enough to
+/// ride out scheduling jitter. If the callback finds the queue empty it plays silence
+/// and counts an underrun rather than blocking. Underruns are whole frames of
+/// silence, so interleaved channels stay in step.
+pub struct BufferedSource {
+    buffer: Arc<SegQueue<f32>>,
+    join: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    buffer_underrun_count: Arc<AtomicUsize>,
+    channels: u16,
+    sample_rate: u32,
+    frame_pos: u16,
+    in_underrun: bool,
+}
+
+impl BufferedSource {
+    pub fn new<S>(mut source: S, callback_frames: usize) -> BufferedSource
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let capacity = 2 * callback_frames.max(1) * channels as usize;
+
+        let buffer = Arc::new(SegQueue::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let join = {
+            let (buffer, stop, finished) = (buffer.clone(), stop.clone(), finished.clone());
+            // Nap for about an eighth of the buffer when it's full
+            let nap = Duration::from_secs_f32(capacity as f32 / (8.0 * sample_rate as f32 * channels as f32));
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if buffer.len() >= capacity {
+                        thread::sleep(nap);
+                        continue;
+                    }
+                    match source.next() {
+                        Some(sample) => buffer.push(sample),
+                        None => break,
+                    }
+                }
+                finished.store(true, Ordering::Release);
+            })
+        };
+
+        BufferedSource {
+            buffer,
+            join: Some(join),
+            stop,
+            finished,
+            buffer_underrun_count: Arc::new(AtomicUsize::new(0)),
+            channels,
+            sample_rate,
+            frame_pos: 0,
+            in_underrun: false,
+        }
+    }
+
+    pub fn get_underrun_counter(&self) -> Arc<AtomicUsize> {
+        self.buffer_underrun_count.clone()
+    }
+}
+
+impl Drop for BufferedSource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(join) = self.join.take() {
+            let _ = join.join();
+        }
+    }
+}
+
+impl Source for BufferedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for BufferedSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Only the worker pushes, so a whole frame queued at a frame boundary can
+        // be popped without running dry halfway
+        if self.frame_pos == 0 {
+            let finished = self.finished.load(Ordering::Acquire);
+            self.in_underrun = self.buffer.len() < self.channels as usize;
+            if self.in_underrun {
+                if finished {
+                    return None;
+                }
+                self.buffer_underrun_count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.frame_pos = (self.frame_pos + 1) % self.channels.max(1);
+
+        if self.in_underrun {
+            Some(0.0)
+        } else {
+            self.buffer.pop()
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::delay::FeedbackDelay;
+use crate::pan::constant_power_gains;
+use crate::reverb::Reverb;
+use synth_core::StateVariableFilter;
+
+/// Middle C: with a split, notes below it go to channel 0 and the rest to channel 1.
+pub const DEFAULT_CHANNEL_SPLIT_NOTE: u8 = 60;
+
+/// A mono insert effect in a [`VoiceChannel`], processing one sample at a time.
+pub trait Effect: Send {
+    fn process(&mut self, input: f32) -> f32;
+    /// Drops any state still sounding, such as echoes or a reverb tail.
+    fn reset(&mut self);
+}
+
+/// The delay line's output already carries the dry signal.
+impl Effect for FeedbackDelay {
+    fn process(&mut self, input: f32) -> f32 {
+        FeedbackDelay::process(self, input)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// Mixed with the dry signal by the reverb's `wet`.
+impl Effect for Reverb {
+    fn process(&mut self, input: f32) -> f32 {
+        let wet = Reverb::process(self, input);
+        input * (1.0 - self.wet) + wet * self.wet
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// The low-pass response.
+impl Effect for StateVariableFilter {
+    fn process(&mut self, input: f32) -> f32 {
+        StateVariableFilter::process(self, input).low
+    }
+
+    fn reset(&mut self) {
+        StateVariableFilter::reset(self);
+    }
+}
+
+/// A group of voices sharing an effects chain, e.g. a bass hand with reverb and a
+/// melody hand with delay. Effects run in order, then the channel is scaled by
+/// `volume` and placed in the stereo field by `pan` (-1.0 left to 1.0 right).
+pub struct VoiceChannel {
+    pub effects: Vec<Box<dyn Effect>>,
+    pub pan: f32,
+    pub volume: f32,
+}
+
+impl VoiceChannel {
+    pub fn new(effects: Vec<Box<dyn Effect>>) -> VoiceChannel {
+        VoiceChannel {
+            effects,
+            pan: 0.0,
+            volume: 1.0,
+        }
+    }
+
+    /// A medium room, leaning left.
+    pub fn reverb(sample_rate: u32) -> VoiceChannel {
+        VoiceChannel {
+            pan: -0.3,
+            ..VoiceChannel::new(vec![Box::new(Reverb::new(sample_rate, 0.6, 0.35))])
+        }
+    }
+
+    /// Dotted-eighth echoes at 120 BPM, leaning right.
+    pub fn delay(sample_rate: u32) -> VoiceChannel {
+        let delay_samples = (0.375 * sample_rate as f32) as usize;
+        VoiceChannel {
+            pan: 0.3,
+            ..VoiceChannel::new(vec![Box::new(FeedbackDelay::new(delay_samples, 0.4))])
+        }
+    }
+
+    /// Runs `input` through the chain, returning `(left, right)`.
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let output = self.effects.iter_mut().fold(input, |sample, effect| effect.process(sample)) * self.volume;
+        let (left, right) = constant_power_gains(self.pan);
+        (output * left, output * right)
+    }
+
+    pub fn reset(&mut self) {
+        for effect in &mut self.effects {
+            effect.reset();
+        }
+    }
+}
+
+/// Which [`VoiceChannel`] each voice of a
+/// [`PolyphonicEngine`](crate::PolyphonicEngine) plays through.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelRouting {
+    /// When set, overrides `voice_channels`: voices playing below this MIDI note use
+    /// channel 0, the others channel 1.
+    pub split_note: Option<u8>,
+    /// Channel by voice index; voices past the end use channel 0.
+    pub voice_channels: Vec<usize>,
+}
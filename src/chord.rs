@@ -0,0 +1,126 @@
+use crate::tuning::{TuningSystem, NOTE_NAMES};
+use std::fmt;
+
+/// A pitch class, 0 = C up to 11 = B, named without an octave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NoteName(pub u8);
+
+impl fmt::Display for NoteName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(NOTE_NAMES[self.0 as usize % 12])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Dominant7,
+    Major7,
+    Minor7,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+}
+
+impl ChordQuality {
+    /// Sevenths first, so a four-note chord isn't read as the triad inside it.
+    pub const ALL: [ChordQuality; 9] = [
+        ChordQuality::Dominant7,
+        ChordQuality::Major7,
+        ChordQuality::Minor7,
+        ChordQuality::Major,
+        ChordQuality::Minor,
+        ChordQuality::Diminished,
+        ChordQuality::Augmented,
+        ChordQuality::Sus2,
+        ChordQuality::Sus4,
+    ];
+
+    /// Chord tones in semitones above the root, in stacking order.
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10],
+            ChordQuality::Major7 => &[0, 4, 7, 11],
+            ChordQuality::Minor7 => &[0, 3, 7, 10],
+            ChordQuality::Diminished => &[0, 3, 6],
+            ChordQuality::Augmented => &[0, 4, 8],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+        }
+    }
+
+    /// The suffix in lead-sheet symbols: "" for major, "m7" for minor seventh.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+        }
+    }
+
+    fn pitch_classes(self, root: u8) -> u16 {
+        self.intervals().iter().fold(0, |mask, interval| mask | 1 << ((root + interval) % 12))
+    }
+}
+
+/// A recognised chord. `inversion` counts which chord tone is in the bass: 0 for
+/// the root, 1 for the third (or the suspended note), 2 for the fifth, 3 for the
+/// seventh.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChordName {
+    pub root: NoteName,
+    pub quality: ChordQuality,
+    pub inversion: u8,
+}
+
+/// Lead-sheet style, with inversions as slash chords: `Cmaj7`, `Am/C`.
+impl fmt::Display for ChordName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.root, self.quality.symbol())?;
+        if self.inversion > 0 {
+            let bass = self.root.0 + self.quality.intervals()[self.inversion as usize];
+            write!(f, "/{}", NoteName(bass % 12))?;
+        }
+        Ok(())
+    }
+}
+
+/// Names the chord `freqs` make together, in equal temperament at A440, or `None`
+/// for fewer than three pitch classes or a set that isn't a known chord. Octave
+/// doublings don't matter; the lowest note decides the inversion.
+///
+/// Symmetric chords have more than one spelling: the one rooted on the bass note
+/// wins, so C-E-G# reads as Caug and E-G#-C as Eaug.
+pub fn detect_chord(freqs: &[f32]) -> Option<ChordName> {
+    let tuning = TuningSystem::default();
+    let notes: Vec<u8> = freqs.iter().filter(|freq| **freq > 0.0).map(|&freq| tuning.nearest_note(freq)).collect();
+    let bass = *notes.iter().min()? % 12;
+    let pitch_classes = notes.iter().fold(0u16, |mask, note| mask | 1 << (note % 12));
+    if pitch_classes.count_ones() < 3 {
+        return None;
+    }
+
+    let roots = std::iter::once(bass).chain((0..12).filter(|&root| root != bass));
+    roots
+        .flat_map(|root| ChordQuality::ALL.into_iter().map(move |quality| (root, quality)))
+        .find(|&(root, quality)| quality.pitch_classes(root) == pitch_classes)
+        .map(|(root, quality)| ChordName {
+            root: NoteName(root),
+            quality,
+            inversion: quality
+                .intervals()
+                .iter()
+                .position(|interval| (root + interval) % 12 == bass)
+                .unwrap_or(0) as u8,
+        })
+}
@@ -0,0 +1,228 @@
+use crate::pan::constant_power_gains;
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use synth_core::{Lfo, LfoPolarity};
+
+/// Shortest delay a voice reads at, so even an unmodulated voice doesn't comb
+/// against the dry signal.
+const BASE_DELAY_MS: f32 = 7.0;
+/// Length of the sweeping window a detuned voice reads through, as in the harmonizer.
+const DETUNE_WINDOW_SECS: f32 = 0.04;
+
+/// One delayed copy in a [`Chorus`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChorusVoice {
+    /// How fast the delay sweeps.
+    pub rate_hz: f32,
+    /// How far the delay sweeps, from 7 ms up to 7 ms plus this.
+    pub depth_ms: f32,
+    /// A constant pitch offset on top of the sweep's wobble.
+    pub detune_cents: f32,
+    /// -1.0 (left) to 1.0 (right).
+    pub pan: f32,
+}
+
+/// The voices and wet mix a [`Chorus`] is built from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChorusPreset {
+    pub voices: Vec<ChorusVoice>,
+    pub wet: f32,
+}
+
+impl Default for ChorusPreset {
+    /// A single centred voice, the plain shimmer.
+    fn default() -> Self {
+        ChorusPreset {
+            voices: vec![ChorusVoice {
+                rate_hz: 0.8,
+                depth_ms: 10.0,
+                detune_cents: 0.0,
+                pan: 0.0,
+            }],
+            wet: 0.5,
+        }
+    }
+}
+
+impl ChorusPreset {
+    /// Seven voices spread evenly from 0.3 to 1.2 Hz, 5 to 25 ms of depth and -15 to
+    /// +15 cents, panned alternately left and right, at a wet mix of 0.7.
+    pub fn thick() -> ChorusPreset {
+        let count = 7;
+        let voices = (0..count)
+            .map(|i| {
+                let t = i as f32 / (count - 1) as f32;
+                ChorusVoice {
+                    rate_hz: 0.3 + 0.9 * t,
+                    depth_ms: 5.0 + 20.0 * t,
+                    detune_cents: -15.0 + 30.0 * t,
+                    pan: if i % 2 == 0 { -0.8 } else { 0.8 },
+                }
+            })
+            .collect();
+        ChorusPreset { voices, wet: 0.7 }
+    }
+}
+
+/// The "super chorus" wall of sound: [`ChorusPreset::thick`] built at `sample_rate`.
+/// It costs seven times the CPU of the single-voice chorus.
+pub fn thick_chorus_preset(sample_rate: u32) -> Chorus {
+    Chorus::from_preset(sample_rate, &ChorusPreset::thick())
+}
+
+struct ChorusLine {
+    lfo: Lfo,
+    depth_samples: f32,
+    /// Read position within the detune window, in samples.
+    sweep: f32,
+    /// Samples the read position moves per input sample.
+    speed: f32,
+    gains: (f32, f32),
+}
+
+/// Mono in, stereo out: every voice reads one shared delay line at its own
+/// LFO-swept delay, and detuned voices also sweep through a window like the
+/// harmonizer's pitch shifter, crossfading two taps to hide the wrap. The cost grows
+/// with the voice count.
+///
+/// `wet` is the mix [`ChorusSource`] uses; [`process`](Self::process) returns only
+/// the chorused signal.
+pub struct Chorus {
+    pub wet: f32,
+    buf: RingBuffer<f32>,
+    lines: Vec<ChorusLine>,
+    base_delay_samples: f32,
+    window: f32,
+    /// Voices add as roughly uncorrelated signals, so scale by 1 / sqrt(count).
+    normalize: f32,
+}
+
+impl Chorus {
+    pub fn from_preset(sample_rate: u32, preset: &ChorusPreset) -> Chorus {
+        let ms = |ms: f32| ms.max(0.0) * sample_rate as f32 / 1000.0;
+        let window = (DETUNE_WINDOW_SECS * sample_rate as f32).max(4.0);
+        let count = preset.voices.len();
+        let lines: Vec<ChorusLine> = preset
+            .voices
+            .iter()
+            .enumerate()
+            .map(|(i, voice)| {
+                let mut lfo = Lfo::new(sample_rate, voice.rate_hz, 1.0, LfoPolarity::Unipolar);
+                // Start the voices at different points so they don't sweep together
+                lfo.set_phase(i as f32 / count.max(1) as f32);
+                ChorusLine {
+                    lfo,
+                    depth_samples: ms(voice.depth_ms),
+                    sweep: 0.0,
+                    speed: 2.0_f32.powf(voice.detune_cents / 1200.0),
+                    gains: constant_power_gains(voice.pan),
+                }
+            })
+            .collect();
+        let longest = lines.iter().map(|line| line.depth_samples).fold(0.0, f32::max);
+
+        Chorus {
+            wet: preset.wet.clamp(0.0, 1.0),
+            // Room for the longest delay plus the detune window and interpolation
+            buf: RingBuffer::new((ms(BASE_DELAY_MS) + longest + window) as usize + 3),
+            lines,
+            base_delay_samples: ms(BASE_DELAY_MS),
+            window,
+            normalize: 1.0 / (count.max(1) as f32).sqrt(),
+        }
+    }
+
+    /// Stores `input` and returns the voices mixed to left and right.
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        self.buf.push(input);
+        let (mut left, mut right) = (0.0, 0.0);
+        for line in &mut self.lines {
+            let delay = 1.0 + self.base_delay_samples + line.depth_samples * line.lfo.tick();
+            let tap = if line.speed == 1.0 {
+                self.buf.read_interpolated(delay)
+            } else {
+                let first = line.sweep;
+                let second = (line.sweep + self.window / 2.0) % self.window;
+                let first_gain = (PI * first / self.window).sin().powi(2);
+                let tap = self.buf.read_interpolated(delay + first) * first_gain
+                    + self.buf.read_interpolated(delay + second) * (1.0 - first_gain);
+                line.sweep = (line.sweep + 1.0 - line.speed).rem_euclid(self.window);
+                tap
+            };
+            left += tap * line.gains.0;
+            right += tap * line.gains.1;
+        }
+        (left * self.normalize, right * self.normalize)
+    }
+}
+
+/// Runs an interleaved stereo source through a [`Chorus`], fed with the two channels
+/// summed, and mixes it in by its wet level. While disabled the source passes
+/// through untouched and the chorus costs nothing, as its line only refills.
+pub struct ChorusSource<S: Source<Item = f32>> {
+    source: S,
+    chorus: Chorus,
+    enabled: Arc<Mutex<bool>>,
+    pending_right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> ChorusSource<S> {
+    pub fn new(source: S, chorus: Chorus) -> ChorusSource<S> {
+        ChorusSource {
+            source,
+            chorus,
+            enabled: Arc::new(Mutex::new(false)),
+            pending_right: None,
+        }
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ChorusSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ChorusSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.source.channels() != 2 {
+            return self.source.next();
+        }
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let left = self.source.next()?;
+        let right = self.source.next()?;
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            self.chorus.buf.push((left + right) * 0.5);
+            self.pending_right = Some(right);
+            return Some(left);
+        }
+
+        let (wet_left, wet_right) = self.chorus.process((left + right) * 0.5);
+        let wet = self.chorus.wet;
+        self.pending_right = Some(right * (1.0 - wet) + wet_right * wet);
+        Some(left * (1.0 - wet) + wet_left * wet)
+    }
+}
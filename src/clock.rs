@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A sample counter shared by everything that needs to agree on the time.
+///
+/// Exactly one source per output stream should advance it, normally the oscillator
+/// attached with [`WaveTableOscillator::set_master_clock`](crate::WaveTableOscillator::set_master_clock).
+/// Everything else only reads it, so events scheduled in samples stay sample-exact and
+/// never drift apart the way separate `Instant` clocks do.
+#[derive(Clone, Debug)]
+pub struct MasterClock {
+    sample_count: Arc<AtomicU64>,
+    sample_rate: u32,
+}
+
+impl MasterClock {
+    pub fn new(sample_rate: u32) -> MasterClock {
+        MasterClock {
+            sample_count: Arc::new(AtomicU64::new(0)),
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn sample_count(&self) -> u64 {
+        self.sample_count.load(Ordering::Acquire)
+    }
+
+    pub fn advance(&self, samples: u64) {
+        self.sample_count.fetch_add(samples, Ordering::Release);
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.sample_count() as f64 / self.sample_rate as f64
+    }
+
+    /// The sample on which something scheduled `time_secs` from the start falls.
+    pub fn event_sample(&self, time_secs: f64) -> u64 {
+        (time_secs * self.sample_rate as f64).round().max(0.0) as u64
+    }
+
+    /// Whether the clock has reached `event_sample`.
+    pub fn has_reached(&self, event_sample: u64) -> bool {
+        self.sample_count() >= event_sample
+    }
+}
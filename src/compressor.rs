@@ -0,0 +1,207 @@
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Master bus compression: gentle, soft-knee gain reduction on the finished stereo
+/// mix, the "glue" that makes the voices sit together, as opposed to the hard
+/// ceiling of the [`SafetyLimiter`](crate::SafetyLimiter) after it.
+///
+/// Levels are handled in dB. Below the knee the mix is untouched; above it every
+/// `ratio` dB in gives one dB out, and across the `knee_width_db` around the
+/// threshold the curve bends smoothly from one to the other. Each channel keeps its
+/// own gain, `gain_l` and `gain_r`, which falls toward the curve over `attack_ms`
+/// and recovers over `release_ms`.
+#[derive(Clone, Debug)]
+pub struct BusCompressor {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub makeup_gain_db: f32,
+    pub knee_width_db: f32,
+    /// The current gain reduction per channel, as a linear factor before makeup.
+    pub gain_l: f32,
+    pub gain_r: f32,
+    sample_rate: u32,
+}
+
+impl BusCompressor {
+    /// The subtle glue setting: 2:1 from -12 dBFS with a 6 dB knee, 10 ms attack,
+    /// 100 ms release and no makeup gain.
+    pub fn new(sample_rate: u32) -> BusCompressor {
+        BusCompressor {
+            threshold_db: -12.0,
+            ratio: 2.0,
+            attack_ms: 10.0,
+            release_ms: 100.0,
+            makeup_gain_db: 0.0,
+            knee_width_db: 6.0,
+            gain_l: 1.0,
+            gain_r: 1.0,
+            sample_rate,
+        }
+    }
+
+    /// The "loud master" preset: 3:1 from -6 dBFS with 3 dB of makeup gain.
+    pub fn loud_master(sample_rate: u32) -> BusCompressor {
+        BusCompressor {
+            threshold_db: -6.0,
+            ratio: 3.0,
+            makeup_gain_db: 3.0,
+            ..BusCompressor::new(sample_rate)
+        }
+    }
+
+    /// Gain change in dB, zero or negative, the static curve applies at `level_db`.
+    pub fn gain_reduction_db(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let slope = 1.0 / self.ratio.max(1.0) - 1.0;
+        let knee = self.knee_width_db.max(0.0);
+        if 2.0 * over <= -knee {
+            0.0
+        } else if 2.0 * over < knee {
+            slope * (over + knee / 2.0).powi(2) / (2.0 * knee)
+        } else {
+            slope * over
+        }
+    }
+
+    fn coeff(&self, ms: f32) -> f32 {
+        (-1000.0 / (ms.max(0.01) * self.sample_rate as f32)).exp()
+    }
+
+    /// One stereo frame.
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let attack = self.coeff(self.attack_ms);
+        let release = self.coeff(self.release_ms);
+        let makeup = 10.0_f32.powf(self.makeup_gain_db / 20.0);
+        let follow = |gain: f32, input: f32| {
+            let level_db = 20.0 * input.abs().max(1e-6).log10();
+            let target = 10.0_f32.powf(self.gain_reduction_db(level_db) / 20.0);
+            let coeff = if target < gain { attack } else { release };
+            target + (gain - target) * coeff
+        };
+        let gain_l = follow(self.gain_l, left);
+        let gain_r = follow(self.gain_r, right);
+        self.gain_l = gain_l;
+        self.gain_r = gain_r;
+        (left * gain_l * makeup, right * gain_r * makeup)
+    }
+}
+
+/// Runs an interleaved stereo source through a [`BusCompressor`], switched by the
+/// enabled control and off to begin with. A mono source passes through untouched.
+pub struct BusCompressorSource<S: Source<Item = f32>> {
+    source: S,
+    compressor: BusCompressor,
+    enabled: Arc<Mutex<bool>>,
+    pending_right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> BusCompressorSource<S> {
+    pub fn new(source: S, compressor: BusCompressor) -> BusCompressorSource<S> {
+        BusCompressorSource {
+            source,
+            compressor,
+            enabled: Arc::new(Mutex::new(false)),
+            pending_right: None,
+        }
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for BusCompressorSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for BusCompressorSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.source.channels() != 2 {
+            return self.source.next();
+        }
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let left = self.source.next()?;
+        let right = self.source.next()?;
+        let (left, right) = if self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            self.compressor.process(left, right)
+        } else {
+            (left, right)
+        };
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    /// Eight sines spread over three octaves, summed and scaled to peak at `peak`.
+    fn composite(peak: f32, len: usize) -> Vec<f32> {
+        let freqs = [110.0, 165.0, 220.0, 277.2, 330.0, 440.0, 554.4, 880.0];
+        let raw: Vec<f32> = (0..len)
+            .map(|i| freqs.iter().map(|f| (TAU * f * i as f32 / 44100.0).sin()).sum())
+            .collect();
+        let max = raw.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+        raw.iter().map(|s| s * peak / max).collect()
+    }
+
+    /// The output peak of each channel over the second half, once the gain has settled.
+    fn settled_peaks(compressor: &mut BusCompressor, left: &[f32], right: &[f32]) -> (f32, f32) {
+        let mut peaks = (0.0_f32, 0.0_f32);
+        for (i, (&l, &r)) in left.iter().zip(right).enumerate() {
+            let (l, r) = compressor.process(l, r);
+            if i >= left.len() / 2 {
+                peaks = (peaks.0.max(l.abs()), peaks.1.max(r.abs()));
+            }
+        }
+        peaks
+    }
+
+    #[test]
+    fn static_curve_bends_through_the_knee() {
+        let compressor = BusCompressor::loud_master(44100);
+        assert_eq!(compressor.gain_reduction_db(-20.0), 0.0);
+        assert_eq!(compressor.gain_reduction_db(-9.0), 0.0);
+        // Halfway up the knee, a quarter of its width in, the slope is half applied
+        assert!((compressor.gain_reduction_db(-6.0) + 2.0 / 3.0 * 3.0 / 4.0).abs() < 1e-5);
+        assert!((compressor.gain_reduction_db(0.0) + 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn loud_composite_is_pulled_down_and_quiet_one_only_gets_makeup() {
+        let mut compressor = BusCompressor::loud_master(44100);
+        let loud = composite(1.0, 44100);
+        let quiet = composite(0.1, 44100);
+        let (loud_peak, quiet_peak) = settled_peaks(&mut compressor, &loud, &quiet);
+        let makeup = 10.0_f32.powf(3.0 / 20.0);
+        // The gain follows the waveform, not just its peaks, so the reduction settles
+        // between none and the 4 dB the curve gives at 0 dBFS
+        let reduction_db = 20.0 * (loud_peak / makeup).log10();
+        assert!((-4.0..-0.5).contains(&reduction_db), "{reduction_db}");
+        assert!(compressor.gain_r > 0.999);
+        assert!((quiet_peak - 0.1 * makeup).abs() < 1e-3, "{quiet_peak}");
+    }
+}
@@ -0,0 +1,114 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Samples rendered between clock reads, to keep `Instant::now` off the per-sample path.
+const TIMED_BLOCK: usize = 64;
+
+/// Passes a source through while adding the time spent rendering it to a shared
+/// nanosecond counter.
+///
+/// The source is pulled a block at a time so the clock is read once per block, which
+/// puts the output [`TIMED_BLOCK`] samples behind the source. Several timers can share
+/// one counter; a [`CpuMonitor`] reads it.
+pub struct CpuTimer<S: Source<Item = f32>> {
+    source: S,
+    busy_nanos: Arc<AtomicU64>,
+    block: Vec<f32>,
+    position: usize,
+}
+
+impl<S: Source<Item = f32>> CpuTimer<S> {
+    pub fn new(source: S, busy_nanos: Arc<AtomicU64>) -> CpuTimer<S> {
+        CpuTimer {
+            source,
+            busy_nanos,
+            block: Vec::with_capacity(TIMED_BLOCK),
+            position: 0,
+        }
+    }
+
+    fn render_block(&mut self) {
+        let start = Instant::now();
+        self.block.clear();
+        self.block.extend(self.source.by_ref().take(TIMED_BLOCK));
+        self.position = 0;
+        self.busy_nanos.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl<S: Source<Item = f32>> Source for CpuTimer<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for CpuTimer<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.block.len() {
+            self.render_block();
+        }
+        let sample = *self.block.get(self.position)?;
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+/// UI-side view of the [`CpuTimer`]s: the share of real time spent rendering audio.
+///
+/// The audio plays in real time, so the wall-clock time between updates is the time
+/// that was available. Near 100% the callback can no longer keep up and the output
+/// starts to drop out.
+pub struct CpuMonitor {
+    busy_nanos: Arc<AtomicU64>,
+    update_interval: Duration,
+    last_update: Instant,
+    last_busy_nanos: u64,
+    load_percent: f32,
+}
+
+impl CpuMonitor {
+    pub fn new(update_interval: Duration) -> CpuMonitor {
+        CpuMonitor {
+            busy_nanos: Arc::new(AtomicU64::new(0)),
+            update_interval,
+            last_update: Instant::now(),
+            last_busy_nanos: 0,
+            load_percent: 0.0,
+        }
+    }
+
+    /// The counter to hand to each [`CpuTimer`].
+    pub fn get_busy_control(&self) -> Arc<AtomicU64> {
+        self.busy_nanos.clone()
+    }
+
+    /// The DSP load as processing time over available time, times 100. It is
+    /// recomputed at most once per update interval and held in between.
+    pub fn cpu_load_percent(&mut self) -> f32 {
+        let elapsed = self.last_update.elapsed();
+        if elapsed >= self.update_interval {
+            let busy = self.busy_nanos.load(Ordering::Relaxed);
+            let busy_delta = busy.saturating_sub(self.last_busy_nanos);
+            self.load_percent = busy_delta as f32 / elapsed.as_nanos().max(1) as f32 * 100.0;
+            self.last_busy_nanos = busy;
+            self.last_update = Instant::now();
+        }
+        self.load_percent
+    }
+}
@@ -0,0 +1,379 @@
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Highest feedback allowed, so the loop always decays.
+const MAX_FEEDBACK: f32 = 0.999;
+/// How long a change of delay time crossfades from the old line to the new one.
+const TIME_CROSSFADE_SECS: f32 = 0.05;
+
+/// A delay time, either fixed or as a fraction of a whole note at the current tempo,
+/// so `Synced { numerator: 3, denominator: 8 }` is a dotted quarter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DelayTime {
+    Milliseconds(f32),
+    Synced { numerator: u8, denominator: u8 },
+}
+
+impl DelayTime {
+    const fn synced(numerator: u8, denominator: u8) -> DelayTime {
+        DelayTime::Synced { numerator, denominator }
+    }
+
+    /// Straight 1/1 to 1/16, then the dotted and triplet versions of each.
+    pub const STANDARD_SYNCED: [DelayTime; 15] = [
+        DelayTime::synced(1, 1),
+        DelayTime::synced(1, 2),
+        DelayTime::synced(1, 4),
+        DelayTime::synced(1, 8),
+        DelayTime::synced(1, 16),
+        DelayTime::synced(3, 2),
+        DelayTime::synced(3, 4),
+        DelayTime::synced(3, 8),
+        DelayTime::synced(3, 16),
+        DelayTime::synced(3, 32),
+        DelayTime::synced(2, 3),
+        DelayTime::synced(1, 3),
+        DelayTime::synced(1, 6),
+        DelayTime::synced(1, 12),
+        DelayTime::synced(1, 24),
+    ];
+}
+
+impl fmt::Display for DelayTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DelayTime::Milliseconds(ms) => write!(f, "{ms} ms"),
+            DelayTime::Synced { numerator, denominator } => write!(f, "{numerator}/{denominator}"),
+        }
+    }
+}
+
+/// Length of `time` in samples at `bpm`, a whole note being four beats. Never less
+/// than one sample.
+pub fn compute_delay_samples(time: DelayTime, bpm: f32, sample_rate: u32) -> usize {
+    let secs = match time {
+        DelayTime::Milliseconds(ms) => ms / 1000.0,
+        DelayTime::Synced { numerator, denominator } => {
+            4.0 * 60.0 / bpm.max(1.0) * numerator as f32 / denominator.max(1) as f32
+        }
+    };
+    ((secs.max(0.0) * sample_rate as f32).round() as usize).max(1)
+}
+
+/// Single-tap feedback delay line.
+///
+/// A delay of a few samples with high feedback rings as a comb filter; a few hundred
+/// milliseconds gives a repeating echo.
+pub struct FeedbackDelay {
+    delay_samples: usize,
+    feedback: f32,
+    buf: RingBuffer<f32>,
+}
+
+impl FeedbackDelay {
+    pub fn new(delay_samples: usize, feedback: f32) -> FeedbackDelay {
+        let delay_samples = delay_samples.max(1);
+        FeedbackDelay {
+            delay_samples,
+            feedback: feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK),
+            buf: RingBuffer::new(delay_samples),
+        }
+    }
+
+    pub fn delay_samples(&self) -> usize {
+        self.delay_samples
+    }
+
+    /// Changing the length clears the line.
+    pub fn set_delay_samples(&mut self, delay_samples: usize) {
+        let delay_samples = delay_samples.max(1);
+        if delay_samples != self.delay_samples {
+            self.delay_samples = delay_samples;
+            self.buf = RingBuffer::new(delay_samples);
+        }
+    }
+
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+    }
+
+    /// Silences the line without changing its length.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let delayed = *self.buf.read_at(self.delay_samples);
+        let output = input + self.feedback * delayed;
+        self.buf.push(output);
+        output
+    }
+}
+
+/// Runs a source through a [`FeedbackDelay`] with shared controls. The line keeps
+/// running on silence while bypassed, so old echoes die away instead of replaying
+/// when it is engaged again. Engaged, it plays `dry * input + wet * echoes`, both
+/// levels 1.0 unless set; bypassed, the input passes untouched.
+///
+/// A new delay time, or a new tempo under a synced one, starts a fresh line and
+/// crossfades to it over 50 ms, both running meanwhile, instead of cutting over.
+///
+/// It's mono, like the sources it wraps; no stereo delay exists yet, and the synth
+/// runs this one before the panner.
+#[doc(alias = "DelayEffect")]
+pub struct DelaySource<S: Source<Item = f32>> {
+    source: S,
+    delay: FeedbackDelay,
+    /// The line being faded out, with how far the fade has got (0.0-1.0).
+    fading: Option<(FeedbackDelay, f32)>,
+    fade_step: f32,
+    enabled: Arc<Mutex<bool>>,
+    feedback: Arc<Mutex<f32>>,
+    dry: Arc<Mutex<f32>>,
+    wet: Arc<Mutex<f32>>,
+    time: Arc<Mutex<DelayTime>>,
+    bpm: Arc<Mutex<f32>>,
+}
+
+impl<S: Source<Item = f32>> DelaySource<S> {
+    pub fn new(source: S, delay_ms: f32, feedback: f32) -> DelaySource<S> {
+        let time = DelayTime::Milliseconds(delay_ms);
+        let sample_rate = source.sample_rate();
+        DelaySource {
+            source,
+            delay: FeedbackDelay::new(compute_delay_samples(time, 120.0, sample_rate), feedback),
+            fading: None,
+            fade_step: 1.0 / (TIME_CROSSFADE_SECS * sample_rate as f32),
+            enabled: Arc::new(Mutex::new(false)),
+            feedback: Arc::new(Mutex::new(feedback)),
+            dry: Arc::new(Mutex::new(1.0)),
+            wet: Arc::new(Mutex::new(1.0)),
+            time: Arc::new(Mutex::new(time)),
+            bpm: Arc::new(Mutex::new(120.0)),
+        }
+    }
+
+    /// The line is resized, crossfading, on the next sample.
+    pub fn set_delay_ms(&mut self, delay_ms: f32) {
+        if let Ok(mut time) = self.time.lock() {
+            *time = DelayTime::Milliseconds(delay_ms);
+        }
+    }
+
+    pub fn set_feedback(&mut self, feedback: f32) {
+        if let Ok(mut shared) = self.feedback.lock() {
+            *shared = feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+        }
+    }
+
+    /// Level of the input under the echoes while engaged, 0.0 to 1.0.
+    pub fn set_dry(&mut self, dry: f32) {
+        if let Ok(mut shared) = self.dry.lock() {
+            *shared = dry.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Level of the echoes against the dry signal, 0.0 to 1.0.
+    pub fn set_wet(&mut self, wet: f32) {
+        if let Ok(mut shared) = self.wet.lock() {
+            *shared = wet.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn get_time_control(&self) -> Arc<Mutex<DelayTime>> {
+        self.time.clone()
+    }
+
+    /// Tempo that synced delay times follow; 120 BPM until set.
+    pub fn get_bpm_control(&self) -> Arc<Mutex<f32>> {
+        self.bpm.clone()
+    }
+
+    /// Starts a crossfade when the time or tempo has moved the delay length.
+    fn follow_time(&mut self) {
+        let (Ok(time), Ok(bpm)) = (self.time.lock(), self.bpm.lock()) else {
+            return;
+        };
+        let delay_samples = compute_delay_samples(*time, *bpm, self.source.sample_rate());
+        if delay_samples == self.delay.delay_samples() {
+            return;
+        }
+        let feedback = self.delay.feedback;
+        let old = std::mem::replace(&mut self.delay, FeedbackDelay::new(delay_samples, feedback));
+        // A change mid-fade drops the line that was already on its way out
+        self.fading = Some((old, 0.0));
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.delay.process(input);
+        let Some((old, progress)) = self.fading.as_mut() else {
+            return output;
+        };
+        let faded = old.process(input);
+        *progress += self.fade_step;
+        let mix = progress.min(1.0);
+        if *progress >= 1.0 {
+            self.fading = None;
+        }
+        faded * (1.0 - mix) + output * mix
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+
+    pub fn get_feedback_control(&self) -> Arc<Mutex<f32>> {
+        self.feedback.clone()
+    }
+
+    pub fn get_dry_control(&self) -> Arc<Mutex<f32>> {
+        self.dry.clone()
+    }
+
+    pub fn get_wet_control(&self) -> Arc<Mutex<f32>> {
+        self.wet.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for DelaySource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for DelaySource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        let enabled = self.enabled.lock().is_ok_and(|enabled| *enabled);
+
+        self.follow_time();
+
+        if !enabled {
+            self.process(0.0);
+            return Some(input);
+        }
+
+        if let Ok(feedback) = self.feedback.lock() {
+            self.delay.set_feedback(*feedback);
+            if let Some((old, _)) = self.fading.as_mut() {
+                old.set_feedback(*feedback);
+            }
+        }
+        let output = self.process(input);
+        let dry = self.dry.lock().map_or(1.0, |dry| dry.clamp(0.0, 1.0));
+        let wet = self.wet.lock().map_or(1.0, |wet| wet.clamp(0.0, 1.0));
+        // The line's output is the input with the echoes added
+        Some(dry * input + wet * (output - input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// A unit step into a sixteenth-note echo at 120 BPM, run until the echoes settle.
+    fn settled_echo() -> DelaySource<SamplesBuffer<f32>> {
+        let mut echo = DelaySource::new(SamplesBuffer::new(1, 48000, vec![1.0; 400_000]), 300.0, 0.5);
+        *echo.get_enabled_control().lock().unwrap() = true;
+        *echo.get_time_control().lock().unwrap() = DelayTime::synced(1, 16);
+        for _ in 0..200_000 {
+            echo.next();
+        }
+        assert!(echo.fading.is_none());
+        echo
+    }
+
+    #[test]
+    fn dry_and_wet_levels_split_input_and_echoes() {
+        let mut impulse = vec![0.0; 100];
+        impulse[0] = 1.0;
+        let mut echo = DelaySource::new(SamplesBuffer::new(1, 1000, impulse), 10.0, 0.5);
+        *echo.get_enabled_control().lock().unwrap() = true;
+        echo.set_dry(0.25);
+        echo.set_wet(0.5);
+        let output: Vec<f32> = echo.by_ref().take(21).collect();
+        assert_eq!((output[0], output[10], output[20]), (0.25, 0.25, 0.125));
+        assert!(output.iter().enumerate().all(|(i, &s)| i % 10 == 0 || s == 0.0));
+    }
+
+    #[test]
+    fn dotted_quarter_at_120_bpm() {
+        assert_eq!(compute_delay_samples(DelayTime::synced(3, 8), 120.0, 48000), 36000);
+        assert_eq!(compute_delay_samples(DelayTime::Milliseconds(250.0), 90.0, 48000), 12000);
+    }
+
+    #[test]
+    fn tempo_change_crossfades_for_the_fade_time() {
+        let mut echo = settled_echo();
+        *echo.get_bpm_control().lock().unwrap() = 100.0;
+        echo.next();
+        assert!(echo.fading.is_some());
+        assert_eq!(echo.delay.delay_samples(), 7200);
+        let mut faded_after = 1;
+        while echo.fading.is_some() {
+            echo.next();
+            faded_after += 1;
+        }
+        let expected = (TIME_CROSSFADE_SECS * 48000.0).round() as i32;
+        assert!((faded_after - expected).abs() <= 1, "{faded_after} samples");
+    }
+
+    #[test]
+    fn tempo_change_has_no_jump_larger_than_the_input_step() {
+        let mut echo = settled_echo();
+        let mut last = echo.next().unwrap();
+        // The echoes hold the output at 2.0; the new, empty line plays just the input
+        assert!((last - 2.0).abs() < 1e-3, "{last}");
+        *echo.get_bpm_control().lock().unwrap() = 100.0;
+        let mut largest_jump: f32 = 0.0;
+        for _ in 0..(TIME_CROSSFADE_SECS * 48000.0) as usize {
+            let sample = echo.next().unwrap();
+            largest_jump = largest_jump.max((sample - last).abs());
+            last = sample;
+        }
+        assert!((last - 1.0).abs() < 1e-2, "{last}");
+        // Cutting straight to the new line would drop by 1.0 at once, the whole input step
+        assert!(largest_jump < 1e-3, "{largest_jump}");
+    }
+
+    #[test]
+    fn no_feedback_passes_the_input_through() {
+        let mut delay = FeedbackDelay::new(10, 0.0);
+        for i in 0..100 {
+            let input = (i as f32 * 0.1).sin();
+            assert_eq!(delay.process(input), input);
+        }
+    }
+
+    #[test]
+    fn an_impulse_comes_back_after_the_delay() {
+        let mut delay = FeedbackDelay::new(7, 0.5);
+        let output: Vec<f32> = (0..22).map(|i| delay.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        let echoes: Vec<(usize, f32)> = output.iter().copied().enumerate().filter(|&(_, s)| s != 0.0).collect();
+        assert_eq!(echoes, [(0, 1.0), (7, 0.5), (14, 0.25), (21, 0.125)]);
+    }
+
+    #[test]
+    fn feedback_is_clamped_below_one() {
+        let mut delay = FeedbackDelay::new(1, 5.0);
+        delay.process(1.0);
+        assert_eq!(delay.process(0.0), MAX_FEEDBACK);
+    }
+}
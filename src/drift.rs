@@ -0,0 +1,53 @@
+use rand::Rng;
+use synth_core::smoothing_coeff;
+
+pub const DEFAULT_DRIFT_RATE_HZ: f32 = 0.1;
+pub const DEFAULT_DRIFT_DEPTH_CENTS: f32 = 3.0;
+
+/// Slow random pitch wander, like an analog oscillator's capacitors warming and
+/// cooling. `rate_hz` times a second a new target within ±`depth_cents` is drawn,
+/// and the offset glides toward it through a one-pole smoother cornered at `rate_hz`,
+/// so it never steps.
+#[derive(Clone, Debug)]
+pub struct TuningDrift {
+    pub rate_hz: f32,
+    pub depth_cents: f32,
+    /// The offset now, in cents.
+    pub current_offset: f32,
+    pub next_target: f32,
+    /// Progress toward the next draw, 0.0-1.0.
+    pub phase: f32,
+    sample_rate: u32,
+}
+
+impl TuningDrift {
+    pub fn new(sample_rate: u32, rate_hz: f32, depth_cents: f32) -> TuningDrift {
+        TuningDrift {
+            rate_hz,
+            depth_cents,
+            current_offset: 0.0,
+            next_target: 0.0,
+            // Draw the first target straight away
+            phase: 1.0,
+            sample_rate,
+        }
+    }
+
+    /// Advances one sample and returns the offset in cents.
+    pub fn tick(&mut self) -> f32 {
+        self.phase += self.rate_hz / self.sample_rate as f32;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+            let depth = self.depth_cents.abs();
+            self.next_target = if depth > 0.0 { rand::thread_rng().gen_range(-depth..depth) } else { 0.0 };
+        }
+        self.current_offset +=
+            (self.next_target - self.current_offset) * smoothing_coeff(self.rate_hz, self.sample_rate);
+        self.current_offset
+    }
+
+    /// [`tick`](Self::tick) as a frequency ratio: `actual_freq = base_freq * ratio`.
+    pub fn tick_ratio(&mut self) -> f32 {
+        2.0_f32.powf(self.tick() / 1200.0)
+    }
+}
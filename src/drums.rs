@@ -0,0 +1,238 @@
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+/// The pieces of the synthesized drum kit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PercKind {
+    Kick,
+    Snare,
+    OpenHiHat,
+    ClosedHiHat,
+    Clap,
+    Tom,
+}
+
+impl PercKind {
+    /// The bottom letter row, left to right: Z kick, X snare, C closed hat, V open
+    /// hat, B clap, N tom.
+    pub fn for_key(key: char) -> Option<PercKind> {
+        match key {
+            'z' => Some(PercKind::Kick),
+            'x' => Some(PercKind::Snare),
+            'c' => Some(PercKind::ClosedHiHat),
+            'v' => Some(PercKind::OpenHiHat),
+            'b' => Some(PercKind::Clap),
+            'n' => Some(PercKind::Tom),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PercKind::Kick => "kick",
+            PercKind::Snare => "snare",
+            PercKind::OpenHiHat => "open hi-hat",
+            PercKind::ClosedHiHat => "closed hi-hat",
+            PercKind::Clap => "clap",
+            PercKind::Tom => "tom",
+        }
+    }
+}
+
+/// One synthesized drum hit, which ends once it has decayed to silence.
+///
+/// Every kind mixes a tone, optionally sweeping down in pitch, with white noise,
+/// each under its own exponential decay. Hi-hats high-pass the noise; the clap
+/// retriggers its noise a few times in quick succession like several hands.
+struct PercussionSynth {
+    sample_rate: f32,
+    elapsed: f32,
+    length: f32,
+    tone_start_hz: f32,
+    tone_end_hz: f32,
+    tone_decay: f32,
+    tone_level: f32,
+    tone_phase: f32,
+    noise_decay: f32,
+    noise_level: f32,
+    high_pass: bool,
+    clap: bool,
+    noise_state: u32,
+    previous_noise: f32,
+}
+
+impl PercussionSynth {
+    fn new(kind: PercKind, sample_rate: u32) -> PercussionSynth {
+        // (tone start Hz, tone end Hz, tone decay s, tone level, noise decay s, noise level)
+        let (tone_start_hz, tone_end_hz, tone_decay, tone_level, noise_decay, noise_level) = match kind {
+            PercKind::Kick => (150.0, 50.0, 0.15, 1.0, 0.005, 0.3),
+            PercKind::Snare => (180.0, 180.0, 0.05, 0.4, 0.1, 0.6),
+            PercKind::ClosedHiHat => (0.0, 0.0, 0.0, 0.0, 0.02, 0.5),
+            PercKind::OpenHiHat => (0.0, 0.0, 0.0, 0.0, 0.15, 0.5),
+            PercKind::Clap => (0.0, 0.0, 0.0, 0.0, 0.1, 0.7),
+            PercKind::Tom => (120.0, 80.0, 0.2, 0.9, 0.01, 0.1),
+        };
+
+        PercussionSynth {
+            sample_rate: sample_rate as f32,
+            elapsed: 0.0,
+            // Long enough for the slower decay to fall about 60 dB
+            length: 7.0 * f32::max(tone_decay, noise_decay) + 0.03,
+            tone_start_hz,
+            tone_end_hz,
+            tone_decay,
+            tone_level,
+            tone_phase: 0.0,
+            noise_decay,
+            noise_level,
+            high_pass: matches!(kind, PercKind::ClosedHiHat | PercKind::OpenHiHat),
+            clap: kind == PercKind::Clap,
+            noise_state: 0x9e37_79b9,
+            previous_noise: 0.0,
+        }
+    }
+
+    fn noise(&mut self) -> f32 {
+        // xorshift32: plenty white for a drum, and cheap enough for the audio thread
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+        self.noise_state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl Source for PercussionSynth {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate as u32
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for PercussionSynth {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.elapsed >= self.length {
+            return None;
+        }
+        let t = self.elapsed;
+        self.elapsed += 1.0 / self.sample_rate;
+
+        let mut output = 0.0;
+        if self.tone_level > 0.0 {
+            // Pitch glides from start to end over the tone's decay
+            let sweep = (-t / self.tone_decay).exp();
+            let freq = self.tone_end_hz + (self.tone_start_hz - self.tone_end_hz) * sweep;
+            output += (TAU * self.tone_phase).sin() * self.tone_level * sweep;
+            self.tone_phase = (self.tone_phase + freq / self.sample_rate).fract();
+        }
+
+        let mut noise = self.noise();
+        if self.high_pass {
+            let white = noise;
+            noise = 0.5 * (white - self.previous_noise);
+            self.previous_noise = white;
+        }
+        // Three quick bursts 10 ms apart, then the main decay from the last one
+        let noise_time = match (self.clap, t < 0.03) {
+            (true, true) => t % 0.01,
+            (true, false) => t - 0.03,
+            (false, _) => t,
+        };
+        output += noise * self.noise_level * (-noise_time / self.noise_decay).exp();
+
+        Some(output * 0.4)
+    }
+}
+
+pub struct PercussionVoice {
+    pub kind: PercKind,
+    pub engine: Box<dyn Source<Item = f32> + Send>,
+}
+
+impl PercussionVoice {
+    pub fn new(kind: PercKind, sample_rate: u32) -> PercussionVoice {
+        PercussionVoice {
+            kind,
+            engine: Box::new(PercussionSynth::new(kind, sample_rate)),
+        }
+    }
+}
+
+/// Plays drum hits queued through the trigger control.
+///
+/// Each kit piece has one voice: hitting it again restarts it, while different pieces
+/// ring over each other.
+pub struct KeyboardDrummer {
+    sample_rate: u32,
+    voices: Vec<PercussionVoice>,
+    triggers: Arc<Mutex<Vec<PercKind>>>,
+}
+
+impl KeyboardDrummer {
+    pub fn new(sample_rate: u32) -> KeyboardDrummer {
+        KeyboardDrummer {
+            sample_rate,
+            voices: Vec::new(),
+            triggers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Push a [`PercKind`] to hit it on the next sample.
+    pub fn get_trigger_control(&self) -> Arc<Mutex<Vec<PercKind>>> {
+        self.triggers.clone()
+    }
+}
+
+impl Source for KeyboardDrummer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for KeyboardDrummer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Ok(mut triggers) = self.triggers.lock() {
+            for kind in triggers.drain(..) {
+                self.voices.retain(|voice| voice.kind != kind);
+                self.voices.push(PercussionVoice::new(kind, self.sample_rate));
+            }
+        }
+
+        let mut sum = 0.0;
+        self.voices.retain_mut(|voice| match voice.engine.next() {
+            Some(sample) => {
+                sum += sample;
+                true
+            }
+            None => false,
+        });
+        Some(sum)
+    }
+}
@@ -0,0 +1,157 @@
+use crate::wave::{generate_pulse_wave_table, generate_wave_table, WaveShape};
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the worker looks for a dirty flag; well under a UI frame.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// What a [`DynamicWaveTable`] computes its table from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveParams {
+    pub shape: WaveShape,
+    pub size: usize,
+    /// Fraction of the cycle a square spends high; 0.5 is a plain square.
+    pub pulse_width: f32,
+    /// Levels of harmonics 1, 2, 3, ... When any are set they replace `shape` with
+    /// their sum of sines, normalized to a peak of 1.0.
+    pub harmonics: Vec<f32>,
+    /// A cycle loaded from elsewhere, such as a Serum wavetable frame. When set it is
+    /// played as it is, whatever its length, in place of everything above.
+    pub custom_table: Option<Vec<f32>>,
+}
+
+impl WaveParams {
+    pub fn new(shape: WaveShape, size: usize) -> WaveParams {
+        WaveParams {
+            shape,
+            size,
+            pulse_width: 0.5,
+            harmonics: Vec::new(),
+            custom_table: None,
+        }
+    }
+
+    /// Builds one cycle from these parameters.
+    pub fn render(&self) -> Vec<f32> {
+        if let Some(table) = self.custom_table.as_ref().filter(|table| !table.is_empty()) {
+            return table.clone();
+        }
+        let size = self.size.max(1);
+        if self.harmonics.iter().any(|level| *level != 0.0) {
+            let mut table: Vec<f32> = (0..size)
+                .map(|i| {
+                    let phase = TAU * i as f32 / size as f32;
+                    self.harmonics
+                        .iter()
+                        .enumerate()
+                        .map(|(n, level)| level * (phase * (n + 1) as f32).sin())
+                        .sum()
+                })
+                .collect();
+            let peak = table.iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+            if peak > 0.0 {
+                table.iter_mut().for_each(|sample| *sample /= peak);
+            }
+            return table;
+        }
+
+        match self.shape {
+            WaveShape::Square => generate_pulse_wave_table(size, self.pulse_width, (size / 2).saturating_sub(1).max(1)),
+            shape => generate_wave_table(shape, size),
+        }
+    }
+}
+
+/// A wave table recomputed on a worker thread whenever its parameters change.
+///
+/// The UI edits the parameters and calls [`mark_dirty`](Self::mark_dirty); the worker
+/// picks that up within a couple of milliseconds, renders the new table without
+/// holding any lock the audio thread needs, then swaps it into `current`. The audio
+/// thread only ever takes `current.read()`, which the worker holds for no longer than
+/// an `Arc` swap, and bumps of `version` tell it when to look. Readers clone the `Arc`,
+/// so every oscillator following the table plays the same copy.
+pub struct DynamicWaveTable {
+    dirty: Arc<AtomicBool>,
+    params: Arc<Mutex<WaveParams>>,
+    current: Arc<RwLock<Arc<Vec<f32>>>>,
+    version: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+    join: Option<JoinHandle<()>>,
+}
+
+impl DynamicWaveTable {
+    pub fn new(params: WaveParams) -> DynamicWaveTable {
+        let dirty = Arc::new(AtomicBool::new(false));
+        let current = Arc::new(RwLock::new(Arc::new(params.render())));
+        let params = Arc::new(Mutex::new(params));
+        let version = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let join = {
+            let (dirty, params, current) = (dirty.clone(), params.clone(), current.clone());
+            let (version, stop) = (version.clone(), stop.clone());
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if !dirty.swap(false, Ordering::Acquire) {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    // Copy the parameters out so the UI isn't held up by the render
+                    let snapshot = match params.lock() {
+                        Ok(params) => params.clone(),
+                        Err(_) => break,
+                    };
+                    let table = Arc::new(snapshot.render());
+                    if let Ok(mut current) = current.write() {
+                        *current = table;
+                    }
+                    version.fetch_add(1, Ordering::Release);
+                }
+            })
+        };
+
+        DynamicWaveTable {
+            dirty,
+            params,
+            current,
+            version,
+            stop,
+            join: Some(join),
+        }
+    }
+
+    pub fn get_params_control(&self) -> Arc<Mutex<WaveParams>> {
+        self.params.clone()
+    }
+
+    pub fn get_dirty_flag(&self) -> Arc<AtomicBool> {
+        self.dirty.clone()
+    }
+
+    /// Asks the worker to recompute the table from the current parameters.
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// The latest table, for the audio thread to read.
+    pub fn current(&self) -> Arc<RwLock<Arc<Vec<f32>>>> {
+        self.current.clone()
+    }
+
+    /// Counts finished recomputes, so readers can tell when `current` has changed.
+    pub fn version(&self) -> Arc<AtomicUsize> {
+        self.version.clone()
+    }
+}
+
+impl Drop for DynamicWaveTable {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(join) = self.join.take() {
+            let _ = join.join();
+        }
+    }
+}
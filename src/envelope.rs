@@ -0,0 +1,223 @@
+use crate::oscillator::WaveTableOscillator;
+use crate::voice::EnvelopePhase;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// An attack-decay-sustain-release amplitude envelope, advanced one sample at a time.
+///
+/// [`note_on`](Self::note_on) rises linearly to full level over `attack_secs`, falls
+/// to `sustain_level` over `decay_secs` and holds there; [`note_off`](Self::note_off)
+/// then falls to silence over `release_secs`. Each stage starts from wherever the
+/// level is, so a note retriggered mid-release doesn't click. The times can be
+/// changed while a note plays and apply from the next sample.
+#[derive(Clone, Debug)]
+pub struct AdsrEnvelope {
+    pub attack_secs: f32,
+    pub decay_secs: f32,
+    /// 0.0 to 1.0.
+    pub sustain_level: f32,
+    pub release_secs: f32,
+    phase: EnvelopePhase,
+    level: f32,
+    /// The level the release started from, so it lasts `release_secs` from any level.
+    release_from: f32,
+    sample_rate: u32,
+}
+
+impl AdsrEnvelope {
+    pub fn new(sample_rate: u32, attack_secs: f32, decay_secs: f32, sustain_level: f32, release_secs: f32) -> AdsrEnvelope {
+        AdsrEnvelope {
+            attack_secs,
+            decay_secs,
+            sustain_level,
+            release_secs,
+            phase: EnvelopePhase::Idle,
+            level: 0.0,
+            release_from: 0.0,
+            sample_rate,
+        }
+    }
+
+    pub fn note_on(&mut self) {
+        self.phase = EnvelopePhase::Attack;
+    }
+
+    pub fn note_off(&mut self) {
+        if self.phase != EnvelopePhase::Idle {
+            self.phase = EnvelopePhase::Release;
+            self.release_from = self.level;
+        }
+    }
+
+    pub fn phase(&self) -> EnvelopePhase {
+        self.phase
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// How far a stage `secs` long moves the level in one sample, taking the whole
+    /// way at once for stages shorter than a sample.
+    fn step(&self, secs: f32) -> f32 {
+        1.0 / (secs * self.sample_rate as f32).max(1.0)
+    }
+
+    /// Advances one sample and returns the level, 0.0 to 1.0.
+    pub fn next_level(&mut self) -> f32 {
+        let sustain = self.sustain_level.clamp(0.0, 1.0);
+        match self.phase {
+            EnvelopePhase::Idle => self.level = 0.0,
+            EnvelopePhase::Attack => {
+                self.level += self.step(self.attack_secs);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.phase = EnvelopePhase::Decay;
+                }
+            }
+            EnvelopePhase::Decay => {
+                self.level -= (1.0 - sustain) * self.step(self.decay_secs);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.phase = EnvelopePhase::Sustain;
+                }
+            }
+            EnvelopePhase::Sustain => self.level = sustain,
+            EnvelopePhase::Release => {
+                self.level -= self.release_from * self.step(self.release_secs);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.phase = EnvelopePhase::Idle;
+                }
+            }
+        }
+        self.level
+    }
+
+    /// Advances `n` samples as [`next_level`](Self::next_level) would, a stage at a
+    /// time rather than sample by sample.
+    pub fn skip(&mut self, mut n: usize) {
+        while n > 0 {
+            let sustain = self.sustain_level.clamp(0.0, 1.0);
+            // Per-sample change, and how far the level has to go to end the stage
+            let (delta, distance) = match self.phase {
+                EnvelopePhase::Idle | EnvelopePhase::Sustain => {
+                    self.next_level();
+                    return;
+                }
+                EnvelopePhase::Attack => (self.step(self.attack_secs), 1.0 - self.level),
+                EnvelopePhase::Decay => (-(1.0 - sustain) * self.step(self.decay_secs), self.level - sustain),
+                EnvelopePhase::Release => (-self.release_from * self.step(self.release_secs), self.level),
+            };
+            let stage_samples = if delta == 0.0 { 1 } else { (distance / delta.abs()).ceil().max(1.0) as usize };
+            if n < stage_samples {
+                self.level += delta * n as f32;
+                return;
+            }
+            // The last sample of the stage lands on its end, as next_level's does
+            self.level += delta * (stage_samples - 1) as f32;
+            self.next_level();
+            n -= stage_samples;
+        }
+    }
+}
+
+/// An oscillator shaped by an [`AdsrEnvelope`], switched by the enabled control and
+/// off to begin with. Notes start and end through the envelope control; while
+/// disabled the oscillator plays at its own constant level.
+pub struct EnvelopedOscillator<S: Source<Item = f32>> {
+    oscillator: S,
+    envelope: Arc<Mutex<AdsrEnvelope>>,
+    enabled: Arc<Mutex<bool>>,
+}
+
+impl<S: Source<Item = f32>> EnvelopedOscillator<S> {
+    pub fn new(oscillator: S, envelope: AdsrEnvelope) -> EnvelopedOscillator<S> {
+        EnvelopedOscillator {
+            oscillator,
+            envelope: Arc::new(Mutex::new(envelope)),
+            enabled: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub fn get_envelope_control(&self) -> Arc<Mutex<AdsrEnvelope>> {
+        self.envelope.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl EnvelopedOscillator<WaveTableOscillator> {
+    /// Seeks `n` samples ahead, moving the envelope on with the oscillator.
+    pub fn skip_samples(&mut self, n: usize) {
+        self.oscillator.skip_samples(n);
+        if let Ok(mut envelope) = self.envelope.lock() {
+            envelope.skip(n);
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for EnvelopedOscillator<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.oscillator.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.oscillator.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.oscillator.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.oscillator.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for EnvelopedOscillator<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.oscillator.next()?;
+        // The envelope keeps time even while bypassed
+        let level = self.envelope.lock().map_or(1.0, |mut envelope| envelope.next_level());
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            return Some(sample);
+        }
+        Some(sample * level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_matches_stepping_through_every_stage() {
+        let mut stepped = AdsrEnvelope::new(1000, 0.05, 0.1, 0.5, 0.2);
+        let mut skipped = stepped.clone();
+        stepped.note_on();
+        skipped.note_on();
+        for n in [10, 70, 200, 0] {
+            for _ in 0..n {
+                stepped.next_level();
+            }
+            skipped.skip(n);
+            assert_eq!(stepped.phase(), skipped.phase());
+            assert!((stepped.level() - skipped.level()).abs() < 1e-4);
+        }
+        stepped.note_off();
+        skipped.note_off();
+        for n in [50, 300] {
+            for _ in 0..n {
+                stepped.next_level();
+            }
+            skipped.skip(n);
+            assert_eq!(stepped.phase(), skipped.phase());
+            assert!((stepped.level() - skipped.level()).abs() < 1e-4);
+        }
+    }
+}
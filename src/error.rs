@@ -0,0 +1,174 @@
+use crate::keymap::KeymapConfigError;
+use crate::midifile::MidiFileError;
+use crate::patch::PatchError;
+use crate::serum::SerumWavetableError;
+use crate::wave::InvalidWaveTableSize;
+use crate::wavefile::WaveLoadError;
+use std::fmt;
+
+/// Everything that can stop the synth from starting or make it quit: one type for
+/// `main` and for programs wrapping the library to match on.
+#[derive(Debug)]
+pub enum SynthError {
+    AudioDeviceError(String),
+    MidiError(String),
+    WavFileError(hound::Error),
+    ConfigError(toml::de::Error),
+    IoError(std::io::Error),
+    /// A value given on the command line or in a file that can't be used.
+    InvalidParameter {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    WaveTableSizeNotPowerOfTwo(usize),
+}
+
+impl SynthError {
+    pub fn invalid_parameter(
+        name: &str,
+        value: impl fmt::Display,
+        reason: impl fmt::Display,
+    ) -> SynthError {
+        SynthError::InvalidParameter {
+            name: name.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SynthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SynthError::AudioDeviceError(message) => write!(f, "audio device error: {message}"),
+            SynthError::MidiError(message) => write!(f, "MIDI error: {message}"),
+            SynthError::WavFileError(error) => write!(f, "WAV file error: {error}"),
+            SynthError::ConfigError(error) => write!(f, "invalid config: {error}"),
+            SynthError::IoError(error) => write!(f, "I/O error: {error}"),
+            SynthError::InvalidParameter {
+                name,
+                value,
+                reason,
+            } if value.is_empty() => {
+                write!(f, "{name}: {reason}")
+            }
+            SynthError::InvalidParameter {
+                name,
+                value,
+                reason,
+            } => write!(f, "{name} '{value}': {reason}"),
+            SynthError::WaveTableSizeNotPowerOfTwo(size) => {
+                write!(f, "wave table size {size} is not a power of two")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SynthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SynthError::WavFileError(error) => Some(error),
+            SynthError::ConfigError(error) => Some(error),
+            SynthError::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SynthError {
+    fn from(error: std::io::Error) -> Self {
+        SynthError::IoError(error)
+    }
+}
+
+impl From<hound::Error> for SynthError {
+    fn from(error: hound::Error) -> Self {
+        SynthError::WavFileError(error)
+    }
+}
+
+impl From<toml::de::Error> for SynthError {
+    fn from(error: toml::de::Error) -> Self {
+        SynthError::ConfigError(error)
+    }
+}
+
+impl From<rodio::StreamError> for SynthError {
+    fn from(error: rodio::StreamError) -> Self {
+        SynthError::AudioDeviceError(error.to_string())
+    }
+}
+
+impl From<rodio::PlayError> for SynthError {
+    fn from(error: rodio::PlayError) -> Self {
+        SynthError::AudioDeviceError(error.to_string())
+    }
+}
+
+impl From<KeymapConfigError> for SynthError {
+    fn from(error: KeymapConfigError) -> Self {
+        match error {
+            KeymapConfigError::Io(error) => SynthError::IoError(error),
+            KeymapConfigError::Parse(error) => SynthError::ConfigError(error),
+            KeymapConfigError::Key(error) => SynthError::invalid_parameter("key map", "", error),
+            KeymapConfigError::Frequency { key, freq_hz } => {
+                SynthError::invalid_parameter("key map", key, format!("{freq_hz} is not a frequency"))
+            }
+        }
+    }
+}
+
+impl From<MidiFileError> for SynthError {
+    fn from(error: MidiFileError) -> Self {
+        match error {
+            MidiFileError::Io(error) => SynthError::IoError(error),
+            other => SynthError::MidiError(other.to_string()),
+        }
+    }
+}
+
+impl From<SerumWavetableError> for SynthError {
+    fn from(error: SerumWavetableError) -> Self {
+        match error {
+            SerumWavetableError::Wav(error) => SynthError::WavFileError(error),
+            SerumWavetableError::FrameOutOfRange {
+                frame_index,
+                frame_count,
+            } => SynthError::invalid_parameter(
+                "wavetable frame",
+                frame_index,
+                format!("the file has {frame_count} frames"),
+            ),
+        }
+    }
+}
+
+impl From<InvalidWaveTableSize> for SynthError {
+    fn from(error: InvalidWaveTableSize) -> Self {
+        if error.0.is_power_of_two() {
+            SynthError::invalid_parameter("wave table size", error.0, error)
+        } else {
+            SynthError::WaveTableSizeNotPowerOfTwo(error.0)
+        }
+    }
+}
+
+impl From<WaveLoadError> for SynthError {
+    fn from(error: WaveLoadError) -> Self {
+        match error {
+            WaveLoadError::Wav(error) => SynthError::WavFileError(error),
+            other => SynthError::invalid_parameter("wave table file", "", other),
+        }
+    }
+}
+
+impl From<PatchError> for SynthError {
+    fn from(error: PatchError) -> Self {
+        match error {
+            PatchError::Io(error) => SynthError::IoError(error),
+            PatchError::Parse { error, .. } => SynthError::ConfigError(error),
+            PatchError::Serialize(error) => SynthError::invalid_parameter("patch", "", error),
+        }
+    }
+}
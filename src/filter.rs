@@ -0,0 +1,136 @@
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+use synth_core::{Lfo, ParameterSmoother, StateVariableFilter};
+
+/// Note keyboard tracking is measured from: middle C.
+pub const TRACKING_REFERENCE_HZ: f32 = 261.63;
+
+/// How quickly the cutoff follows its control and the tracked note.
+const CUTOFF_SMOOTHING_MS: f32 = 10.0;
+
+/// How far the cutoff follows the played note. At 1.0 it moves hertz for hertz with
+/// the note, so the filter keeps passing the fundamental; at 0.5 half as far; at 0.0
+/// it stays put.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterTrackingMode {
+    pub keyboard_tracking: f32,
+}
+
+impl FilterTrackingMode {
+    /// `base_cutoff` moved by the played note's distance from [`TRACKING_REFERENCE_HZ`].
+    pub fn cutoff_hz(self, base_cutoff: f32, played_freq: f32) -> f32 {
+        if played_freq <= 0.0 {
+            return base_cutoff;
+        }
+        (base_cutoff + self.keyboard_tracking * (played_freq - TRACKING_REFERENCE_HZ)).max(20.0)
+    }
+}
+
+/// Runs a source through a low-pass [`StateVariableFilter`] with shared controls.
+/// Cutoff changes, including those from keyboard tracking, glide over about 10 ms;
+/// the LFO is added after the glide.
+pub struct SvfSource<S: Source<Item = f32>> {
+    source: S,
+    filter: StateVariableFilter,
+    cutoff_smoother: ParameterSmoother,
+    enabled: Arc<Mutex<bool>>,
+    cutoff_hz: Arc<Mutex<f32>>,
+    resonance: Arc<Mutex<f32>>,
+    tracking: Arc<Mutex<FilterTrackingMode>>,
+    played_freq: Option<Arc<Mutex<f32>>>,
+    cutoff_lfo: Arc<Mutex<Option<Lfo>>>,
+}
+
+impl<S: Source<Item = f32>> SvfSource<S> {
+    pub fn new(source: S, cutoff_hz: f32, resonance: f32) -> SvfSource<S> {
+        let sample_rate = source.sample_rate();
+        let mut cutoff_smoother = ParameterSmoother::new_with_time(CUTOFF_SMOOTHING_MS, sample_rate);
+        cutoff_smoother.reset(cutoff_hz);
+        SvfSource {
+            source,
+            filter: StateVariableFilter::new(sample_rate, cutoff_hz, resonance),
+            cutoff_smoother,
+            enabled: Arc::new(Mutex::new(false)),
+            cutoff_hz: Arc::new(Mutex::new(cutoff_hz)),
+            resonance: Arc::new(Mutex::new(resonance)),
+            tracking: Arc::new(Mutex::new(FilterTrackingMode::default())),
+            played_freq: None,
+            cutoff_lfo: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Lets the cutoff follow `frequency`, the played note, as far as the tracking
+    /// control says.
+    pub fn set_tracked_frequency(&mut self, frequency: Arc<Mutex<f32>>) {
+        self.played_freq = Some(frequency);
+    }
+
+    pub fn get_tracking_control(&self) -> Arc<Mutex<FilterTrackingMode>> {
+        self.tracking.clone()
+    }
+
+    /// An LFO sweeping the cutoff, its depth in hertz; `None` holds it still.
+    pub fn get_cutoff_lfo_control(&self) -> Arc<Mutex<Option<Lfo>>> {
+        self.cutoff_lfo.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+
+    pub fn get_cutoff_control(&self) -> Arc<Mutex<f32>> {
+        self.cutoff_hz.clone()
+    }
+
+    pub fn get_resonance_control(&self) -> Arc<Mutex<f32>> {
+        self.resonance.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for SvfSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for SvfSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            return Some(input);
+        }
+
+        let played_freq = self.played_freq.as_ref().and_then(|freq| freq.lock().ok().map(|freq| *freq));
+        let tracking = self.tracking.lock().map_or(FilterTrackingMode::default(), |tracking| *tracking);
+        if let Ok(cutoff) = self.cutoff_hz.lock() {
+            self.cutoff_smoother.set_target(tracking.cutoff_hz(*cutoff, played_freq.unwrap_or(0.0)));
+            let mut cutoff = self.cutoff_smoother.tick();
+            if let Ok(mut lfo) = self.cutoff_lfo.lock() {
+                if let Some(lfo) = lfo.as_mut() {
+                    cutoff = lfo.modulate(cutoff).max(20.0);
+                }
+            }
+            self.filter.set_cutoff_hz(cutoff);
+        }
+        if let Ok(resonance) = self.resonance.lock() {
+            self.filter.set_resonance(*resonance);
+        }
+
+        Some(self.filter.process(input).low)
+    }
+}
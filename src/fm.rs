@@ -0,0 +1,240 @@
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Operator tuning for a two-operator FM voice. Ratios are relative to the played note.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FmPreset {
+    pub name: &'static str,
+    pub carrier_ratio: f32,
+    pub modulator_ratio: f32,
+    pub mod_index: f32,
+    pub feedback: f32,
+    /// How much the carrier's own last output feeds back into its phase; see
+    /// [`FmFeedback`].
+    pub carrier_feedback: f32,
+    /// Shapes the modulation index over each note instead of holding `mod_index`.
+    pub index_envelope: Option<FmIndexEnvelope>,
+}
+
+/// How the FM modulation index moves over a note: up to `attack_index` over
+/// `attack_secs`, down to `sustain_index` over `decay_secs`, held there, then down to
+/// zero over `release_secs` once the note is released.
+///
+/// A high attack and low sustain gives the bright, clangy onset settling into a
+/// purer tone that DX7-style patches are known for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FmIndexEnvelope {
+    pub attack_index: f32,
+    pub attack_secs: f32,
+    pub decay_secs: f32,
+    pub sustain_index: f32,
+    pub release_secs: f32,
+}
+
+impl FmIndexEnvelope {
+    /// A metallic strike that decays to a nearly pure tone.
+    pub const BELL: FmIndexEnvelope = FmIndexEnvelope {
+        attack_index: 10.0,
+        attack_secs: 0.002,
+        decay_secs: 1.5,
+        sustain_index: 0.5,
+        release_secs: 0.5,
+    };
+
+    /// The index `elapsed_secs` into a note, or `released_secs` after its release,
+    /// which started from `release_from`.
+    fn index_at(&self, elapsed_secs: f32, released: Option<(f32, f32)>) -> f32 {
+        if let Some((released_secs, release_from)) = released {
+            let progress = released_secs / self.release_secs.max(1e-4);
+            return release_from * (1.0 - progress).max(0.0);
+        }
+        if elapsed_secs < self.attack_secs {
+            return self.attack_index * elapsed_secs / self.attack_secs;
+        }
+        let progress = ((elapsed_secs - self.attack_secs) / self.decay_secs.max(1e-4)).min(1.0);
+        self.attack_index + (self.sustain_index - self.attack_index) * progress
+    }
+}
+
+pub const BUILTIN_FM_PRESETS: &[FmPreset] = &[
+    FmPreset { name: "Bell",          carrier_ratio: 1.0, modulator_ratio: 3.5, mod_index: 5.0, feedback: 0.0, carrier_feedback: 0.0, index_envelope: Some(FmIndexEnvelope::BELL) },
+    FmPreset { name: "ElectricPiano", carrier_ratio: 1.0, modulator_ratio: 1.0, mod_index: 2.0, feedback: 0.0, carrier_feedback: 0.0, index_envelope: None },
+    FmPreset { name: "Brass",         carrier_ratio: 1.0, modulator_ratio: 1.0, mod_index: 3.5, feedback: 0.3, carrier_feedback: 0.0, index_envelope: None },
+    FmPreset { name: "Marimba",       carrier_ratio: 1.0, modulator_ratio: 3.0, mod_index: 4.0, feedback: 0.0, carrier_feedback: 0.0, index_envelope: None },
+    FmPreset { name: "Bass",          carrier_ratio: 1.0, modulator_ratio: 1.0, mod_index: 7.0, feedback: 0.0, carrier_feedback: 0.0, index_envelope: None },
+];
+
+/// Appends `preset` to a TOML file as a `[[preset]]` table, creating the file if needed.
+pub fn save_fm_preset(path: &Path, preset: &FmPreset) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    // Debug formatting gives valid TOML strings and floats, and keeps f32 values short
+    writeln!(file, "[[preset]]")?;
+    writeln!(file, "name = {:?}", preset.name)?;
+    writeln!(file, "carrier_ratio = {:?}", preset.carrier_ratio)?;
+    writeln!(file, "modulator_ratio = {:?}", preset.modulator_ratio)?;
+    writeln!(file, "mod_index = {:?}", preset.mod_index)?;
+    writeln!(file, "feedback = {:?}", preset.feedback)?;
+    writeln!(file, "carrier_feedback = {:?}", preset.carrier_feedback)?;
+    if let Some(envelope) = &preset.index_envelope {
+        writeln!(
+            file,
+            "index_envelope = {{ attack_index = {:?}, attack_secs = {:?}, decay_secs = {:?}, sustain_index = {:?}, release_secs = {:?} }}",
+            envelope.attack_index, envelope.attack_secs, envelope.decay_secs, envelope.sustain_index, envelope.release_secs
+        )?;
+    }
+    writeln!(file)
+}
+
+/// Self-feedback for an FM operator, as in DX7 algorithms like 4: the operator's
+/// previous output is added to its own phase.
+///
+/// Around 0.1-0.3 it adds a little harmonic richness, and past 1.0 a sine turns
+/// sawtooth-like. A `feedback_amount` of 0.0 leaves the operator untouched.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FmFeedback {
+    pub feedback_amount: f32,
+    prev_output: f32,
+}
+
+impl FmFeedback {
+    pub fn new(feedback_amount: f32) -> FmFeedback {
+        FmFeedback {
+            feedback_amount,
+            prev_output: 0.0,
+        }
+    }
+
+    /// Phase offset in radians for the operator's next sample.
+    pub fn phase_offset(&self) -> f32 {
+        self.feedback_amount * self.prev_output
+    }
+
+    /// Remembers the operator's output for the next sample.
+    pub fn record(&mut self, output: f32) {
+        self.prev_output = output;
+    }
+}
+
+/// A sine carrier phase-modulated by a sine modulator, which can feed back into itself.
+///
+/// A change to a new, non-zero frequency counts as a note-on for the preset's index
+/// envelope; [`note_on`](Self::note_on) and [`note_off`](Self::note_off) drive it
+/// directly.
+pub struct FmOscillator {
+    sample_rate: u32,
+    frequency: Arc<Mutex<f32>>,
+    preset: Arc<Mutex<FmPreset>>,
+    carrier_phase: f32,
+    modulator_phase: f32,
+    last_modulator: f32,
+    carrier_feedback: FmFeedback,
+    last_freq: f32,
+    note_secs: f32,
+    /// Seconds since release and the index the release started from.
+    released: Option<(f32, f32)>,
+}
+
+impl FmOscillator {
+    pub fn new(sample_rate: u32, frequency: Arc<Mutex<f32>>, preset: FmPreset) -> FmOscillator {
+        FmOscillator {
+            sample_rate,
+            frequency,
+            preset: Arc::new(Mutex::new(preset)),
+            carrier_phase: 0.0,
+            modulator_phase: 0.0,
+            last_modulator: 0.0,
+            carrier_feedback: FmFeedback::new(preset.carrier_feedback),
+            last_freq: 0.0,
+            note_secs: 0.0,
+            released: None,
+        }
+    }
+
+    pub fn get_preset_control(&self) -> Arc<Mutex<FmPreset>> {
+        self.preset.clone()
+    }
+
+    /// Restarts the index envelope from its attack.
+    pub fn note_on(&mut self) {
+        self.note_secs = 0.0;
+        self.released = None;
+    }
+
+    /// Starts the index envelope's release from wherever it is.
+    pub fn note_off(&mut self) {
+        if self.released.is_none() {
+            let envelope = self.preset.lock().ok().and_then(|p| p.index_envelope);
+            let current = envelope.map_or(0.0, |envelope| envelope.index_at(self.note_secs, None));
+            self.released = Some((0.0, current));
+        }
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let freq = self.frequency.lock().map_or(0.0, |f| *f);
+        if freq != self.last_freq {
+            if freq == 0.0 {
+                self.note_off();
+            } else {
+                self.note_on();
+            }
+            self.last_freq = freq;
+        }
+        if freq == 0.0 {
+            return 0.0;
+        }
+        let Ok(preset) = self.preset.lock().map(|p| *p) else {
+            return 0.0;
+        };
+
+        let modulator = (self.modulator_phase * TAU + preset.feedback * self.last_modulator).sin();
+        let mod_index = match preset.index_envelope {
+            Some(envelope) => envelope.index_at(self.note_secs, self.released),
+            None => preset.mod_index,
+        };
+        self.carrier_feedback.feedback_amount = preset.carrier_feedback;
+        let phase_offset = mod_index * modulator + self.carrier_feedback.phase_offset();
+        let carrier = (self.carrier_phase * TAU + phase_offset).sin();
+        self.carrier_feedback.record(carrier);
+        self.last_modulator = modulator;
+
+        let step = freq / self.sample_rate as f32;
+        self.carrier_phase = (self.carrier_phase + step * preset.carrier_ratio).fract();
+        self.modulator_phase = (self.modulator_phase + step * preset.modulator_ratio).fract();
+        let dt = 1.0 / self.sample_rate as f32;
+        match &mut self.released {
+            Some((released_secs, _)) => *released_secs += dt,
+            None => self.note_secs += dt,
+        }
+
+        carrier * 0.3
+    }
+}
+
+impl Source for FmOscillator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for FmOscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
@@ -0,0 +1,169 @@
+use crate::ringbuf::RingBuffer;
+use crate::window::FftWindow;
+use rodio::Source;
+use rustfft::num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::{PI, TAU};
+use std::sync::{Arc, Mutex};
+
+const FFT_SIZE: usize = 2048;
+const HOP: usize = FFT_SIZE / 4;
+const CROSSFADE_SECS: f32 = 0.1;
+
+/// Holds one FFT frame of a mono source and resynthesizes it indefinitely.
+///
+/// The live signal is analysed every hop. Engaging the freeze control captures the
+/// latest frame's magnitudes, along with how far each bin's phase moved over the last
+/// hop, and then keeps rotating the phases at that rate through inverse FFTs and
+/// overlap-add. Engaging and releasing both crossfade over 100 ms.
+pub struct SpectralFreeze<S: Source<Item = f32>> {
+    source: S,
+    frozen_magnitudes: Vec<f32>,
+    frozen_phases: Vec<f32>,
+    phase_advance: Vec<f32>,
+    previous_phases: Vec<f32>,
+    fft_size: usize,
+    hop: usize,
+    running: Arc<Mutex<bool>>,
+    captured: bool,
+    mix: f32,
+    mix_step: f32,
+    window: Vec<f32>,
+    input: RingBuffer<f32>,
+    overlap: Vec<f32>,
+    hop_pos: usize,
+    spectrum: Vec<Complex32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+}
+
+impl<S: Source<Item = f32>> SpectralFreeze<S> {
+    pub fn new(source: S) -> SpectralFreeze<S> {
+        let mut planner = FftPlanner::new();
+        let bins = FFT_SIZE / 2 + 1;
+        let mix_step = 1.0 / (CROSSFADE_SECS * source.sample_rate() as f32);
+
+        SpectralFreeze {
+            source,
+            frozen_magnitudes: vec![0.0; bins],
+            frozen_phases: vec![0.0; bins],
+            phase_advance: vec![0.0; bins],
+            previous_phases: vec![0.0; bins],
+            fft_size: FFT_SIZE,
+            hop: HOP,
+            running: Arc::new(Mutex::new(false)),
+            captured: false,
+            mix: 0.0,
+            mix_step,
+            window: FftWindow::Hann.coefficients(FFT_SIZE),
+            input: RingBuffer::new(FFT_SIZE),
+            overlap: vec![0.0; FFT_SIZE],
+            hop_pos: 0,
+            spectrum: vec![Complex32::default(); FFT_SIZE],
+            forward: planner.plan_fft_forward(FFT_SIZE),
+            inverse: planner.plan_fft_inverse(FFT_SIZE),
+        }
+    }
+
+    /// `true` holds the current spectrum, `false` returns to the live signal.
+    pub fn get_freeze_control(&self) -> Arc<Mutex<bool>> {
+        self.running.clone()
+    }
+
+    fn process_hop(&mut self, running: bool) {
+        let bins = self.fft_size / 2 + 1;
+
+        // Analyse the newest fft_size samples, oldest first
+        for i in 0..self.fft_size {
+            let sample = *self.input.read_at(self.fft_size - i);
+            self.spectrum[i] = Complex32::new(sample * self.window[i], 0.0);
+        }
+        self.forward.process(&mut self.spectrum);
+
+        for bin in 0..bins {
+            let phase = self.spectrum[bin].arg();
+            if running && !self.captured {
+                self.frozen_magnitudes[bin] = self.spectrum[bin].norm();
+                self.frozen_phases[bin] = phase;
+                self.phase_advance[bin] = phase - self.previous_phases[bin];
+            }
+            self.previous_phases[bin] = phase;
+        }
+        if running {
+            self.captured = true;
+        }
+
+        self.overlap.rotate_left(self.hop);
+        let tail = self.fft_size - self.hop;
+        self.overlap[tail..].fill(0.0);
+        if !self.captured {
+            return;
+        }
+
+        for bin in 0..bins {
+            let phase = (self.frozen_phases[bin] + self.phase_advance[bin] + PI).rem_euclid(TAU) - PI;
+            self.frozen_phases[bin] = phase;
+            self.spectrum[bin] = Complex32::from_polar(self.frozen_magnitudes[bin], phase);
+        }
+        // Mirror the bins so the inverse transform comes out real
+        for bin in bins..self.fft_size {
+            self.spectrum[bin] = self.spectrum[self.fft_size - bin].conj();
+        }
+        self.inverse.process(&mut self.spectrum);
+
+        // Hann squared at 75% overlap sums to 1.5, and the inverse FFT is unscaled
+        let scale = 1.0 / (1.5 * self.fft_size as f32);
+        for i in 0..self.fft_size {
+            self.overlap[i] += self.spectrum[i].re * self.window[i] * scale;
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for SpectralFreeze<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for SpectralFreeze<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let live = self.source.next()?;
+        let running = self.running.lock().is_ok_and(|running| *running);
+
+        self.input.push(live);
+        let frozen = self.overlap[self.hop_pos];
+        self.hop_pos += 1;
+        if self.hop_pos == self.hop {
+            self.hop_pos = 0;
+            self.process_hop(running);
+        }
+
+        let target = if running && self.captured { 1.0 } else { 0.0 };
+        if self.mix < target {
+            self.mix = (self.mix + self.mix_step).min(target);
+        } else if self.mix > target {
+            self.mix = (self.mix - self.mix_step).max(target);
+        }
+        // Drop the snapshot once fully back on the live signal, so the next freeze
+        // captures afresh
+        if !running && self.mix == 0.0 {
+            self.captured = false;
+        }
+
+        Some(live * (1.0 - self.mix) + frozen * self.mix)
+    }
+}
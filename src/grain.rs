@@ -0,0 +1,140 @@
+use std::f32::consts::PI;
+
+/// Amplitude curves for shaping a grain, evaluated over its length from 0.0 to 1.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GrainEnvelopeShape {
+    Hann,
+    /// Linear ramps either side of a flat top taking `flat_top_ratio` of the grain.
+    Trapezoid { flat_top_ratio: f32 },
+    /// A bell on the grain's midpoint, `width` its standard deviation as a fraction of
+    /// the grain. Lowered and rescaled so it still starts and ends at zero.
+    Gaussian { width: f32 },
+    Triangle,
+}
+
+/// `shape` at `position_normalized` through the grain: 0.0 at both edges and 1.0 at
+/// the peak, which for these shapes is the middle. Positions outside 0.0-1.0 are silent.
+pub fn evaluate_grain_envelope(shape: GrainEnvelopeShape, position_normalized: f32) -> f32 {
+    if !(0.0..=1.0).contains(&position_normalized) {
+        return 0.0;
+    }
+
+    let p = position_normalized;
+    // Distance from the nearer edge, 0.0-0.5
+    let edge_distance = p.min(1.0 - p);
+    match shape {
+        GrainEnvelopeShape::Hann => 0.5 - 0.5 * (2.0 * PI * p).cos(),
+        GrainEnvelopeShape::Trapezoid { flat_top_ratio } => {
+            let ramp = (1.0 - flat_top_ratio.clamp(0.0, 1.0)) / 2.0;
+            if ramp <= 0.0 {
+                1.0
+            } else {
+                (edge_distance / ramp).min(1.0)
+            }
+        }
+        GrainEnvelopeShape::Gaussian { width } => {
+            let width = width.max(1e-3);
+            let bell = |p: f32| (-0.5 * ((p - 0.5) / width).powi(2)).exp();
+            let edge = bell(0.0);
+            ((bell(p) - edge) / (1.0 - edge)).max(0.0)
+        }
+        GrainEnvelopeShape::Triangle => 2.0 * edge_distance,
+    }
+}
+
+/// A grain's amplitude envelope: the rising half of `shape` stretched over the first
+/// `attack_ratio` of the grain, the falling half over the last `release_ratio`, and
+/// full level in between. Ratios of 0.5 each give the plain shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrainEnvelope {
+    pub shape: GrainEnvelopeShape,
+    pub attack_ratio: f32,
+    pub release_ratio: f32,
+}
+
+impl Default for GrainEnvelope {
+    fn default() -> Self {
+        GrainEnvelope::new(GrainEnvelopeShape::Hann)
+    }
+}
+
+impl GrainEnvelope {
+    pub fn new(shape: GrainEnvelopeShape) -> GrainEnvelope {
+        GrainEnvelope {
+            shape,
+            attack_ratio: 0.5,
+            release_ratio: 0.5,
+        }
+    }
+
+    pub fn amplitude(&self, position_normalized: f32) -> f32 {
+        let p = position_normalized;
+        if !(0.0..=1.0).contains(&p) {
+            return 0.0;
+        }
+
+        // Attack and release share the grain if together they ask for more than all of it
+        let total = self.attack_ratio.max(0.0) + self.release_ratio.max(0.0);
+        let scale = if total > 1.0 { 1.0 / total } else { 1.0 };
+        let attack = self.attack_ratio.max(0.0) * scale;
+        let release = self.release_ratio.max(0.0) * scale;
+
+        let shape_position = if p < attack {
+            0.5 * p / attack
+        } else if p > 1.0 - release {
+            1.0 - 0.5 * (1.0 - p) / release
+        } else {
+            0.5
+        };
+        evaluate_grain_envelope(self.shape, shape_position)
+    }
+
+    /// The envelope sampled at `len` points from edge to edge, for multiplying into a
+    /// grain buffer.
+    pub fn table(&self, len: usize) -> Vec<f32> {
+        if len < 2 {
+            return vec![1.0; len];
+        }
+        (0..len).map(|i| self.amplitude(i as f32 / (len - 1) as f32)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: [GrainEnvelopeShape; 4] = [
+        GrainEnvelopeShape::Hann,
+        GrainEnvelopeShape::Trapezoid { flat_top_ratio: 0.4 },
+        GrainEnvelopeShape::Gaussian { width: 0.15 },
+        GrainEnvelopeShape::Triangle,
+    ];
+
+    #[test]
+    fn every_shape_is_silent_at_the_edges_and_full_in_the_middle() {
+        for shape in SHAPES {
+            assert!(evaluate_grain_envelope(shape, 0.0).abs() < 1e-6, "{shape:?} at 0.0");
+            assert!((evaluate_grain_envelope(shape, 0.5) - 1.0).abs() < 1e-6, "{shape:?} at 0.5");
+            assert!(evaluate_grain_envelope(shape, 1.0).abs() < 1e-6, "{shape:?} at 1.0");
+        }
+    }
+
+    #[test]
+    fn trapezoid_holds_across_its_flat_top() {
+        let shape = GrainEnvelopeShape::Trapezoid { flat_top_ratio: 0.4 };
+        assert_eq!(evaluate_grain_envelope(shape, 0.3), 1.0);
+        assert_eq!(evaluate_grain_envelope(shape, 0.7), 1.0);
+        assert!((evaluate_grain_envelope(shape, 0.15) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn short_attack_reaches_full_level_early() {
+        let envelope = GrainEnvelope {
+            attack_ratio: 0.1,
+            ..GrainEnvelope::new(GrainEnvelopeShape::Triangle)
+        };
+        assert!((envelope.amplitude(0.05) - 0.5).abs() < 1e-6);
+        assert_eq!(envelope.amplitude(0.1), 1.0);
+        assert_eq!(envelope.amplitude(1.0), 0.0);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::delay::FeedbackDelay;
+use crate::envelope::AdsrEnvelope;
+use crate::resonator::{BiquadResonator, ResonatorBank};
+use crate::reverb::Reverb;
+use rodio::Source;
+use std::fmt;
+use synth_core::{StateVariableFilter, WaveTableCore};
+
+/// A processing block in an [`AudioGraph`]. Each node sees one value per input
+/// port and produces a single output sample per tick.
+pub trait DspNode: Send {
+    fn process(&mut self, inputs: &[f32]) -> f32;
+
+    /// The concrete type, for diagnostics.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Marks DSP types whose per-sample processing never locks, allocates or blocks, so
+/// they can't stall the audio thread behind a lower-priority one.
+///
+/// Sources with `Arc<Mutex<_>>` controls don't qualify: the control thread can hold
+/// the lock just as the audio callback needs it. [`AudioGraph::add_node`] only takes
+/// nodes with this marker, so putting anything else on the graph that way fails to
+/// compile.
+pub trait RealtimeSafe {}
+
+impl RealtimeSafe for StateVariableFilter {}
+impl<T: AsRef<[f32]> + Send> RealtimeSafe for WaveTableCore<T> {}
+impl RealtimeSafe for AdsrEnvelope {}
+impl RealtimeSafe for FeedbackDelay {}
+impl RealtimeSafe for BiquadResonator {}
+impl RealtimeSafe for ResonatorBank {}
+impl RealtimeSafe for Reverb {}
+
+impl<S: Source<Item = f32> + Send + RealtimeSafe> RealtimeSafe for SourceNode<S> {}
+
+/// A node in an [`AudioGraph`] that wasn't checked for [`RealtimeSafe`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealtimeSafetyViolation {
+    pub node: usize,
+    pub type_name: &'static str,
+}
+
+impl fmt::Display for RealtimeSafetyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {} ({}) is not known to be real-time safe", self.node, self.type_name)
+    }
+}
+
+impl std::error::Error for RealtimeSafetyViolation {}
+
+/// Lets any mono rodio source act as a generator node. Inputs are ignored and an
+/// exhausted source falls silent.
+pub struct SourceNode<S: Source<Item = f32> + Send> {
+    source: S,
+}
+
+impl<S: Source<Item = f32> + Send> SourceNode<S> {
+    pub fn new(source: S) -> SourceNode<S> {
+        SourceNode { source }
+    }
+}
+
+impl<S: Source<Item = f32> + Send> DspNode for SourceNode<S> {
+    fn process(&mut self, _inputs: &[f32]) -> f32 {
+        self.source.next().unwrap_or(0.0)
+    }
+}
+
+/// Sums all inputs and outputs the low-pass response.
+impl DspNode for StateVariableFilter {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        StateVariableFilter::process(self, inputs.iter().sum()).low
+    }
+}
+
+/// Plays the table at the core's own increment; inputs are ignored.
+impl<T: AsRef<[f32]> + Send> DspNode for WaveTableCore<T> {
+    fn process(&mut self, _inputs: &[f32]) -> f32 {
+        self.next_sample(None)
+    }
+}
+
+/// Scales the sum of the inputs by the envelope's level.
+impl DspNode for AdsrEnvelope {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        inputs.iter().sum::<f32>() * self.next_level()
+    }
+}
+
+/// The rest sum all inputs into their one mono input.
+impl DspNode for FeedbackDelay {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        FeedbackDelay::process(self, inputs.iter().sum())
+    }
+}
+
+impl DspNode for BiquadResonator {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        BiquadResonator::process(self, inputs.iter().sum())
+    }
+}
+
+impl DspNode for ResonatorBank {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        ResonatorBank::process(self, inputs.iter().sum())
+    }
+}
+
+impl DspNode for Reverb {
+    fn process(&mut self, inputs: &[f32]) -> f32 {
+        Reverb::process(self, inputs.iter().sum())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    UnknownNode(usize),
+    Cycle,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownNode(index) => write!(f, "edge refers to missing node {index}"),
+            GraphError::Cycle => write!(f, "audio graph contains a cycle"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+struct GraphNode {
+    node: Box<dyn DspNode>,
+    inputs: Vec<f32>,
+    /// `(from_node, port)` pairs feeding this node, resolved once at construction
+    incoming: Vec<(usize, usize)>,
+    output: f32,
+    /// Added through [`AudioGraph::add_node`], so known to be [`RealtimeSafe`].
+    realtime_safe: bool,
+}
+
+/// Directed acyclic graph of DSP nodes, evaluated one sample at a time.
+///
+/// An edge `(from_node, port, to_node)` feeds `from_node`'s output into input slot
+/// `port` of `to_node`; several edges into the same port are summed. The evaluation
+/// order is sorted once in [`AudioGraph::new`], so `tick` never re-walks the edges.
+///
+/// This is library API only: the synth itself still plays through the fixed chain of
+/// sources `main` builds, and nothing in the binary runs on a graph.
+pub struct AudioGraph {
+    sample_rate: u32,
+    nodes: Vec<GraphNode>,
+    edges: Vec<(usize, usize, usize)>,
+    order: Vec<usize>,
+    output_node: usize,
+}
+
+impl AudioGraph {
+    pub fn new(
+        sample_rate: u32,
+        nodes: Vec<Box<dyn DspNode>>,
+        edges: Vec<(usize, usize, usize)>,
+        output_node: usize,
+    ) -> Result<AudioGraph, GraphError> {
+        if output_node >= nodes.len() {
+            return Err(GraphError::UnknownNode(output_node));
+        }
+
+        let mut nodes: Vec<GraphNode> = nodes
+            .into_iter()
+            .map(|node| GraphNode {
+                node,
+                inputs: Vec::new(),
+                incoming: Vec::new(),
+                output: 0.0,
+                realtime_safe: false,
+            })
+            .collect();
+
+        for &(from, port, to) in &edges {
+            for index in [from, to] {
+                if index >= nodes.len() {
+                    return Err(GraphError::UnknownNode(index));
+                }
+            }
+            let target = &mut nodes[to];
+            target.incoming.push((from, port));
+            if target.inputs.len() <= port {
+                target.inputs.resize(port + 1, 0.0);
+            }
+        }
+
+        let order = topological_order(nodes.len(), &edges)?;
+
+        Ok(AudioGraph {
+            sample_rate,
+            nodes,
+            edges,
+            order,
+            output_node,
+        })
+    }
+
+    /// A graph with no nodes, to build up with [`add_node`](Self::add_node) so that
+    /// every node is [`RealtimeSafe`]. It plays silence until it has an output node.
+    pub fn empty(sample_rate: u32) -> AudioGraph {
+        AudioGraph {
+            sample_rate,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            order: Vec::new(),
+            output_node: 0,
+        }
+    }
+
+    pub fn edges(&self) -> &[(usize, usize, usize)] {
+        &self.edges
+    }
+
+    /// Adds an unconnected node and returns its index.
+    pub fn add_node<N: DspNode + RealtimeSafe + 'static>(&mut self, node: N) -> usize {
+        self.nodes.push(GraphNode {
+            node: Box::new(node),
+            inputs: Vec::new(),
+            incoming: Vec::new(),
+            output: 0.0,
+            realtime_safe: true,
+        });
+        self.order.push(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Adds the edge `(from_node, port, to_node)` and re-sorts the evaluation order.
+    /// An edge that would close a cycle is refused and the graph left as it was.
+    pub fn connect(&mut self, from: usize, port: usize, to: usize) -> Result<(), GraphError> {
+        for index in [from, to] {
+            if index >= self.nodes.len() {
+                return Err(GraphError::UnknownNode(index));
+            }
+        }
+        self.edges.push((from, port, to));
+        match topological_order(self.nodes.len(), &self.edges) {
+            Ok(order) => self.order = order,
+            Err(error) => {
+                self.edges.pop();
+                return Err(error);
+            }
+        }
+
+        let target = &mut self.nodes[to];
+        target.incoming.push((from, port));
+        if target.inputs.len() <= port {
+            target.inputs.resize(port + 1, 0.0);
+        }
+        Ok(())
+    }
+
+    pub fn set_output_node(&mut self, output_node: usize) -> Result<(), GraphError> {
+        if output_node >= self.nodes.len() {
+            return Err(GraphError::UnknownNode(output_node));
+        }
+        self.output_node = output_node;
+        Ok(())
+    }
+
+    /// Evaluates every node once and returns the output node's sample.
+    pub fn tick(&mut self) -> f32 {
+        for &index in &self.order {
+            let mut inputs = std::mem::take(&mut self.nodes[index].inputs);
+            inputs.fill(0.0);
+            for &(from, port) in &self.nodes[index].incoming {
+                inputs[port] += self.nodes[from].output;
+            }
+
+            let node = &mut self.nodes[index];
+            node.output = node.node.process(&inputs);
+            node.inputs = inputs;
+        }
+
+        self.nodes.get(self.output_node).map_or(0.0, |node| node.output)
+    }
+}
+
+/// Checks that every node of `graph` came in through [`AudioGraph::add_node`], and
+/// so is [`RealtimeSafe`]. Nodes handed to [`AudioGraph::new`] are boxed trait objects
+/// whose safety can't be known, so they are reported.
+pub fn check_realtime_safety(graph: &AudioGraph) -> Result<(), RealtimeSafetyViolation> {
+    match graph.nodes.iter().position(|node| !node.realtime_safe) {
+        Some(node) => Err(RealtimeSafetyViolation {
+            node,
+            type_name: graph.nodes[node].node.type_name(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Kahn's algorithm; fails if some nodes never reach zero in-degree.
+fn topological_order(
+    node_count: usize,
+    edges: &[(usize, usize, usize)],
+) -> Result<Vec<usize>, GraphError> {
+    let mut in_degree = vec![0; node_count];
+    for &(_, _, to) in edges {
+        in_degree[to] += 1;
+    }
+
+    let mut ready: Vec<usize> = (0..node_count).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(node_count);
+
+    while let Some(index) = ready.pop() {
+        order.push(index);
+        for &(from, _, to) in edges {
+            if from == index {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push(to);
+                }
+            }
+        }
+    }
+
+    if order.len() == node_count {
+        Ok(order)
+    } else {
+        Err(GraphError::Cycle)
+    }
+}
+
+impl Source for AudioGraph {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for AudioGraph {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.tick())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs `value` plus the sum of its inputs, so a node's output shows what fed it.
+    struct Add(f32);
+
+    impl DspNode for Add {
+        fn process(&mut self, inputs: &[f32]) -> f32 {
+            self.0 + inputs.iter().sum::<f32>()
+        }
+    }
+
+    fn nodes(values: &[f32]) -> Vec<Box<dyn DspNode>> {
+        values.iter().map(|&value| Box::new(Add(value)) as Box<dyn DspNode>).collect()
+    }
+
+    fn position(graph: &AudioGraph, node: usize) -> usize {
+        graph.order.iter().position(|&index| index == node).unwrap()
+    }
+
+    #[test]
+    fn diamond_runs_each_node_after_its_inputs() {
+        // 0 feeds 1 and 2, which both feed 3
+        let edges = vec![(0, 0, 1), (0, 0, 2), (1, 0, 3), (2, 1, 3)];
+        let mut graph = AudioGraph::new(44100, nodes(&[1.0, 10.0, 100.0, 1000.0]), edges, 3).unwrap();
+        assert!(position(&graph, 0) < position(&graph, 1));
+        assert!(position(&graph, 0) < position(&graph, 2));
+        assert!(position(&graph, 1) < position(&graph, 3));
+        assert!(position(&graph, 2) < position(&graph, 3));
+        // Every input is this tick's, so the first sample is already the settled sum
+        assert_eq!(graph.tick(), 1000.0 + 11.0 + 101.0);
+    }
+
+    #[test]
+    fn back_edge_is_refused_and_the_graph_left_alone() {
+        let mut graph = AudioGraph::new(44100, nodes(&[1.0, 2.0, 3.0]), vec![(0, 0, 1), (1, 0, 2)], 2).unwrap();
+        let (edges, order) = (graph.edges().to_vec(), graph.order.clone());
+        assert_eq!(graph.connect(2, 0, 0), Err(GraphError::Cycle));
+        assert_eq!(graph.edges(), edges);
+        assert_eq!(graph.order, order);
+        assert!(graph.nodes[0].incoming.is_empty());
+        assert_eq!(graph.tick(), 6.0);
+    }
+
+    #[test]
+    fn back_edge_fails_construction() {
+        let result = AudioGraph::new(44100, nodes(&[1.0, 2.0]), vec![(0, 0, 1), (1, 0, 0)], 1);
+        assert!(matches!(result, Err(GraphError::Cycle)));
+    }
+
+    #[test]
+    fn realtime_safe_nodes_pass_the_check() {
+        let mut graph = AudioGraph::empty(44100);
+        assert_eq!(graph.tick(), 0.0);
+        let filter = graph.add_node(BiquadResonator::new(44100, 1000.0, 1.0, 0.0));
+        let delay = graph.add_node(FeedbackDelay::new(2, 0.5));
+        graph.connect(filter, 0, delay).unwrap();
+        graph.set_output_node(delay).unwrap();
+        assert_eq!(check_realtime_safety(&graph), Ok(()));
+    }
+
+    #[test]
+    fn boxed_nodes_fail_the_check() {
+        let mut graph = AudioGraph::new(44100, nodes(&[1.0]), Vec::new(), 0).unwrap();
+        let delay = graph.add_node(FeedbackDelay::new(2, 0.5));
+        graph.connect(0, 0, delay).unwrap();
+        // Node 0 came in boxed through new, so nothing vouches for it
+        let violation = check_realtime_safety(&graph).unwrap_err();
+        assert_eq!(violation.node, 0);
+        assert!(violation.type_name.ends_with("Add"), "{}", violation.type_name);
+    }
+
+    #[test]
+    fn sources_into_one_port_are_summed() {
+        let mut graph = AudioGraph::new(44100, nodes(&[0.25, 0.5, 0.0]), vec![(0, 0, 2), (1, 0, 2)], 2).unwrap();
+        assert_eq!(graph.nodes[2].inputs.len(), 1);
+        assert_eq!(graph.tick(), 0.75);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Length of the pitch shifter's sweeping delay window.
+const WINDOW_SECS: f32 = 0.04;
+
+/// One pitch-shifted copy mixed in by a [`Harmonizer`].
+///
+/// Shifting works by reading the delay buffer at `2^(interval / 12)` times normal
+/// speed. The read point sweeps through the window, so two taps half a window apart
+/// are crossfaded to hide each one's jump back when it wraps around.
+pub struct HarmonizerVoice {
+    pub interval_semitones: i32,
+    pub mix: f32,
+    delay_buf: RingBuffer<f32>,
+    window: f32,
+    delay: f32,
+}
+
+impl HarmonizerVoice {
+    pub fn new(sample_rate: u32, interval_semitones: i32, mix: f32) -> HarmonizerVoice {
+        let window = (WINDOW_SECS * sample_rate as f32).max(4.0);
+        HarmonizerVoice {
+            interval_semitones,
+            mix,
+            // Room for the whole window plus the interpolation neighbour
+            delay_buf: RingBuffer::new(window as usize + 2),
+            window,
+            delay: 0.0,
+        }
+    }
+
+    /// Stores `input` and returns the shifted signal. An interval of 0 passes the
+    /// input through untouched.
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.delay_buf.push(input);
+        let shifted = if self.interval_semitones == 0 {
+            input
+        } else {
+            let first = self.delay;
+            let second = (self.delay + self.window / 2.0) % self.window;
+            // sin² + cos² keeps the pair at constant gain, and each tap is silent
+            // where it wraps
+            let first_gain = (PI * first / self.window).sin().powi(2);
+            self.tap(first) * first_gain + self.tap(second) * (1.0 - first_gain)
+        };
+
+        let speed = 2.0_f32.powf(self.interval_semitones as f32 / 12.0);
+        self.delay = (self.delay + 1.0 - speed).rem_euclid(self.window);
+        shifted
+    }
+
+    /// The sample `delay` samples behind the newest one, interpolated.
+    fn tap(&self, delay: f32) -> f32 {
+        self.delay_buf.read_interpolated(1.0 + delay)
+    }
+}
+
+/// Interval sets for [`Harmonizer::from_preset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HarmonyPreset {
+    ThirdUp,
+    FifthUp,
+    OctaveUp,
+    OctaveDown,
+}
+
+impl HarmonyPreset {
+    pub fn interval_semitones(self) -> i32 {
+        match self {
+            HarmonyPreset::ThirdUp => 4,
+            HarmonyPreset::FifthUp => 7,
+            HarmonyPreset::OctaveUp => 12,
+            HarmonyPreset::OctaveDown => -12,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HarmonyPreset::ThirdUp => "+3rd",
+            HarmonyPreset::FifthUp => "+5th",
+            HarmonyPreset::OctaveUp => "+octave",
+            HarmonyPreset::OctaveDown => "-octave",
+        }
+    }
+
+    /// Steps through off -> +3rd -> +5th -> +octave -> -octave -> off.
+    pub fn cycle(preset: Option<HarmonyPreset>) -> Option<HarmonyPreset> {
+        match preset {
+            None => Some(HarmonyPreset::ThirdUp),
+            Some(HarmonyPreset::ThirdUp) => Some(HarmonyPreset::FifthUp),
+            Some(HarmonyPreset::FifthUp) => Some(HarmonyPreset::OctaveUp),
+            Some(HarmonyPreset::OctaveUp) => Some(HarmonyPreset::OctaveDown),
+            Some(HarmonyPreset::OctaveDown) => None,
+        }
+    }
+}
+
+/// Mixes pitch-shifted copies of a signal with the dry signal.
+pub struct Harmonizer {
+    pub voices: Vec<HarmonizerVoice>,
+}
+
+impl Harmonizer {
+    pub fn new(voices: Vec<HarmonizerVoice>) -> Harmonizer {
+        Harmonizer { voices }
+    }
+
+    pub fn from_preset(sample_rate: u32, preset: HarmonyPreset) -> Harmonizer {
+        Harmonizer::new(vec![HarmonizerVoice::new(sample_rate, preset.interval_semitones(), 0.5)])
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let wet: f32 = self.voices.iter_mut().map(|voice| voice.mix * voice.process(input)).sum();
+        input + wet
+    }
+}
+
+/// Runs a source through a [`Harmonizer`] picked by a shared preset control; `None`
+/// bypasses it.
+pub struct HarmonizerSource<S: Source<Item = f32>> {
+    source: S,
+    harmonizer: Option<Harmonizer>,
+    active_preset: Option<HarmonyPreset>,
+    preset: Arc<Mutex<Option<HarmonyPreset>>>,
+}
+
+impl<S: Source<Item = f32>> HarmonizerSource<S> {
+    pub fn new(source: S) -> HarmonizerSource<S> {
+        HarmonizerSource {
+            source,
+            harmonizer: None,
+            active_preset: None,
+            preset: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn get_preset_control(&self) -> Arc<Mutex<Option<HarmonyPreset>>> {
+        self.preset.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for HarmonizerSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for HarmonizerSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+
+        let preset = self.preset.lock().ok().and_then(|preset| *preset);
+        if preset != self.active_preset {
+            self.active_preset = preset;
+            self.harmonizer = preset.map(|preset| Harmonizer::from_preset(self.source.sample_rate(), preset));
+        }
+
+        match &mut self.harmonizer {
+            Some(harmonizer) => Some(harmonizer.process(input)),
+            None => Some(input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    fn sine(freq: f32, len: usize) -> impl Iterator<Item = f32> {
+        (0..len).map(move |i| (TAU * freq * i as f32 / 44100.0).sin())
+    }
+
+    #[test]
+    fn unison_voice_is_the_input() {
+        let mut voice = HarmonizerVoice::new(44100, 0, 1.0);
+        for input in sine(440.0, 4410) {
+            assert_eq!(voice.process(input), input);
+        }
+        let mut harmonizer = Harmonizer::new(vec![HarmonizerVoice::new(44100, 0, 0.5)]);
+        for input in sine(440.0, 4410) {
+            assert_eq!(harmonizer.process(input), input * 1.5);
+        }
+    }
+
+    #[test]
+    fn octave_up_moves_the_pitch_up_an_octave() {
+        let mut voice = HarmonizerVoice::new(44100, 12, 1.0);
+        let shifted: Vec<f32> = sine(220.0, 44100).map(|input| voice.process(input)).collect();
+        // Over the last half second, past the first window. The crossfade warbles at the
+        // window rate, so the energy sits in sidebands around 440 Hz rather than on it,
+        // but none is left at the original pitch
+        let tail = &shifted[22050..];
+        let magnitude = |freq: f32| {
+            let (re, im) = tail.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, sample)| {
+                let angle = TAU * freq * i as f32 / 44100.0;
+                (re + sample * angle.cos(), im + sample * angle.sin())
+            });
+            (re * re + im * im).sqrt() / (tail.len() as f32 / 2.0)
+        };
+        let octave_band = (400..=480).map(|freq| magnitude(freq as f32)).fold(0.0, f32::max);
+        let original = magnitude(220.0);
+        assert!(octave_band > 0.3 && original < 0.01, "octave band {octave_band}, 220 Hz {original}");
+    }
+}
@@ -0,0 +1,115 @@
+use crate::error::SynthError;
+use crossbeam::queue::SegQueue;
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, FromSample, SampleFormat, SizedSample};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// The most microphone audio queued for [`MicThroughSource`](crate::MicThroughSource);
+/// older samples are dropped, so the monitored signal never falls further behind.
+pub const MIC_THROUGH_MAX_LATENCY_SECS: f32 = 0.03;
+
+/// Where a modulation target takes its signal from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModulationSource {
+    Off,
+    Microphone,
+}
+
+/// The last second of the default input device, mixed down to mono.
+pub struct AudioInput {
+    pub buffer: Arc<Mutex<VecDeque<f32>>>,
+    pub sample_rate: u32,
+    /// Every captured sample in order, for playing the microphone through.
+    pub(crate) through: Arc<SegQueue<f32>>,
+    // Capture stops when the stream is dropped
+    _stream: cpal::Stream,
+}
+
+impl AudioInput {
+    /// Copy of the newest `n` samples, oldest first.
+    pub fn latest(&self, n: usize) -> Vec<f32> {
+        let Ok(buffer) = self.buffer.lock() else {
+            return Vec::new();
+        };
+        buffer.iter().skip(buffer.len().saturating_sub(n)).copied().collect()
+    }
+
+    /// RMS level of the newest `window` samples.
+    pub fn rms(&self, window: usize) -> f32 {
+        let Ok(buffer) = self.buffer.lock() else {
+            return 0.0;
+        };
+        let window = window.min(buffer.len());
+        if window == 0 {
+            return 0.0;
+        }
+
+        let sum: f32 = buffer.iter().rev().take(window).map(|s| s * s).sum();
+        (sum / window as f32).sqrt()
+    }
+}
+
+pub fn open_default_input() -> Result<AudioInput, SynthError> {
+    let device_error = |error: &dyn std::fmt::Display| SynthError::AudioDeviceError(error.to_string());
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| device_error(&"no default input device"))?;
+    let config = device.default_input_config().map_err(|error| device_error(&error))?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+
+    let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(sample_rate as usize)));
+    let through = Arc::new(SegQueue::new());
+    let queues = (buffer.clone(), through.clone());
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => build_input_stream::<f32>(&device, &config.into(), channels, queues),
+        SampleFormat::I16 => build_input_stream::<i16>(&device, &config.into(), channels, queues),
+        SampleFormat::U16 => build_input_stream::<u16>(&device, &config.into(), channels, queues),
+        other => return Err(device_error(&format!("unsupported input sample format {other}"))),
+    }
+    .map_err(|error| device_error(&error))?;
+    stream.play().map_err(|error| device_error(&error))?;
+
+    Ok(AudioInput {
+        buffer,
+        sample_rate,
+        through,
+        _stream: stream,
+    })
+}
+
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    (buffer, through): (Arc<Mutex<VecDeque<f32>>>, Arc<SegQueue<f32>>),
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let capacity = config.sample_rate.0 as usize;
+    let through_capacity = (MIC_THROUGH_MAX_LATENCY_SECS * config.sample_rate.0 as f32) as usize;
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            let Ok(mut buffer) = buffer.lock() else {
+                return;
+            };
+            for frame in data.chunks(channels) {
+                let mono = frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / channels as f32;
+                if buffer.len() == capacity {
+                    buffer.pop_front();
+                }
+                buffer.push_back(mono);
+                while through.len() >= through_capacity {
+                    through.pop();
+                }
+                through.push(mono);
+            }
+        },
+        |err| eprint!("Audio input error: {err}\r\n"),
+        None,
+    )
+}
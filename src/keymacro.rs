@@ -0,0 +1,180 @@
+use crate::error::SynthError;
+use crate::keymap::{keycode_display, parse_keycode};
+use crossterm::event::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Macro slots, one per function key F1-F8 in macro mode.
+pub const MACRO_SLOTS: usize = 8;
+
+/// A recorded run of notes: each key with the time since the one before it, or
+/// since recording started for the first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyboardMacro {
+    pub events: Vec<(KeyCode, Duration)>,
+}
+
+impl KeyboardMacro {
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Start to last key.
+    pub fn duration(&self) -> Duration {
+        self.events.iter().map(|&(_, delay)| delay).sum()
+    }
+}
+
+/// Records keys with their timing into a [`KeyboardMacro`].
+#[derive(Clone, Debug)]
+pub struct MacroRecorder {
+    pub events: Vec<(KeyCode, Duration)>,
+    pub recording: bool,
+    pub start_time: Instant,
+    last_event: Instant,
+}
+
+impl Default for MacroRecorder {
+    fn default() -> Self {
+        let now = Instant::now();
+        MacroRecorder {
+            events: Vec::new(),
+            recording: false,
+            start_time: now,
+            last_event: now,
+        }
+    }
+}
+
+impl MacroRecorder {
+    /// Drops anything recorded so far and starts again from now.
+    pub fn start(&mut self) {
+        self.start_at(Instant::now());
+    }
+
+    pub fn start_at(&mut self, now: Instant) {
+        self.events.clear();
+        self.recording = true;
+        self.start_time = now;
+        self.last_event = now;
+    }
+
+    /// Adds `key` if recording.
+    pub fn record(&mut self, key: KeyCode) {
+        self.record_at(key, Instant::now());
+    }
+
+    pub fn record_at(&mut self, key: KeyCode, now: Instant) {
+        if self.recording {
+            self.events.push((key, now.duration_since(self.last_event)));
+            self.last_event = now;
+        }
+    }
+
+    /// Ends the recording and hands it over.
+    pub fn stop(&mut self) -> KeyboardMacro {
+        self.recording = false;
+        KeyboardMacro {
+            events: std::mem::take(&mut self.events),
+        }
+    }
+}
+
+/// Plays a [`KeyboardMacro`] back by handing out its keys as they fall due, for the
+/// event loop to treat like presses.
+#[derive(Clone, Debug, Default)]
+pub struct MacroPlayer {
+    queue: VecDeque<(Instant, KeyCode)>,
+}
+
+impl MacroPlayer {
+    /// Schedules `keyboard_macro` from `now`, after anything already playing.
+    pub fn play(&mut self, keyboard_macro: &KeyboardMacro, now: Instant) {
+        let mut due = self.queue.back().map_or(now, |&(last, _)| last.max(now));
+        for &(key, delay) in &keyboard_macro.events {
+            due += delay;
+            self.queue.push_back((due, key));
+        }
+    }
+
+    /// The next key whose time has come.
+    pub fn next_due(&mut self, now: Instant) -> Option<KeyCode> {
+        match self.queue.front() {
+            Some(&(due, _)) if due <= now => self.queue.pop_front().map(|(_, key)| key),
+            _ => None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    pub fn stop(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// A key as stored in a macro file: its name, as in the key table, and the delay in
+/// milliseconds.
+#[derive(Serialize, Deserialize)]
+struct StoredKey {
+    key: String,
+    after_ms: u64,
+}
+
+/// The [`MACRO_SLOTS`] macros, saved to and loaded from JSON for sharing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MacroBank {
+    pub slots: [Option<KeyboardMacro>; MACRO_SLOTS],
+}
+
+impl MacroBank {
+    /// The bank as a JSON array of eight slots, each `null` or a list of
+    /// `{"key": "a", "after_ms": 250}`.
+    pub fn to_json(&self) -> String {
+        let slots: Vec<Option<Vec<StoredKey>>> = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let events = &slot.as_ref()?.events;
+                let stored = events.iter().map(|&(key, delay)| StoredKey {
+                    key: keycode_display(key),
+                    after_ms: delay.as_millis() as u64,
+                });
+                Some(stored.collect())
+            })
+            .collect();
+        serde_json::to_string_pretty(&slots).unwrap_or_default()
+    }
+
+    /// Reads [`to_json`](Self::to_json)'s format. Slots past the eighth are ignored.
+    pub fn from_json(json: &str) -> Result<MacroBank, SynthError> {
+        let invalid = |reason: String| SynthError::invalid_parameter("macro file", "", reason);
+        let slots: Vec<Option<Vec<StoredKey>>> = serde_json::from_str(json).map_err(|error| invalid(error.to_string()))?;
+        let mut bank = MacroBank::default();
+        for (slot, stored) in bank.slots.iter_mut().zip(slots) {
+            let Some(stored) = stored else {
+                continue;
+            };
+            let events = stored
+                .into_iter()
+                .map(|StoredKey { key, after_ms }| {
+                    let code = parse_keycode(&key).map_err(|error| invalid(error.to_string()))?;
+                    Ok((code, Duration::from_millis(after_ms)))
+                })
+                .collect::<Result<_, SynthError>>()?;
+            *slot = Some(KeyboardMacro { events });
+        }
+        Ok(bank)
+    }
+
+    pub fn load(path: &Path) -> Result<MacroBank, SynthError> {
+        MacroBank::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SynthError> {
+        Ok(std::fs::write(path, self.to_json())?)
+    }
+}
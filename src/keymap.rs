@@ -0,0 +1,539 @@
+use crate::scale::ScaleHighlighter;
+use crate::tuning::{note_name, TuningSystem};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventState};
+use crate::theme::Theme;
+use crossterm::style::Attribute;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// The playable keys as they sit on the keyboard, row by row, for the layout guide.
+/// Keys that don't fit the physical rows (shifted symbols, editing and arrow keys)
+/// get rows of their own.
+pub const KEYBOARD_LAYOUT: [&[KeyCode]; 7] = [
+    &[
+        KeyCode::F(1),
+        KeyCode::F(2),
+        KeyCode::F(3),
+        KeyCode::F(4),
+        KeyCode::F(5),
+        KeyCode::F(6),
+        KeyCode::F(7),
+        KeyCode::F(8),
+        KeyCode::F(9),
+        KeyCode::F(10),
+        KeyCode::F(11),
+        KeyCode::F(12),
+    ],
+    &[
+        KeyCode::Char('`'),
+        KeyCode::Char('1'),
+        KeyCode::Char('2'),
+        KeyCode::Char('3'),
+        KeyCode::Char('4'),
+        KeyCode::Char('5'),
+        KeyCode::Char('6'),
+        KeyCode::Char('7'),
+        KeyCode::Char('8'),
+        KeyCode::Char('9'),
+        KeyCode::Char('0'),
+        KeyCode::Char('-'),
+        KeyCode::Char('='),
+    ],
+    &[
+        KeyCode::Char('~'),
+        KeyCode::Char('!'),
+        KeyCode::Char('@'),
+        KeyCode::Char('#'),
+        KeyCode::Char('$'),
+        KeyCode::Char('%'),
+        KeyCode::Char('^'),
+        KeyCode::Char('&'),
+        KeyCode::Char('*'),
+        KeyCode::Char('('),
+        KeyCode::Char(')'),
+    ],
+    &[
+        KeyCode::Char('q'),
+        KeyCode::Char('w'),
+        KeyCode::Char('e'),
+        KeyCode::Char('r'),
+        KeyCode::Char('t'),
+        KeyCode::Char('y'),
+        KeyCode::Char('u'),
+        KeyCode::Char('i'),
+        KeyCode::Char('o'),
+        KeyCode::Char('p'),
+        KeyCode::Char('['),
+        KeyCode::Char(']'),
+        KeyCode::Char('\\'),
+    ],
+    &[
+        KeyCode::Char('a'),
+        KeyCode::Char('s'),
+        KeyCode::Char('d'),
+        KeyCode::Char('f'),
+        KeyCode::Char('g'),
+        KeyCode::Char('h'),
+        KeyCode::Char('j'),
+        KeyCode::Char('k'),
+        KeyCode::Char('l'),
+        KeyCode::Char(';'),
+        KeyCode::Char('\''),
+        KeyCode::Enter,
+    ],
+    &[
+        KeyCode::Char('z'),
+        KeyCode::Char('x'),
+        KeyCode::Char('c'),
+        KeyCode::Char('v'),
+        KeyCode::Char('b'),
+        KeyCode::Char('n'),
+        KeyCode::Char('m'),
+        KeyCode::Char(','),
+        KeyCode::Char('.'),
+        KeyCode::Char('/'),
+    ],
+    &[
+        KeyCode::Char(' '),
+        KeyCode::Tab,
+        KeyCode::Backspace,
+        KeyCode::Delete,
+        KeyCode::Insert,
+        KeyCode::Home,
+        KeyCode::End,
+        KeyCode::PageUp,
+        KeyCode::PageDown,
+        KeyCode::Up,
+        KeyCode::Down,
+        KeyCode::Left,
+        KeyCode::Right,
+    ],
+];
+
+/// Short label for a key in the layout guide.
+fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::F(n) => format!("F{n}"),
+        KeyCode::Char(' ') => "Spc".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Enter => "Ent".to_string(),
+        KeyCode::Backspace => "Bksp".to_string(),
+        KeyCode::Delete => "Del".to_string(),
+        KeyCode::Insert => "Ins".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDn".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Rght".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// A key's full name for printed tables: `a`, `F1`, `Space`, `PageUp`.
+pub fn keycode_display(key: KeyCode) -> String {
+    match key {
+        KeyCode::F(n) => format!("F{n}"),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// The octave the numpad digits play by default, the one below the `z` row: 0 is C2
+/// and 9 is A2.
+pub const DEFAULT_NUMPAD_OCTAVE: u8 = 2;
+
+/// A key on the numeric keypad.
+///
+/// Crossterm reports keypad keys as the same characters as the main keys and only
+/// tells them apart by [`KeyEventState::KEYPAD`], which terminals send when keyboard
+/// enhancement is on. Elsewhere they arrive as the number row and symbols.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumpadKey {
+    Digit(u8),
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl NumpadKey {
+    pub fn from_key_event(event: &KeyEvent) -> Option<NumpadKey> {
+        if !event.state.contains(KeyEventState::KEYPAD) {
+            return None;
+        }
+        match event.code {
+            KeyCode::Char(c @ '0'..='9') => Some(NumpadKey::Digit(c as u8 - b'0')),
+            KeyCode::Char('+') => Some(NumpadKey::Add),
+            KeyCode::Char('-') => Some(NumpadKey::Subtract),
+            KeyCode::Char('*') => Some(NumpadKey::Multiply),
+            KeyCode::Char('/') => Some(NumpadKey::Divide),
+            _ => None,
+        }
+    }
+}
+
+/// A key name [`parse_keycode`] can't read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyParseError {
+    Empty,
+    Unknown(String),
+}
+
+impl fmt::Display for KeyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyParseError::Empty => write!(f, "empty key name"),
+            KeyParseError::Unknown(name) => write!(f, "unknown key '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for KeyParseError {}
+
+/// The key [`keycode_display`] names, for the keys the synth plays.
+pub fn parse_keycode(name: &str) -> Result<KeyCode, KeyParseError> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => return Err(KeyParseError::Empty),
+        (Some(c), None) => return Ok(KeyCode::Char(c)),
+        _ => {}
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+        return Ok(KeyCode::F(n));
+    }
+    Ok(match name {
+        "Space" => KeyCode::Char(' '),
+        "Enter" => KeyCode::Enter,
+        "Tab" => KeyCode::Tab,
+        "CapsLock" => KeyCode::CapsLock,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Insert" => KeyCode::Insert,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        _ => return Err(KeyParseError::Unknown(name.to_string())),
+    })
+}
+
+/// The file a `keymap.toml` is read from in the working directory.
+pub const KEYMAP_FILE: &str = "keymap.toml";
+
+/// A `keymap.toml`: key names as [`keycode_display`] writes them, each with the
+/// frequency it plays in Hz, like `"a" = 261.63` or `"F1" = 55.0`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct KeyMapConfig {
+    pub keys: HashMap<String, f32>,
+}
+
+/// Why a key map file couldn't be used.
+#[derive(Debug)]
+pub enum KeymapConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Key(KeyParseError),
+    /// A frequency that isn't a positive number of Hz.
+    Frequency { key: String, freq_hz: f32 },
+}
+
+impl fmt::Display for KeymapConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapConfigError::Io(error) => write!(f, "key map file error: {error}"),
+            KeymapConfigError::Parse(error) => write!(f, "invalid key map: {error}"),
+            KeymapConfigError::Key(error) => write!(f, "invalid key map: {error}"),
+            KeymapConfigError::Frequency { key, freq_hz } => {
+                write!(f, "invalid key map: '{key}' = {freq_hz} is not a frequency")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapConfigError {}
+
+impl From<std::io::Error> for KeymapConfigError {
+    fn from(error: std::io::Error) -> Self {
+        KeymapConfigError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for KeymapConfigError {
+    fn from(error: toml::de::Error) -> Self {
+        KeymapConfigError::Parse(error)
+    }
+}
+
+impl From<KeyParseError> for KeymapConfigError {
+    fn from(error: KeyParseError) -> Self {
+        KeymapConfigError::Key(error)
+    }
+}
+
+/// Reads a [`KeyMapConfig`] file into key codes.
+pub fn load_keymap(path: &Path) -> Result<HashMap<KeyCode, f32>, KeymapConfigError> {
+    let config: KeyMapConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
+    config
+        .keys
+        .into_iter()
+        .map(|(key, freq_hz)| {
+            if !(freq_hz.is_finite() && freq_hz > 0.0) {
+                return Err(KeymapConfigError::Frequency { key, freq_hz });
+            }
+            Ok((parse_keycode(&key)?, freq_hz))
+        })
+        .collect()
+}
+
+/// Key-to-frequency assignments for the computer keyboard, editable at runtime.
+///
+/// The numpad digits play their own octave, C to A chromatically, on top of the
+/// key map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyFrequencyTable {
+    keys: HashMap<KeyCode, f32>,
+    numpad_octave: u8,
+}
+
+impl Default for KeyFrequencyTable {
+    fn default() -> Self {
+        KeyFrequencyTable {
+            keys: default_key_frequencies(),
+            numpad_octave: DEFAULT_NUMPAD_OCTAVE,
+        }
+    }
+}
+
+impl KeyFrequencyTable {
+    pub fn get(&self, key: &KeyCode) -> Option<f32> {
+        self.keys.get(key).copied()
+    }
+
+    pub fn remap(&mut self, key: KeyCode, freq_hz: f32) {
+        self.keys.insert(key, freq_hz);
+    }
+
+    pub fn unmap(&mut self, key: KeyCode) {
+        self.keys.remove(&key);
+    }
+
+    pub fn reset_to_default(&mut self) {
+        self.keys = default_key_frequencies();
+    }
+
+    /// The built-in map with `keys` assigned on top; keys left out keep their notes.
+    pub fn with_keys(keys: HashMap<KeyCode, f32>) -> KeyFrequencyTable {
+        let mut table = KeyFrequencyTable::default();
+        table.keys.extend(keys);
+        table
+    }
+
+    /// The frequency numpad `digit` plays, in equal temperament.
+    pub fn numpad(&self, digit: u8) -> Option<f32> {
+        (digit <= 9).then(|| TuningSystem::default().frequency(12 * (self.numpad_octave + 1) + digit))
+    }
+
+    pub fn numpad_octave(&self) -> u8 {
+        self.numpad_octave
+    }
+
+    /// Moves the numpad digits to `octave`, 0 (C0) to 8 (C8).
+    pub fn set_numpad_octave(&mut self, octave: u8) {
+        self.numpad_octave = octave.min(8);
+    }
+
+    /// The layout guide: one line per [`KEYBOARD_LAYOUT`] row, each key shown with the
+    /// note it plays and coloured by octave from the theme's [`Theme::octave_colors`]
+    /// (1-2, 3-4, 5-6, then 7 and up; blue, green, yellow and red in the dark theme).
+    /// `highlight` is drawn reversed, for the key just played. Unmapped keys are dimmed.
+    ///
+    /// With a `scale`, keys are coloured by it instead and out-of-scale keys are dimmed
+    /// too; a highlighted out-of-scale key is drawn reversed in the theme's error color,
+    /// as its note will be quantized.
+    pub fn render_layout(
+        &self,
+        tuning: &TuningSystem,
+        theme: &Theme,
+        highlight: Option<KeyCode>,
+        scale: Option<&ScaleHighlighter>,
+    ) -> Vec<String> {
+        KEYBOARD_LAYOUT
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&key| {
+                        let Some(freq) = self.get(&key) else {
+                            let cell = format!("{:>4} --- ", key_label(key));
+                            return format!("{}{cell}{}", Attribute::Dim, Attribute::NormalIntensity);
+                        };
+                        let note = tuning.nearest_note(freq);
+                        let [low, middle, high, top] = theme.octave_colors();
+                        let color = match note as i32 / 12 - 1 {
+                            ..=2 => low,
+                            3..=4 => middle,
+                            5..=6 => high,
+                            _ => top,
+                        };
+                        let cell = format!("{:>4} {:<3}", key_label(key), note_name(note));
+                        let (color, dim) = match scale.map(|scale| scale.color(note)) {
+                            None => (color, false),
+                            Some(Some(scale_color)) => (scale_color, false),
+                            Some(None) if highlight == Some(key) => (theme.error, false),
+                            Some(None) => (theme.primary, true),
+                        };
+                        let cell = if highlight == Some(key) {
+                            format!("{}{cell}{}", Attribute::Reverse, Attribute::NoReverse)
+                        } else if dim {
+                            format!("{}{cell}{}", Attribute::Dim, Attribute::NormalIntensity)
+                        } else {
+                            cell
+                        };
+                        format!("{} ", theme.paint(&cell, color))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// A plain-text reference card: a header, then every mapped key and numpad digit
+    /// with the name of its nearest note and its frequency, lowest first.
+    pub fn render_table(&self, tuning: &TuningSystem) -> String {
+        let mut rows: Vec<(String, f32)> =
+            self.iter_sorted_by_frequency().map(|(key, freq)| (keycode_display(key), freq)).collect();
+        rows.extend((0..=9).filter_map(|digit| Some((format!("Numpad {digit}"), self.numpad(digit)?))));
+        // Keys sharing a frequency come out of the map in any order
+        rows.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut table = format!("{:<10} {:<7} Frequency (Hz)\n", "Key", "Note");
+        for (key, freq) in rows {
+            let note = note_name(tuning.nearest_note(freq));
+            table.push_str(&format!("{key:<10} {note:<7} {freq:.2}\n"));
+        }
+        table
+    }
+
+    pub fn iter_sorted_by_frequency(&self) -> impl Iterator<Item = (KeyCode, f32)> {
+        let mut entries: Vec<(KeyCode, f32)> = self.keys.iter().map(|(&key, &freq)| (key, freq)).collect();
+        entries.sort_by(|a, b| a.1.total_cmp(&b.1));
+        entries.into_iter()
+    }
+}
+
+/// Complete keyboard frequency mapping
+fn default_key_frequencies() -> HashMap<KeyCode, f32> {
+    let mut key_frequencies = HashMap::new();
+
+    // Function keys and special keys
+    key_frequencies.insert(KeyCode::F(1), 55.00);        // A1
+    key_frequencies.insert(KeyCode::F(2), 58.27);        // A#1
+    key_frequencies.insert(KeyCode::F(3), 61.74);        // B1
+    key_frequencies.insert(KeyCode::F(4), 65.41);        // C2
+    key_frequencies.insert(KeyCode::F(5), 69.30);        // C#2
+    key_frequencies.insert(KeyCode::F(6), 73.42);        // D2
+    key_frequencies.insert(KeyCode::F(7), 77.78);        // D#2
+    key_frequencies.insert(KeyCode::F(8), 82.41);        // E2
+    key_frequencies.insert(KeyCode::F(9), 87.31);        // F2
+    key_frequencies.insert(KeyCode::F(10), 92.50);       // F#2
+    key_frequencies.insert(KeyCode::F(11), 98.00);       // G2
+    key_frequencies.insert(KeyCode::F(12), 103.83);      // G#2
+
+    // Number row
+    key_frequencies.insert(KeyCode::Char('1'), 1046.50); // C6
+    key_frequencies.insert(KeyCode::Char('2'), 1108.73); // C#6
+    key_frequencies.insert(KeyCode::Char('3'), 1174.66); // D6
+    key_frequencies.insert(KeyCode::Char('4'), 1244.51); // D#6
+    key_frequencies.insert(KeyCode::Char('5'), 1318.51); // E6
+    key_frequencies.insert(KeyCode::Char('6'), 1396.91); // F6
+    key_frequencies.insert(KeyCode::Char('7'), 1479.98); // F#6
+    key_frequencies.insert(KeyCode::Char('8'), 1567.98); // G6
+    key_frequencies.insert(KeyCode::Char('9'), 1661.22); // G#6
+    key_frequencies.insert(KeyCode::Char('0'), 1760.00); // A6
+    key_frequencies.insert(KeyCode::Char('-'), 1864.66); // A#6
+    key_frequencies.insert(KeyCode::Char('='), 1975.53); // B6
+
+    // Top row (QWERTY)
+    key_frequencies.insert(KeyCode::Char('q'), 2093.00); // C7
+    key_frequencies.insert(KeyCode::Char('w'), 523.25);  // C5
+    key_frequencies.insert(KeyCode::Char('e'), 554.37);  // C#5
+    key_frequencies.insert(KeyCode::Char('r'), 587.33);  // D5
+    key_frequencies.insert(KeyCode::Char('t'), 622.25);  // D#5
+    key_frequencies.insert(KeyCode::Char('y'), 659.25);  // E5
+    key_frequencies.insert(KeyCode::Char('u'), 698.46);  // F5
+    key_frequencies.insert(KeyCode::Char('i'), 739.99);  // F#5
+    key_frequencies.insert(KeyCode::Char('o'), 783.99);  // G5
+    key_frequencies.insert(KeyCode::Char('p'), 830.61);  // G#5
+    key_frequencies.insert(KeyCode::Char('['), 880.00);  // A5
+    key_frequencies.insert(KeyCode::Char(']'), 932.33);  // A#5
+    key_frequencies.insert(KeyCode::Char('\\'), 987.77); // B5
+
+    // Home row (ASDF)
+    key_frequencies.insert(KeyCode::Char('a'), 261.63);  // C4
+    key_frequencies.insert(KeyCode::Char('s'), 277.18);  // C#4
+    key_frequencies.insert(KeyCode::Char('d'), 293.66);  // D4
+    key_frequencies.insert(KeyCode::Char('f'), 311.13);  // D#4
+    key_frequencies.insert(KeyCode::Char('g'), 329.63);  // E4
+    key_frequencies.insert(KeyCode::Char('h'), 349.23);  // F4
+    key_frequencies.insert(KeyCode::Char('j'), 369.99);  // F#4
+    key_frequencies.insert(KeyCode::Char('k'), 392.00);  // G4
+    key_frequencies.insert(KeyCode::Char('l'), 415.30);  // G#4
+    key_frequencies.insert(KeyCode::Char(';'), 440.00);  // A4
+    key_frequencies.insert(KeyCode::Char('\''), 466.16); // A#4
+
+    // Bottom row (ZXCV)
+    key_frequencies.insert(KeyCode::Char('z'), 130.81);  // C3
+    key_frequencies.insert(KeyCode::Char('x'), 138.59);  // C#3
+    key_frequencies.insert(KeyCode::Char('c'), 146.83);  // D3
+    key_frequencies.insert(KeyCode::Char('v'), 155.56);  // D#3
+    key_frequencies.insert(KeyCode::Char('b'), 164.81);  // E3
+    key_frequencies.insert(KeyCode::Char('n'), 174.61);  // F3
+    key_frequencies.insert(KeyCode::Char('m'), 185.00);  // F#3
+    key_frequencies.insert(KeyCode::Char(','), 196.00);  // G3
+    key_frequencies.insert(KeyCode::Char('.'), 207.65);  // G#3
+    key_frequencies.insert(KeyCode::Char('/'), 220.00);  // A3
+
+    // Special keys
+    key_frequencies.insert(KeyCode::Char(' '), 110.00);     // A2
+    key_frequencies.insert(KeyCode::Tab, 116.54);       // A#2
+    key_frequencies.insert(KeyCode::Enter, 123.47);     // B2
+    key_frequencies.insert(KeyCode::Backspace, 233.08); // A#3
+    key_frequencies.insert(KeyCode::Delete, 246.94);    // B3
+    key_frequencies.insert(KeyCode::Insert, 2217.46);   // C#7
+    key_frequencies.insert(KeyCode::Home, 2349.32);     // D7
+    key_frequencies.insert(KeyCode::End, 2489.02);      // D#7
+    key_frequencies.insert(KeyCode::PageUp, 2637.02);   // E7
+    key_frequencies.insert(KeyCode::PageDown, 2793.83); // F7
+
+    // Arrow keys
+    key_frequencies.insert(KeyCode::Up, 41.20);         // E1
+    key_frequencies.insert(KeyCode::Down, 43.65);       // F1
+    key_frequencies.insert(KeyCode::Left, 46.25);       // F#1
+    key_frequencies.insert(KeyCode::Right, 49.00);      // G1
+
+    // Additional punctuation
+    key_frequencies.insert(KeyCode::Char('`'), 32.70);  // C1
+    key_frequencies.insert(KeyCode::Char('~'), 34.65);  // C#1
+    key_frequencies.insert(KeyCode::Char('!'), 36.71);  // D1
+    key_frequencies.insert(KeyCode::Char('@'), 38.89);  // D#1
+    key_frequencies.insert(KeyCode::Char('#'), 2959.96); // F#7
+    key_frequencies.insert(KeyCode::Char('$'), 3135.96); // G7
+    key_frequencies.insert(KeyCode::Char('%'), 3322.44); // G#7
+    key_frequencies.insert(KeyCode::Char('^'), 3520.00); // A7
+    key_frequencies.insert(KeyCode::Char('&'), 3729.31); // A#7
+    key_frequencies.insert(KeyCode::Char('*'), 3951.07); // B7
+    key_frequencies.insert(KeyCode::Char('('), 4186.01); // C8
+    key_frequencies.insert(KeyCode::Char(')'), 4434.92); // C#8
+
+    key_frequencies
+}
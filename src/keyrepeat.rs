@@ -0,0 +1,44 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use std::collections::HashSet;
+
+/// Tells a key going down from the terminal's auto-repeat of a held key, so holding
+/// a note doesn't retrigger it.
+///
+/// It needs key release events, which terminals only send with crossterm's keyboard
+/// enhancement turned on (and always on Windows). Without them a key would stay held
+/// forever and never play again.
+#[derive(Clone, Debug, Default)]
+pub struct KeyRepeatSuppressor {
+    active_keys: HashSet<KeyCode>,
+}
+
+impl KeyRepeatSuppressor {
+    /// Returns true for a fresh key press, and false for repeats and releases.
+    pub fn handle(&mut self, event: &KeyEvent) -> bool {
+        match event.kind {
+            KeyEventKind::Press => self.active_keys.insert(event.code),
+            KeyEventKind::Repeat => false,
+            KeyEventKind::Release => {
+                self.active_keys.remove(&event.code);
+                // Shift can change between press and release, so the letter may come
+                // back up in the other case
+                if let KeyCode::Char(c) = event.code {
+                    for case in c.to_lowercase().chain(c.to_uppercase()) {
+                        self.active_keys.remove(&KeyCode::Char(case));
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    pub fn is_held(&self, code: KeyCode) -> bool {
+        self.active_keys.contains(&code)
+    }
+
+    /// Forgets every held key, e.g. when the window loses focus and releases go
+    /// elsewhere.
+    pub fn clear(&mut self) {
+        self.active_keys.clear();
+    }
+}
@@ -0,0 +1,155 @@
+use crate::error::SynthError;
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, InputCallbackInfo, OutputCallbackInfo, SampleFormat, SizedSample};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long each probe stream runs for.
+const PROBE_DURATION: Duration = Duration::from_millis(300);
+
+/// Where the delay between playing a note and hearing it comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencyReport {
+    /// From the microphone to the input callback; `None` without an input device.
+    pub input_latency: Option<Duration>,
+    /// From the output callback to the speaker.
+    pub output_latency: Duration,
+    /// Everything together, including the processing chain's own buffering.
+    pub total_latency: Duration,
+    /// Frames per output callback.
+    pub buffer_size: usize,
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        match self.input_latency {
+            Some(input) => writeln!(f, "Input latency:  {:.1} ms", ms(input))?,
+            None => writeln!(f, "Input latency:  no input device")?,
+        }
+        writeln!(f, "Output latency: {:.1} ms", ms(self.output_latency))?;
+        writeln!(f, "Buffer size:    {} frames", self.buffer_size)?;
+        write!(f, "Total latency:  {:.1} ms", ms(self.total_latency))
+    }
+}
+
+/// What a probe stream's callbacks saw: the longest delay between a callback and
+/// its audio reaching or leaving the device, and the frames in the last buffer.
+#[derive(Default)]
+struct Probe {
+    latency: Duration,
+    frames: usize,
+}
+
+/// Measures the default devices' latency by running short silent streams on them
+/// and reading cpal's callback timestamps. Where a backend reports no timestamps,
+/// one buffer's length stands in.
+///
+/// Rodio's mixer pulls straight from the sources in cpal's callback, so it adds no
+/// buffer of its own beyond the device's. `chain_latency_frames` is whatever the
+/// processing chain holds on top, such as a [`BufferedSource`](crate::BufferedSource)
+/// queue; it counts at the output's sample rate.
+pub fn benchmark_latency(chain_latency_frames: usize) -> Result<LatencyReport, SynthError> {
+    let device_error = |error: &dyn fmt::Display| SynthError::AudioDeviceError(error.to_string());
+    let host = cpal::default_host();
+
+    let output = host.default_output_device().ok_or_else(|| device_error(&"no default output device"))?;
+    let config = output.default_output_config().map_err(|error| device_error(&error))?;
+    let sample_rate = config.sample_rate().0;
+    let channels = config.channels() as usize;
+    let probe = Arc::new(Mutex::new(Probe::default()));
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => probe_output::<f32>(&output, &config.into(), channels, probe.clone()),
+        SampleFormat::I16 => probe_output::<i16>(&output, &config.into(), channels, probe.clone()),
+        SampleFormat::U16 => probe_output::<u16>(&output, &config.into(), channels, probe.clone()),
+        other => return Err(device_error(&format!("unsupported output sample format {other}"))),
+    }
+    .map_err(|error| device_error(&error))?;
+    stream.play().map_err(|error| device_error(&error))?;
+    thread::sleep(PROBE_DURATION);
+    drop(stream);
+    let (output_latency, buffer_size) = probe_result(&probe, sample_rate);
+
+    // Microphone modulation is optional, so a missing or unusable input isn't an error
+    let input_latency = host.default_input_device().and_then(|input| {
+        let config = input.default_input_config().ok()?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels() as usize;
+        let probe = Arc::new(Mutex::new(Probe::default()));
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => probe_input::<f32>(&input, &config.into(), channels, probe.clone()),
+            SampleFormat::I16 => probe_input::<i16>(&input, &config.into(), channels, probe.clone()),
+            SampleFormat::U16 => probe_input::<u16>(&input, &config.into(), channels, probe.clone()),
+            _ => return None,
+        }
+        .ok()?;
+        stream.play().ok()?;
+        thread::sleep(PROBE_DURATION);
+        drop(stream);
+        Some(probe_result(&probe, sample_rate).0)
+    });
+
+    let chain_latency = Duration::from_secs_f64(chain_latency_frames as f64 / sample_rate as f64);
+    Ok(LatencyReport {
+        input_latency,
+        output_latency,
+        total_latency: input_latency.unwrap_or_default() + output_latency + chain_latency,
+        buffer_size,
+    })
+}
+
+fn probe_result(probe: &Mutex<Probe>, sample_rate: u32) -> (Duration, usize) {
+    let Ok(probe) = probe.lock() else {
+        return (Duration::ZERO, 0);
+    };
+    let latency = if probe.latency.is_zero() {
+        Duration::from_secs_f64(probe.frames as f64 / sample_rate as f64)
+    } else {
+        probe.latency
+    };
+    (latency, probe.frames)
+}
+
+fn record(probe: &Mutex<Probe>, latency: Option<Duration>, frames: usize) {
+    if let Ok(mut probe) = probe.lock() {
+        probe.latency = probe.latency.max(latency.unwrap_or_default());
+        probe.frames = frames;
+    }
+}
+
+fn probe_output<T: SizedSample + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    probe: Arc<Mutex<Probe>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &OutputCallbackInfo| {
+            data.fill(T::EQUILIBRIUM);
+            let timestamp = info.timestamp();
+            record(&probe, timestamp.playback.duration_since(&timestamp.callback), data.len() / channels.max(1));
+        },
+        |_| {},
+        None,
+    )
+}
+
+fn probe_input<T: SizedSample + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    channels: usize,
+    probe: Arc<Mutex<Probe>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    device.build_input_stream(
+        config,
+        move |data: &[T], info: &InputCallbackInfo| {
+            let timestamp = info.timestamp();
+            record(&probe, timestamp.callback.duration_since(&timestamp.capture), data.len() / channels.max(1));
+        },
+        |_| {},
+        None,
+    )
+}
@@ -0,0 +1,213 @@
+mod additive;
+mod aftertouch;
+mod arpeggiator;
+mod block;
+mod buffered;
+mod channel;
+mod chord;
+mod chorus;
+mod clock;
+mod compressor;
+mod cpu;
+mod delay;
+mod drift;
+mod drums;
+mod dynwave;
+mod envelope;
+mod error;
+mod filter;
+mod fm;
+mod freeze;
+mod gate;
+mod grain;
+mod graph;
+mod harmonizer;
+mod input;
+mod keymacro;
+mod keymap;
+mod keyrepeat;
+mod latency;
+mod limiter;
+mod looper;
+mod matrix;
+mod meter;
+mod micthrough;
+mod midicc;
+mod midifile;
+mod midiout;
+mod midiport;
+mod midirecord;
+mod mixer;
+mod morph;
+mod onepole;
+mod oscillator;
+mod overtone;
+mod pan;
+mod patch;
+mod pedal;
+mod pitch;
+mod poly;
+mod resonator;
+mod reverb;
+mod ringbuf;
+mod sampler;
+mod scale;
+mod scope;
+mod sequencer;
+mod serum;
+mod shortcut;
+mod spectrum;
+mod stutter;
+mod supersaw;
+mod sysex;
+mod tapestop;
+mod tempo;
+mod theme;
+mod tonnetz;
+mod trainer;
+mod tremolo;
+mod tui;
+mod tuning;
+mod unison;
+mod velocity;
+mod vocoder;
+mod voice;
+mod wave;
+mod wavefile;
+mod waveguide;
+mod waveshaper;
+mod wavrecord;
+mod widener;
+mod window;
+
+pub use additive::{AdditivePreset, AdditiveSynthesizer, MAX_PARTIALS};
+pub use aftertouch::{PolyAftertouch, MAX_VIBRATO_DEPTH, SCROLL_DEPTH_STEP};
+pub use arpeggiator::{ArpPattern, Arpeggiator};
+pub use block::BlockSource;
+pub use buffered::BufferedSource;
+pub use channel::{ChannelRouting, Effect, VoiceChannel, DEFAULT_CHANNEL_SPLIT_NOTE};
+pub use chord::{detect_chord, ChordName, ChordQuality, NoteName};
+pub use chorus::{thick_chorus_preset, Chorus, ChorusPreset, ChorusSource, ChorusVoice};
+pub use clock::MasterClock;
+pub use compressor::{BusCompressor, BusCompressorSource};
+pub use cpu::{CpuMonitor, CpuTimer};
+pub use delay::{compute_delay_samples, DelaySource, DelayTime, FeedbackDelay};
+pub use drift::{TuningDrift, DEFAULT_DRIFT_DEPTH_CENTS, DEFAULT_DRIFT_RATE_HZ};
+pub use drums::{KeyboardDrummer, PercKind, PercussionVoice};
+pub use dynwave::{DynamicWaveTable, WaveParams};
+pub use envelope::{AdsrEnvelope, EnvelopedOscillator};
+pub use error::SynthError;
+pub use filter::{FilterTrackingMode, SvfSource, TRACKING_REFERENCE_HZ};
+pub use fm::{save_fm_preset, FmFeedback, FmIndexEnvelope, FmOscillator, FmPreset, BUILTIN_FM_PRESETS};
+pub use freeze::SpectralFreeze;
+pub use gate::{parse_gate_pattern, Gate, GateSource, TRANCE_GATE_PATTERN};
+pub use grain::{evaluate_grain_envelope, GrainEnvelope, GrainEnvelopeShape};
+pub use graph::{
+    check_realtime_safety, AudioGraph, DspNode, GraphError, RealtimeSafe, RealtimeSafetyViolation, SourceNode,
+};
+pub use harmonizer::{Harmonizer, HarmonizerSource, HarmonizerVoice, HarmonyPreset};
+pub use input::{open_default_input, AudioInput, ModulationSource, MIC_THROUGH_MAX_LATENCY_SECS};
+pub use keymacro::{KeyboardMacro, MacroBank, MacroPlayer, MacroRecorder, MACRO_SLOTS};
+pub use keymap::{
+    keycode_display, load_keymap, parse_keycode, KeyFrequencyTable, KeyMapConfig, KeyParseError, KeymapConfigError,
+    NumpadKey, DEFAULT_NUMPAD_OCTAVE, KEYBOARD_LAYOUT, KEYMAP_FILE,
+};
+pub use keyrepeat::KeyRepeatSuppressor;
+pub use latency::{benchmark_latency, LatencyReport};
+pub use limiter::SafetyLimiter;
+pub use looper::{LiveLooper, LooperSource};
+pub use matrix::{
+    BusInput, EffectBus, OscillatorMatrix, DELAY_SEND_BUS, MAIN_LEFT_BUS, MAIN_RIGHT_BUS, MAX_BUSES, MAX_VOICES,
+    REVERB_SEND_BUS,
+};
+pub use meter::{PeakMeter, PeakReader};
+pub use micthrough::{enable_mic_through, MicThroughSource};
+pub use midicc::{
+    CcTarget, ChannelModeMessage, MidiCcMapper, ParseCcMapError, ALL_NOTES_OFF_CC, ALL_SOUND_OFF_CC,
+    CHANNEL_MODE_CCS,
+};
+pub use midifile::{play_midi_file, play_midi_timeline, MidiFileError, MidiFileEvent, MidiTimeline};
+pub use midiout::{
+    midi_panic, midi_panic_messages, MidiOutput, MIDI_CHANNELS, RESET_ALL_CONTROLLERS_CC, SUSTAIN_CC,
+};
+pub use midiport::{midi_output_port_names, MidiPort, MIDI_CLIENT_NAME};
+pub use midirecord::MidiFileRecorder;
+pub use mixer::{mix_voices_simd, Mixer};
+pub use morph::{MultiOscillator, MAX_MORPH, MORPH_SHAPES};
+pub use onepole::{HighPassFilter, LowPassFilter};
+pub use oscillator::{
+    temperature_correction_cents, FrequencyError, InvalidRenderLength, LfoTarget, OscillatorState,
+    StereoWaveTableOscillator, SubInterval, SubOscillatorMode, ThresholdGate, WaveTableOscillator,
+    WaveTableOscillatorState, DEFAULT_SMOOTHING_HZ, REFERENCE_TEMPERATURE_CELSIUS,
+};
+pub use overtone::{OvertoneFilter, OvertoneFilterSource, OvertonePreset, OVERTONE_COUNT};
+pub use pan::{
+    apply_balance, constant_power_gains, pan_control, pan_indicator, register_pan, AutoPan, AutoPanMode,
+    ConstantPowerPanner, StereoBalance,
+};
+pub use patch::{
+    apply_preset, capture_preset, preset_from_bitfield, preset_to_bitfield, AbComparison, AbSlot, PatchControls,
+    PatchError, PatchMemory, PatchVoice, Preset, PATCH_COUNT,
+};
+pub use pedal::{SustainController, SustainPedalSimulator};
+pub use pitch::{detect_pitch_autocorrelation, estimate_frequency, PITCH_CONFIDENCE_THRESHOLD};
+pub use poly::{PhaseReference, PolyphonicEngine};
+pub use resonator::{BiquadResonator, ResonatorBank, ResonatorSource};
+pub use reverb::{AttackBypassReverb, Reverb};
+pub use ringbuf::RingBuffer;
+pub use sampler::{LoopMode, LoopPoint, SamplePlayer};
+pub use scale::{
+    find_scale, search_scales, NoteQuantizer, RandomPitchMode, Scale, ScaleChooser, ScaleHighlighter, ScaleMembership,
+    SCALE_LIBRARY,
+};
+pub use scope::{
+    LissajousDisplay, Oscilloscope, ScopeTap, StereoTap, TriggerMode, WaveformPreview, LISSAJOUS_HISTORY,
+};
+pub use sequencer::{
+    spawn_note_sequencer, AutomationCurve, AutomationTargets, CrossfadeSequencer, NoteSequencer, PatternStep,
+    RecordedNote, SequencerStep, StepAutomation, StepSequencer, TempoMap, PATTERN_STEPS,
+};
+pub use serum::{read_serum_frame, serum_frame_count, SerumWavetableError, SERUM_FRAME_SIZE};
+pub use shortcut::{Action, EffectType, ShortcutLayer};
+pub use spectrum::{find_spectral_peaks, magnitude_spectrum, measure_thd, PEAK_FLOOR_DB, THD_FFT_SIZE};
+pub use stutter::{StutterEffect, StutterSource};
+pub use supersaw::SuperSaw;
+pub use sysex::{
+    handle_sysex, parse_sysex_preset, preset_to_sysex, sysex_param, SysexError, SYSEX_MANUFACTURER_ID,
+    SYSEX_PATCH_LOAD,
+};
+pub use tapestop::{TapeStop, TapeStopSource};
+pub use tempo::TempoTapper;
+pub use theme::{Theme, THEME_NAMES};
+pub use tonnetz::TonnetzDisplay;
+pub use trainer::{parse_interval, IntervalQuestion, IntervalTrainer, INTERVAL_NAMES};
+pub use tremolo::{Tremolo, TremoloSync};
+pub use tui::{sliding_rms, AppState, Dashboard};
+pub use tuning::{
+    analyze_chord, cents, freq_to_midi_note, note_name, IntervalAnalysis, ParseTuningSystemError, TuningSystem,
+    BEATING_THRESHOLD_CENTS,
+};
+pub use unison::{UnisonOscillator, MAX_UNISON_VOICES};
+pub use velocity::NoteVelocityMapper;
+pub use vocoder::{BandpassFilter, Vocoder};
+pub use voice::{
+    steal_oldest_voice, steal_release_voice, EnvelopePhase, EnvelopeState, PolyphonyMode, VoiceAllocationStrategy,
+    VoiceId, VoicePool, VoiceSlot,
+};
+pub use wave::{
+    fast_sin, generate_band_limited_wave_table, generate_pulse_wave_table, generate_tone, generate_wave_table,
+    generate_wave_table_with, validate_wave_table_size, write_tone_to_wav, InvalidWaveTableSize, ParseWaveShapeError,
+    SineMode, WaveShape, MAX_WAVE_TABLE_SIZE, MIN_WAVE_TABLE_SIZE,
+};
+pub use wavefile::{load_wave_table_from_wav, WaveLoadError};
+pub use waveguide::{OnePoleFilter, WaveguideString, SUSTAIN_LOSS};
+pub use waveshaper::{ShaperPreset, WaveShaper};
+pub use wavrecord::{RecordingSource, WavRecording, WavSessionRecorder};
+pub use widener::{StereoWidener, StereoWidenerSource};
+pub use window::{apply_window, FftWindow};
+
+// The no_std DSP kernels, re-exported so the app-level API doesn't change
+pub use synth_core::{
+    InterpolationMode, Lfo, LfoPolarity, LfoShape, ParameterSmoother, StateVariableFilter, SvfOutput, WaveTableCore,
+    SELF_OSCILLATION_THRESHOLD,
+};
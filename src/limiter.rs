@@ -0,0 +1,124 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Output-bus protection: a soft-knee peak limiter with a hard ceiling at ±1.0.
+///
+/// Gain reduction starts at `threshold - knee_width / 2` and follows a quadratic curve
+/// until the output sits flat at `threshold`. The curve is driven by a peak envelope
+/// that jumps up on transients and decays through the one-pole `release_coeff`; the gain
+/// itself moves toward the curve over `attack_samp` samples. Anything the envelope is too slow
+/// to catch is clamped to the ceiling. Input samples beyond ±1.0, which would have
+/// clipped without the limiter, are counted in the clip counter.
+///
+/// For interleaved stereo, one gain envelope covers both channels, which keeps the
+/// stereo image steady.
+pub struct SafetyLimiter<S: Source<Item = f32>> {
+    source: S,
+    threshold: f32,
+    knee_width: f32,
+    attack_samp: usize,
+    release_coeff: f32,
+    gain: f32,
+    envelope: f32,
+    clip_count: Arc<AtomicUsize>,
+}
+
+impl<S: Source<Item = f32>> SafetyLimiter<S> {
+    /// Limits at -0.45 dBFS with a 1.5 ms attack and 100 ms release.
+    pub fn new(source: S) -> SafetyLimiter<S> {
+        let frame_rate = source.sample_rate() as f32 * source.channels() as f32;
+        SafetyLimiter {
+            source,
+            threshold: 0.95,
+            knee_width: 0.1,
+            attack_samp: ((0.0015 * frame_rate) as usize).max(1),
+            release_coeff: (-1.0 / (0.1 * frame_rate)).exp(),
+            gain: 1.0,
+            envelope: 0.0,
+            clip_count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Running count of input samples that would have clipped.
+    pub fn get_clip_counter(&self) -> Arc<AtomicUsize> {
+        self.clip_count.clone()
+    }
+
+    /// Gain the static curve wants for an input of magnitude `level`.
+    fn target_gain(&self, level: f32) -> f32 {
+        let knee_start = self.threshold - self.knee_width / 2.0;
+        let knee_end = self.threshold + self.knee_width / 2.0;
+
+        let output = if level <= knee_start {
+            return 1.0;
+        } else if level < knee_end {
+            let over = level - knee_start;
+            level - over * over / (2.0 * self.knee_width)
+        } else {
+            self.threshold
+        };
+
+        output / level
+    }
+}
+
+impl<S: Source<Item = f32>> Source for SafetyLimiter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for SafetyLimiter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        let level = input.abs();
+        if level > 1.0 {
+            self.clip_count.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.envelope = level.max(self.envelope * self.release_coeff);
+        let target = self.target_gain(self.envelope);
+        if target < self.gain {
+            self.gain -= (self.gain - target) / self.attack_samp as f32;
+        } else {
+            self.gain = target;
+        }
+
+        Some((input * self.gain).clamp(-1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f32::consts::TAU;
+
+    #[test]
+    fn twice_full_scale_never_leaves_the_ceiling() {
+        let overloaded: Vec<f32> = (0..44100).map(|i| 2.0 * (TAU * 440.0 * i as f32 / 44100.0).sin()).collect();
+        let mut limiter = SafetyLimiter::new(SamplesBuffer::new(1, 44100, overloaded));
+        let clips = limiter.get_clip_counter();
+        let output: Vec<f32> = limiter.by_ref().collect();
+        assert!(output.iter().all(|sample| sample.abs() <= 1.01));
+        assert!(clips.load(Ordering::Relaxed) > 0);
+        // Once the gain has caught up, peaks sit in the knee rather than at the clamp
+        let settled_peak = output[22050..].iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!(settled_peak > 0.9 && settled_peak < 0.99, "{settled_peak}");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::clock::MasterClock;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Records a fixed-length loop and plays it back under the live signal.
+///
+/// Positions in the loop come from the master clock's sample count, so the loop stays
+/// phase-locked to everything else driven by that clock. Recording writes over the
+/// loop as it goes; recording while the loop plays overdubs onto it.
+pub struct LiveLooper {
+    buffer: Vec<f32>,
+    length_secs: f32,
+    record_pos: usize,
+    play_pos: usize,
+    recording: bool,
+    playing: bool,
+    sample_rate: u32,
+}
+
+impl LiveLooper {
+    pub fn new(sample_rate: u32, length_secs: f32) -> LiveLooper {
+        let length = ((length_secs * sample_rate as f32).round() as usize).max(1);
+        LiveLooper {
+            buffer: vec![0.0; length],
+            length_secs,
+            record_pos: 0,
+            play_pos: 0,
+            recording: false,
+            playing: false,
+            sample_rate,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn length_secs(&self) -> f32 {
+        self.length_secs
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// The loop button: empty -> recording -> playing -> overdubbing -> playing.
+    /// Starting a fresh recording wipes the old loop.
+    pub fn press(&mut self) {
+        match (self.recording, self.playing) {
+            (false, false) => {
+                self.buffer.fill(0.0);
+                self.recording = true;
+            }
+            (true, false) => {
+                self.recording = false;
+                self.playing = true;
+            }
+            (false, true) => self.recording = true,
+            (true, true) => self.recording = false,
+        }
+    }
+
+    /// Stops and empties the loop.
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.recording = false;
+        self.playing = false;
+    }
+
+    /// Mixes the loop into `input` at master clock sample `sample_count`, recording
+    /// `input` if armed.
+    pub fn process(&mut self, input: f32, sample_count: u64) -> f32 {
+        let position = (sample_count % self.buffer.len() as u64) as usize;
+        self.record_pos = position;
+        self.play_pos = position;
+
+        let looped = if self.playing { self.buffer[self.play_pos] } else { 0.0 };
+        if self.recording {
+            self.buffer[self.record_pos] = looped + input;
+        }
+        input + looped
+    }
+}
+
+/// Runs a source through a shared [`LiveLooper`], timed by `clock`.
+pub struct LooperSource<S: Source<Item = f32>> {
+    source: S,
+    looper: Arc<Mutex<LiveLooper>>,
+    clock: MasterClock,
+}
+
+impl<S: Source<Item = f32>> LooperSource<S> {
+    pub fn new(source: S, clock: MasterClock, length_secs: f32) -> LooperSource<S> {
+        let looper = LiveLooper::new(source.sample_rate(), length_secs);
+        LooperSource {
+            source,
+            looper: Arc::new(Mutex::new(looper)),
+            clock,
+        }
+    }
+
+    pub fn get_looper_control(&self) -> Arc<Mutex<LiveLooper>> {
+        self.looper.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for LooperSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for LooperSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        let sample_count = self.clock.sample_count();
+
+        match self.looper.lock() {
+            Ok(mut looper) => Some(looper.process(input, sample_count)),
+            Err(_) => Some(input),
+        }
+    }
+}
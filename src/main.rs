@@ -1,247 +1,1190 @@
-use rodio::{Source, Sink};
-use std::collections::HashMap;
+use exposrog::{
+    benchmark_latency, detect_chord, generate_wave_table, keycode_display, load_keymap, load_wave_table_from_wav,
+    measure_thd, midi_output_port_names, open_default_input, pan_control, parse_interval, parse_keycode,
+    play_midi_timeline, read_serum_frame, serum_frame_count, spawn_note_sequencer, thick_chorus_preset,
+    validate_wave_table_size, write_tone_to_wav, AdditivePreset, AdditiveSynthesizer, AdsrEnvelope, AppState,
+    Arpeggiator, BufferedSource, BusCompressor, BusCompressorSource, CcTarget, ChannelModeMessage, ChordName,
+    ChorusSource, ConstantPowerPanner, CpuMonitor, CpuTimer, Dashboard, DelaySource, DynamicWaveTable, Effect,
+    EnvelopedOscillator, FmOscillator, Gate, GateSource, HarmonizerSource, HighPassFilter, InterpolationMode,
+    KeyFrequencyTable, KeyRepeatSuppressor, KeyboardDrummer, LfoShape, LissajousDisplay, LooperSource, LowPassFilter,
+    MacroBank, MacroPlayer, MacroRecorder, MasterClock, MicThroughSource, MidiCcMapper, MidiFileEvent, MidiFileRecorder,
+    MidiPort, MidiTimeline, Mixer, ModulationSource, NoteSequencer, NoteVelocityMapper, Oscilloscope,
+    OvertoneFilterSource, PatchControls, PatchMemory, PeakMeter, PeakReader, PolyAftertouch, PolyphonicEngine,
+    ResonatorBank, ResonatorSource, Reverb, SafetyLimiter, ScopeTap, ShaperPreset, ShortcutLayer, SpectralFreeze,
+    StepSequencer, StereoBalance, StereoTap, StereoWidener, StereoWidenerSource, StutterSource, SuperSaw,
+    SustainController, SustainPedalSimulator, SvfSource, SynthError, TapeStopSource, TempoTapper, Theme, TonnetzDisplay,
+    Tremolo, TremoloSync, TriggerMode, TuningSystem, UnisonOscillator, VoiceChannel, WavSessionRecorder, WaveParams,
+    WaveShape, WaveShaper, WaveTableOscillator, WaveguideString, BUILTIN_FM_PRESETS, DEFAULT_CHANNEL_SPLIT_NOTE,
+    DEFAULT_NUMPAD_OCTAVE, KEYMAP_FILE, LISSAJOUS_HISTORY, MAX_UNISON_VOICES, MIDI_CLIENT_NAME,
+    REFERENCE_TEMPERATURE_CELSIUS, SERUM_FRAME_SIZE, THEME_NAMES,
+};
+use session::{RemapState, Session};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rodio::Sink;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::f32::consts::SQRT_2;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    event::{
+        self, DisableMouseCapture, Event, KeyCode, KeyEvent, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    cursor::{MoveTo, Show},
+    execute,
+    style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, Clear, ClearType, LeaveAlternateScreen,
+    },
 };
 
-struct WaveTableOscillator {
-    sample_rate: u32,
-    wave_table: Vec<f32>,
-    index: f32,
-    index_increment: f32,
-    frequency: Arc<Mutex<f32>>,
-}
+/// Set while `--tui` draws the dashboard, which shows status messages in place of the
+/// terminal.
+static DASHBOARD_STATE: Mutex<Option<Arc<Mutex<AppState>>>> = Mutex::new(None);
 
-impl WaveTableOscillator {
-    fn new(sample_rate: u32, wave_table: Vec<f32>) -> WaveTableOscillator {
-        WaveTableOscillator {
-            sample_rate,
-            wave_table,
-            index: 0.0,
-            index_increment: 0.0,
-            frequency: Arc::new(Mutex::new(0.0)),
+/// Set while the keyboard enhancement flags are pushed, so they're popped only once.
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+/// Status messages written with `print!` go to the dashboard's log while it's up, so
+/// they don't draw over it.
+macro_rules! print {
+    ($($arg:tt)*) => {
+        match $crate::DASHBOARD_STATE.lock().ok().and_then(|state| state.clone()) {
+            Some(state) => {
+                if let Ok(mut state) = state.lock() {
+                    state.log(&format!($($arg)*));
+                }
+            }
+            None => std::print!($($arg)*),
         }
+    };
+}
+
+// Declared below `print!` so the key handlers write through it as well
+mod session;
+
+/// Updates what the dashboard shows, if it's up.
+fn update_dashboard(update: impl FnOnce(&mut AppState)) {
+    let Some(shared) = DASHBOARD_STATE.lock().ok().and_then(|state| state.clone()) else {
+        return;
+    };
+    let Ok(mut state) = shared.lock() else {
+        return;
+    };
+    update(&mut state);
+}
+
+/// The built-in key map, with any [`KEYMAP_FILE`] in the working directory on top.
+fn load_key_frequencies() -> Result<KeyFrequencyTable, SynthError> {
+    let path = Path::new(KEYMAP_FILE);
+    if !path.exists() {
+        return Ok(KeyFrequencyTable::default());
     }
+    let keys = load_keymap(path)?;
+    println!("Loaded {} keys from {KEYMAP_FILE}", keys.len());
+    Ok(KeyFrequencyTable::with_keys(keys))
+}
 
-    fn get_frequency_control(&self) -> Arc<Mutex<f32>> {
-        self.frequency.clone()
+/// Undoes everything main does to the terminal, whichever way it exits: the panic
+/// hook, the Ctrl+C handler and the guard on main all call it. Leaving the alternate
+/// screen or showing the cursor when they weren't changed does nothing.
+fn restore_terminal() {
+    let mut stdout = std::io::stdout();
+    if KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+        let _ = execute!(stdout, PopKeyboardEnhancementFlags);
     }
+    let _ = execute!(stdout, DisableMouseCapture, LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout, ResetColor, Show);
+}
 
-    fn update_frequency(&mut self) {
-        if let Ok(freq) = self.frequency.lock() {
-            self.index_increment = *freq * self.wave_table.len() as f32 / self.sample_rate as f32;
-        }
+/// Lists the MIDI output ports and asks which to play into. On Unix, Enter makes a
+/// virtual port instead, as does having no ports at all.
+fn choose_midi_port() -> Result<MidiPort, SynthError> {
+    let names = midi_output_port_names()?;
+    if names.is_empty() {
+        #[cfg(unix)]
+        return MidiPort::open_virtual();
+        #[cfg(not(unix))]
+        return Err(SynthError::MidiError("no MIDI output ports".to_string()));
+    }
+    println!("MIDI output ports:");
+    for (index, name) in names.iter().enumerate() {
+        println!("  {index}: {name}");
+    }
+    if cfg!(unix) {
+        print!("Port number, or Enter for a virtual port named {MIDI_CLIENT_NAME}: ");
+    } else {
+        print!("Port number: ");
+    }
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    #[cfg(unix)]
+    if answer.is_empty() {
+        return MidiPort::open_virtual();
     }
+    MidiPort::open(parse_value("MIDI port", answer)?)
+}
+
+/// The audio callback size `--buffered` renders ahead for.
+const BUFFERED_CALLBACK_FRAMES: usize = 1024;
+/// Voices the polyphonic engine plays in full polyphony.
+const POLY_VOICES: usize = 8;
+/// Glide time for Alt+R when `--portamento` doesn't give one.
+const DEFAULT_PORTAMENTO_MS: u32 = 150;
+/// The CC targets with something to drive; `--cc-map` refuses the rest.
+/// `ReverbWet` waits on a reverb in the main chain.
+const CC_WIRED: [CcTarget; 12] = [
+    CcTarget::Volume,
+    CcTarget::FilterCutoff,
+    CcTarget::FilterResonance,
+    CcTarget::EnvAttack,
+    CcTarget::EnvDecay,
+    CcTarget::EnvSustain,
+    CcTarget::EnvRelease,
+    CcTarget::LfoRate,
+    CcTarget::LfoDepth,
+    CcTarget::ModIndex,
+    CcTarget::PulseWidth,
+    CcTarget::GlideTime,
+];
+
+/// Options for the interactive keyboard mode.
+struct CliOptions {
+    wave_table_size: usize,
+    microphone: bool,
+    buffered: bool,
+    /// Lets the oscilloscope trace run free instead of triggering on rising edges.
+    no_trigger: bool,
+    loop_length_secs: Option<f32>,
+    midi_file: Option<PathBuf>,
+    record_midi: Option<PathBuf>,
+    /// Sends the notes played to a MIDI output port chosen at startup.
+    midi_out: bool,
+    /// Draws the note, wave table, VU meter and messages as a full-screen dashboard.
+    tui: bool,
+    /// Keyboard macros loaded at start, if the file exists, and saved on exit.
+    macro_file: Option<PathBuf>,
+    cc_map: MidiCcMapper,
+    ambient_temp_celsius: f32,
+    /// Plays sub-bass and ultrasonic frequencies instead of silencing them.
+    no_frequency_gate: bool,
+    /// Seeds the generative sequencer, so a session can be replayed exactly.
+    seed: Option<u64>,
+    /// A Serum wavetable whose frames the digit keys pick in wavetable mode.
+    serum_wavetable: Option<PathBuf>,
+    /// A single-cycle WAV file played instead of the generated sine.
+    wave_file: Option<PathBuf>,
+    /// Intervals the ear trainer asks about, in semitones.
+    trainer_intervals: Vec<u8>,
+    theme: Theme,
+    /// Prints the audio devices' latency and exits instead of playing.
+    report_latency: bool,
+    /// Prints each interpolation mode's distortion and exits instead of playing.
+    report_thd: bool,
+    interpolation: InterpolationMode,
+    /// Prints the keyboard's notes and exits instead of playing.
+    print_key_table: bool,
+    /// The octave the numpad digits start in.
+    numpad_octave: u8,
+    /// Moves to the next shortcut layer.
+    layer_key: KeyCode,
+    /// Latches sustain on and off.
+    sustain_key: KeyCode,
+    /// Gives the polyphonic engine a reverb channel below middle C and a delay
+    /// channel above it.
+    channel_split: bool,
+    /// Attack, decay and release in seconds and the sustain level, which also
+    /// start the synth with its envelope on.
+    adsr: Option<(f32, f32, f32, f32)>,
+    /// Glide time between notes, which also starts the synth with portamento on.
+    portamento_ms: Option<u32>,
+    /// Voices stacked on the main oscillator, and how far apart they're detuned.
+    unison_voices: usize,
+    unison_detune_cents: f32,
+}
 
-    fn get_sample(&mut self) -> f32 {
-        self.update_frequency();
+impl CliOptions {
+    fn parse(args: &[String]) -> Result<CliOptions, SynthError> {
+        let mut options = CliOptions {
+            wave_table_size: 64,
+            microphone: false,
+            buffered: false,
+            no_trigger: false,
+            loop_length_secs: None,
+            midi_file: None,
+            record_midi: None,
+            midi_out: false,
+            tui: false,
+            macro_file: None,
+            cc_map: MidiCcMapper::default(),
+            ambient_temp_celsius: REFERENCE_TEMPERATURE_CELSIUS,
+            no_frequency_gate: false,
+            seed: None,
+            serum_wavetable: None,
+            wave_file: None,
+            trainer_intervals: (1..=12).collect(),
+            theme: Theme::dark(),
+            report_latency: false,
+            report_thd: false,
+            interpolation: InterpolationMode::Lerp,
+            print_key_table: false,
+            numpad_octave: DEFAULT_NUMPAD_OCTAVE,
+            layer_key: KeyCode::Tab,
+            sustain_key: KeyCode::CapsLock,
+            channel_split: false,
+            adsr: None,
+            portamento_ms: None,
+            unison_voices: 1,
+            unison_detune_cents: 15.0,
+        };
 
-        if self.index_increment == 0.0 {
-            return 0.0;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--wavetable-size" => {
+                    let value = option_value(&mut args, "--wavetable-size", "a value")?;
+                    options.wave_table_size = validate_wave_table_size(parse_value("--wavetable-size", value)?)?;
+                }
+                "--mic" => options.microphone = true,
+                "--buffered" => options.buffered = true,
+                "--report-latency" => options.report_latency = true,
+                "--report-thd" => options.report_thd = true,
+                "--interpolation" => {
+                    let value = option_value(&mut args, "--interpolation", "linear or cubic")?;
+                    options.interpolation = InterpolationMode::ALL
+                        .into_iter()
+                        .find(|mode| mode.to_string() == *value)
+                        .ok_or_else(|| SynthError::invalid_parameter("--interpolation", value, "not linear or cubic"))?;
+                }
+                "--print-key-table" => options.print_key_table = true,
+                "--channel-split" => options.channel_split = true,
+                "--no-trigger" => options.no_trigger = true,
+                "--no-frequency-gate" => options.no_frequency_gate = true,
+                "--midi" => options.midi_out = true,
+                "--tui" => options.tui = true,
+                "--loop-length" => {
+                    let value = option_value(&mut args, "--loop-length", "a value")?;
+                    let secs: f32 = parse_value("--loop-length", value)?;
+                    if secs.is_nan() || secs <= 0.0 {
+                        return Err(SynthError::invalid_parameter("--loop-length", value, "must be positive"));
+                    }
+                    options.loop_length_secs = Some(secs);
+                }
+                "--midi-file" => {
+                    let value = option_value(&mut args, "--midi-file", "a path")?;
+                    options.midi_file = Some(PathBuf::from(value));
+                }
+                "--macro-file" => {
+                    let value = option_value(&mut args, "--macro-file", "a path")?;
+                    options.macro_file = Some(PathBuf::from(value));
+                }
+                "--record-midi" => {
+                    let value = option_value(&mut args, "--record-midi", "a path")?;
+                    options.record_midi = Some(PathBuf::from(value));
+                }
+                "--cc-map" => {
+                    let value = option_value(&mut args, "--cc-map", "a list like 74:FilterCutoff,71:FilterResonance")?;
+                    options.cc_map = MidiCcMapper::parse(value)
+                        .map_err(|error| SynthError::invalid_parameter("--cc-map", value, error))?;
+                    if let Some(target) = options.cc_map.mappings.values().find(|target| !CC_WIRED.contains(target)) {
+                        return Err(SynthError::invalid_parameter(
+                            "--cc-map",
+                            value,
+                            format!("{target} isn't connected to anything in this build"),
+                        ));
+                    }
+                }
+                "--ambient-temp" => {
+                    let value = option_value(&mut args, "--ambient-temp", "a value in °C")?;
+                    let celsius: f32 = parse_value("--ambient-temp", value)?;
+                    if !celsius.is_finite() {
+                        return Err(SynthError::invalid_parameter("--ambient-temp", value, "must be a number"));
+                    }
+                    options.ambient_temp_celsius = celsius;
+                }
+                "--seed" => {
+                    let value = option_value(&mut args, "--seed", "a value")?;
+                    options.seed = Some(parse_value("--seed", value)?);
+                }
+                "--serum-wavetable" => {
+                    let value = option_value(&mut args, "--serum-wavetable", "a path")?;
+                    options.serum_wavetable = Some(PathBuf::from(value));
+                }
+                "--wave" => {
+                    let value = option_value(&mut args, "--wave", "a path")?;
+                    options.wave_file = Some(PathBuf::from(value));
+                }
+                "--trainer-intervals" => {
+                    let value = option_value(&mut args, "--trainer-intervals", "a list like m3,M3,P5")?;
+                    options.trainer_intervals = value
+                        .split(',')
+                        .map(|name| {
+                            let unknown = || SynthError::invalid_parameter("--trainer-intervals", name, "unknown interval");
+                            parse_interval(name).ok_or_else(unknown)
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "--numpad-octave" => {
+                    let value = option_value(&mut args, "--numpad-octave", "an octave from 0 to 8")?;
+                    options.numpad_octave = parse_value("--numpad-octave", value)?;
+                    if options.numpad_octave > 8 {
+                        return Err(SynthError::invalid_parameter("--numpad-octave", value, "must be 0 to 8"));
+                    }
+                }
+                "--layer-key" => {
+                    let value = option_value(&mut args, "--layer-key", "a key name like Tab or CapsLock")?;
+                    options.layer_key = parse_keycode(value)
+                        .map_err(|error| SynthError::invalid_parameter("--layer-key", value, error))?;
+                }
+                "--sustain-key" => {
+                    let value = option_value(&mut args, "--sustain-key", "a key name like CapsLock or Insert")?;
+                    options.sustain_key = parse_keycode(value)
+                        .map_err(|error| SynthError::invalid_parameter("--sustain-key", value, error))?;
+                }
+                "--adsr" => {
+                    let value = option_value(&mut args, "--adsr", "times and a level like 0.01,0.2,0.7,0.5")?;
+                    let stages = value
+                        .split(',')
+                        .map(|stage| parse_value("--adsr", stage))
+                        .collect::<Result<Vec<f32>, _>>()?;
+                    let [attack, decay, sustain, release] = stages[..] else {
+                        return Err(SynthError::invalid_parameter("--adsr", value, "needs four values"));
+                    };
+                    if [attack, decay, release].iter().any(|secs| secs.is_nan() || *secs < 0.0) {
+                        return Err(SynthError::invalid_parameter("--adsr", value, "times must not be negative"));
+                    }
+                    if !(0.0..=1.0).contains(&sustain) {
+                        return Err(SynthError::invalid_parameter("--adsr", value, "sustain must be 0 to 1"));
+                    }
+                    options.adsr = Some((attack, decay, sustain, release));
+                }
+                "--portamento" => {
+                    let value = option_value(&mut args, "--portamento", "a glide time in ms, up to 500")?;
+                    let ms = parse_value("--portamento", value)?;
+                    if ms > 500 {
+                        return Err(SynthError::invalid_parameter("--portamento", value, "must be at most 500 ms"));
+                    }
+                    options.portamento_ms = Some(ms);
+                }
+                "--unison" => {
+                    let value = option_value(&mut args, "--unison", "a number of voices")?;
+                    options.unison_voices = parse_value("--unison", value)?;
+                    if !(1..=MAX_UNISON_VOICES).contains(&options.unison_voices) {
+                        let reason = format!("must be 1 to {MAX_UNISON_VOICES}");
+                        return Err(SynthError::invalid_parameter("--unison", value, reason));
+                    }
+                }
+                "--unison-detune" => {
+                    let value = option_value(&mut args, "--unison-detune", "a detune in cents")?;
+                    options.unison_detune_cents = parse_value("--unison-detune", value)?;
+                    if !(0.0..=100.0).contains(&options.unison_detune_cents) {
+                        return Err(SynthError::invalid_parameter("--unison-detune", value, "must be 0 to 100 cents"));
+                    }
+                }
+                "--theme" => {
+                    let value = option_value(&mut args, "--theme", "a theme name or a theme.toml path")?;
+                    options.theme = match Theme::builtin(value) {
+                        Some(theme) => theme,
+                        None if value.ends_with(".toml") => Theme::load(Path::new(value))?,
+                        None => {
+                            let reason = format!("not one of {} or a .toml file", THEME_NAMES.join(", "));
+                            return Err(SynthError::invalid_parameter("--theme", value, reason));
+                        }
+                    };
+                }
+                other => return Err(SynthError::invalid_parameter("argument", other, "unknown argument")),
+            }
         }
 
-        let sample = self.lerp();
-        self.index += self.index_increment;
-        self.index %= self.wave_table.len() as f32;
-        sample * 0.3
+        Ok(options)
     }
+}
 
-    fn lerp(&self) -> f32 {
-        let truncated_index = self.index as usize;
-        let next_index = (truncated_index + 1) % self.wave_table.len();
+/// The value following `option` on the command line.
+fn option_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+    expected: &str,
+) -> Result<&'a String, SynthError> {
+    args.next().ok_or_else(|| SynthError::invalid_parameter(option, "", format!("needs {expected}")))
+}
+
+fn parse_value<T>(option: &str, value: &str) -> Result<T, SynthError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|error| SynthError::invalid_parameter(option, value, error))
+}
 
-        let next_index_weight = self.index - truncated_index as f32;
-        let truncated_index_weight = 1.0 - next_index_weight;
+/// `--generate-tone <freq> <waveform> <duration_ms> <output.wav>`
+fn generate_tone_command(args: &[String]) -> Result<(), SynthError> {
+    let [freq, waveform, duration_ms, output] = args else {
+        return Err(SynthError::invalid_parameter(
+            "--generate-tone",
+            "",
+            "usage: --generate-tone <freq> <waveform> <duration_ms> <output.wav>",
+        ));
+    };
 
-        truncated_index_weight * self.wave_table[truncated_index]
-            + next_index_weight * self.wave_table[next_index]
-    }
+    let freq: f32 = parse_value("freq", freq)?;
+    let waveform: WaveShape = parse_value("waveform", waveform)?;
+    let duration_ms: u32 = parse_value("duration_ms", duration_ms)?;
+
+    write_tone_to_wav(Path::new(output), freq, waveform, duration_ms)?;
+    println!("Wrote {duration_ms} ms of {freq} Hz {waveform} to {output}");
+    Ok(())
 }
 
-impl Source for WaveTableOscillator {
-    fn current_frame_len(&self) -> Option<usize> {
-        None
-    }
+fn main() -> Result<(), SynthError> {
+    // A panic skips the cleanup below, so put the terminal back before reporting it
+    std::panic::set_hook(Box::new(|info| {
+        restore_terminal();
+        eprintln!("Panic: {info}");
+    }));
 
-    fn channels(&self) -> u16 {
-        1
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--generate-tone") {
+        return generate_tone_command(&args[2..]);
     }
 
-    fn sample_rate(&self) -> u32 {
-        self.sample_rate
+    let options = CliOptions::parse(&args[1..])?;
+    if options.print_key_table {
+        let mut key_frequencies = load_key_frequencies()?;
+        key_frequencies.set_numpad_octave(options.numpad_octave);
+        print!("{}", key_frequencies.render_table(&TuningSystem::default()));
+        return Ok(());
+    }
+    if options.report_latency {
+        // A full BufferedSource queue sits between the chain and the device
+        let chain_latency_frames = if options.buffered { 2 * BUFFERED_CALLBACK_FRAMES } else { 0 };
+        println!("{}", benchmark_latency(chain_latency_frames)?);
+        return Ok(());
+    }
+    if options.report_thd {
+        // The worst case this synth plays: a small table read high up the keyboard
+        for interpolation in InterpolationMode::ALL {
+            let mut oscillator = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 64));
+            oscillator.set_interpolation(interpolation);
+            let thd = measure_thd(&mut oscillator, 2000.0, 10);
+            println!("{interpolation:>6}: {:.4}% THD, 2 kHz sine from a 64-sample table", thd * 100.0);
+        }
+        return Ok(());
+    }
+    let wave_table = generate_wave_table(WaveShape::Sine, options.wave_table_size);
+    // Waveform edits are rendered on a worker thread and picked up by the oscillator
+    let mut wave_params = WaveParams::new(WaveShape::Sine, options.wave_table_size);
+    let serum_frames = match &options.serum_wavetable {
+        Some(path) => {
+            let frame_count = serum_frame_count(path)?;
+            if frame_count == 0 {
+                return Err(SynthError::invalid_parameter(
+                    "--serum-wavetable",
+                    path.display(),
+                    format!("holds no whole {SERUM_FRAME_SIZE}-sample frames"),
+                ));
+            }
+            wave_params.custom_table = Some(read_serum_frame(path, 0)?);
+            Some((path.clone(), frame_count))
+        }
+        None => None,
+    };
+    if let Some(path) = &options.wave_file {
+        if serum_frames.is_some() {
+            return Err(SynthError::invalid_parameter("--wave", path.display(), "can't be used with --serum-wavetable"));
+        }
+        wave_params.custom_table = Some(load_wave_table_from_wav(path, options.wave_table_size)?);
     }
+    let dynamic_table = DynamicWaveTable::new(wave_params);
+    let wave_params_control = dynamic_table.get_params_control();
 
-    fn total_duration(&self) -> Option<std::time::Duration> {
-        None
+    // Create oscillator; it drives the master clock everything else times itself by
+    let clock = MasterClock::new(44100);
+    let mut oscillator = WaveTableOscillator::new(44100, wave_table.clone());
+    oscillator.set_dynamic_wave_table(&dynamic_table);
+    oscillator.set_interpolation(options.interpolation);
+    oscillator.detune_by_temperature(options.ambient_temp_celsius);
+    if options.no_frequency_gate {
+        oscillator.clear_frequency_gate();
     }
-}
+    oscillator.set_master_clock(clock.clone());
+    let frequency_control = oscillator.get_frequency_control();
+    let amplitude_control = oscillator.get_amplitude_control();
+    let sub_oscillator_control = oscillator.get_sub_oscillator_control();
+    let drift_control = oscillator.get_drift_control();
+    let lfo_control = oscillator.get_lfo_control();
+    let portamento_control = oscillator.get_portamento_control();
+    oscillator.set_portamento_time_ms(options.portamento_ms.unwrap_or(0));
+    // The glide Alt+R turns back on, changed by the glide time CC
+    let portamento_ms = options.portamento_ms.filter(|&ms| ms > 0).unwrap_or(DEFAULT_PORTAMENTO_MS);
+    let lfo_target_control = oscillator.get_lfo_target_control();
 
-impl Iterator for WaveTableOscillator {
-    type Item = f32;
+    let microphone = if options.microphone { Some(open_default_input()?) } else { None };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        Some(self.get_sample())
+    // Set up audio output
+    let (_stream, stream_handle) = rodio::OutputStream::try_default()?;
+    let sink = Sink::try_new(&stream_handle)?;
+    let (attack, decay, sustain, release) = options.adsr.unwrap_or((0.01, 0.2, 0.7, 0.5));
+    let mut oscillator = UnisonOscillator::new(oscillator);
+    oscillator.set_num_voices(options.unison_voices);
+    oscillator.set_detune_cents(options.unison_detune_cents);
+    let unison_voices_control = oscillator.get_voices_control();
+    let oscillator = EnvelopedOscillator::new(oscillator, AdsrEnvelope::new(44100, attack, decay, sustain, release));
+    let envelope_control = oscillator.get_envelope_control();
+    let envelope_enabled_control = oscillator.get_enabled_control();
+    if let (Some(_), Ok(mut enabled)) = (options.adsr, envelope_enabled_control.lock()) {
+        *enabled = true;
     }
-}
+    let resonator = ResonatorSource::new(oscillator, ResonatorBank::guitar_body(44100));
+    let resonator_enabled_control = resonator.get_enabled_control();
+    let resonator_bank_control = resonator.get_bank_control();
+    let mut filter = SvfSource::new(resonator, 1000.0, 0.0);
+    filter.set_tracked_frequency(frequency_control.clone());
+    let filter_tracking_control = filter.get_tracking_control();
+    let filter_lfo_control = filter.get_cutoff_lfo_control();
+    let filter_enabled_control = filter.get_enabled_control();
+    let filter_resonance_control = filter.get_resonance_control();
+    let filter_cutoff_control = filter.get_cutoff_control();
+    let mut overtones = OvertoneFilterSource::new(filter);
+    overtones.set_tracked_frequency(frequency_control.clone());
+    let overtone_control = overtones.get_filter_control();
+    // One shaper for each of ShaperPreset::ALL, with at most one of them on
+    let hard_clip = WaveShaper::from_preset(overtones, ShaperPreset::HardClip);
+    let hard_clip_controls = (hard_clip.get_enabled_control(), hard_clip.get_drive_control());
+    let soft_clip = WaveShaper::from_preset(hard_clip, ShaperPreset::SoftClip);
+    let soft_clip_controls = (soft_clip.get_enabled_control(), soft_clip.get_drive_control());
+    let bit_crush = WaveShaper::from_preset(soft_clip, ShaperPreset::BitCrush);
+    let bit_crush_controls = (bit_crush.get_enabled_control(), bit_crush.get_drive_control());
+    let shaper_controls = [hard_clip_controls, soft_clip_controls, bit_crush_controls];
+    let low_pass = LowPassFilter::new(bit_crush, 4000.0);
+    let low_pass_enabled_control = low_pass.get_enabled_control();
+    let low_pass_cutoff_control = low_pass.get_cutoff_control();
+    let high_pass = HighPassFilter::new(low_pass, 80.0);
+    let high_pass_enabled_control = high_pass.get_enabled_control();
+    let high_pass_cutoff_control = high_pass.get_cutoff_control();
+    let harmonizer = HarmonizerSource::new(high_pass);
+    let harmony_control = harmonizer.get_preset_control();
+    let step_sequencer = StepSequencer::new(120.0);
+    // Sixteenths at the sequencer's tempo, through the main voice
+    let arpeggiator = Arpeggiator::new(step_sequencer.bpm(), 4);
+    let tempo_tapper = TempoTapper::fixed_bpm(step_sequencer.bpm());
+    // Default to one 4/4 bar at the sequencer tempo
+    let loop_length = options.loop_length_secs.unwrap_or(4.0 * 60.0 / step_sequencer.bpm());
+    // The mic gets a reverb of its own and then shares the rest of the chain
+    let mic_reverb: Box<dyn Effect> = Box::new(Reverb::new(44100, 0.5, 0.3));
+    let mic_through = MicThroughSource::new(harmonizer, microphone.as_ref(), vec![mic_reverb]);
+    let mic_through_control = mic_through.get_enabled_control();
+    let vocoder_control = mic_through.get_vocoder_control();
+    let looper = LooperSource::new(mic_through, clock.clone(), loop_length);
+    let looper_control = looper.get_looper_control();
+    let freeze = SpectralFreeze::new(looper);
+    let freeze_control = freeze.get_freeze_control();
+    let echo = DelaySource::new(freeze, 300.0, 0.4);
+    let echo_control = echo.get_enabled_control();
+    let echo_time_control = echo.get_time_control();
+    let echo_feedback_control = echo.get_feedback_control();
+    let echo_bpm_control = echo.get_bpm_control();
+    if let Ok(mut bpm) = echo_bpm_control.lock() {
+        *bpm = step_sequencer.bpm();
+    }
+    // Balance is kept the same way as pan, -1.0 to 1.0 in f32 bits
+    let balance_control = pan_control(0.0);
+    let pan_control = pan_control(0.0);
+    let tape_stop = TapeStopSource::new(echo, 1.0);
+    let tape_stop_control = tape_stop.get_engaged_control();
+    let tremolo_sync = TremoloSync {
+        subdivision: 8,
+        master_clock: clock.clone(),
+        bpm: step_sequencer.bpm(),
+    };
+    let stutter = StutterSource::new(tape_stop, step_sequencer.bpm(), 3);
+    let stutter_trigger = stutter.get_trigger_control();
+    let stutter_bpm_control = stutter.get_bpm_control();
+    let tremolo = Tremolo::new(stutter, 5.0, 0.6, tremolo_sync);
+    let tremolo_control = tremolo.get_enabled_control();
+    let tremolo_synced_control = tremolo.get_synced_control();
+    let gate = GateSource::new(tremolo, Gate::trance(step_sequencer.bpm(), clock.clone()));
+    let gate_enabled_control = gate.get_enabled_control();
+    let gate_control = gate.get_gate_control();
+    let scope_tap = ScopeTap::new(gate, 2048);
+    let trigger_mode = if options.no_trigger { TriggerMode::Free } else { TriggerMode::RisingEdge(0.0) };
+    let scope_samples = scope_tap.get_samples_control();
+    let oscilloscope = Oscilloscope::new(scope_samples.clone(), trigger_mode);
+    let panner = ConstantPowerPanner::new(scope_tap, pan_control.clone());
+    let balance = StereoBalance::new(panner, balance_control.clone());
+    let chorus = ChorusSource::new(balance, thick_chorus_preset(44100));
+    let chorus_control = chorus.get_enabled_control();
+    let widener = StereoWidenerSource::new(chorus, StereoWidener::new(44100, 10.0, 1.5));
+    let widener_control = widener.get_enabled_control();
+    let stereo_tap = StereoTap::new(widener, LISSAJOUS_HISTORY);
+    let stereo_frames = stereo_tap.get_frames_control();
+    let lissajous = LissajousDisplay::new(33, 16);
+    let bus_compressor = BusCompressorSource::new(stereo_tap, BusCompressor::loud_master(44100));
+    let bus_compressor_control = bus_compressor.get_enabled_control();
+    let limiter = SafetyLimiter::new(bus_compressor);
+    let clip_counter = limiter.get_clip_counter();
+    // Ctrl+X records what's about to reach the speakers
+    let wav_recorder = WavSessionRecorder::new(44100);
+    let meter = PeakMeter::new(wav_recorder.tap(limiter));
+    let peak_reader = PeakReader::new(meter.get_peak_control(), 0.05);
+    // Every sink's rendering counts toward the DSP load shown next to the meter
+    let cpu_monitor = CpuMonitor::new(Duration::from_millis(500));
+    let meter = CpuTimer::new(meter, cpu_monitor.get_busy_control());
+    // Optionally render the effect chain ahead of the audio callback
+    let underrun_counter = if options.buffered {
+        let buffered = BufferedSource::new(meter, BUFFERED_CALLBACK_FRAMES);
+        let counter = buffered.get_underrun_counter();
+        sink.append(buffered);
+        Some(counter)
+    } else {
+        sink.append(meter);
+        None
+    };
+
+    // The super saw plays on its own sink so it can be swapped in without rebuilding the chain
+    let supersaw = SuperSaw::new(44100, frequency_control.clone());
+    let supersaw_detune_control = supersaw.get_detune_control();
+    let supersaw_mix_control = supersaw.get_mix_center_control();
+    let supersaw_sink = Sink::try_new(&stream_handle)?;
+    supersaw_sink.append(CpuTimer::new(
+        StereoBalance::new(ConstantPowerPanner::new(supersaw, pan_control.clone()), balance_control.clone()),
+        cpu_monitor.get_busy_control(),
+    ));
+    supersaw_sink.pause();
+
+    let fm = FmOscillator::new(44100, frequency_control.clone(), BUILTIN_FM_PRESETS[0]);
+    let fm_preset_control = fm.get_preset_control();
+    let fm_sink = Sink::try_new(&stream_handle)?;
+    fm_sink.append(CpuTimer::new(
+        StereoBalance::new(ConstantPowerPanner::new(fm, pan_control.clone()), balance_control.clone()),
+        cpu_monitor.get_busy_control(),
+    ));
+    fm_sink.pause();
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let wave_table_size = 64;
-    let mut wave_table: Vec<f32> = Vec::with_capacity(wave_table_size);
+    let string = WaveguideString::new(44100, frequency_control.clone());
+    let string_loss_control = string.get_loss_factor_control();
+    let string_sink = Sink::try_new(&stream_handle)?;
+    string_sink.append(CpuTimer::new(
+        StereoBalance::new(ConstantPowerPanner::new(string, pan_control.clone()), balance_control.clone()),
+        cpu_monitor.get_busy_control(),
+    ));
+    string_sink.pause();
 
-    // Generate a sine wave table
-    for i in 0..wave_table_size {
-        wave_table.push((2.0 * std::f32::consts::PI * i as f32 / wave_table_size as f32).sin());
+    let additive = AdditiveSynthesizer::new(44100, frequency_control.clone(), AdditivePreset::default());
+    let additive_partials_control = additive.get_partials_control();
+    let additive_sink = Sink::try_new(&stream_handle)?;
+    additive_sink.append(CpuTimer::new(
+        StereoBalance::new(ConstantPowerPanner::new(additive, pan_control.clone()), balance_control.clone()),
+        cpu_monitor.get_busy_control(),
+    ));
+    additive_sink.pause();
+
+    // The note sequencer loops on a sawtooth voice of its own, under whatever is played,
+    // and the drums ring over whatever voice is active; the two share an always-playing mix
+    let sequencer_table = generate_wave_table(WaveShape::Sawtooth, options.wave_table_size);
+    let sequencer_voice = WaveTableOscillator::new(44100, sequencer_table);
+    let note_sequencer = Arc::new(Mutex::new(NoteSequencer::new(step_sequencer.bpm(), 4)));
+    spawn_note_sequencer(note_sequencer.clone(), sequencer_voice.get_frequency_control());
+    let drummer = KeyboardDrummer::new(44100);
+    let drum_triggers = drummer.get_trigger_control();
+    let mut backing = Mixer::<8>::new(44100);
+    backing.add_source(sequencer_voice, 1.0, 0.0);
+    // Centred, each side gets the kit 3 dB down; this keeps it as loud as a mono sink played it
+    backing.add_source(drummer, SQRT_2, 0.0);
+    let backing_sink = Sink::try_new(&stream_handle)?;
+    backing_sink.append(CpuTimer::new(
+        StereoBalance::new(backing, balance_control.clone()),
+        cpu_monitor.get_busy_control(),
+    ));
+
+    // Polyphony plays on its own sink too, from voices that follow the waveform edits
+    let mut poly_prototype = WaveTableOscillator::new(44100, wave_table.clone());
+    poly_prototype.set_dynamic_wave_table(&dynamic_table);
+    poly_prototype.set_interpolation(options.interpolation);
+    poly_prototype.detune_by_temperature(options.ambient_temp_celsius);
+    if options.no_frequency_gate {
+        poly_prototype.clear_frequency_gate();
+    }
+    // The voices' clones share the prototype's level
+    let poly_amplitude_control = poly_prototype.get_amplitude_control();
+    let mut poly_engine = PolyphonicEngine::from_prototype(poly_prototype, POLY_VOICES);
+    poly_engine.set_channel_mode_control(options.cc_map.get_channel_mode_control());
+    let voice_pool_control = poly_engine.get_voice_pool_control();
+    let aftertouch = PolyAftertouch::new(poly_engine.get_lfo_depth_controls());
+    let poly_sink = Sink::try_new(&stream_handle)?;
+    if options.channel_split {
+        poly_engine.set_channels(vec![VoiceChannel::reverb(44100), VoiceChannel::delay(44100)]);
+        if let Ok(mut routing) = poly_engine.get_channel_routing_control().lock() {
+            routing.split_note = Some(DEFAULT_CHANNEL_SPLIT_NOTE);
+        }
+        // The channels already place the engine in stereo
+        poly_sink.append(CpuTimer::new(
+            StereoBalance::new(poly_engine, balance_control.clone()),
+            cpu_monitor.get_busy_control(),
+        ));
+    } else {
+        poly_sink.append(CpuTimer::new(
+            StereoBalance::new(ConstantPowerPanner::new(poly_engine, pan_control.clone()), balance_control.clone()),
+            cpu_monitor.get_busy_control(),
+        ));
     }
+    poly_sink.pause();
+    // Seeded from the clock so unmapped keys sound different each run; xorshift needs
+    // a non-zero state
+    let unmapped_rng = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64) | 1;
 
-    // Create oscillator
-    let oscillator = WaveTableOscillator::new(44100, wave_table);
-    let frequency_control = oscillator.get_frequency_control();
+    let patch_controls = PatchControls {
+        filter_enabled: filter_enabled_control.clone(),
+        filter_cutoff_hz: filter_cutoff_control.clone(),
+        filter_resonance: filter_resonance_control.clone(),
+        sub_oscillator: sub_oscillator_control.clone(),
+        fm_preset: fm_preset_control.clone(),
+        supersaw_detune_cents: supersaw_detune_control.clone(),
+        supersaw_mix_center: supersaw_mix_control.clone(),
+    };
 
-    // Set up audio output
-    let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
-    sink.append(oscillator);
-
-    // Complete keyboard frequency mapping
-    let mut key_frequencies = HashMap::new();
-
-    // Function keys and special keys
-    key_frequencies.insert(KeyCode::F(1), 55.00);        // A1
-    key_frequencies.insert(KeyCode::F(2), 58.27);        // A#1
-    key_frequencies.insert(KeyCode::F(3), 61.74);        // B1
-    key_frequencies.insert(KeyCode::F(4), 65.41);        // C2
-    key_frequencies.insert(KeyCode::F(5), 69.30);        // C#2
-    key_frequencies.insert(KeyCode::F(6), 73.42);        // D2
-    key_frequencies.insert(KeyCode::F(7), 77.78);        // D#2
-    key_frequencies.insert(KeyCode::F(8), 82.41);        // E2
-    key_frequencies.insert(KeyCode::F(9), 87.31);        // F2
-    key_frequencies.insert(KeyCode::F(10), 92.50);       // F#2
-    key_frequencies.insert(KeyCode::F(11), 98.00);       // G2
-    key_frequencies.insert(KeyCode::F(12), 103.83);      // G#2
-
-    // Number row
-    key_frequencies.insert(KeyCode::Char('1'), 1046.50); // C6
-    key_frequencies.insert(KeyCode::Char('2'), 1108.73); // C#6
-    key_frequencies.insert(KeyCode::Char('3'), 1174.66); // D6
-    key_frequencies.insert(KeyCode::Char('4'), 1244.51); // D#6
-    key_frequencies.insert(KeyCode::Char('5'), 1318.51); // E6
-    key_frequencies.insert(KeyCode::Char('6'), 1396.91); // F6
-    key_frequencies.insert(KeyCode::Char('7'), 1479.98); // F#6
-    key_frequencies.insert(KeyCode::Char('8'), 1567.98); // G6
-    key_frequencies.insert(KeyCode::Char('9'), 1661.22); // G#6
-    key_frequencies.insert(KeyCode::Char('0'), 1760.00); // A6
-    key_frequencies.insert(KeyCode::Char('-'), 1864.66); // A#6
-    key_frequencies.insert(KeyCode::Char('='), 1975.53); // B6
-
-    // Top row (QWERTY)
-    key_frequencies.insert(KeyCode::Char('q'), 2093.00); // C7
-    key_frequencies.insert(KeyCode::Char('w'), 523.25);  // C5
-    key_frequencies.insert(KeyCode::Char('e'), 554.37);  // C#5
-    key_frequencies.insert(KeyCode::Char('r'), 587.33);  // D5
-    key_frequencies.insert(KeyCode::Char('t'), 622.25);  // D#5
-    key_frequencies.insert(KeyCode::Char('y'), 659.25);  // E5
-    key_frequencies.insert(KeyCode::Char('u'), 698.46);  // F5
-    key_frequencies.insert(KeyCode::Char('i'), 739.99);  // F#5
-    key_frequencies.insert(KeyCode::Char('o'), 783.99);  // G5
-    key_frequencies.insert(KeyCode::Char('p'), 830.61);  // G#5
-    key_frequencies.insert(KeyCode::Char('['), 880.00);  // A5
-    key_frequencies.insert(KeyCode::Char(']'), 932.33);  // A#5
-    key_frequencies.insert(KeyCode::Char('\\'), 987.77); // B5
-
-    // Home row (ASDF)
-    key_frequencies.insert(KeyCode::Char('a'), 261.63);  // C4
-    key_frequencies.insert(KeyCode::Char('s'), 277.18);  // C#4
-    key_frequencies.insert(KeyCode::Char('d'), 293.66);  // D4
-    key_frequencies.insert(KeyCode::Char('f'), 311.13);  // D#4
-    key_frequencies.insert(KeyCode::Char('g'), 329.63);  // E4
-    key_frequencies.insert(KeyCode::Char('h'), 349.23);  // F4
-    key_frequencies.insert(KeyCode::Char('j'), 369.99);  // F#4
-    key_frequencies.insert(KeyCode::Char('k'), 392.00);  // G4
-    key_frequencies.insert(KeyCode::Char('l'), 415.30);  // G#4
-    key_frequencies.insert(KeyCode::Char(';'), 440.00);  // A4
-    key_frequencies.insert(KeyCode::Char('\''), 466.16); // A#4
-
-    // Bottom row (ZXCV)
-    key_frequencies.insert(KeyCode::Char('z'), 130.81);  // C3
-    key_frequencies.insert(KeyCode::Char('x'), 138.59);  // C#3
-    key_frequencies.insert(KeyCode::Char('c'), 146.83);  // D3
-    key_frequencies.insert(KeyCode::Char('v'), 155.56);  // D#3
-    key_frequencies.insert(KeyCode::Char('b'), 164.81);  // E3
-    key_frequencies.insert(KeyCode::Char('n'), 174.61);  // F3
-    key_frequencies.insert(KeyCode::Char('m'), 185.00);  // F#3
-    key_frequencies.insert(KeyCode::Char(','), 196.00);  // G3
-    key_frequencies.insert(KeyCode::Char('.'), 207.65);  // G#3
-    key_frequencies.insert(KeyCode::Char('/'), 220.00);  // A3
-
-    // Special keys
-    key_frequencies.insert(KeyCode::Char(' '), 110.00);     // A2
-    key_frequencies.insert(KeyCode::Tab, 116.54);       // A#2
-    key_frequencies.insert(KeyCode::Enter, 123.47);     // B2
-    key_frequencies.insert(KeyCode::Backspace, 233.08); // A#3
-    key_frequencies.insert(KeyCode::Delete, 246.94);    // B3
-    key_frequencies.insert(KeyCode::Insert, 2217.46);   // C#7
-    key_frequencies.insert(KeyCode::Home, 2349.32);     // D7
-    key_frequencies.insert(KeyCode::End, 2489.02);      // D#7
-    key_frequencies.insert(KeyCode::PageUp, 2637.02);   // E7
-    key_frequencies.insert(KeyCode::PageDown, 2793.83); // F7
-
-    // Arrow keys
-    key_frequencies.insert(KeyCode::Up, 41.20);         // E1
-    key_frequencies.insert(KeyCode::Down, 43.65);       // F1
-    key_frequencies.insert(KeyCode::Left, 46.25);       // F#1
-    key_frequencies.insert(KeyCode::Right, 49.00);      // G1
-
-    // Additional punctuation
-    key_frequencies.insert(KeyCode::Char('`'), 32.70);  // C1
-    key_frequencies.insert(KeyCode::Char('~'), 34.65);  // C#1
-    key_frequencies.insert(KeyCode::Char('!'), 36.71);  // D1
-    key_frequencies.insert(KeyCode::Char('@'), 38.89);  // D#1
-    key_frequencies.insert(KeyCode::Char('#'), 2959.96); // F#7
-    key_frequencies.insert(KeyCode::Char('$'), 3135.96); // G7
-    key_frequencies.insert(KeyCode::Char('%'), 3322.44); // G#7
-    key_frequencies.insert(KeyCode::Char('^'), 3520.00); // A7
-    key_frequencies.insert(KeyCode::Char('&'), 3729.31); // A#7
-    key_frequencies.insert(KeyCode::Char('*'), 3951.07); // B7
-    key_frequencies.insert(KeyCode::Char('('), 4186.01); // C8
-    key_frequencies.insert(KeyCode::Char(')'), 4434.92); // C#8
+    let mut key_frequencies = load_key_frequencies()?;
+    key_frequencies.set_numpad_octave(options.numpad_octave);
+    let rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
 
+    // Everything after this is drawn in the theme's colors, on its background
+    let theme = options.theme;
+    print!("{}{}", SetBackgroundColor(theme.background), SetForegroundColor(theme.primary));
+    if theme.background != Color::Reset {
+        print!("{}{}", Clear(ClearType::All), MoveTo(0, 0));
+    }
     println!("Press ESC to exit");
+    println!("Shift+U: cycle sub-oscillator, Ctrl+U: sub-oscillator mix");
+    println!("Shift+F: toggle filter, Shift+R: filter resonance, Alt+F: filter keyboard tracking, Alt+L: filter LFO");
+    println!("Alt+O: overtone filter (odd, even or fifths harmonics only)");
+    println!("Shift+X: filter LFO shape (sine, sample and hold, smooth sample and hold)");
+    println!("Shift+M: toggle peak meter, Shift+O: toggle oscilloscope, Alt+Left/Right: pan");
+    println!("Alt+H: register pan, placing each note by pitch like a piano, bass left and treble right");
+    println!("< and >: stereo balance");
+    println!("Ctrl+O: Lissajous (left vs right) display");
+    println!("Shift+Y: plot the oscillator's wave table");
+    println!("Alt+N: Tonnetz lattice of the notes played, scrolled with the arrow keys");
+    println!("Shift+P: list the strongest partials");
+    println!("Alt+T: toggle tremolo, Ctrl+T: switch tremolo between free and beat-synced");
+    println!("Ctrl+K: remap a key, Alt+K: restore the default key map");
+    println!("Numpad, where the terminal tells it apart: 0-9 play their own octave, * and / move it,");
+    println!("  + and - change the volume, Ctrl++ and Ctrl+- the tempo");
+    println!("Shift+S: toggle super saw, Ctrl+S: super saw detune, Alt+S: super saw center mix");
+    println!("Ctrl+F: cycle FM presets, Shift+Z: spectral freeze, Shift+E: echo, Alt+E: echo time");
+    println!("Alt+X: cycle additive presets (organ, brass, clarinet), summed from their harmonics");
+    println!("  and on the sound design layer, , and . shorten and lengthen the echo, n and m its feedback");
+    println!("Sound design layer D: waveshaper (hard clip, soft clip, bit crush, off), 9 and 0 its drive");
+    println!("Shift+H: cycle harmonizer intervals, Shift+T: tape stop / start");
+    println!("Shift+L: record / play / overdub loop, Ctrl+L: clear loop");
+    println!("Shift+C: choose a scale to lock notes to (type to search, Up/Down, Enter)");
+    println!("Shift+W: waveguide string, Ctrl+W: switch between sustained and pizzicato");
+    println!("Shift+V: cycle waveform, Ctrl+1-4: sine, square, sawtooth or triangle");
+    println!("Alt+V: step the square's pulse width");
+    if serum_frames.is_some() {
+        println!("Shift+N: wavetable mode, where 0-9 pick frames of the Serum wavetable");
+    }
+    println!("Shift+G: rhythmic gate, Alt+G: type a gate pattern");
+    println!("Shift+K: tap tempo for the sequencer, echo, gate and stutter");
+    println!("\": stutter, repeating the next eighth note three times");
+    println!("Shift+Q: interval ear training");
+    println!("?: show the keyboard layout, coloured by the locked scale if there is one");
+    println!("Shift+B: cycle body resonance (guitar, piano, off)");
+    println!("Shift+D: drum mode (Z kick, X snare, C/V closed/open hat, B clap, N tom)");
+    println!("Ctrl+P: select a patch by number (0-127), Ctrl+G: random sequencer pattern");
+    println!("Ctrl+R: random pitch mode, where every key plays a random note of the scale");
+    println!("Ctrl+D: analog tuning drift");
+    println!("Ctrl+E: thick seven-voice chorus, Alt+W: Haas stereo widener, Ctrl+B: master bus compressor");
+    println!("Ctrl+M: record a keyboard macro, Ctrl+Shift+M: play it, Alt+M: macro mode, where F1-F8 pick one");
+    println!("  (Ctrl+M needs a terminal that tells it apart from Enter)");
+    println!("F5: pedal mode, where space is held as a sustain pedal instead of playing A2");
+    println!("Ctrl+A: arpeggiator over the held keys at the sequencer tempo, Ctrl+N: its pattern, Ctrl+Y: latch");
+    println!("Ctrl+Z: panic, silencing the synth and sending MIDI all notes off");
+    println!("Alt+P: polyphony (8 voices, duophony, legato mono, off), where held keys sound together");
+    println!("  and the mouse wheel deepens the newest note's vibrato; Ctrl+V lists the voices");
+    println!("Ctrl+Up/Ctrl+Down: transpose the keyboard an octave up or down (up to 4 either way)");
+    println!("+/_ (Shift+= and Shift+-): raise and lower the oscillator level");
+    println!("Alt+Q: record sequencer steps from the notes played (Enter adds a rest), again to loop them;");
+    println!("  Alt+Z clears them, and the tempo keys set their speed");
+    println!("Alt+U: unison, stacking 3, 5 or 7 detuned copies of the main oscillator, spread by --unison-detune");
+    println!("Alt+I: LFO on the main oscillator (vibrato, tremolo, off); Alt+Left/Right rate, Alt+Up/Down depth");
+    println!("Ctrl+X: record the main voice and its effects to recording_<timestamp>.wav, Ctrl+X again to stop");
+    println!("Alt+R: portamento, gliding from each note to the next");
+    println!("Alt+D: ADSR envelope on the main oscillator, attacking on a key press and releasing when it comes up");
+    println!("Alt+A: A/B comparison, where A and B pick a config, Alt+C copies A to B and Alt+S swaps them");
+    println!(
+        "{}: next shortcut layer (notes, sound design, performance), each listing its keys",
+        keycode_display(options.layer_key)
+    );
+    println!(
+        "{}: latch sustain, holding notes after their keys come up until it's pressed again",
+        keycode_display(options.sustain_key)
+    );
+    if microphone.is_some() {
+        println!("Shift+I: route microphone level to filter cutoff");
+        println!("Shift+J: play the microphone through the effects");
+        println!("Alt+J: vocoder, where the microphone shapes the synth (try a saw and speak)");
+        println!("Shift+A: auto-follow sung pitch");
+    }
+
+    // Controller moves land in per-target atomics and are applied with the meter refresh
+    let mut cc_mapper = options.cc_map.clone();
+    let mut cc_controls = Vec::new();
+    for target in CcTarget::ALL {
+        if !cc_mapper.mappings.values().any(|mapped| *mapped == target) {
+            continue;
+        }
+        cc_controls.push((target, cc_mapper.get_target_control(target)));
+    }
+
+    // Play a MIDI file monophonically in the background, newest note winning. The
+    // notes held together still name a chord for the status line.
+    let midi_chord: Arc<Mutex<Option<ChordName>>> = Arc::new(Mutex::new(None));
+    if let Some(path) = &options.midi_file {
+        let timeline = MidiTimeline::load(path)?;
+        for channel in &timeline.ignored_channels {
+            println!("Warning: ignoring notes on MIDI channel {channel}");
+        }
+        println!("Playing {} ({:.1} s)", path.display(), timeline.duration_secs());
+
+        let frequency_control = frequency_control.clone();
+        let midi_chord = midi_chord.clone();
+        thread::spawn(move || {
+            let tuning = TuningSystem::default();
+            let mut current_note = None;
+            let mut held_notes = BTreeSet::new();
+            play_midi_timeline(&timeline, |event| {
+                let panic = match event {
+                    MidiFileEvent::ControlChange { controller, .. } => ChannelModeMessage::from_cc(controller),
+                    _ => None,
+                };
+                let changed = match event {
+                    MidiFileEvent::NoteOn { note, .. } => held_notes.insert(note),
+                    MidiFileEvent::NoteOff { note } => held_notes.remove(&note),
+                    MidiFileEvent::ControlChange { .. } if panic.is_some() => {
+                        let had_notes = !held_notes.is_empty();
+                        held_notes.clear();
+                        had_notes
+                    }
+                    MidiFileEvent::ControlChange { .. } => false,
+                };
+                if changed {
+                    let freqs: Vec<f32> = held_notes.iter().map(|&note| tuning.frequency(note)).collect();
+                    if let Ok(mut chord) = midi_chord.lock() {
+                        *chord = detect_chord(&freqs);
+                    }
+                }
+                let frequency = match event {
+                    MidiFileEvent::NoteOn { note, .. } => {
+                        current_note = Some(note);
+                        tuning.frequency(note)
+                    }
+                    MidiFileEvent::NoteOff { note } if current_note == Some(note) => {
+                        current_note = None;
+                        0.0
+                    }
+                    MidiFileEvent::NoteOff { .. } => return,
+                    // The monophonic voice has no release or effect state to clear, so
+                    // both panic messages just stop the note
+                    MidiFileEvent::ControlChange { controller, value } if panic.is_some() => {
+                        // The polyphonic engine hears it through the mapper
+                        cc_mapper.control_change(controller, value);
+                        current_note = None;
+                        0.0
+                    }
+                    MidiFileEvent::ControlChange { controller, value } => {
+                        cc_mapper.control_change(controller, value);
+                        return;
+                    }
+                };
+                if let Ok(mut freq) = frequency_control.lock() {
+                    *freq = frequency;
+                }
+            });
+        });
+    }
+
+    // Record played notes as they happen. SIGTERM skips the end of main, so the
+    // handler closes the file off itself.
+    let midi_port = if options.midi_out { Some(choose_midi_port()?) } else { None };
+    if let Some(port) = &midi_port {
+        println!("Sending notes to MIDI port {}", port.name());
+    }
+    let midi_recorder = match &options.record_midi {
+        Some(path) => Some(Arc::new(Mutex::new(MidiFileRecorder::create(path)?))),
+        None => None,
+    };
+    if let (Some(recorder), Some(path)) = (&midi_recorder, &options.record_midi) {
+        println!("Recording MIDI to {}", path.display());
+        let recorder = recorder.clone();
+        ctrlc::set_handler(move || {
+            if let Ok(mut recorder) = recorder.lock() {
+                let _ = recorder.finish();
+            }
+            restore_terminal();
+            std::process::exit(0);
+        })
+        .map_err(|error| SynthError::IoError(std::io::Error::other(error)))?;
+    }
 
     // Enable raw mode for immediate key detection
     enable_raw_mode()?;
+    // Key releases let a held note ignore the terminal's auto-repeat, and
+    // disambiguated keys tell the numpad apart. Windows sends releases without asking
+    let keyboard_enhanced = supports_keyboard_enhancement().unwrap_or(false);
+    if keyboard_enhanced {
+        let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES | KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES;
+        execute!(std::io::stdout(), PushKeyboardEnhancementFlags(flags))?;
+        KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+    }
+    let key_repeats = (keyboard_enhanced || cfg!(windows)).then(KeyRepeatSuppressor::default);
+    // Covers every `?` between here and the end of main
+    scopeguard::defer! {
+        restore_terminal();
+    }
+    // Declared after the guard, so an early return closes it before the terminal is restored
+    let mut dashboard = None;
+    if options.tui {
+        let table = dynamic_table.current().read().map(|table| table.clone()).unwrap_or_default();
+        let state = Arc::new(Mutex::new(AppState::new(table)));
+        dashboard = Some(Dashboard::spawn(state.clone(), scope_samples.clone())?);
+        if let Ok(mut shared) = DASHBOARD_STATE.lock() {
+            *shared = Some(state);
+        }
+    }
+    let wave_table_version = dynamic_table.version();
+    let (columns, _) = crossterm::terminal::size().unwrap_or((80, 24));
+    let mut session = Session {
+        sink,
+        frequency_control,
+        amplitude_control,
+        sub_oscillator_control,
+        drift_control,
+        lfo_control,
+        lfo_target_control,
+        portamento_control,
+        portamento_ms,
+        unison_voices_control,
+        envelope_control,
+        envelope_enabled_control,
+        dynamic_table,
+        wave_params_control,
+        wave_table_version,
+        serum_frames,
+        wavetable_mode: false,
+
+        resonator_enabled_control,
+        resonator_bank_control,
+        resonator_body: "off",
+        filter_enabled_control,
+        filter_cutoff_control,
+        filter_resonance_control,
+        filter_tracking_control,
+        filter_lfo_control,
+        filter_lfo_shape: LfoShape::Sine,
+        overtone_control,
+        overtone_preset: None,
+        shaper_controls,
+        shaper_index: None,
+        low_pass_enabled_control,
+        low_pass_cutoff_control,
+        high_pass_enabled_control,
+        high_pass_cutoff_control,
+
+        harmony_control,
+        microphone,
+        mic_through_control,
+        vocoder_control,
+        cutoff_modulation: ModulationSource::Off,
+        auto_follow: false,
+        looper_control,
+        freeze_control,
+        echo_control,
+        echo_time_control,
+        echo_feedback_control,
+        echo_bpm_control,
+        echo_time_index: None,
+        tape_stop_control,
+        stutter_trigger,
+        stutter_bpm_control,
+        tremolo_control,
+        tremolo_synced_control,
+        gate_enabled_control,
+        gate_control,
+        pan_control,
+        balance_control,
+        register_panning: false,
+        chorus_control,
+        widener_control,
+        bus_compressor_control,
+        wav_recorder,
+
+        theme,
+        scope_samples,
+        oscilloscope,
+        stereo_frames,
+        lissajous,
+        peak_reader,
+        cpu_monitor,
+        clip_counter,
+        last_clip_count: 0,
+        dashboard,
+        dashboard_table_version: None,
+        show_meter: false,
+        show_scope: false,
+        show_lissajous: false,
+        show_waveform: false,
+        waveform_version: None,
+        show_layout: false,
+        layout_flash: None,
+        show_tonnetz: false,
+        tonnetz: TonnetzDisplay::new(columns.saturating_sub(3) / 4, 5),
+        tonnetz_dirty: false,
+        tonnetz_chord: None,
+        last_meter_update: Instant::now(),
+        last_lissajous_update: Instant::now(),
+
+        supersaw_sink,
+        supersaw_detune_control,
+        supersaw_mix_control,
+        fm_sink,
+        fm_preset_control,
+        fm_preset_index: None,
+        string_sink,
+        string_loss_control,
+        additive_sink,
+        additive_partials_control,
+        additive_preset_index: None,
+        poly_sink,
+        poly_amplitude_control,
+        voice_pool_control,
+        aftertouch,
+        poly_mode: None,
+        drum_triggers,
+        drum_mode: false,
+        patch_memory: PatchMemory::default(),
+        patch_controls,
+        ab_comparison: None,
+
+        step_sequencer,
+        arpeggiator,
+        arp_enabled: false,
+        last_arp_tick: Instant::now(),
+        tempo_tapper,
+        note_sequencer,
+        backing_sink,
+        sequencer_recording: false,
+
+        key_frequencies,
+        octave_offset: 0,
+        scale_lock: None,
+        random_pitch: false,
+        velocity_mapper: NoteVelocityMapper::default(),
+        master_volume: 1.0,
+        note_amplitude: 1.0,
+        sustain: SustainController::default(),
+        sustain_pedal: SustainPedalSimulator::default(),
+        sustain_key: options.sustain_key,
+        held_notes: HashSet::new(),
+        pedal_held_note: false,
+        key_repeats,
+        keyboard_enhanced,
+        unmapped_rng,
+        rng,
+        macro_voice: None,
+        last_voice: None,
+        scheduled_tones: VecDeque::new(),
+
+        remap_state: RemapState::Idle,
+        scale_chooser: None,
+        patch_input: None,
+        gate_pattern_input: None,
+        interval_trainer: None,
+        trainer_guess: String::new(),
+        trainer_intervals: options.trainer_intervals.clone(),
+
+        shortcut_layer: ShortcutLayer {
+            cycle_key: options.layer_key,
+            ..ShortcutLayer::default()
+        },
+        macro_bank: match &options.macro_file {
+            Some(path) if path.exists() => MacroBank::load(path)?,
+            _ => MacroBank::default(),
+        },
+        macro_recorder: MacroRecorder::default(),
+        macro_player: MacroPlayer::default(),
+        macro_mode: false,
+        macro_slot: 0,
+
+        cc_controls,
+        midi_chord,
+        playing_midi_file: options.midi_file.is_some(),
+        midi_port,
+        midi_recorder,
+        midi_note: None,
+    };
 
     loop {
-        if event::poll(Duration::from_millis(10))? {
-            if let Event::Key(KeyEvent { code, .. }) = event::read()? {
-                match code {
-                    KeyCode::Esc => break,
-                    key => {
-                        if let Some(&frequency) = key_frequencies.get(&key) {
-                            // Play the note
-                            if let Ok(mut freq) = frequency_control.lock() {
-                                *freq = frequency;
-                            }
-                        } else {
-                            // For any unmapped key, assign a random frequency
-                            if let Ok(mut freq) = frequency_control.lock() {
-                                *freq = 200.0 + (std::ptr::addr_of!(key) as usize % 1000) as f32;
-                            }
-                        }
-                    }
-                }
+        // A macro's keys go through the same handling as the keyboard's
+        let macro_event = session.macro_player.next_due(Instant::now()).map(|key| Event::Key(KeyEvent::from(key)));
+        let from_macro = macro_event.is_some();
+        if from_macro || event::poll(Duration::from_millis(10))? {
+            let event = match macro_event {
+                Some(event) => event,
+                None => event::read()?,
+            };
+            match event {
+                Event::Key(key_event) if session.handle_key(key_event, from_macro)?.is_break() => break,
+                Event::Mouse(mouse_event) => session.handle_mouse(mouse_event),
+                _ => {}
             }
         }
 
+        session.play_scheduled_tones();
+        session.tick_arpeggiator();
+        session.refresh_meter()?;
+        session.redraw_displays()?;
+
         // Small delay to prevent excessive CPU usage
         thread::sleep(Duration::from_millis(1));
     }
 
     // Restore terminal
+    if let Some(mut dashboard) = session.dashboard.take() {
+        if let Ok(mut shared) = DASHBOARD_STATE.lock() {
+            shared.take();
+        }
+        dashboard.stop()?;
+    }
     disable_raw_mode()?;
+    if let Some(note) = session.midi_note {
+        // The recording ends its own held notes when it's finished
+        session::send_midi_event(None, &mut session.midi_port, MidiFileEvent::NoteOff { note })?;
+    }
+    print!("{ResetColor}");
+    if let Some(Ok(mut recorder)) = session.midi_recorder.as_ref().map(|r| r.lock()) {
+        recorder.finish()?;
+        if let Some(path) = &options.record_midi {
+            println!("Recorded MIDI to {}", path.display());
+        }
+    }
+    if let Some(counter) = underrun_counter {
+        println!("Buffer underruns: {}", counter.load(Ordering::Relaxed));
+    }
+    if let Some(path) = &options.macro_file {
+        session.macro_bank.save(path)?;
+        println!("Saved keyboard macros to {}", path.display());
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+pub const MAX_VOICES: usize = 16;
+pub const MAX_BUSES: usize = 4;
+
+pub const MAIN_LEFT_BUS: usize = 0;
+pub const MAIN_RIGHT_BUS: usize = 1;
+pub const REVERB_SEND_BUS: usize = 2;
+pub const DELAY_SEND_BUS: usize = 3;
+
+/// A bus's summed signal as a [`Source`], for an [`EffectBus`] effect to process.
+///
+/// It plays whatever the [`OscillatorMatrix`] last put on the bus, one sample per
+/// frame, so an effect built on it must take one sample from it per sample it gives.
+pub struct BusInput {
+    sample_rate: u32,
+    sample: Arc<AtomicU32>,
+}
+
+impl Source for BusInput {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for BusInput {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(f32::from_bits(self.sample.load(Ordering::Relaxed)))
+    }
+}
+
+/// An effect fed from one bus of an [`OscillatorMatrix`] and returned to
+/// `output_bus`. A stereo effect returns its left channel to `output_bus` and its
+/// right to the bus after it, so a stereo reverb returning to [`MAIN_LEFT_BUS`]
+/// lands on both main outputs.
+pub struct EffectBus {
+    pub effect: Box<dyn Source<Item = f32> + Send>,
+    pub output_bus: usize,
+}
+
+/// Routes each voice to any mix of buses, like the sends on a mixing console.
+///
+/// `routing_matrix[voice][bus]` is the gain from a voice to a bus. Buses
+/// [`MAIN_LEFT_BUS`] and [`MAIN_RIGHT_BUS`] are the output, and by convention
+/// [`REVERB_SEND_BUS`] and [`DELAY_SEND_BUS`] feed effects. Effects run in the order
+/// they were added, each after the voices, so one can feed a later one's bus. The
+/// output is interleaved stereo, like [`Mixer`](crate::Mixer).
+pub struct OscillatorMatrix {
+    sample_rate: u32,
+    pub routing_matrix: [[f32; MAX_BUSES]; MAX_VOICES],
+    voices: [Option<Box<dyn Source<Item = f32> + Send>>; MAX_VOICES],
+    bus_feeds: [Arc<AtomicU32>; MAX_BUSES],
+    /// Each effect with the bus it reads.
+    effects: Vec<(usize, EffectBus)>,
+    pending_right: Option<f32>,
+}
+
+impl OscillatorMatrix {
+    pub fn new(sample_rate: u32) -> OscillatorMatrix {
+        OscillatorMatrix {
+            sample_rate,
+            routing_matrix: [[0.0; MAX_BUSES]; MAX_VOICES],
+            voices: std::array::from_fn(|_| None),
+            bus_feeds: std::array::from_fn(|_| Arc::new(AtomicU32::new(0.0_f32.to_bits()))),
+            effects: Vec::new(),
+            pending_right: None,
+        }
+    }
+
+    /// Puts `source` in the first free voice slot, routed to both main buses at
+    /// unity, and returns its index; `None` if all [`MAX_VOICES`] are taken.
+    pub fn add_voice(&mut self, source: impl Source<Item = f32> + Send + 'static) -> Option<usize> {
+        let voice = self.voices.iter().position(Option::is_none)?;
+        self.voices[voice] = Some(Box::new(source));
+        self.routing_matrix[voice] = [0.0; MAX_BUSES];
+        self.routing_matrix[voice][MAIN_LEFT_BUS] = 1.0;
+        self.routing_matrix[voice][MAIN_RIGHT_BUS] = 1.0;
+        Some(voice)
+    }
+
+    pub fn remove_voice(&mut self, voice: usize) {
+        if let Some(slot) = self.voices.get_mut(voice) {
+            *slot = None;
+        }
+    }
+
+    pub fn set_route(&mut self, voice: usize, bus: usize, gain: f32) {
+        if let Some(route) = self.routing_matrix.get_mut(voice).and_then(|row| row.get_mut(bus)) {
+            *route = gain;
+        }
+    }
+
+    /// A source playing `bus`, to build an effect on; `None` for no such bus.
+    pub fn bus_input(&self, bus: usize) -> Option<BusInput> {
+        Some(BusInput {
+            sample_rate: self.sample_rate,
+            sample: self.bus_feeds.get(bus)?.clone(),
+        })
+    }
+
+    /// Runs `effect` on `input_bus`, which its effect should be reading through
+    /// [`bus_input`](Self::bus_input). Returns `false`, dropping it, if either bus
+    /// doesn't exist.
+    pub fn add_effect(&mut self, input_bus: usize, effect: EffectBus) -> bool {
+        if input_bus >= MAX_BUSES || effect.output_bus >= MAX_BUSES {
+            return false;
+        }
+        self.effects.push((input_bus, effect));
+        true
+    }
+
+    fn mix_frame(&mut self) -> (f32, f32) {
+        let mut buses = [0.0; MAX_BUSES];
+        for (slot, routes) in self.voices.iter_mut().zip(&self.routing_matrix) {
+            let Some(voice) = slot.as_mut() else {
+                continue;
+            };
+            match voice.next() {
+                Some(sample) => {
+                    for (bus, gain) in buses.iter_mut().zip(routes) {
+                        *bus += sample * gain;
+                    }
+                }
+                None => *slot = None,
+            }
+        }
+
+        for (input_bus, bus) in &mut self.effects {
+            self.bus_feeds[*input_bus].store(buses[*input_bus].to_bits(), Ordering::Relaxed);
+            let left = bus.effect.next().unwrap_or(0.0);
+            buses[bus.output_bus] += left;
+            if bus.effect.channels() == 2 {
+                let right = bus.effect.next().unwrap_or(0.0);
+                if let Some(next_bus) = buses.get_mut(bus.output_bus + 1) {
+                    *next_bus += right;
+                }
+            }
+        }
+
+        (buses[MAIN_LEFT_BUS], buses[MAIN_RIGHT_BUS])
+    }
+}
+
+impl Source for OscillatorMatrix {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for OscillatorMatrix {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let (left, right) = self.mix_frame();
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
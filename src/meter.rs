@@ -0,0 +1,109 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Passes a source through untouched while recording its peak absolute level.
+///
+/// The peak lives in an `AtomicU32` holding `f32` bits. Non-negative floats order the
+/// same way as their bit patterns, so `fetch_max` on the bits is a float max and the
+/// audio thread never takes a lock.
+pub struct PeakMeter<S: Source<Item = f32>> {
+    source: S,
+    peak: Arc<AtomicU32>,
+}
+
+impl<S: Source<Item = f32>> PeakMeter<S> {
+    pub fn new(source: S) -> PeakMeter<S> {
+        PeakMeter {
+            source,
+            peak: Arc::new(AtomicU32::new(0.0_f32.to_bits())),
+        }
+    }
+
+    pub fn get_peak_control(&self) -> Arc<AtomicU32> {
+        self.peak.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for PeakMeter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for PeakMeter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        self.peak.fetch_max(sample.abs().to_bits(), Ordering::Relaxed);
+        Some(sample)
+    }
+}
+
+/// UI-side view of a [`PeakMeter`] that lets the held peak fall back toward zero.
+pub struct PeakReader {
+    peak: Arc<AtomicU32>,
+    decay_per_second: f32,
+}
+
+impl PeakReader {
+    /// `decay_per_second` is the fraction of the level kept after one second, e.g. 0.05.
+    pub fn new(peak: Arc<AtomicU32>, decay_per_second: f32) -> PeakReader {
+        PeakReader {
+            peak,
+            decay_per_second: decay_per_second.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Returns the current peak and decays the stored value by `elapsed`.
+    pub fn poll(&self, elapsed: Duration) -> f32 {
+        let factor = self.decay_per_second.powf(elapsed.as_secs_f32());
+        // fetch_update retries if the audio thread raised the peak in between
+        let previous = self
+            .peak
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f32::from_bits(bits) * factor).to_bits())
+            })
+            .unwrap_or_else(|bits| bits);
+        f32::from_bits(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn peak_reaches_full_scale_within_ten_samples() {
+        let square: Vec<f32> = (0..100).map(|i| if i % 4 < 2 { 1.0 } else { -1.0 }).collect();
+        let mut meter = PeakMeter::new(SamplesBuffer::new(1, 44100, square));
+        let peak = meter.get_peak_control();
+        for _ in 0..10 {
+            meter.next();
+        }
+        assert_eq!(f32::from_bits(peak.load(Ordering::Relaxed)), 1.0);
+    }
+
+    #[test]
+    fn reader_decays_the_held_peak() {
+        let peak = Arc::new(AtomicU32::new(1.0_f32.to_bits()));
+        let reader = PeakReader::new(peak.clone(), 0.25);
+        assert_eq!(reader.poll(Duration::from_millis(500)), 1.0);
+        assert!((reader.poll(Duration::ZERO) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::channel::Effect;
+use crate::input::AudioInput;
+use crate::vocoder::Vocoder;
+use crossbeam::queue::SegQueue;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Bands in the vocoder, enough for intelligible speech.
+const VOCODER_BANDS: usize = 16;
+
+/// Mixes the microphone, run through its own effects, into a mono source, for
+/// singing through the synth's effects or processing an instrument with them.
+///
+/// The input device's samples are resampled to the source's rate on the fly. At
+/// most [`MIC_THROUGH_MAX_LATENCY_SECS`](crate::MIC_THROUGH_MAX_LATENCY_SECS) of
+/// audio waits between the two devices; when the output's buffer is larger than
+/// that, the mic drops out for part of each buffer. While disabled the queue is
+/// drained, so switching on plays the microphone as it is now.
+///
+/// With the vocoder on, the synth is shaped by the dry microphone instead of being
+/// mixed with it, whether or not the mic itself is switched on.
+pub struct MicThroughSource<S: Source<Item = f32>> {
+    source: S,
+    through: Arc<SegQueue<f32>>,
+    effects: Vec<Box<dyn Effect>>,
+    enabled: Arc<Mutex<bool>>,
+    mic_level: Arc<Mutex<f32>>,
+    synth_level: Arc<Mutex<f32>>,
+    vocoder: Vocoder,
+    vocoder_enabled: Arc<Mutex<bool>>,
+    /// Input samples per output sample.
+    step: f32,
+    position: f32,
+    previous: f32,
+    current: f32,
+}
+
+/// Routes `input` through `effects` and mixes it with `source`. The mic starts
+/// switched off, at full level.
+pub fn enable_mic_through<S: Source<Item = f32>>(
+    input: &AudioInput,
+    source: S,
+    effects: Vec<Box<dyn Effect>>,
+) -> MicThroughSource<S> {
+    MicThroughSource::new(source, Some(input), effects)
+}
+
+impl<S: Source<Item = f32>> MicThroughSource<S> {
+    /// Without an input there is nothing to mix in and the source plays as it is,
+    /// scaled by the synth level.
+    pub fn new(source: S, input: Option<&AudioInput>, effects: Vec<Box<dyn Effect>>) -> MicThroughSource<S> {
+        let step = input.map_or(1.0, |input| input.sample_rate as f32 / source.sample_rate() as f32);
+        let vocoder = Vocoder::new(source.sample_rate(), VOCODER_BANDS);
+        MicThroughSource {
+            source,
+            through: input.map_or_else(|| Arc::new(SegQueue::new()), |input| input.through.clone()),
+            effects,
+            enabled: Arc::new(Mutex::new(false)),
+            mic_level: Arc::new(Mutex::new(1.0)),
+            synth_level: Arc::new(Mutex::new(1.0)),
+            vocoder,
+            vocoder_enabled: Arc::new(Mutex::new(false)),
+            step,
+            position: 0.0,
+            previous: 0.0,
+            current: 0.0,
+        }
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+
+    pub fn get_mic_level_control(&self) -> Arc<Mutex<f32>> {
+        self.mic_level.clone()
+    }
+
+    pub fn get_synth_level_control(&self) -> Arc<Mutex<f32>> {
+        self.synth_level.clone()
+    }
+
+    pub fn get_vocoder_control(&self) -> Arc<Mutex<bool>> {
+        self.vocoder_enabled.clone()
+    }
+
+    /// The next microphone sample at the source's rate, by linear interpolation. An
+    /// empty queue holds the last sample.
+    fn next_input(&mut self) -> f32 {
+        self.position += self.step;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            self.previous = self.current;
+            self.current = self.through.pop().unwrap_or(self.current);
+        }
+        self.previous + (self.current - self.previous) * self.position
+    }
+}
+
+impl<S: Source<Item = f32>> Source for MicThroughSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for MicThroughSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let synth = self.source.next()? * self.synth_level.lock().map_or(1.0, |level| *level);
+        if self.vocoder_enabled.lock().is_ok_and(|enabled| *enabled) {
+            let mic = self.next_input() * self.mic_level.lock().map_or(0.0, |level| *level);
+            return Some(self.vocoder.process(synth, mic));
+        }
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            while self.through.pop().is_some() {}
+            return Some(synth);
+        }
+
+        let mic = self.next_input();
+        let mic = self.effects.iter_mut().fold(mic, |sample, effect| effect.process(sample));
+        Some(synth + mic * self.mic_level.lock().map_or(0.0, |level| *level))
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A parameter a MIDI controller knob or fader can drive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CcTarget {
+    Volume,
+    FilterCutoff,
+    FilterResonance,
+    EnvAttack,
+    EnvDecay,
+    EnvSustain,
+    EnvRelease,
+    LfoRate,
+    LfoDepth,
+    ReverbWet,
+    ModIndex,
+    PulseWidth,
+    GlideTime,
+}
+
+impl CcTarget {
+    pub const ALL: [CcTarget; 13] = [
+        CcTarget::Volume,
+        CcTarget::FilterCutoff,
+        CcTarget::FilterResonance,
+        CcTarget::EnvAttack,
+        CcTarget::EnvDecay,
+        CcTarget::EnvSustain,
+        CcTarget::EnvRelease,
+        CcTarget::LfoRate,
+        CcTarget::LfoDepth,
+        CcTarget::ReverbWet,
+        CcTarget::ModIndex,
+        CcTarget::PulseWidth,
+        CcTarget::GlideTime,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CcTarget::Volume => "Volume",
+            CcTarget::FilterCutoff => "FilterCutoff",
+            CcTarget::FilterResonance => "FilterResonance",
+            CcTarget::EnvAttack => "EnvAttack",
+            CcTarget::EnvDecay => "EnvDecay",
+            CcTarget::EnvSustain => "EnvSustain",
+            CcTarget::EnvRelease => "EnvRelease",
+            CcTarget::LfoRate => "LfoRate",
+            CcTarget::LfoDepth => "LfoDepth",
+            CcTarget::ReverbWet => "ReverbWet",
+            CcTarget::ModIndex => "ModIndex",
+            CcTarget::PulseWidth => "PulseWidth",
+            CcTarget::GlideTime => "GlideTime",
+        }
+    }
+
+    /// Maps a 7-bit controller value onto the parameter's range. Frequencies and
+    /// times sweep exponentially, so the knob's travel sounds even.
+    pub fn scale(self, value: u8) -> f32 {
+        let t = value.min(127) as f32 / 127.0;
+        let exponential = |low: f32, high: f32| low * (high / low).powf(t);
+        match self {
+            CcTarget::Volume
+            | CcTarget::FilterResonance
+            | CcTarget::EnvSustain
+            | CcTarget::LfoDepth
+            | CcTarget::ReverbWet => t,
+            CcTarget::FilterCutoff => exponential(20.0, 20_000.0),
+            CcTarget::EnvAttack | CcTarget::EnvDecay | CcTarget::EnvRelease => exponential(0.001, 5.0),
+            CcTarget::LfoRate => exponential(0.1, 20.0),
+            CcTarget::ModIndex => 10.0 * t,
+            CcTarget::PulseWidth => 0.05 + 0.9 * t,
+            CcTarget::GlideTime => 2.0 * t,
+        }
+    }
+}
+
+/// The controller numbers of the channel mode messages, which aren't free for
+/// mapping.
+pub const CHANNEL_MODE_CCS: std::ops::RangeInclusive<u8> = 120..=127;
+pub const ALL_SOUND_OFF_CC: u8 = 120;
+pub const ALL_NOTES_OFF_CC: u8 = 123;
+
+/// The MIDI panic messages. Their value byte carries no meaning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelModeMessage {
+    /// Silences everything straight away, effect tails included.
+    AllSoundOff,
+    /// Releases every held note as if its key had come up.
+    AllNotesOff,
+}
+
+impl ChannelModeMessage {
+    pub fn from_cc(cc: u8) -> Option<ChannelModeMessage> {
+        match cc {
+            ALL_SOUND_OFF_CC => Some(ChannelModeMessage::AllSoundOff),
+            ALL_NOTES_OFF_CC => Some(ChannelModeMessage::AllNotesOff),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CcTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseCcMapError(String);
+
+impl fmt::Display for ParseCcMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid CC mapping '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseCcMapError {}
+
+impl FromStr for CcTarget {
+    type Err = ParseCcMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CcTarget::ALL
+            .into_iter()
+            .find(|target| target.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ParseCcMapError(s.to_string()))
+    }
+}
+
+/// Routes MIDI control changes to synth parameters.
+///
+/// Each target gets an `f32` stored as bits in an `Arc<AtomicU32>`, like the pan
+/// control, already scaled to its range. It holds NaN until its controller moves;
+/// a reader that swaps NaN back in sees each change once, and can tell an untouched
+/// knob from one at zero.
+///
+/// All Sound Off and All Notes Off are always handled, whatever the mappings, and
+/// left in the channel mode control for the engine to act on.
+#[derive(Clone, Debug, Default)]
+pub struct MidiCcMapper {
+    pub mappings: HashMap<u8, CcTarget>,
+    values: HashMap<CcTarget, Arc<AtomicU32>>,
+    channel_mode: Arc<Mutex<Option<ChannelModeMessage>>>,
+}
+
+impl MidiCcMapper {
+    /// Reads `--cc-map` syntax: comma-separated `cc:Target` pairs such as
+    /// `74:FilterCutoff,71:FilterResonance`.
+    pub fn parse(spec: &str) -> Result<MidiCcMapper, ParseCcMapError> {
+        let mut mapper = MidiCcMapper::default();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let invalid = || ParseCcMapError(entry.to_string());
+            let (cc, target) = entry.split_once(':').ok_or_else(invalid)?;
+            let cc: u8 = cc.trim().parse().map_err(|_| invalid())?;
+            if cc > 127 || CHANNEL_MODE_CCS.contains(&cc) {
+                return Err(invalid());
+            }
+            mapper.map(cc, target.parse().map_err(|_| invalid())?);
+        }
+        Ok(mapper)
+    }
+
+    pub fn map(&mut self, cc: u8, target: CcTarget) {
+        self.mappings.insert(cc, target);
+        self.get_target_control(target);
+    }
+
+    /// The shared value for `target`, in its own units.
+    pub fn get_target_control(&mut self, target: CcTarget) -> Arc<AtomicU32> {
+        self.values
+            .entry(target)
+            .or_insert_with(|| Arc::new(AtomicU32::new(f32::NAN.to_bits())))
+            .clone()
+    }
+
+    /// The latest unhandled panic message; pass it to
+    /// [`PolyphonicEngine::set_channel_mode_control`](crate::PolyphonicEngine::set_channel_mode_control).
+    pub fn get_channel_mode_control(&self) -> Arc<Mutex<Option<ChannelModeMessage>>> {
+        self.channel_mode.clone()
+    }
+
+    /// Handles a `ControlChange(cc, value)`, returning the target it moved and its
+    /// new value, or `None` for an unmapped controller or a panic message.
+    pub fn control_change(&self, cc: u8, value: u8) -> Option<(CcTarget, f32)> {
+        if let Some(message) = ChannelModeMessage::from_cc(cc) {
+            if let Ok(mut pending) = self.channel_mode.lock() {
+                // All Notes Off arriving after All Sound Off mustn't soften it
+                if *pending != Some(ChannelModeMessage::AllSoundOff) {
+                    *pending = Some(message);
+                }
+            }
+            return None;
+        }
+        let target = *self.mappings.get(&cc)?;
+        let scaled = target.scale(value);
+        if let Some(handle) = self.values.get(&target) {
+            handle.store(scaled.to_bits(), Ordering::Relaxed);
+        }
+        Some((target, scaled))
+    }
+}
@@ -0,0 +1,167 @@
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Tempo a file plays at until its first tempo event: 120 BPM.
+const DEFAULT_MICROS_PER_BEAT: u32 = 500_000;
+
+/// A note or controller event from a MIDI file. Note-ons with velocity 0 arrive as
+/// `NoteOff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MidiFileEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+    ControlChange { controller: u8, value: u8 },
+}
+
+impl MidiFileEvent {
+    /// The event as a channel 1 message. Note-ons have a velocity of at least 1, and
+    /// note-offs the default release velocity, 64.
+    pub fn message(self) -> [u8; 3] {
+        match self {
+            MidiFileEvent::NoteOn { note, velocity } => [0x90, note & 0x7f, velocity.clamp(1, 127)],
+            MidiFileEvent::NoteOff { note } => [0x80, note & 0x7f, 64],
+            MidiFileEvent::ControlChange { controller, value } => [0xb0, controller & 0x7f, value & 0x7f],
+        }
+    }
+}
+
+/// Every channel 1 note and controller event in a file in playing order, timed in
+/// seconds from the start, with all tracks merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiTimeline {
+    pub events: Vec<(f64, MidiFileEvent)>,
+    /// Other channels that had events, numbered from 1; they don't play.
+    pub ignored_channels: BTreeSet<u8>,
+}
+
+#[derive(Debug)]
+pub enum MidiFileError {
+    Io(std::io::Error),
+    Parse(midly::Error),
+    /// Format 2 files hold independent sequences rather than one song.
+    UnsupportedFormat,
+}
+
+impl fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiFileError::Io(error) => write!(f, "MIDI file error: {error}"),
+            MidiFileError::Parse(error) => write!(f, "invalid MIDI file: {error}"),
+            MidiFileError::UnsupportedFormat => write!(f, "only format 0 and 1 MIDI files can be played"),
+        }
+    }
+}
+
+impl std::error::Error for MidiFileError {}
+
+impl From<std::io::Error> for MidiFileError {
+    fn from(error: std::io::Error) -> Self {
+        MidiFileError::Io(error)
+    }
+}
+
+impl MidiTimeline {
+    pub fn load(path: &Path) -> Result<MidiTimeline, MidiFileError> {
+        let bytes = std::fs::read(path)?;
+        MidiTimeline::parse(&bytes)
+    }
+
+    /// Parses format 0 or 1 Standard MIDI File data.
+    pub fn parse(bytes: &[u8]) -> Result<MidiTimeline, MidiFileError> {
+        let smf = Smf::parse(bytes).map_err(MidiFileError::Parse)?;
+        if smf.header.format == Format::Sequential {
+            return Err(MidiFileError::UnsupportedFormat);
+        }
+
+        // Put every track's events on one tick timeline. The sort is stable, so
+        // events on the same tick keep their track order.
+        let mut merged = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0u64;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                merged.push((tick, event.kind));
+            }
+        }
+        merged.sort_by_key(|&(tick, _)| tick);
+
+        let mut timeline = MidiTimeline::default();
+        let mut micros_per_beat = DEFAULT_MICROS_PER_BEAT;
+        let mut last_tick = 0;
+        let mut secs = 0.0;
+        for (tick, kind) in merged {
+            // Timecode files tick at a fixed rate; metrical ones follow the tempo
+            let secs_per_tick = match smf.header.timing {
+                Timing::Metrical(ticks_per_beat) => {
+                    micros_per_beat as f64 / 1_000_000.0 / ticks_per_beat.as_int().max(1) as f64
+                }
+                Timing::Timecode(fps, subframes) => 1.0 / (fps.as_f32() as f64 * subframes.max(1) as f64),
+            };
+            secs += (tick - last_tick) as f64 * secs_per_tick;
+            last_tick = tick;
+
+            match kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => micros_per_beat = tempo.as_int(),
+                TrackEventKind::Midi { channel, message } => {
+                    let event = match message {
+                        MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => MidiFileEvent::NoteOn {
+                            note: key.as_int(),
+                            velocity: vel.as_int(),
+                        },
+                        MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                            MidiFileEvent::NoteOff { note: key.as_int() }
+                        }
+                        MidiMessage::Controller { controller, value } => MidiFileEvent::ControlChange {
+                            controller: controller.as_int(),
+                            value: value.as_int(),
+                        },
+                        _ => continue,
+                    };
+                    if channel.as_int() == 0 {
+                        timeline.events.push((secs, event));
+                    } else {
+                        timeline.ignored_channels.insert(channel.as_int() + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(timeline)
+    }
+
+    /// Length in seconds, up to the last event.
+    pub fn duration_secs(&self) -> f64 {
+        self.events.last().map_or(0.0, |&(secs, _)| secs)
+    }
+}
+
+/// Plays the file in real time, calling `handle` with each channel 1 event as it
+/// comes due. Blocks until the last event; other channels get a warning on stderr.
+pub fn play_midi_file(path: &Path, handle: impl FnMut(MidiFileEvent)) -> Result<(), MidiFileError> {
+    let timeline = MidiTimeline::load(path)?;
+    for channel in &timeline.ignored_channels {
+        eprintln!("Warning: ignoring notes on MIDI channel {channel}");
+    }
+    play_midi_timeline(&timeline, handle);
+    Ok(())
+}
+
+/// [`play_midi_file`] for an already loaded timeline.
+pub fn play_midi_timeline(timeline: &MidiTimeline, mut handle: impl FnMut(MidiFileEvent)) {
+    // Sleep toward absolute times from the start, so the sleeps' own overshoot
+    // doesn't add up over a long file
+    let start = Instant::now();
+    for &(secs, event) in &timeline.events {
+        let due = start + Duration::from_secs_f64(secs);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+        handle(event);
+    }
+}
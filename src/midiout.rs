@@ -0,0 +1,35 @@
+use crate::midicc::{ALL_NOTES_OFF_CC, ALL_SOUND_OFF_CC};
+use std::io;
+
+/// The number of MIDI channels a message can address.
+pub const MIDI_CHANNELS: u8 = 16;
+pub const SUSTAIN_CC: u8 = 64;
+pub const RESET_ALL_CONTROLLERS_CC: u8 = 121;
+
+/// Somewhere raw MIDI messages can be sent, such as a
+/// [`MidiFileRecorder`](crate::MidiFileRecorder).
+pub trait MidiOutput {
+    /// Sends one complete message, status byte first.
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+}
+
+/// What [`midi_panic`] sends: on every channel, sustain off, All Sound Off, Reset
+/// All Controllers and All Notes Off.
+pub fn midi_panic_messages() -> Vec<[u8; 3]> {
+    (0..MIDI_CHANNELS)
+        .flat_map(|channel| {
+            let status = 0xb0 | channel;
+            [SUSTAIN_CC, ALL_SOUND_OFF_CC, RESET_ALL_CONTROLLERS_CC, ALL_NOTES_OFF_CC]
+                .map(|controller| [status, controller, 0])
+        })
+        .collect()
+}
+
+/// Silences everything listening on `output`, the MIDI panic button. Sending is
+/// synchronous; it is an emergency stop, not a timed event.
+pub fn midi_panic(output: &mut dyn MidiOutput) -> io::Result<()> {
+    for message in midi_panic_messages() {
+        output.send(&message)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use crate::error::SynthError;
+use crate::midiout::MidiOutput;
+use midir::MidiOutputConnection;
+use std::io;
+
+/// Name the synth shows other MIDI software, and gives a virtual port.
+pub const MIDI_CLIENT_NAME: &str = "exposrog";
+
+fn midi_error(error: impl std::fmt::Display) -> SynthError {
+    SynthError::MidiError(error.to_string())
+}
+
+/// The MIDI output ports there are to connect to, by index.
+pub fn midi_output_port_names() -> Result<Vec<String>, SynthError> {
+    let output = midir::MidiOutput::new(MIDI_CLIENT_NAME).map_err(midi_error)?;
+    output.ports().iter().map(|port| output.port_name(port).map_err(midi_error)).collect()
+}
+
+/// A connection to a hardware or software MIDI output, through `midir`.
+pub struct MidiPort {
+    connection: MidiOutputConnection,
+    name: String,
+}
+
+impl MidiPort {
+    /// Connects to port `index` of [`midi_output_port_names`].
+    pub fn open(index: usize) -> Result<MidiPort, SynthError> {
+        let output = midir::MidiOutput::new(MIDI_CLIENT_NAME).map_err(midi_error)?;
+        let ports = output.ports();
+        let port = ports.get(index).ok_or_else(|| {
+            SynthError::invalid_parameter("MIDI port", index, format!("there are {} ports", ports.len()))
+        })?;
+        let name = output.port_name(port).map_err(midi_error)?;
+        let connection = output.connect(port, MIDI_CLIENT_NAME).map_err(midi_error)?;
+        Ok(MidiPort { connection, name })
+    }
+
+    /// Creates a port named [`MIDI_CLIENT_NAME`] that other programs connect to,
+    /// where the platform supports it.
+    #[cfg(unix)]
+    pub fn open_virtual() -> Result<MidiPort, SynthError> {
+        use midir::os::unix::VirtualOutput;
+        let output = midir::MidiOutput::new(MIDI_CLIENT_NAME).map_err(midi_error)?;
+        let connection = output.create_virtual(MIDI_CLIENT_NAME).map_err(midi_error)?;
+        Ok(MidiPort {
+            connection,
+            name: MIDI_CLIENT_NAME.to_string(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl MidiOutput for MidiPort {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        self.connection.send(message).map_err(io::Error::other)
+    }
+}
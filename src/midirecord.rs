@@ -0,0 +1,180 @@
+use crate::midifile::MidiFileEvent;
+use crate::midiout::MidiOutput;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Instant;
+
+const TICKS_PER_BEAT: u16 = 480;
+/// 120 BPM, so a tick is 1/960 s.
+const MICROS_PER_BEAT: u32 = 500_000;
+const TICKS_PER_SEC: f64 = TICKS_PER_BEAT as f64 * 1_000_000.0 / MICROS_PER_BEAT as f64;
+
+/// Streams note events into a format 0 Standard MIDI File as they are played, timed
+/// by the wall clock since the file was created.
+///
+/// The track's byte length is only known at the end, so [`finish`](Self::finish)
+/// seeks back to fill it in. Dropping the recorder finishes it too, and any notes
+/// still held get their note-offs first.
+pub struct MidiFileRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    last_tick: u64,
+    track_len: u32,
+    held: BTreeSet<u8>,
+    finished: bool,
+}
+
+impl MidiFileRecorder {
+    pub fn create(path: &Path) -> io::Result<MidiFileRecorder> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"MThd")?;
+        writer.write_all(&6u32.to_be_bytes())?;
+        writer.write_all(&0u16.to_be_bytes())?; // format 0
+        writer.write_all(&1u16.to_be_bytes())?; // one track
+        writer.write_all(&TICKS_PER_BEAT.to_be_bytes())?;
+        writer.write_all(b"MTrk")?;
+        writer.write_all(&0u32.to_be_bytes())?; // patched by finish
+
+        let mut recorder = MidiFileRecorder {
+            writer,
+            start: Instant::now(),
+            last_tick: 0,
+            track_len: 0,
+            held: BTreeSet::new(),
+            finished: false,
+        };
+        let tempo = MICROS_PER_BEAT.to_be_bytes();
+        recorder.write_event(0, &[0xff, 0x51, 0x03, tempo[1], tempo[2], tempo[3]])?;
+        Ok(recorder)
+    }
+
+    /// Appends `event` on channel 1 at the current time.
+    pub fn record(&mut self, event: MidiFileEvent) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        let delta = self.delta_now();
+        match event {
+            MidiFileEvent::NoteOn { note, .. } => {
+                self.held.insert(note & 0x7f);
+            }
+            MidiFileEvent::NoteOff { note } => {
+                self.held.remove(&(note & 0x7f));
+            }
+            MidiFileEvent::ControlChange { .. } => {}
+        }
+        self.write_event(delta, &event.message())
+    }
+
+    /// Releases held notes, ends the track and flushes the file. Later calls, and
+    /// later events, do nothing.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        for note in std::mem::take(&mut self.held) {
+            self.record(MidiFileEvent::NoteOff { note })?;
+        }
+        self.write_event(0, &[0xff, 0x2f, 0x00])?;
+        self.finished = true;
+
+        // The track length sits just before the track data, after the 14-byte header
+        self.writer.seek(SeekFrom::Start(18))?;
+        self.writer.write_all(&self.track_len.to_be_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    /// Ticks since the last event, moving the last event time up to now.
+    fn delta_now(&mut self) -> u64 {
+        let tick = (self.start.elapsed().as_secs_f64() * TICKS_PER_SEC).round() as u64;
+        let delta = tick.saturating_sub(self.last_tick);
+        self.last_tick = self.last_tick.max(tick);
+        delta
+    }
+
+    fn write_event(&mut self, delta: u64, bytes: &[u8]) -> io::Result<()> {
+        // Variable-length quantity: 7 bits per byte, most significant first, with
+        // the top bit set on all but the last. Deltas cap at the format's 28 bits.
+        let delta = delta.min(0x0fff_ffff) as u32;
+        let mut vlq = [0u8; 4];
+        let mut len = 0;
+        for shift in [21, 14, 7, 0] {
+            let group = ((delta >> shift) & 0x7f) as u8;
+            if len > 0 || group != 0 || shift == 0 {
+                vlq[len] = if shift == 0 { group } else { group | 0x80 };
+                len += 1;
+            }
+        }
+
+        self.writer.write_all(&vlq[..len])?;
+        self.writer.write_all(bytes)?;
+        self.track_len += (len + bytes.len()) as u32;
+        Ok(())
+    }
+}
+
+/// Writes channel messages on any channel into the file at the current time.
+impl MidiOutput for MidiFileRecorder {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if self.finished || message.is_empty() {
+            return Ok(());
+        }
+        // A channel 1 All Notes Off or All Sound Off already ends the held notes
+        if let [0xb0, 120 | 123, _] = message {
+            self.held.clear();
+        }
+        let delta = self.delta_now();
+        self.write_event(delta, message)
+    }
+}
+
+impl Drop for MidiFileRecorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+    use std::time::Duration;
+
+    #[test]
+    fn recorded_file_parses_with_midly() {
+        let path = std::env::temp_dir().join(format!("exposrog-record-{}.mid", std::process::id()));
+        let mut recorder = MidiFileRecorder::create(&path).unwrap();
+        recorder.record(MidiFileEvent::NoteOn { note: 60, velocity: 100 }).unwrap();
+        // Pretend 20 s went by, for a delta long enough to need three VLQ bytes
+        recorder.start -= Duration::from_secs(20);
+        recorder.record(MidiFileEvent::NoteOn { note: 64, velocity: 90 }).unwrap();
+        recorder.record(MidiFileEvent::NoteOff { note: 60 }).unwrap();
+        // Dropping finishes the file, releasing the E still held
+        drop(recorder);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.header.timing, Timing::Metrical(TICKS_PER_BEAT.into()));
+        assert_eq!(smf.tracks.len(), 1);
+
+        let track = &smf.tracks[0];
+        assert_eq!(track[0].kind, TrackEventKind::Meta(MetaMessage::Tempo(MICROS_PER_BEAT.into())));
+        let notes: Vec<(u8, bool)> = track
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Midi { message: MidiMessage::NoteOn { key, .. }, .. } => Some((key.as_int(), true)),
+                TrackEventKind::Midi { message: MidiMessage::NoteOff { key, .. }, .. } => Some((key.as_int(), false)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(notes, [(60, true), (64, true), (60, false), (64, false)]);
+        // The second note-on lands 20 s in
+        let delta = track[2].delta.as_int() as f64;
+        assert!((delta - 20.0 * TICKS_PER_SEC).abs() < 10.0, "{delta} ticks");
+        assert_eq!(track.last().unwrap().kind, TrackEventKind::Meta(MetaMessage::EndOfTrack));
+    }
+}
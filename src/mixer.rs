@@ -0,0 +1,233 @@
+use crate::pan::constant_power_gains;
+use rodio::Source;
+
+/// Sum of `samples[i] * gains[i]` over eight voices, in one AVX multiply and a
+/// horizontal add on builds with AVX enabled (e.g. `-C target-cpu=native`). That's
+/// about 1.2x the scalar loop in `benches/mix_voices.rs`: the loads and the
+/// horizontal add cost most of what the wide multiply saves.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx"))]
+#[inline]
+pub fn mix_voices_simd(samples: &[f32; 8], gains: &[f32; 8]) -> f32 {
+    use std::arch::x86_64::*;
+
+    // SAFETY: AVX is enabled at compile time, and both arrays hold the eight floats
+    // the unaligned loads read
+    unsafe {
+        let products = _mm256_mul_ps(_mm256_loadu_ps(samples.as_ptr()), _mm256_loadu_ps(gains.as_ptr()));
+        let quad = _mm_add_ps(_mm256_castps256_ps128(products), _mm256_extractf128_ps(products, 1));
+        let pair = _mm_add_ps(quad, _mm_movehl_ps(quad, quad));
+        _mm_cvtss_f32(_mm_add_ss(pair, _mm_shuffle_ps(pair, pair, 1)))
+    }
+}
+
+/// Sum of `samples[i] * gains[i]` over eight voices.
+#[cfg(not(all(target_arch = "x86_64", target_feature = "avx")))]
+#[inline]
+pub fn mix_voices_simd(samples: &[f32; 8], gains: &[f32; 8]) -> f32 {
+    samples.iter().zip(gains).map(|(sample, gain)| sample * gain).sum()
+}
+
+/// Sums up to `N` mono sources into an interleaved stereo stream.
+///
+/// rodio needs a plain sample type, so instead of yielding `(left, right)`
+/// pairs the mixer reports two channels and alternates left and right samples.
+pub struct Mixer<const N: usize> {
+    sample_rate: u32,
+    sources: [Option<Box<dyn Source<Item = f32> + Send>>; N],
+    gains: [f32; N],
+    pans: [f32; N],
+    /// Each slot's gain times its pan's, kept up to date so a frame needs no trig
+    left_gains: [f32; N],
+    right_gains: [f32; N],
+    pending_right: Option<f32>,
+}
+
+impl<const N: usize> Mixer<N> {
+    pub fn new(sample_rate: u32) -> Mixer<N> {
+        Mixer {
+            sample_rate,
+            sources: std::array::from_fn(|_| None),
+            gains: [1.0; N],
+            pans: [0.0; N],
+            left_gains: [0.0; N],
+            right_gains: [0.0; N],
+            pending_right: None,
+        }
+    }
+
+    /// Puts `source` in the first free slot and returns its index, or `None` if
+    /// the mixer is full. `pan` runs from -1.0 (left) to 1.0 (right).
+    pub fn add_source(
+        &mut self,
+        source: impl Source<Item = f32> + Send + 'static,
+        gain: f32,
+        pan: f32,
+    ) -> Option<usize> {
+        let slot = self.sources.iter().position(Option::is_none)?;
+        self.sources[slot] = Some(Box::new(source));
+        self.gains[slot] = gain;
+        self.pans[slot] = pan.clamp(-1.0, 1.0);
+        self.update_gains(slot);
+        Some(slot)
+    }
+
+    pub fn set_gain(&mut self, slot: usize, gain: f32) {
+        if let Some(g) = self.gains.get_mut(slot) {
+            *g = gain;
+            self.update_gains(slot);
+        }
+    }
+
+    pub fn set_pan(&mut self, slot: usize, pan: f32) {
+        if let Some(p) = self.pans.get_mut(slot) {
+            *p = pan.clamp(-1.0, 1.0);
+            self.update_gains(slot);
+        }
+    }
+
+    fn update_gains(&mut self, slot: usize) {
+        let (left_gain, right_gain) = constant_power_gains(self.pans[slot]);
+        self.left_gains[slot] = self.gains[slot] * left_gain;
+        self.right_gains[slot] = self.gains[slot] * right_gain;
+    }
+
+    pub fn remove_source(&mut self, slot: usize) {
+        if let Some(source) = self.sources.get_mut(slot) {
+            *source = None;
+        }
+    }
+
+    /// Pulls a sample from every source, then sums them eight slots at a time with
+    /// [`mix_voices_simd`]; a last, partial group is padded with silence.
+    fn mix_frame(&mut self) -> (f32, f32) {
+        let mut samples = [0.0; N];
+        for (slot, sample) in self.sources.iter_mut().zip(&mut samples) {
+            if let Some(source) = slot {
+                match source.next() {
+                    Some(next) => *sample = next,
+                    None => *slot = None,
+                }
+            }
+        }
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for start in (0..N).step_by(8) {
+            let lane = |values: &[f32; N]| -> [f32; 8] {
+                std::array::from_fn(|i| values.get(start + i).copied().unwrap_or(0.0))
+            };
+            let lane_samples = lane(&samples);
+            left += mix_voices_simd(&lane_samples, &lane(&self.left_gains));
+            right += mix_voices_simd(&lane_samples, &lane(&self.right_gains));
+        }
+
+        (left, right)
+    }
+}
+
+impl<const N: usize> Source for Mixer<N> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl<const N: usize> Iterator for Mixer<N> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let (left, right) = self.mix_frame();
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    fn constant(value: f32, len: usize) -> SamplesBuffer<f32> {
+        SamplesBuffer::new(1, 44100, vec![value; len])
+    }
+
+    fn frame(mixer: &mut Mixer<8>) -> (f32, f32) {
+        (mixer.next().unwrap(), mixer.next().unwrap())
+    }
+
+    #[test]
+    fn each_source_gets_its_own_gain() {
+        let mut mixer = Mixer::<8>::new(44100);
+        let quiet = mixer.add_source(constant(1.0, 16), 0.25, 0.0).unwrap();
+        mixer.add_source(constant(1.0, 16), 0.5, 0.0);
+        let (left, right) = frame(&mut mixer);
+        assert!((left - 0.75 * FRAC_1_SQRT_2).abs() < 1e-6, "{left}");
+        assert!((right - 0.75 * FRAC_1_SQRT_2).abs() < 1e-6, "{right}");
+        mixer.set_gain(quiet, 0.0);
+        let (left, _) = frame(&mut mixer);
+        assert!((left - 0.5 * FRAC_1_SQRT_2).abs() < 1e-6, "{left}");
+    }
+
+    #[test]
+    fn pans_with_constant_power_into_interleaved_stereo() {
+        let mut mixer = Mixer::<8>::new(44100);
+        let slot = mixer.add_source(constant(1.0, 16), 1.0, -1.0).unwrap();
+        assert_eq!(mixer.channels(), 2);
+        let (left, right) = frame(&mut mixer);
+        assert!((left - 1.0).abs() < 1e-6 && right.abs() < 1e-6, "{left} {right}");
+        mixer.set_pan(slot, 0.5);
+        let (left, right) = frame(&mut mixer);
+        let (left_gain, right_gain) = constant_power_gains(0.5);
+        assert_eq!((left, right), (left_gain, right_gain));
+        assert!((left * left + right * right - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn finished_sources_free_their_slots() {
+        let mut mixer = Mixer::<8>::new(44100);
+        mixer.add_source(constant(1.0, 2), 1.0, 0.0);
+        let lasting = mixer.add_source(constant(0.5, 16), 1.0, 0.0).unwrap();
+        frame(&mut mixer);
+        frame(&mut mixer);
+        // The short source runs out here and only the other is left
+        let (left, _) = frame(&mut mixer);
+        assert!((left - 0.5 * FRAC_1_SQRT_2).abs() < 1e-6, "{left}");
+        assert_eq!(mixer.add_source(constant(1.0, 16), 1.0, 0.0), Some(0));
+        mixer.remove_source(lasting);
+        assert_eq!(mixer.add_source(constant(1.0, 16), 1.0, 0.0), Some(lasting));
+    }
+
+    #[test]
+    fn sums_slots_past_the_first_eight() {
+        let mut mixer = Mixer::<10>::new(44100);
+        for _ in 0..10 {
+            mixer.add_source(constant(0.1, 16), 1.0, -1.0);
+        }
+        let (left, right) = (mixer.next().unwrap(), mixer.next().unwrap());
+        assert!((left - 1.0).abs() < 1e-5 && right.abs() < 1e-6, "{left} {right}");
+    }
+
+    #[test]
+    fn full_mixer_refuses_sources() {
+        let mut mixer = Mixer::<2>::new(44100);
+        mixer.add_source(constant(1.0, 16), 1.0, 0.0);
+        mixer.add_source(constant(1.0, 16), 1.0, 0.0);
+        assert_eq!(mixer.add_source(constant(1.0, 16), 1.0, 0.0), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::oscillator::WaveTableOscillator;
+use crate::wave::{generate_wave_table, WaveShape};
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use synth_core::Lfo;
+
+/// The shapes a [`MultiOscillator`] morphs through, in order: morph 0.0 is a
+/// sine, 1.0 a triangle, 2.0 a square and 3.0 a sawtooth.
+pub const MORPH_SHAPES: [WaveShape; 4] = [WaveShape::Sine, WaveShape::Triangle, WaveShape::Square, WaveShape::Sawtooth];
+
+/// Highest morph value, the sawtooth.
+pub const MAX_MORPH: f32 = (MORPH_SHAPES.len() - 1) as f32;
+
+/// One-knob waveform morphing: crossfades between adjacent [`MORPH_SHAPES`], so morph
+/// 0.5 is half sine and half triangle.
+///
+/// Each shape has its own wave table oscillator, but only the two either side of the
+/// morph value run. One that comes back into use picks up the phase of the one it
+/// fades against, so the crossfade never cancels. Morph is shared as `f32` bits in
+/// an `AtomicU32`, and an optional LFO sweeps it around that value.
+pub struct MultiOscillator {
+    sample_rate: u32,
+    oscillators: Vec<WaveTableOscillator>,
+    frequency: Arc<Mutex<f32>>,
+    morph: Arc<AtomicU32>,
+    morph_lfo: Arc<Mutex<Option<Lfo>>>,
+    /// The lower of the two running oscillators.
+    active: usize,
+}
+
+impl MultiOscillator {
+    pub fn new(sample_rate: u32, table_size: usize, frequency: Arc<Mutex<f32>>) -> MultiOscillator {
+        MultiOscillator {
+            sample_rate,
+            oscillators: MORPH_SHAPES
+                .iter()
+                .map(|&shape| WaveTableOscillator::new(sample_rate, generate_wave_table(shape, table_size)))
+                .collect(),
+            frequency,
+            morph: Arc::new(AtomicU32::new(0.0_f32.to_bits())),
+            morph_lfo: Arc::new(Mutex::new(None)),
+            active: 0,
+        }
+    }
+
+    /// Morph position, 0.0 to [`MAX_MORPH`], as `f32` bits.
+    pub fn get_morph_control(&self) -> Arc<AtomicU32> {
+        self.morph.clone()
+    }
+
+    /// An LFO adding to the morph position each sample; `None` leaves it still.
+    pub fn get_morph_lfo_control(&self) -> Arc<Mutex<Option<Lfo>>> {
+        self.morph_lfo.clone()
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let mut morph = f32::from_bits(self.morph.load(Ordering::Relaxed));
+        if let Ok(mut lfo) = self.morph_lfo.lock() {
+            if let Some(lfo) = lfo.as_mut() {
+                morph = lfo.modulate(morph);
+            }
+        }
+        let morph = if morph.is_finite() { morph.clamp(0.0, MAX_MORPH) } else { 0.0 };
+
+        // At exactly 3.0 the sawtooth is the upper of the pair, fully faded in
+        let lower = (morph.floor() as usize).min(self.oscillators.len() - 2);
+        let blend = morph - lower as f32;
+        let freq_hz = self.frequency.lock().map_or(0.0, |freq| *freq);
+
+        if lower != self.active {
+            self.activate(lower, freq_hz);
+        }
+        let (low, high) = self.oscillators[lower..].split_at_mut(1);
+        let (low, high) = (&mut low[0], &mut high[0]);
+        for oscillator in [&mut *low, &mut *high] {
+            if let Ok(mut freq) = oscillator.get_frequency_control().lock() {
+                *freq = freq_hz;
+            }
+        }
+        low.get_sample() * (1.0 - blend) + high.get_sample() * blend
+    }
+
+    /// Starts running the pair from `lower`, bringing whichever of them was idle in
+    /// at pitch and in phase with one that was running.
+    fn activate(&mut self, lower: usize, freq_hz: f32) {
+        let previous = [self.active, self.active + 1];
+        let phase = self.oscillators[self.active].phase();
+        for index in [lower, lower + 1] {
+            if !previous.contains(&index) {
+                let oscillator = &mut self.oscillators[index];
+                oscillator.set_frequency_direct(freq_hz);
+                oscillator.reset_phase_to(phase);
+            }
+        }
+        self.active = lower;
+    }
+}
+
+impl Source for MultiOscillator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for MultiOscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
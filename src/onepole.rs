@@ -0,0 +1,178 @@
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+use synth_core::smoothing_coeff;
+
+/// The one-pole state behind [`LowPassFilter`] and [`HighPassFilter`]: one
+/// smoother per channel, `y[n] = a * x[n] + (1 - a) * y[n-1]`, with `a` worked out
+/// again whenever the cutoff or the source's sample rate moves.
+struct OnePoleState {
+    cutoff_hz: Arc<Mutex<f32>>,
+    enabled: Arc<Mutex<bool>>,
+    coefficient: f32,
+    /// The cutoff and sample rate `coefficient` was worked out for.
+    tuned_for: (f32, u32),
+    previous: Vec<f32>,
+    channel: usize,
+}
+
+impl OnePoleState {
+    fn new(cutoff_hz: f32) -> OnePoleState {
+        OnePoleState {
+            cutoff_hz: Arc::new(Mutex::new(cutoff_hz)),
+            enabled: Arc::new(Mutex::new(false)),
+            coefficient: 1.0,
+            tuned_for: (f32::NAN, 0),
+            previous: Vec::new(),
+            channel: 0,
+        }
+    }
+
+    fn set_cutoff_hz(&mut self, cutoff_hz: f32) {
+        if let Ok(mut cutoff) = self.cutoff_hz.lock() {
+            *cutoff = cutoff_hz;
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.lock().is_ok_and(|enabled| *enabled)
+    }
+
+    /// Low-passes `input`, the next interleaved sample of a `channels`-channel
+    /// source at `sample_rate`.
+    fn low_pass(&mut self, input: f32, channels: u16, sample_rate: u32) -> f32 {
+        let cutoff_hz = self.cutoff_hz.lock().map_or(self.tuned_for.0, |cutoff| *cutoff);
+        if (cutoff_hz, sample_rate) != self.tuned_for {
+            // Past Nyquist a cutoff stops meaning anything
+            let nyquist = sample_rate as f32 / 2.0;
+            self.coefficient = smoothing_coeff(cutoff_hz.clamp(1.0, nyquist), sample_rate).clamp(0.0, 1.0);
+            self.tuned_for = (cutoff_hz, sample_rate);
+        }
+        let channels = channels.max(1) as usize;
+        if self.previous.len() != channels {
+            self.previous = vec![0.0; channels];
+            self.channel = 0;
+        }
+        let previous = &mut self.previous[self.channel];
+        *previous = self.coefficient * input + (1.0 - self.coefficient) * *previous;
+        self.channel = (self.channel + 1) % channels;
+        *previous
+    }
+}
+
+/// Softens a source with a one-pole low-pass: a gentle 6 dB per octave roll-off
+/// above the cutoff, for taming bright square and saw waves without the resonance
+/// of the [`SvfSource`](crate::SvfSource). Switched by the enabled control and off
+/// to begin with.
+pub struct LowPassFilter<S: Source<Item = f32>> {
+    source: S,
+    state: OnePoleState,
+}
+
+impl<S: Source<Item = f32>> LowPassFilter<S> {
+    pub fn new(source: S, cutoff_hz: f32) -> LowPassFilter<S> {
+        LowPassFilter {
+            source,
+            state: OnePoleState::new(cutoff_hz),
+        }
+    }
+
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f32) {
+        self.state.set_cutoff_hz(cutoff_hz);
+    }
+
+    pub fn get_cutoff_control(&self) -> Arc<Mutex<f32>> {
+        self.state.cutoff_hz.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.state.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for LowPassFilter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for LowPassFilter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        if !self.state.is_enabled() {
+            return Some(sample);
+        }
+        Some(self.state.low_pass(sample, self.source.channels(), self.source.sample_rate()))
+    }
+}
+
+/// The one-pole low-pass subtracted from its input: removes rumble and thins the
+/// sound below the cutoff. Switched by the enabled control and off to begin with.
+pub struct HighPassFilter<S: Source<Item = f32>> {
+    source: S,
+    state: OnePoleState,
+}
+
+impl<S: Source<Item = f32>> HighPassFilter<S> {
+    pub fn new(source: S, cutoff_hz: f32) -> HighPassFilter<S> {
+        HighPassFilter {
+            source,
+            state: OnePoleState::new(cutoff_hz),
+        }
+    }
+
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f32) {
+        self.state.set_cutoff_hz(cutoff_hz);
+    }
+
+    pub fn get_cutoff_control(&self) -> Arc<Mutex<f32>> {
+        self.state.cutoff_hz.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.state.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for HighPassFilter<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for HighPassFilter<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        if !self.state.is_enabled() {
+            return Some(sample);
+        }
+        Some(sample - self.state.low_pass(sample, self.source.channels(), self.source.sample_rate()))
+    }
+}
@@ -0,0 +1,1164 @@
+use crate::block::BlockSource;
+use crate::clock::MasterClock;
+use crate::drift::{TuningDrift, DEFAULT_DRIFT_DEPTH_CENTS, DEFAULT_DRIFT_RATE_HZ};
+use crate::dynwave::DynamicWaveTable;
+use crate::pitch::estimate_frequency;
+use crate::serum::{read_serum_frame, SerumWavetableError};
+use crate::wave::{generate_wave_table_with, SineMode, WaveShape};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use synth_core::{InterpolationMode, Lfo, LfoPolarity, WaveTableCore};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+pub enum SubInterval {
+    OneOctaveDown,
+    TwoOctavesDown,
+    FifthDown,
+}
+
+impl SubInterval {
+    /// Factor the main frequency is divided by to get the sub-oscillator frequency.
+    pub fn ratio(self) -> f32 {
+        match self {
+            SubInterval::OneOctaveDown => 2.0,
+            SubInterval::TwoOctavesDown => 4.0,
+            SubInterval::FifthDown => 1.5,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SubInterval::OneOctaveDown => "-1 octave",
+            SubInterval::TwoOctavesDown => "-2 octaves",
+            SubInterval::FifthDown => "-fifth",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubOscillatorMode {
+    pub interval: SubInterval,
+    pub mix: f32,
+}
+
+impl SubOscillatorMode {
+    /// Steps through off -> one octave -> two octaves -> fifth -> off, keeping the mix.
+    pub fn cycle(mode: Option<SubOscillatorMode>) -> Option<SubOscillatorMode> {
+        let mix = mode.map_or(0.5, |m| m.mix);
+        let interval = match mode.map(|m| m.interval) {
+            None => SubInterval::OneOctaveDown,
+            Some(SubInterval::OneOctaveDown) => SubInterval::TwoOctavesDown,
+            Some(SubInterval::TwoOctavesDown) => SubInterval::FifthDown,
+            Some(SubInterval::FifthDown) => return None,
+        };
+        Some(SubOscillatorMode { interval, mix })
+    }
+}
+
+/// Plain-data copy of an oscillator's state for saving, restoring and undo.
+///
+/// Field names are part of the on-disk format, so rename with care.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WaveTableOscillatorState {
+    pub wave_table: Vec<f32>,
+    pub sample_rate: u32,
+    pub index: f32,
+    pub index_increment: f32,
+    pub frequency: f32,
+}
+
+/// A render length whose sample count doesn't fit in memory's address space, or isn't
+/// a finite, non-negative number of seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InvalidRenderLength(pub f32);
+
+impl fmt::Display for InvalidRenderLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot render {} seconds of audio", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRenderLength {}
+
+/// Why [`WaveTableOscillator::verify_frequency`] couldn't measure a frequency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrequencyError {
+    /// The render held fewer than two upward zero crossings: silence, or too short
+    /// for the pitch.
+    TooFewCrossings { n_samples: usize },
+}
+
+impl fmt::Display for FrequencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrequencyError::TooFewCrossings { n_samples } => {
+                write!(f, "fewer than two zero crossings in {n_samples} samples")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrequencyError {}
+
+/// The controls a [`WaveTableOscillator`] shares with its clones, read in one go.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SharedControls {
+    drifting: bool,
+    /// The LFO's target and, unless its lock failed, its settings
+    lfo: Option<(LfoTarget, Option<Lfo>)>,
+    glide_ms: u32,
+    sub: Option<SubOscillatorMode>,
+    amplitude: f32,
+}
+
+/// What a [`WaveTableOscillator`]'s modulation LFO moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LfoTarget {
+    /// Vibrato: the frequency times `1.0 + depth * lfo`.
+    Pitch,
+    /// Tremolo: the output times `1.0 - depth * (1.0 - lfo) / 2.0`, dipping by up
+    /// to `depth` and back.
+    Amplitude,
+}
+
+impl fmt::Display for LfoTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LfoTarget::Pitch => write!(f, "vibrato"),
+            LfoTarget::Amplitude => write!(f, "tremolo"),
+        }
+    }
+}
+
+/// Where a [`WaveTableOscillator`]'s portamento has got to between two notes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PortamentoState {
+    current_freq: f32,
+    target_freq: f32,
+    glide_samples_remaining: u32,
+}
+
+impl PortamentoState {
+    /// The frequency to play this sample on the way to `target_freq`, arriving after
+    /// `glide_samples`. Gliding from or to silence jumps straight there.
+    fn step(&mut self, target_freq: f32, glide_samples: u32) -> f32 {
+        if target_freq != self.target_freq {
+            self.target_freq = target_freq;
+            let sounding = self.current_freq > 0.0 && target_freq > 0.0;
+            self.glide_samples_remaining = if sounding { glide_samples } else { 0 };
+        }
+        if self.glide_samples_remaining == 0 {
+            self.current_freq = self.target_freq;
+        } else {
+            // Equal ratios per sample, so the glide is even in pitch
+            let ratio = (self.target_freq / self.current_freq).powf(1.0 / self.glide_samples_remaining as f32);
+            self.current_freq *= ratio;
+            self.glide_samples_remaining -= 1;
+        }
+        self.current_freq
+    }
+
+    fn settle(&mut self, freq: f32) {
+        *self = PortamentoState {
+            current_freq: freq,
+            target_freq: freq,
+            glide_samples_remaining: 0,
+        };
+    }
+}
+
+/// Whether an oscillator is playing or on its way out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OscillatorState {
+    Playing,
+    /// A linear fade over `total` samples with `remaining` still to go.
+    FadingOut { remaining: usize, total: usize },
+}
+
+/// Length of the release fade that ends a [`WaveTableOscillator::play_one_shot`].
+const ONE_SHOT_RELEASE_SAMPLES: usize = 128;
+
+/// Default corner of the one-pole smoother applied to frequency changes.
+pub const DEFAULT_SMOOTHING_HZ: f32 = 200.0;
+
+/// Output level of a new oscillator, leaving headroom for the effects after it.
+const DEFAULT_AMPLITUDE: f32 = 0.3;
+
+/// The band of frequencies an oscillator will play; requests outside it are silent.
+/// The default 20 Hz to 20 kHz is the range of hearing, so nothing downstream spends
+/// time on rumble or ultrasound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThresholdGate {
+    pub min_hz: f32,
+    pub max_hz: f32,
+}
+
+impl Default for ThresholdGate {
+    fn default() -> Self {
+        ThresholdGate {
+            min_hz: 20.0,
+            max_hz: 20_000.0,
+        }
+    }
+}
+
+impl ThresholdGate {
+    pub fn passes(&self, freq_hz: f32) -> bool {
+        (self.min_hz..=self.max_hz).contains(&freq_hz)
+    }
+}
+
+/// Temperature instruments are tuned at, in °C.
+pub const REFERENCE_TEMPERATURE_CELSIUS: f32 = 20.0;
+/// Pitch drift per °C of warming; negative, as the instrument goes flat.
+const CENTS_PER_DEGREE: f32 = -0.3;
+
+/// How far an instrument tuned at `reference_celsius` drifts at `temp_celsius`, in
+/// cents: 0.3 cents flat per degree warmer, so 10 °C warmer is -3 cents.
+pub fn temperature_correction_cents(temp_celsius: f32, reference_celsius: f32) -> f32 {
+    CENTS_PER_DEGREE * (temp_celsius - reference_celsius)
+}
+
+/// One cycle that oscillators can share; cloning only bumps the count.
+#[derive(Clone, Debug)]
+struct SharedWaveTable(Arc<Vec<f32>>);
+
+impl AsRef<[f32]> for SharedWaveTable {
+    fn as_ref(&self) -> &[f32] {
+        &self.0
+    }
+}
+
+/// The audio side of a [`DynamicWaveTable`]: the shared table plus the last version
+/// copied out of it.
+#[derive(Clone)]
+struct DynamicTableReader {
+    current: Arc<RwLock<Arc<Vec<f32>>>>,
+    version: Arc<AtomicUsize>,
+    seen: usize,
+}
+
+/// A [`WaveTableCore`] playing at the frequency in a shared control, with a shared
+/// sub-oscillator setting and an optional master clock.
+///
+/// The wave table is held in an `Arc`, so clones, such as a polyphonic engine's
+/// voices, share one copy of it.
+pub struct WaveTableOscillator {
+    sample_rate: u32,
+    core: WaveTableCore<SharedWaveTable>,
+    frequency: Arc<Mutex<f32>>,
+    /// Output level, 0.0 to 1.0, shared with clones.
+    amplitude: Arc<Mutex<f32>>,
+    sub_mode: Arc<Mutex<Option<SubOscillatorMode>>>,
+    clock: Option<MasterClock>,
+    /// Fixed ratio applied on top of the shared frequency control.
+    detune_ratio: f32,
+    /// Pitch drift from [`WaveTableOscillator::detune_by_temperature`], as a ratio.
+    temperature_ratio: f32,
+    drift: TuningDrift,
+    drift_enabled: Arc<Mutex<bool>>,
+    /// The drift's offset this sample, as a ratio.
+    drift_ratio: f32,
+    /// Rate, depth and shape for the modulation LFO; `lfo` runs with them.
+    lfo_settings: Arc<Mutex<Lfo>>,
+    lfo_target: Arc<Mutex<Option<LfoTarget>>>,
+    lfo: Lfo,
+    /// The LFO's pitch offset this sample, as a ratio.
+    lfo_ratio: f32,
+    /// Glide time between notes in milliseconds, 0 for none.
+    portamento_ms: Arc<Mutex<u32>>,
+    portamento: PortamentoState,
+    frequency_gate: Option<ThresholdGate>,
+    dynamic_table: Option<DynamicTableReader>,
+    /// Phase offset for the next sample only, in cycles.
+    phase_offset: f32,
+    phase_modulator: Option<Box<dyn Iterator<Item = f32> + Send>>,
+    state: OscillatorState,
+    /// Samples until a one-shot note starts its release.
+    one_shot_remaining: Option<usize>,
+}
+
+impl WaveTableOscillator {
+    pub fn new(sample_rate: u32, wave_table: Vec<f32>) -> WaveTableOscillator {
+        WaveTableOscillator {
+            sample_rate,
+            core: WaveTableCore::new(sample_rate, SharedWaveTable(Arc::new(wave_table)), DEFAULT_SMOOTHING_HZ),
+            frequency: Arc::new(Mutex::new(0.0)),
+            amplitude: Arc::new(Mutex::new(DEFAULT_AMPLITUDE)),
+            sub_mode: Arc::new(Mutex::new(None)),
+            clock: None,
+            detune_ratio: 1.0,
+            temperature_ratio: 1.0,
+            drift: TuningDrift::new(sample_rate, DEFAULT_DRIFT_RATE_HZ, DEFAULT_DRIFT_DEPTH_CENTS),
+            drift_enabled: Arc::new(Mutex::new(false)),
+            drift_ratio: 1.0,
+            lfo_settings: Arc::new(Mutex::new(Lfo::new(sample_rate, 5.0, 0.01, LfoPolarity::Bipolar))),
+            lfo_target: Arc::new(Mutex::new(None)),
+            lfo: Lfo::new(sample_rate, 5.0, 0.01, LfoPolarity::Bipolar),
+            lfo_ratio: 1.0,
+            portamento_ms: Arc::new(Mutex::new(0)),
+            portamento: PortamentoState::default(),
+            frequency_gate: Some(ThresholdGate::default()),
+            dynamic_table: None,
+            phase_offset: 0.0,
+            phase_modulator: None,
+            state: OscillatorState::Playing,
+            one_shot_remaining: None,
+        }
+    }
+
+    /// An oscillator playing frame `frame_index` of a Serum wavetable `.wav`.
+    pub fn from_serum_wavetable(
+        path: &Path,
+        frame_index: usize,
+        sample_rate: u32,
+    ) -> Result<WaveTableOscillator, SerumWavetableError> {
+        Ok(WaveTableOscillator::new(sample_rate, read_serum_frame(path, frame_index)?))
+    }
+
+    /// Rebuilds an oscillator from a snapshot. The sub-oscillator starts off, and the
+    /// oscillator gets a fresh frequency control primed with the saved frequency.
+    pub fn from_snapshot(state: WaveTableOscillatorState) -> WaveTableOscillator {
+        let mut oscillator = WaveTableOscillator::new(state.sample_rate, state.wave_table);
+        oscillator.core.restore(state.index, state.index_increment);
+        oscillator.frequency = Arc::new(Mutex::new(state.frequency));
+        oscillator
+    }
+
+    pub fn snapshot(&self) -> WaveTableOscillatorState {
+        WaveTableOscillatorState {
+            wave_table: self.core.wave_table().to_vec(),
+            sample_rate: self.sample_rate,
+            index: self.core.index(),
+            index_increment: self.core.increment(),
+            frequency: self.frequency.lock().map_or(0.0, |freq| *freq),
+        }
+    }
+
+    pub fn get_frequency_control(&self) -> Arc<Mutex<f32>> {
+        self.frequency.clone()
+    }
+
+    pub fn get_amplitude_control(&self) -> Arc<Mutex<f32>> {
+        self.amplitude.clone()
+    }
+
+    /// Sets the output level, clamped to 0.0..=1.0.
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        if let Ok(mut level) = self.amplitude.lock() {
+            *level = amplitude.clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn interpolation(&self) -> InterpolationMode {
+        self.core.interpolation()
+    }
+
+    /// How the table is read between samples; cubic is cleaner on small tables.
+    pub fn set_interpolation(&mut self, interpolation: InterpolationMode) {
+        self.core.set_interpolation(interpolation);
+    }
+
+    /// The frequency actually playing, after smoothing and any detune, rather than
+    /// the target in the frequency control.
+    pub fn current_frequency_hz(&self) -> f32 {
+        self.current_index_increment() * self.sample_rate as f32 / self.core.wave_table().len() as f32
+    }
+
+    /// Wave table samples advanced per output sample right now.
+    pub fn current_index_increment(&self) -> f32 {
+        self.core.increment()
+    }
+
+    /// Output samples per cycle at the current pitch; infinite while silent at 0 Hz.
+    pub fn compute_period_samples(&self) -> f32 {
+        self.core.wave_table().len() as f32 / self.current_index_increment()
+    }
+
+    /// Sets the frequency and skips the smoother, so the next sample is already at
+    /// pitch. For offline rendering; live code should go through the control.
+    pub fn set_frequency_direct(&mut self, freq_hz: f32) {
+        if let Ok(mut freq) = self.frequency.lock() {
+            *freq = freq_hz;
+        }
+        self.portamento.settle(freq_hz);
+        self.update_frequency();
+        self.core.settle();
+    }
+
+    /// Multiplies the frequency by `ratio`, so `3.0 / 2.0` moves up a perfect fifth.
+    /// Goes through the frequency control, so the change is smoothed like any other.
+    pub fn frequency_ratio(&mut self, ratio: f32) {
+        if let Ok(mut freq) = self.frequency.lock() {
+            *freq *= ratio;
+        }
+        self.update_frequency();
+    }
+
+    /// [`WaveTableOscillator::frequency_ratio`] in cents; 1200 is an octave.
+    pub fn frequency_cents(&mut self, cents: f32) {
+        self.frequency_ratio(2.0_f32.powf(cents / 1200.0));
+    }
+
+    /// Plays as an instrument tuned at [`REFERENCE_TEMPERATURE_CELSIUS`] would at
+    /// `temp_celsius`, per [`temperature_correction_cents`].
+    pub fn detune_by_temperature(&mut self, temp_celsius: f32) {
+        let cents = temperature_correction_cents(temp_celsius, REFERENCE_TEMPERATURE_CELSIUS);
+        self.temperature_ratio = 2.0_f32.powf(cents / 1200.0);
+        self.update_frequency();
+    }
+
+    /// Fixed detune on top of the shared frequency control, in cents.
+    pub fn set_detune_cents(&mut self, cents: f32) {
+        self.detune_ratio = 2.0_f32.powf(cents / 1200.0);
+        self.update_frequency();
+    }
+
+    /// A clone that follows this oscillator's frequency control instead of taking its
+    /// own, for stacking voices on one note.
+    pub fn unison_voice(&self) -> WaveTableOscillator {
+        WaveTableOscillator {
+            frequency: self.frequency.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Turns on the slow analog-style pitch wander of [`TuningDrift`]. Clones share
+    /// the switch but each drifts its own way.
+    pub fn get_drift_control(&self) -> Arc<Mutex<bool>> {
+        self.drift_enabled.clone()
+    }
+
+    /// The modulation LFO's rate, depth (0.0 to 1.0) and shape. Clones share the
+    /// settings but each keeps its own phase.
+    pub fn get_lfo_control(&self) -> Arc<Mutex<Lfo>> {
+        self.lfo_settings.clone()
+    }
+
+    /// What the modulation LFO moves, or `None` for nothing.
+    pub fn get_lfo_target_control(&self) -> Arc<Mutex<Option<LfoTarget>>> {
+        self.lfo_target.clone()
+    }
+
+    /// Glides between notes over `ms` milliseconds instead of jumping, 0 to turn it
+    /// off. Clones share the setting.
+    pub fn set_portamento_time_ms(&mut self, ms: u32) {
+        if let Ok(mut portamento_ms) = self.portamento_ms.lock() {
+            *portamento_ms = ms;
+        }
+    }
+
+    pub fn get_portamento_control(&self) -> Arc<Mutex<u32>> {
+        self.portamento_ms.clone()
+    }
+
+    /// Steps the modulation LFO, returning its pitch ratio and gain for this sample.
+    fn tick_lfo(&mut self, lfo: Option<(LfoTarget, Option<Lfo>)>) -> (f32, f32) {
+        let Some((target, settings)) = lfo else {
+            return (1.0, 1.0);
+        };
+        if let Some(settings) = settings {
+            self.lfo.rate_hz = settings.rate_hz;
+            self.lfo.depth = settings.depth.clamp(0.0, 1.0);
+            self.lfo.shape = settings.shape;
+        }
+        let value = self.lfo.tick();
+        match target {
+            LfoTarget::Pitch => (1.0 + self.lfo.depth * value, 1.0),
+            LfoTarget::Amplitude => (1.0, 1.0 - self.lfo.depth * (1.0 - value) / 2.0),
+        }
+    }
+
+    /// Swaps in a drift with other settings, e.g. deeper or faster.
+    pub fn set_drift(&mut self, drift: TuningDrift) {
+        self.drift = drift;
+    }
+
+    /// Silences requested frequencies outside `min_hz..=max_hz`; see [`ThresholdGate`].
+    pub fn set_frequency_gate(&mut self, min_hz: f32, max_hz: f32) {
+        self.frequency_gate = Some(ThresholdGate { min_hz, max_hz });
+        self.update_frequency();
+    }
+
+    /// Lets every frequency through, the full range included.
+    pub fn clear_frequency_gate(&mut self) {
+        self.frequency_gate = None;
+        self.update_frequency();
+    }
+
+    /// Renders `duration_secs * sample_rate` samples, rounded, at the current frequency
+    /// (see [`WaveTableOscillator::set_frequency_direct`]). Runs as fast as it can
+    /// rather than in real time, so long renders block.
+    #[must_use = "the rendered samples are the only output"]
+    pub fn to_audio_buffer(&mut self, duration_secs: f32) -> Result<Vec<f32>, InvalidRenderLength> {
+        let sample_count = (duration_secs as f64 * self.sample_rate as f64).round();
+        if !(0.0..usize::MAX as f64).contains(&sample_count) {
+            return Err(InvalidRenderLength(duration_secs));
+        }
+        Ok((0..sample_count as usize).map(|_| self.get_sample()).collect())
+    }
+
+    /// Renders `n_samples` with the pitch smoother settled and measures the result's
+    /// frequency with [`estimate_frequency`], a check on the pitch computation. With
+    /// the sub-oscillator off it should match [`current_frequency_hz`](Self::current_frequency_hz)
+    /// to within a hertz or two.
+    pub fn verify_frequency(&mut self, n_samples: usize) -> Result<f32, FrequencyError> {
+        self.update_frequency();
+        self.core.settle();
+        let samples: Vec<f32> = (0..n_samples).map(|_| self.get_sample()).collect();
+        estimate_frequency(&samples, self.sample_rate).ok_or(FrequencyError::TooFewCrossings { n_samples })
+    }
+
+    pub fn get_sub_oscillator_control(&self) -> Arc<Mutex<Option<SubOscillatorMode>>> {
+        self.sub_mode.clone()
+    }
+
+    /// Makes this oscillator advance `clock` once per sample it plays. Attach a clock to
+    /// only one oscillator per stream, or it counts too fast.
+    pub fn set_master_clock(&mut self, clock: MasterClock) {
+        self.clock = Some(clock);
+    }
+
+    /// Splits into a stereo pair reading the same wave table: the left channel plays at
+    /// the shared frequency and the right `detune_cents` away, so the two beat gently
+    /// against each other. Both follow this oscillator's frequency and sub-oscillator
+    /// controls; a master clock stays with the left side.
+    pub fn split_stereo(self, detune_cents: f32) -> StereoWaveTableOscillator {
+        let right = WaveTableOscillator {
+            sample_rate: self.sample_rate,
+            core: self.core.clone(),
+            frequency: self.frequency.clone(),
+            amplitude: self.amplitude.clone(),
+            sub_mode: self.sub_mode.clone(),
+            clock: None,
+            detune_ratio: self.detune_ratio * 2.0_f32.powf(detune_cents / 1200.0),
+            temperature_ratio: self.temperature_ratio,
+            drift: self.drift.clone(),
+            drift_enabled: self.drift_enabled.clone(),
+            drift_ratio: self.drift_ratio,
+            lfo_settings: self.lfo_settings.clone(),
+            lfo_target: self.lfo_target.clone(),
+            lfo: self.lfo,
+            lfo_ratio: self.lfo_ratio,
+            portamento_ms: self.portamento_ms.clone(),
+            portamento: self.portamento,
+            frequency_gate: self.frequency_gate,
+            dynamic_table: self.dynamic_table.clone(),
+            phase_offset: 0.0,
+            phase_modulator: None,
+            state: self.state,
+            one_shot_remaining: self.one_shot_remaining,
+        };
+
+        StereoWaveTableOscillator {
+            left: self,
+            right,
+            pending_right: None,
+        }
+    }
+
+    /// Replaces the wave table with a sine of the same size, computed with `mode`.
+    pub fn set_sine_mode(&mut self, mode: SineMode) {
+        let len = self.core.wave_table().len();
+        self.set_wave_table_arc(Arc::new(generate_wave_table_with(WaveShape::Sine, len, mode)));
+    }
+
+    /// Plays `table` from the next sample, keeping the phase. Once the oscillator is
+    /// playing on another thread, switch waveforms through a [`DynamicWaveTable`]
+    /// instead, which the oscillator picks up from its lock-free reader.
+    pub fn set_wave_table(&mut self, table: Vec<f32>) {
+        self.set_wave_table_arc(Arc::new(table));
+    }
+
+    /// Plays `table` from the next sample, keeping the phase. Oscillators given the
+    /// same `Arc` share one copy; each still needs the new `Arc` to change waveform.
+    pub fn set_wave_table_arc(&mut self, table: Arc<Vec<f32>>) {
+        self.core.set_wave_table(SharedWaveTable(table));
+    }
+
+    /// The table in use, for handing to other oscillators.
+    pub fn wave_table_arc(&self) -> Arc<Vec<f32>> {
+        self.core.table().0.clone()
+    }
+
+    /// Plays from `table` from now on, picking up each recompute as it lands. Every
+    /// oscillator following the table shares the worker's copy of each recompute.
+    pub fn set_dynamic_wave_table(&mut self, table: &DynamicWaveTable) {
+        self.dynamic_table = Some(DynamicTableReader {
+            current: table.current(),
+            version: table.version(),
+            seen: usize::MAX,
+        });
+        self.follow_dynamic_table();
+    }
+
+    fn follow_dynamic_table(&mut self) {
+        let Some(reader) = self.dynamic_table.as_mut() else {
+            return;
+        };
+        let version = reader.version.load(Ordering::Acquire);
+        if version == reader.seen {
+            return;
+        }
+        let Ok(table) = reader.current.read() else {
+            return;
+        };
+        reader.seen = version;
+        if !table.is_empty() {
+            self.core.set_wave_table(SharedWaveTable(table.clone()));
+        }
+    }
+
+    /// Sets how quickly the playing pitch follows frequency changes. Higher values
+    /// track faster; the default of 200 Hz settles in under 5 ms without zipper noise.
+    pub fn set_smoothing_hz(&mut self, smoothing_hz: f32) {
+        self.core.set_smoothing_hz(smoothing_hz);
+    }
+
+    fn update_frequency(&mut self) {
+        let Ok(freq) = self.frequency.lock().map(|freq| *freq) else {
+            return;
+        };
+        let glide_ms = self.portamento_ms.lock().map_or(0, |ms| *ms);
+        self.step_frequency(freq, glide_ms);
+    }
+
+    fn step_frequency(&mut self, freq: f32, glide_ms: u32) {
+        let target = if self.frequency_gate.is_some_and(|gate| !gate.passes(freq)) { 0.0 } else { freq };
+        let glide_samples = (glide_ms as u64 * self.sample_rate as u64 / 1000) as u32;
+        let freq = self.portamento.step(target, glide_samples);
+        let ratio = self.detune_ratio * self.temperature_ratio * self.drift_ratio * self.lfo_ratio;
+        self.core.set_frequency(freq * ratio);
+    }
+
+    fn sub_oscillator(&self) -> Option<SubOscillatorMode> {
+        self.sub_mode.lock().ok().and_then(|mode| *mode)
+    }
+
+    /// Reads the next sample `offset_radians` away from the natural phase, without
+    /// moving the phase accumulator: phase modulation, as on DX-style FM synths.
+    /// Offsets before one sample add up.
+    pub fn phase_modulate(&mut self, offset_radians: f32) {
+        self.phase_offset += offset_radians / TAU;
+    }
+
+    /// Phase-modulates every sample by the next value from `modulator`, in radians,
+    /// until it runs out.
+    pub fn set_phase_modulator(&mut self, modulator: impl Iterator<Item = f32> + Send + 'static) {
+        self.phase_modulator = Some(Box::new(modulator));
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let controls = self.shared_controls();
+        let frequency = self.frequency.lock().ok().map(|freq| *freq);
+        self.get_sample_with(frequency, &controls)
+    }
+
+    /// Reads the controls this oscillator shares with its clones, for
+    /// [`get_sample_with`](Self::get_sample_with).
+    pub(crate) fn shared_controls(&self) -> SharedControls {
+        SharedControls {
+            drifting: self.drift_enabled.lock().is_ok_and(|enabled| *enabled),
+            lfo: self
+                .lfo_target
+                .lock()
+                .ok()
+                .and_then(|target| *target)
+                .map(|target| (target, self.lfo_settings.lock().ok().map(|settings| *settings))),
+            glide_ms: self.portamento_ms.lock().map_or(0, |ms| *ms),
+            sub: self.sub_oscillator(),
+            amplitude: self.amplitude.lock().map_or(DEFAULT_AMPLITUDE, |level| *level),
+        }
+    }
+
+    /// [`get_sample`](Self::get_sample) at `frequency`, with `controls` read earlier,
+    /// so it takes no locks; with no frequency the pitch stays where it is. A
+    /// polyphonic engine reads its prototype's controls once a block and plays every
+    /// voice, which shares them, this way.
+    pub(crate) fn get_sample_with(&mut self, frequency: Option<f32>, controls: &SharedControls) -> f32 {
+        self.follow_dynamic_table();
+        self.drift_ratio = if controls.drifting { self.drift.tick_ratio() } else { 1.0 };
+        let (lfo_ratio, lfo_gain) = self.tick_lfo(controls.lfo);
+        self.lfo_ratio = lfo_ratio;
+        if let Some(freq) = frequency {
+            self.step_frequency(freq, controls.glide_ms);
+        }
+        if let Some(modulator) = self.phase_modulator.as_mut() {
+            match modulator.next() {
+                Some(offset) => self.phase_modulate(offset),
+                None => self.phase_modulator = None,
+            }
+        }
+        let sub = controls.sub.map(|mode| (mode.interval.ratio(), mode.mix));
+        let phase_offset = std::mem::take(&mut self.phase_offset);
+        let sample = self.core.next_sample_phase_shifted(sub, phase_offset) * controls.amplitude * lfo_gain;
+        if let Some(remaining) = self.one_shot_remaining.as_mut() {
+            match remaining {
+                0 => self.fade_out(ONE_SHOT_RELEASE_SAMPLES),
+                _ => *remaining -= 1,
+            }
+        }
+        match &mut self.state {
+            OscillatorState::Playing => sample,
+            OscillatorState::FadingOut { remaining: 0, .. } => 0.0,
+            OscillatorState::FadingOut { remaining, total } => {
+                let gain = *remaining as f32 / *total as f32;
+                *remaining -= 1;
+                sample * gain
+            }
+        }
+    }
+
+    /// Fills `buf` with the next samples, as that many calls to `next` would, but
+    /// reading the shared controls once for the whole block instead of once a sample.
+    ///
+    /// Drift, the LFO, a glide, phase modulation, a one-shot and a fade all move
+    /// sample by sample, so while any of them is running this falls back to `next`.
+    pub fn render_block(&mut self, buf: &mut [f32]) {
+        let target = self.frequency.lock().map(|freq| match self.frequency_gate {
+            Some(gate) if !gate.passes(*freq) => 0.0,
+            _ => *freq,
+        });
+        let glide_ms = self.portamento_ms.lock().map_or(0, |ms| *ms);
+        let drifting = self.drift_enabled.lock().is_ok_and(|enabled| *enabled);
+        let lfo_running = self.lfo_target.lock().is_ok_and(|target| target.is_some());
+        let steady = match target {
+            Ok(target) => {
+                !drifting
+                    && !lfo_running
+                    && self.phase_modulator.is_none()
+                    && self.phase_offset == 0.0
+                    && self.one_shot_remaining.is_none()
+                    && self.state == OscillatorState::Playing
+                    && self.portamento.glide_samples_remaining == 0
+                    && (glide_ms == 0 || target == self.portamento.target_freq)
+            }
+            Err(_) => false,
+        };
+        let (true, Ok(target)) = (steady, target) else {
+            for sample in buf {
+                *sample = self.next().unwrap_or(0.0);
+            }
+            return;
+        };
+
+        self.follow_dynamic_table();
+        if let Some(clock) = &self.clock {
+            clock.advance(buf.len() as u64);
+        }
+        (self.drift_ratio, self.lfo_ratio) = (1.0, 1.0);
+        let freq = self.portamento.step(target, 0);
+        self.core.set_frequency(freq * self.detune_ratio * self.temperature_ratio);
+        let sub = self.sub_oscillator().map(|mode| (mode.interval.ratio(), mode.mix));
+        let amplitude = self.amplitude.lock().map_or(DEFAULT_AMPLITUDE, |level| *level);
+        for sample in buf {
+            *sample = self.core.next_sample(sub) * amplitude;
+        }
+    }
+
+    /// Fades the output linearly to silence over `duration_samples`, after which
+    /// it stays silent and the oscillator, as an iterator, ends. A few milliseconds,
+    /// such as 128 samples, is enough to take the click out of cutting a voice off.
+    ///
+    /// On an owned oscillator, method syntax picks rodio's `Source::fade_out`, so call
+    /// this through a `&mut` or as `WaveTableOscillator::fade_out(&mut oscillator, n)`.
+    pub fn fade_out(&mut self, duration_samples: usize) {
+        self.one_shot_remaining = None;
+        self.state = OscillatorState::FadingOut {
+            remaining: duration_samples,
+            total: duration_samples,
+        };
+    }
+
+    /// Starts a note at `freq` from the top of its cycle and releases it on its own
+    /// after `duration_samples`, for percussion triggers that have no note-off. As a
+    /// source it then ends once the release fade is over.
+    pub fn play_one_shot(&mut self, freq: f32, duration_samples: usize) {
+        self.set_frequency_direct(freq);
+        self.reset_phase();
+        self.state = OscillatorState::Playing;
+        self.one_shot_remaining = Some(duration_samples);
+    }
+
+    pub fn state(&self) -> OscillatorState {
+        self.state
+    }
+
+    /// True once a fade-out has reached silence.
+    pub fn is_faded_out(&self) -> bool {
+        matches!(self.state, OscillatorState::FadingOut { remaining: 0, .. })
+    }
+
+    /// How far through the cycle the oscillator is, 0.0-1.0.
+    pub fn phase(&self) -> f32 {
+        self.core.phase()
+    }
+
+    /// Restarts the cycle from the top of the wave table, so oscillators reset
+    /// together start in phase.
+    pub fn reset_phase(&mut self) {
+        self.reset_phase_to(0.0);
+    }
+
+    /// Jumps to `phase_normalized` (0.0-1.0) of the way through the cycle. Staggering
+    /// voices (0.0, 0.25, 0.5, ...) avoids phase cancellation when they start together.
+    pub fn reset_phase_to(&mut self, phase_normalized: f32) {
+        self.core.set_phase(phase_normalized);
+    }
+
+    /// Advances the oscillator by `n` samples without rendering them, for seeking
+    /// during offline rendering. Assumes the frequency stays put over the skipped span,
+    /// so the frequency smoother is treated as settled. A master clock advances by `n`,
+    /// as it would have over the samples played.
+    pub fn skip_samples(&mut self, n: usize) {
+        if let Some(clock) = &self.clock {
+            clock.advance(n as u64);
+        }
+        self.update_frequency();
+        let sub_ratio = self.sub_oscillator().map(|mode| mode.interval.ratio());
+        self.core.skip(n, sub_ratio);
+    }
+}
+
+/// Copies the phase and settings and shares the wave table, for setting voices up from
+/// a prototype. The clone gets its own frequency control, primed with the current
+/// frequency, so voices can play different notes; the sub-oscillator control stays
+/// shared, and a master clock and phase modulator aren't copied, since only one
+/// oscillator should advance or consume them.
+impl Clone for WaveTableOscillator {
+    fn clone(&self) -> Self {
+        WaveTableOscillator {
+            sample_rate: self.sample_rate,
+            core: self.core.clone(),
+            frequency: Arc::new(Mutex::new(self.frequency.lock().map_or(0.0, |freq| *freq))),
+            amplitude: self.amplitude.clone(),
+            sub_mode: self.sub_mode.clone(),
+            clock: None,
+            detune_ratio: self.detune_ratio,
+            temperature_ratio: self.temperature_ratio,
+            drift: self.drift.clone(),
+            drift_enabled: self.drift_enabled.clone(),
+            drift_ratio: self.drift_ratio,
+            lfo_settings: self.lfo_settings.clone(),
+            lfo_target: self.lfo_target.clone(),
+            lfo: self.lfo,
+            lfo_ratio: self.lfo_ratio,
+            portamento_ms: self.portamento_ms.clone(),
+            portamento: self.portamento,
+            frequency_gate: self.frequency_gate,
+            dynamic_table: self.dynamic_table.clone(),
+            phase_offset: 0.0,
+            phase_modulator: None,
+            state: self.state,
+            one_shot_remaining: self.one_shot_remaining,
+        }
+    }
+
+    /// Like `clone`, but keeps `self`'s own frequency control, writing the value into
+    /// it, so taking over another oscillator's note doesn't allocate. Everything else
+    /// `clone` shares is shared here too, so the audio thread can do this.
+    fn clone_from(&mut self, source: &Self) {
+        let frequency = source.frequency.lock().map_or(0.0, |freq| *freq);
+        if let Ok(mut freq) = self.frequency.lock() {
+            *freq = frequency;
+        }
+        self.sample_rate = source.sample_rate;
+        self.core.clone_from(&source.core);
+        self.amplitude.clone_from(&source.amplitude);
+        self.sub_mode.clone_from(&source.sub_mode);
+        self.clock = None;
+        self.detune_ratio = source.detune_ratio;
+        self.temperature_ratio = source.temperature_ratio;
+        self.drift.clone_from(&source.drift);
+        self.drift_enabled.clone_from(&source.drift_enabled);
+        self.drift_ratio = source.drift_ratio;
+        self.lfo_settings.clone_from(&source.lfo_settings);
+        self.lfo_target.clone_from(&source.lfo_target);
+        self.lfo = source.lfo;
+        self.lfo_ratio = source.lfo_ratio;
+        self.portamento_ms.clone_from(&source.portamento_ms);
+        self.portamento = source.portamento;
+        self.frequency_gate = source.frequency_gate;
+        self.dynamic_table.clone_from(&source.dynamic_table);
+        self.phase_offset = 0.0;
+        self.phase_modulator = None;
+        self.state = source.state;
+        self.one_shot_remaining = source.one_shot_remaining;
+    }
+}
+
+impl Source for WaveTableOscillator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl BlockSource for WaveTableOscillator {
+    fn render_block(&mut self, buf: &mut [f32]) {
+        WaveTableOscillator::render_block(self, buf);
+    }
+}
+
+impl Iterator for WaveTableOscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_faded_out() {
+            return None;
+        }
+        if let Some(clock) = &self.clock {
+            clock.advance(1);
+        }
+        Some(self.get_sample())
+    }
+}
+
+/// Two detuned [`WaveTableOscillator`]s, one per channel, made with
+/// [`WaveTableOscillator::split_stereo`]. Plays as interleaved stereo.
+pub struct StereoWaveTableOscillator {
+    left: WaveTableOscillator,
+    right: WaveTableOscillator,
+    pending_right: Option<f32>,
+}
+
+impl StereoWaveTableOscillator {
+    pub fn get_frequency_control(&self) -> Arc<Mutex<f32>> {
+        self.left.get_frequency_control()
+    }
+
+    /// The next `(left, right)` pair.
+    pub fn next_frame(&mut self) -> (f32, f32) {
+        if let Some(clock) = &self.left.clock {
+            clock.advance(1);
+        }
+        (self.left.get_sample(), self.right.get_sample())
+    }
+}
+
+impl Source for StereoWaveTableOscillator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.left.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for StereoWaveTableOscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let (left, right) = self.next_frame();
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave::{generate_wave_table, WaveShape};
+    use std::f32::consts::TAU;
+
+    #[test]
+    fn skip_samples_lands_where_stepping_does() {
+        let table: Vec<f32> = (0..2048).map(|i| (i as f32 / 2048.0 * std::f32::consts::TAU).sin()).collect();
+        let mut stepped = WaveTableOscillator::new(44100, table.clone());
+        let mut skipped = WaveTableOscillator::new(44100, table);
+        *stepped.get_frequency_control().lock().unwrap() = 440.0;
+        *skipped.get_frequency_control().lock().unwrap() = 440.0;
+        for _ in 0..44100 {
+            stepped.next();
+        }
+        skipped.skip_samples(44100);
+        let (a, b) = (stepped.next().unwrap(), skipped.next().unwrap());
+        // Stepping accumulates the phase in f32, skipping in f64
+        assert!((a - b).abs() < 1e-3, "{a} vs {b}");
+    }
+
+    #[test]
+    fn skip_samples_advances_the_master_clock() {
+        let table = generate_wave_table(WaveShape::Sine, 2048);
+        let (stepped_clock, skipped_clock) = (MasterClock::new(44100), MasterClock::new(44100));
+        let mut stepped = WaveTableOscillator::new(44100, table.clone());
+        let mut skipped = WaveTableOscillator::new(44100, table);
+        stepped.set_master_clock(stepped_clock.clone());
+        skipped.set_master_clock(skipped_clock.clone());
+        for oscillator in [&mut stepped, &mut skipped] {
+            *oscillator.get_frequency_control().lock().unwrap() = 440.0;
+        }
+        let last = (0..=1000).map(|_| stepped.next().unwrap()).last().unwrap();
+        skipped.skip_samples(1000);
+        let sample = skipped.next().unwrap();
+        assert_eq!(skipped_clock.sample_count(), 1001);
+        assert_eq!(skipped_clock.sample_count(), stepped_clock.sample_count());
+        assert!((last - sample).abs() < 1e-3, "{last} vs {sample}");
+    }
+
+    #[test]
+    fn snapshot_format_reads_a_saved_state() {
+        // Written by an earlier version; a failure here means saved states no longer load
+        let json = concat!(
+            r#"{"wave_table":[0.0,1.0,0.0,-1.0],"sample_rate":44100,"#,
+            r#""index":1.5,"index_increment":0.25,"frequency":2756.25}"#,
+        );
+        let state: WaveTableOscillatorState = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            state,
+            WaveTableOscillatorState {
+                wave_table: vec![0.0, 1.0, 0.0, -1.0],
+                sample_rate: 44100,
+                index: 1.5,
+                index_increment: 0.25,
+                frequency: 2756.25,
+            }
+        );
+        let oscillator = WaveTableOscillator::from_snapshot(state.clone());
+        assert_eq!(oscillator.snapshot(), state);
+        assert_eq!(serde_json::to_string(&state).unwrap(), json);
+    }
+
+    #[test]
+    fn frequency_ratio_doubles_the_increment_through_the_smoother() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        osc.set_frequency_direct(440.0);
+        let start = osc.snapshot().index_increment;
+        osc.frequency_ratio(2.0);
+        // Smoothed rather than a jump: one time constant is 1 / (2 pi 200 Hz), 35 samples
+        let time_constant = (44100.0 / (TAU * DEFAULT_SMOOTHING_HZ)).round() as usize;
+        osc.next();
+        assert!(osc.snapshot().index_increment < 1.1 * start);
+        for _ in 1..time_constant {
+            osc.next();
+        }
+        let progress = (osc.snapshot().index_increment - start) / start;
+        assert!(progress > 0.6 && progress < 0.7, "{progress}");
+        for _ in 0..10 * time_constant {
+            osc.next();
+        }
+        assert!((osc.snapshot().index_increment / start - 2.0).abs() < 1e-3);
+        assert_eq!(*osc.get_frequency_control().lock().unwrap(), 880.0);
+    }
+
+    #[test]
+    fn frequency_cents_moves_by_equal_tempered_steps() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        osc.set_frequency_direct(440.0);
+        osc.frequency_cents(700.0);
+        let fifth = *osc.get_frequency_control().lock().unwrap();
+        assert!((fifth - 440.0 * 2.0_f32.powf(7.0 / 12.0)).abs() < 1e-3, "{fifth}");
+        osc.frequency_cents(-1900.0);
+        assert!((*osc.get_frequency_control().lock().unwrap() - 220.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn temperature_correction_is_three_cents_per_ten_degrees() {
+        assert!((temperature_correction_cents(30.0, REFERENCE_TEMPERATURE_CELSIUS) + 3.0).abs() < 1e-4);
+        assert!((temperature_correction_cents(10.0, REFERENCE_TEMPERATURE_CELSIUS) - 3.0).abs() < 1e-4);
+        assert_eq!(temperature_correction_cents(20.0, REFERENCE_TEMPERATURE_CELSIUS), 0.0);
+    }
+
+    #[test]
+    fn detune_by_temperature_flattens_the_increment() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        osc.set_frequency_direct(440.0);
+        let start = osc.snapshot().index_increment;
+        osc.detune_by_temperature(30.0);
+        osc.set_frequency_direct(440.0);
+        let ratio = osc.snapshot().index_increment / start;
+        assert!((ratio - 2.0_f32.powf(-3.0 / 1200.0)).abs() < 1e-5, "{ratio}");
+    }
+
+    #[test]
+    fn verify_frequency_measures_440_hz() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        osc.set_frequency_direct(440.0);
+        let measured = osc.verify_frequency(4096).unwrap();
+        assert!((measured - 440.0).abs() < 2.0, "{measured}");
+    }
+
+    #[test]
+    fn verify_frequency_needs_two_crossings() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        osc.set_frequency_direct(440.0);
+        assert_eq!(osc.verify_frequency(64), Err(FrequencyError::TooFewCrossings { n_samples: 64 }));
+    }
+
+    #[test]
+    fn clone_from_keeps_its_own_frequency_control() {
+        let mut source = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 2048));
+        source.set_frequency_direct(440.0);
+        source.skip_samples(100);
+        let mut copy = source.clone();
+        let control = copy.get_frequency_control();
+        source.set_frequency_direct(660.0);
+        copy.clone_from(&source);
+        assert!(Arc::ptr_eq(&control, &copy.get_frequency_control()));
+        assert_eq!(*control.lock().unwrap(), 660.0);
+        assert_eq!(copy.phase(), source.phase());
+    }
+
+    #[test]
+    fn get_sample_with_shared_controls_matches_get_sample() {
+        let mut locked = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sawtooth, 2048));
+        locked.set_portamento_time_ms(20);
+        locked.get_lfo_control().lock().unwrap().depth = 0.5;
+        *locked.get_lfo_target_control().lock().unwrap() = Some(LfoTarget::Amplitude);
+        locked.set_frequency_direct(220.0);
+        let mut unlocked = locked.clone();
+        *locked.get_frequency_control().lock().unwrap() = 330.0;
+        let controls = locked.shared_controls();
+        for _ in 0..2000 {
+            assert_eq!(unlocked.get_sample_with(Some(330.0), &controls), locked.get_sample());
+        }
+    }
+
+    #[test]
+    fn opposite_phases_cancel() {
+        let table = generate_wave_table(WaveShape::Sine, 2048);
+        let mut a = WaveTableOscillator::new(44100, table.clone());
+        let mut b = WaveTableOscillator::new(44100, table);
+        for oscillator in [&mut a, &mut b] {
+            *oscillator.get_frequency_control().lock().unwrap() = 440.0;
+            // Played a while first, so the reset has somewhere to come back from
+            oscillator.skip_samples(1234);
+        }
+        a.reset_phase();
+        b.reset_phase_to(0.5);
+        for _ in 0..1000 {
+            let sum = a.next().unwrap() + b.next().unwrap();
+            assert!(sum.abs() < 1e-3, "{sum}");
+        }
+    }
+}
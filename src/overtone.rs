@@ -0,0 +1,219 @@
+use crate::ringbuf::RingBuffer;
+use crate::window::FftWindow;
+use rodio::Source;
+use rustfft::num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use std::sync::{Arc, Mutex};
+
+const FFT_SIZE: usize = 2048;
+const HOP: usize = FFT_SIZE / 4;
+const CROSSFADE_SECS: f32 = 0.05;
+/// How many harmonics an [`OvertoneFilter`] pattern covers.
+pub const OVERTONE_COUNT: usize = 16;
+
+/// Named [`OvertoneFilter`] patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OvertonePreset {
+    /// Harmonics 1, 3, 5 and 7: a square wave's character.
+    OddOnly,
+    /// Harmonics 2, 4, 6 and 8: hollow, like an ocarina.
+    EvenOnly,
+    /// Harmonics 1, 3, 6 and 9: an open-fifth drone.
+    FifthsOnly,
+}
+
+impl OvertonePreset {
+    pub const ALL: [OvertonePreset; 3] =
+        [OvertonePreset::OddOnly, OvertonePreset::EvenOnly, OvertonePreset::FifthsOnly];
+
+    /// The harmonic numbers the preset passes, counting the fundamental as 1.
+    pub fn harmonics(self) -> &'static [usize] {
+        match self {
+            OvertonePreset::OddOnly => &[1, 3, 5, 7],
+            OvertonePreset::EvenOnly => &[2, 4, 6, 8],
+            OvertonePreset::FifthsOnly => &[1, 3, 6, 9],
+        }
+    }
+}
+
+/// Which harmonics of the fundamental pass: `pattern[i]` is harmonic `i + 1`.
+/// Everything else, including harmonics past the pattern, is blocked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OvertoneFilter {
+    pub pattern: [bool; OVERTONE_COUNT],
+}
+
+impl Default for OvertoneFilter {
+    fn default() -> Self {
+        OvertoneFilter {
+            pattern: [true; OVERTONE_COUNT],
+        }
+    }
+}
+
+impl From<OvertonePreset> for OvertoneFilter {
+    fn from(preset: OvertonePreset) -> Self {
+        let mut pattern = [false; OVERTONE_COUNT];
+        for &harmonic in preset.harmonics() {
+            pattern[harmonic - 1] = true;
+        }
+        OvertoneFilter { pattern }
+    }
+}
+
+impl OvertoneFilter {
+    /// 1.0 or 0.0 for a component at `frequency`, by the harmonic it lies closest to.
+    /// With no fundamental everything passes.
+    pub fn gain(&self, frequency: f32, fundamental: f32) -> f32 {
+        if fundamental <= 0.0 {
+            return 1.0;
+        }
+        let harmonic = (frequency / fundamental).round() as usize;
+        match harmonic.checked_sub(1).and_then(|i| self.pattern.get(i)) {
+            Some(true) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Runs a mono source through an [`OvertoneFilter`] following the played note.
+///
+/// Every FFT bin is scaled by the gain of the harmonic nearest to it, then the
+/// frames are resynthesized by overlap-add. The bins are about 21 Hz apart at
+/// 44.1 kHz, so harmonics of notes much below 60 Hz start to blur together. The
+/// filtered signal runs one FFT frame, about 46 ms, behind the dry one; switching
+/// crossfades over 50 ms.
+pub struct OvertoneFilterSource<S: Source<Item = f32>> {
+    source: S,
+    filter: Arc<Mutex<Option<OvertoneFilter>>>,
+    fundamental: Option<Arc<Mutex<f32>>>,
+    last_filter: OvertoneFilter,
+    mix: f32,
+    mix_step: f32,
+    window: Vec<f32>,
+    input: RingBuffer<f32>,
+    overlap: Vec<f32>,
+    hop_pos: usize,
+    spectrum: Vec<Complex32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+}
+
+impl<S: Source<Item = f32>> OvertoneFilterSource<S> {
+    pub fn new(source: S) -> OvertoneFilterSource<S> {
+        let mut planner = FftPlanner::new();
+        let mix_step = 1.0 / (CROSSFADE_SECS * source.sample_rate() as f32);
+
+        OvertoneFilterSource {
+            source,
+            filter: Arc::new(Mutex::new(None)),
+            fundamental: None,
+            last_filter: OvertoneFilter::default(),
+            mix: 0.0,
+            mix_step,
+            window: FftWindow::Hann.coefficients(FFT_SIZE),
+            input: RingBuffer::new(FFT_SIZE),
+            overlap: vec![0.0; FFT_SIZE],
+            hop_pos: 0,
+            spectrum: vec![Complex32::default(); FFT_SIZE],
+            forward: planner.plan_fft_forward(FFT_SIZE),
+            inverse: planner.plan_fft_inverse(FFT_SIZE),
+        }
+    }
+
+    /// The filter in use; `None` passes the source through.
+    pub fn get_filter_control(&self) -> Arc<Mutex<Option<OvertoneFilter>>> {
+        self.filter.clone()
+    }
+
+    /// Takes the fundamental from `frequency`, the played note. Without it
+    /// everything passes.
+    pub fn set_tracked_frequency(&mut self, frequency: Arc<Mutex<f32>>) {
+        self.fundamental = Some(frequency);
+    }
+
+    fn process_hop(&mut self, filter: &OvertoneFilter) {
+        // Analyse the newest samples, oldest first
+        for i in 0..FFT_SIZE {
+            let sample = *self.input.read_at(FFT_SIZE - i);
+            self.spectrum[i] = Complex32::new(sample * self.window[i], 0.0);
+        }
+        self.forward.process(&mut self.spectrum);
+
+        let fundamental = self.fundamental.as_ref().and_then(|f| f.lock().ok().map(|f| *f)).unwrap_or(0.0);
+        let bin_hz = self.source.sample_rate() as f32 / FFT_SIZE as f32;
+        let bins = FFT_SIZE / 2 + 1;
+        for bin in 0..bins {
+            self.spectrum[bin] *= filter.gain(bin as f32 * bin_hz, fundamental);
+        }
+        // Mirror the bins so the inverse transform comes out real
+        for bin in bins..FFT_SIZE {
+            self.spectrum[bin] = self.spectrum[FFT_SIZE - bin].conj();
+        }
+        self.inverse.process(&mut self.spectrum);
+
+        self.overlap.rotate_left(HOP);
+        self.overlap[FFT_SIZE - HOP..].fill(0.0);
+        // Hann squared at 75% overlap sums to 1.5, and the inverse FFT is unscaled
+        let scale = 1.0 / (1.5 * FFT_SIZE as f32);
+        for i in 0..FFT_SIZE {
+            self.overlap[i] += self.spectrum[i].re * self.window[i] * scale;
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for OvertoneFilterSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for OvertoneFilterSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let dry = self.source.next()?;
+        let filter = self.filter.lock().ok().and_then(|filter| *filter);
+
+        self.input.push(dry);
+        let wet = self.overlap[self.hop_pos];
+        self.hop_pos += 1;
+        if self.hop_pos == HOP {
+            self.hop_pos = 0;
+            // Keep resynthesizing with the last pattern while fading out. Once off,
+            // drop the old frames so switching back on doesn't replay them
+            if let Some(filter) = filter {
+                self.last_filter = filter;
+            }
+            if filter.is_some() || self.mix > 0.0 {
+                let last_filter = self.last_filter;
+                self.process_hop(&last_filter);
+            } else {
+                self.overlap.fill(0.0);
+            }
+        }
+
+        let target = if filter.is_some() { 1.0 } else { 0.0 };
+        if self.mix < target {
+            self.mix = (self.mix + self.mix_step).min(target);
+        } else if self.mix > target {
+            self.mix = (self.mix - self.mix_step).max(target);
+        }
+        if self.mix == 0.0 {
+            return Some(dry);
+        }
+        Some(dry * (1.0 - self.mix) + wet * self.mix)
+    }
+}
@@ -0,0 +1,226 @@
+use rand::Rng;
+use rodio::Source;
+use std::f32::consts::FRAC_PI_4;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use synth_core::ParameterSmoother;
+
+/// How quickly the panner follows a moved pan control.
+const PAN_SMOOTHING_MS: f32 = 10.0;
+
+/// The ends of a piano's keyboard, A0 and C8, which [`register_pan`] puts hard left
+/// and hard right.
+const PIANO_RANGE_HZ: (f32, f32) = (27.5, 4186.0);
+
+/// Turns a mono source into interleaved stereo using the constant-power pan law.
+///
+/// The pan position is shared as `f32` bits in an `AtomicU32`, from -1.0 (left)
+/// through 0.0 (center, both channels at about 0.707) to 1.0 (right). Moves glide
+/// over about 10 ms.
+pub struct ConstantPowerPanner<S: Source<Item = f32>> {
+    source: S,
+    pan: Arc<AtomicU32>,
+    smoother: ParameterSmoother,
+    pending_right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> ConstantPowerPanner<S> {
+    pub fn new(source: S, pan: Arc<AtomicU32>) -> ConstantPowerPanner<S> {
+        let mut smoother = ParameterSmoother::new_with_time(PAN_SMOOTHING_MS, source.sample_rate());
+        smoother.reset(f32::from_bits(pan.load(Ordering::Relaxed)));
+        ConstantPowerPanner {
+            source,
+            pan,
+            smoother,
+            pending_right: None,
+        }
+    }
+
+    /// Moves the shared pan control, which every panner sharing it follows.
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan.store(pan.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Where a note at `freq_hz` sits if the stereo field is laid out like a piano seen
+/// from the bench: bass left, treble right, with equal steps per octave.
+pub fn register_pan(freq_hz: f32) -> f32 {
+    let (low, high) = PIANO_RANGE_HZ;
+    let position = (freq_hz.max(f32::MIN_POSITIVE) / low).log2() / (high / low).log2();
+    (2.0 * position - 1.0).clamp(-1.0, 1.0)
+}
+
+/// A shared pan control for [`ConstantPowerPanner`], starting at `pan`.
+pub fn pan_control(pan: f32) -> Arc<AtomicU32> {
+    Arc::new(AtomicU32::new(pan.clamp(-1.0, 1.0).to_bits()))
+}
+
+/// Left and right gains for a pan position in -1.0..=1.0.
+pub fn constant_power_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+impl<S: Source<Item = f32>> Source for ConstantPowerPanner<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ConstantPowerPanner<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let input = self.source.next()?;
+        self.smoother.set_target(f32::from_bits(self.pan.load(Ordering::Relaxed)));
+        let (left, right) = constant_power_gains(self.smoother.tick());
+        self.pending_right = Some(input * right);
+        Some(input * left)
+    }
+}
+
+/// Left and right after `balance` in -1.0..=1.0: -1.0 silences the right channel,
+/// 1.0 the left, and 0.0 leaves both alone. Unlike panning, nothing moves between
+/// channels; one side is only turned down.
+pub fn apply_balance(left: f32, right: f32, balance: f32) -> (f32, f32) {
+    let balance = balance.clamp(-1.0, 1.0);
+    (left * (1.0 - balance.max(0.0)), right * (1.0 - (-balance).max(0.0)))
+}
+
+/// Balances an interleaved stereo source by [`apply_balance`]. The balance is
+/// shared as `f32` bits in an `AtomicU32` like the pan control, and moves glide
+/// over about 10 ms. A mono source passes through untouched.
+pub struct StereoBalance<S: Source<Item = f32>> {
+    source: S,
+    balance: Arc<AtomicU32>,
+    smoother: ParameterSmoother,
+    pending_right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> StereoBalance<S> {
+    pub fn new(source: S, balance: Arc<AtomicU32>) -> StereoBalance<S> {
+        let mut smoother = ParameterSmoother::new_with_time(PAN_SMOOTHING_MS, source.sample_rate());
+        smoother.reset(f32::from_bits(balance.load(Ordering::Relaxed)));
+        StereoBalance {
+            source,
+            balance,
+            smoother,
+            pending_right: None,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for StereoBalance<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for StereoBalance<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.source.channels() != 2 {
+            return self.source.next();
+        }
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let left = self.source.next()?;
+        let right = self.source.next()?;
+        self.smoother.set_target(f32::from_bits(self.balance.load(Ordering::Relaxed)));
+        let (left, right) = apply_balance(left, right, self.smoother.tick());
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
+
+/// How an [`AutoPan`] spreads polyphonic voices across the stereo field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AutoPanMode {
+    #[default]
+    Center,
+    /// Even voices at -0.7, odd ones at +0.7.
+    AlternatingLR,
+    /// Voices spaced evenly from -1.0 to 1.0, repeating every `n`.
+    FullSpread(usize),
+    /// A random position per voice, drawn the first time it is used.
+    RandomPerVoice,
+}
+
+/// Gives each voice of a pool its own pan position by voice index, so a voice keeps
+/// its place whatever notes it plays.
+#[derive(Clone, Debug, Default)]
+pub struct AutoPan {
+    pub mode: AutoPanMode,
+    random_pans: Vec<f32>,
+}
+
+impl AutoPan {
+    pub fn new(mode: AutoPanMode) -> AutoPan {
+        AutoPan {
+            mode,
+            random_pans: Vec::new(),
+        }
+    }
+
+    /// Where `voice` sits, -1.0 (left) to 1.0 (right).
+    pub fn pan_for(&mut self, voice: usize) -> f32 {
+        match self.mode {
+            AutoPanMode::Center => 0.0,
+            AutoPanMode::AlternatingLR if voice.is_multiple_of(2) => -0.7,
+            AutoPanMode::AlternatingLR => 0.7,
+            AutoPanMode::FullSpread(n) if n < 2 => 0.0,
+            AutoPanMode::FullSpread(n) => -1.0 + 2.0 * (voice % n) as f32 / (n - 1) as f32,
+            AutoPanMode::RandomPerVoice => {
+                while self.random_pans.len() <= voice {
+                    self.random_pans.push(rand::thread_rng().gen_range(-1.0..=1.0));
+                }
+                self.random_pans[voice]
+            }
+        }
+    }
+}
+
+/// A pan position drawn as a marker on a `width`-character track, such as `L  |o  R`
+/// for a little right of centre: `L` and `R` mark the ends, `|` the centre and `o` the
+/// position.
+pub fn pan_indicator(pan: f32, width: usize) -> String {
+    let width = width.max(3);
+    let mut track = vec![' '; width];
+    track[0] = 'L';
+    track[width - 1] = 'R';
+    track[width / 2] = '|';
+    let position = ((pan.clamp(-1.0, 1.0) + 1.0) / 2.0 * (width - 1) as f32).round() as usize;
+    track[position] = 'o';
+    track.into_iter().collect()
+}
@@ -0,0 +1,519 @@
+use crate::fm::{FmPreset, BUILTIN_FM_PRESETS};
+use crate::oscillator::{SubInterval, SubOscillatorMode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub const PATCH_COUNT: usize = 128;
+
+/// Which of the synth's sound sources a patch plays through.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PatchVoice {
+    WaveTable,
+    SuperSaw,
+    Fm,
+}
+
+/// A complete set of sound settings, stored on disk as TOML.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub voice: PatchVoice,
+    /// Name of one of the built-in FM presets, used by [`PatchVoice::Fm`].
+    pub fm_preset: String,
+    pub sub_oscillator: Option<SubOscillatorMode>,
+    pub filter_enabled: bool,
+    pub filter_cutoff_hz: f32,
+    pub filter_resonance: f32,
+    pub supersaw_detune_cents: f32,
+    pub supersaw_mix_center: f32,
+}
+
+impl Preset {
+    /// Everything at the synth's startup settings.
+    pub fn init() -> Preset {
+        Preset {
+            name: "Init".to_string(),
+            voice: PatchVoice::WaveTable,
+            fm_preset: BUILTIN_FM_PRESETS[0].name.to_string(),
+            sub_oscillator: None,
+            filter_enabled: false,
+            filter_cutoff_hz: 1000.0,
+            filter_resonance: 0.0,
+            supersaw_detune_cents: 25.0,
+            supersaw_mix_center: 0.4,
+        }
+    }
+}
+
+/// The shared controls a [`Preset`] is written into.
+pub struct PatchControls {
+    pub filter_enabled: Arc<Mutex<bool>>,
+    pub filter_cutoff_hz: Arc<Mutex<f32>>,
+    pub filter_resonance: Arc<Mutex<f32>>,
+    pub sub_oscillator: Arc<Mutex<Option<SubOscillatorMode>>>,
+    pub fm_preset: Arc<Mutex<FmPreset>>,
+    pub supersaw_detune_cents: Arc<Mutex<f32>>,
+    pub supersaw_mix_center: Arc<Mutex<f32>>,
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    Io(std::io::Error),
+    Serialize(toml::ser::Error),
+    Parse { file: String, error: toml::de::Error },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Io(error) => write!(f, "patch file error: {error}"),
+            PatchError::Serialize(error) => write!(f, "could not encode patch: {error}"),
+            PatchError::Parse { file, error } => write!(f, "invalid patch {file}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<std::io::Error> for PatchError {
+    fn from(error: std::io::Error) -> Self {
+        PatchError::Io(error)
+    }
+}
+
+/// 128 patch slots, numbered like MIDI programs.
+pub struct PatchMemory {
+    patches: [Option<Preset>; PATCH_COUNT],
+    active_patch: usize,
+}
+
+impl Default for PatchMemory {
+    /// Slots 0-9 hold the built-in patches; the rest are empty.
+    fn default() -> Self {
+        let mut memory = PatchMemory::empty();
+        for (slot, preset) in builtin_patches().into_iter().enumerate() {
+            memory.patches[slot] = Some(preset);
+        }
+        memory
+    }
+}
+
+impl PatchMemory {
+    pub fn empty() -> PatchMemory {
+        PatchMemory {
+            patches: std::array::from_fn(|_| None),
+            active_patch: 0,
+        }
+    }
+
+    pub fn active_patch(&self) -> usize {
+        self.active_patch
+    }
+
+    pub fn get(&self, program: usize) -> Option<&Preset> {
+        self.patches.get(program)?.as_ref()
+    }
+
+    pub fn store(&mut self, program: usize, preset: Preset) {
+        if let Some(slot) = self.patches.get_mut(program) {
+            *slot = Some(preset);
+        }
+    }
+
+    /// Writes patch `program` into `controls` with [`apply_preset`] and makes it the
+    /// active patch. Returns `None` for an empty slot.
+    pub fn activate(&mut self, program: usize, controls: &PatchControls) -> Option<&Preset> {
+        let preset = self.patches.get(program)?.as_ref()?;
+        apply_preset(preset, controls);
+        self.active_patch = program;
+        Some(preset)
+    }
+
+    /// Writes each non-empty slot to `dir/patch_NNN.toml`, creating `dir` if needed.
+    pub fn save_to_directory(&self, dir: &Path) -> Result<(), PatchError> {
+        std::fs::create_dir_all(dir)?;
+        for (program, preset) in self.patches.iter().enumerate() {
+            if let Some(preset) = preset {
+                let contents = toml::to_string(preset).map_err(PatchError::Serialize)?;
+                std::fs::write(dir.join(format!("patch_{program:03}.toml")), contents)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads every `patch_NNN.toml` in `dir`; other files are ignored.
+    pub fn load_from_directory(dir: &Path) -> Result<PatchMemory, PatchError> {
+        let mut memory = PatchMemory::empty();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(file) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let program = file
+                .strip_prefix("patch_")
+                .and_then(|rest| rest.strip_suffix(".toml"))
+                .and_then(|number| number.parse::<usize>().ok());
+            let Some(program) = program.filter(|&program| program < PATCH_COUNT) else {
+                continue;
+            };
+
+            let contents = std::fs::read_to_string(&path)?;
+            let preset = toml::from_str(&contents).map_err(|error| PatchError::Parse {
+                file: file.to_string(),
+                error,
+            })?;
+            memory.patches[program] = Some(preset);
+        }
+        Ok(memory)
+    }
+}
+
+/// Writes `preset` into `controls`. Every control is locked before any is written, so
+/// the audio thread never plays a half-applied patch.
+pub fn apply_preset(preset: &Preset, controls: &PatchControls) {
+    let fm_preset = BUILTIN_FM_PRESETS
+        .iter()
+        .find(|fm| fm.name.eq_ignore_ascii_case(&preset.fm_preset))
+        .copied()
+        .unwrap_or(BUILTIN_FM_PRESETS[0]);
+
+    let locks = (
+        controls.filter_enabled.lock(),
+        controls.filter_cutoff_hz.lock(),
+        controls.filter_resonance.lock(),
+        controls.sub_oscillator.lock(),
+        controls.fm_preset.lock(),
+        controls.supersaw_detune_cents.lock(),
+        controls.supersaw_mix_center.lock(),
+    );
+    if let (Ok(mut enabled), Ok(mut cutoff), Ok(mut resonance), Ok(mut sub), Ok(mut fm), Ok(mut detune), Ok(mut mix)) =
+        locks
+    {
+        *enabled = preset.filter_enabled;
+        *cutoff = preset.filter_cutoff_hz;
+        *resonance = preset.filter_resonance;
+        *sub = preset.sub_oscillator;
+        *fm = fm_preset;
+        *detune = preset.supersaw_detune_cents;
+        *mix = preset.supersaw_mix_center;
+    }
+}
+
+/// Reads `controls` back into a preset, the inverse of [`apply_preset`]. The voice isn't
+/// held in a control, so the caller says which one is playing.
+pub fn capture_preset(name: &str, voice: PatchVoice, controls: &PatchControls) -> Preset {
+    let mut preset = Preset {
+        name: name.to_string(),
+        voice,
+        ..Preset::init()
+    };
+    if let Ok(enabled) = controls.filter_enabled.lock() {
+        preset.filter_enabled = *enabled;
+    }
+    if let Ok(cutoff) = controls.filter_cutoff_hz.lock() {
+        preset.filter_cutoff_hz = *cutoff;
+    }
+    if let Ok(resonance) = controls.filter_resonance.lock() {
+        preset.filter_resonance = *resonance;
+    }
+    if let Ok(sub) = controls.sub_oscillator.lock() {
+        preset.sub_oscillator = *sub;
+    }
+    if let Ok(fm) = controls.fm_preset.lock() {
+        preset.fm_preset = fm.name.to_string();
+    }
+    if let Ok(detune) = controls.supersaw_detune_cents.lock() {
+        preset.supersaw_detune_cents = *detune;
+    }
+    if let Ok(mix) = controls.supersaw_mix_center.lock() {
+        preset.supersaw_mix_center = *mix;
+    }
+    preset
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbSlot {
+    A,
+    B,
+}
+
+/// Two complete configurations to flip between while designing a sound. Whatever is
+/// playing belongs to the selected slot: edits made meanwhile are captured into it
+/// before switching away, so neither side loses its tweaks.
+#[derive(Clone, Debug)]
+pub struct AbComparison {
+    pub config_a: Preset,
+    pub config_b: Preset,
+    selected: AbSlot,
+}
+
+impl AbComparison {
+    /// Starts with both slots holding `current`, with A selected.
+    pub fn new(current: Preset) -> AbComparison {
+        AbComparison {
+            config_a: Preset {
+                name: "A".to_string(),
+                ..current.clone()
+            },
+            config_b: Preset {
+                name: "B".to_string(),
+                ..current
+            },
+            selected: AbSlot::A,
+        }
+    }
+
+    pub fn selected(&self) -> AbSlot {
+        self.selected
+    }
+
+    pub fn selected_config(&self) -> &Preset {
+        match self.selected {
+            AbSlot::A => &self.config_a,
+            AbSlot::B => &self.config_b,
+        }
+    }
+
+    fn capture(&mut self, voice: PatchVoice, controls: &PatchControls) {
+        let name = self.selected_config().name.clone();
+        let captured = capture_preset(&name, voice, controls);
+        match self.selected {
+            AbSlot::A => self.config_a = captured,
+            AbSlot::B => self.config_b = captured,
+        }
+    }
+
+    /// Keeps what is playing in the current slot, then writes `slot` into `controls`.
+    /// `voice` is the voice playing now; the new one is in the returned preset.
+    pub fn select(&mut self, slot: AbSlot, voice: PatchVoice, controls: &PatchControls) -> &Preset {
+        self.capture(voice, controls);
+        self.selected = slot;
+        apply_preset(self.selected_config(), controls);
+        self.selected_config()
+    }
+
+    /// Copies A over B, as a starting point for a variation.
+    pub fn copy_a_to_b(&mut self, voice: PatchVoice, controls: &PatchControls) -> &Preset {
+        self.capture(voice, controls);
+        self.config_b = Preset {
+            name: self.config_b.name.clone(),
+            ..self.config_a.clone()
+        };
+        apply_preset(self.selected_config(), controls);
+        self.selected_config()
+    }
+
+    /// Exchanges the two configurations, keeping the selected slot.
+    pub fn swap(&mut self, voice: PatchVoice, controls: &PatchControls) -> &Preset {
+        self.capture(voice, controls);
+        std::mem::swap(&mut self.config_a, &mut self.config_b);
+        std::mem::swap(&mut self.config_a.name, &mut self.config_b.name);
+        apply_preset(self.selected_config(), controls);
+        self.selected_config()
+    }
+}
+
+fn builtin_patches() -> Vec<Preset> {
+    let init = Preset::init();
+    let fm = |name: &str, fm_preset: &str| Preset {
+        name: name.to_string(),
+        voice: PatchVoice::Fm,
+        fm_preset: fm_preset.to_string(),
+        ..Preset::init()
+    };
+
+    vec![
+        init.clone(),
+        Preset {
+            name: "Sine Pad".to_string(),
+            sub_oscillator: Some(SubOscillatorMode { interval: SubInterval::OneOctaveDown, mix: 0.3 }),
+            filter_enabled: true,
+            filter_cutoff_hz: 1200.0,
+            ..init.clone()
+        },
+        fm("FM Bell", "Bell"),
+        fm("FM Electric Piano", "ElectricPiano"),
+        fm("FM Brass", "Brass"),
+        fm("FM Marimba", "Marimba"),
+        fm("FM Bass", "Bass"),
+        Preset {
+            name: "Super Saw Lead".to_string(),
+            voice: PatchVoice::SuperSaw,
+            supersaw_detune_cents: 30.0,
+            supersaw_mix_center: 0.3,
+            ..init.clone()
+        },
+        Preset {
+            name: "Sub Bass".to_string(),
+            sub_oscillator: Some(SubOscillatorMode { interval: SubInterval::TwoOctavesDown, mix: 0.6 }),
+            filter_enabled: true,
+            filter_cutoff_hz: 400.0,
+            ..init.clone()
+        },
+        Preset {
+            name: "Resonant Sine".to_string(),
+            filter_enabled: true,
+            filter_cutoff_hz: 800.0,
+            filter_resonance: 0.8,
+            ..init
+        },
+    ]
+}
+
+/// Lowest and highest cutoffs the bitfield's log-scale cutoff code can express.
+pub(crate) const BITFIELD_CUTOFF_RANGE: (f32, f32) = (20.0, 20000.0);
+
+/// Packs a patch into 64 bits, e.g. to share it as a 16-digit hex string. From the
+/// least significant bit:
+///
+/// | bits  | field                                         |
+/// |-------|-----------------------------------------------|
+/// | 0-2   | voice                                         |
+/// | 3-5   | FM preset, as an index into the built-ins     |
+/// | 6-7   | sub-oscillator interval, 0 = off              |
+/// | 8-11  | sub-oscillator mix, 0-15                      |
+/// | 12-14 | filter type, 0 = off, 1 = low-pass            |
+/// | 15-21 | filter cutoff, 20 Hz-20 kHz on a log scale    |
+/// | 22-27 | filter resonance, 0-63                        |
+/// | 28-34 | super saw detune, whole cents up to 127       |
+/// | 35-39 | super saw center mix, 0-31                    |
+///
+/// Bits 40-63 are reserved and written as zero. The name isn't stored, and the other
+/// values come back rounded to their field's resolution.
+pub fn preset_to_bitfield(preset: &Preset) -> u64 {
+    let voice = match preset.voice {
+        PatchVoice::WaveTable => 0,
+        PatchVoice::SuperSaw => 1,
+        PatchVoice::Fm => 2,
+    };
+    let fm_preset = BUILTIN_FM_PRESETS
+        .iter()
+        .position(|fm| fm.name.eq_ignore_ascii_case(&preset.fm_preset))
+        .unwrap_or(0) as u64;
+    let (sub_interval, sub_mix) = match preset.sub_oscillator {
+        None => (0, 0),
+        Some(mode) => {
+            let interval = match mode.interval {
+                SubInterval::OneOctaveDown => 1,
+                SubInterval::TwoOctavesDown => 2,
+                SubInterval::FifthDown => 3,
+            };
+            (interval, quantize(mode.mix, 1.0, 15))
+        }
+    };
+    let (low, high) = BITFIELD_CUTOFF_RANGE;
+    let cutoff_position = (preset.filter_cutoff_hz.clamp(low, high) / low).ln() / (high / low).ln();
+
+    let fields = [
+        (voice, 3),
+        (fm_preset, 3),
+        (sub_interval, 2),
+        (sub_mix, 4),
+        (preset.filter_enabled as u64, 3),
+        (quantize(cutoff_position, 1.0, 127), 7),
+        (quantize(preset.filter_resonance, 1.0, 63), 6),
+        (quantize(preset.supersaw_detune_cents, 127.0, 127), 7),
+        (quantize(preset.supersaw_mix_center, 1.0, 31), 5),
+    ];
+
+    let mut bits = 0;
+    let mut shift = 0;
+    for (value, width) in fields {
+        bits |= (value & ((1 << width) - 1)) << shift;
+        shift += width;
+    }
+    bits
+}
+
+/// Unpacks a patch written by [`preset_to_bitfield`]. Out-of-range codes fall back to
+/// the first choice for that field.
+pub fn preset_from_bitfield(bits: u64) -> Preset {
+    let mut shift = 0;
+    let mut field = |width: u32| {
+        let value = (bits >> shift) & ((1 << width) - 1);
+        shift += width;
+        value
+    };
+
+    let voice = match field(3) {
+        1 => PatchVoice::SuperSaw,
+        2 => PatchVoice::Fm,
+        _ => PatchVoice::WaveTable,
+    };
+    let fm_preset = BUILTIN_FM_PRESETS
+        .get(field(3) as usize)
+        .unwrap_or(&BUILTIN_FM_PRESETS[0])
+        .name
+        .to_string();
+    let sub_interval = match field(2) {
+        1 => Some(SubInterval::OneOctaveDown),
+        2 => Some(SubInterval::TwoOctavesDown),
+        3 => Some(SubInterval::FifthDown),
+        _ => None,
+    };
+    let sub_mix = field(4) as f32 / 15.0;
+    let filter_enabled = field(3) == 1;
+    let (low, high) = BITFIELD_CUTOFF_RANGE;
+    let filter_cutoff_hz = low * (high / low).powf(field(7) as f32 / 127.0);
+
+    Preset {
+        name: format!("Shared {bits:016x}"),
+        voice,
+        fm_preset,
+        sub_oscillator: sub_interval.map(|interval| SubOscillatorMode { interval, mix: sub_mix }),
+        filter_enabled,
+        filter_cutoff_hz,
+        filter_resonance: field(6) as f32 / 63.0,
+        supersaw_detune_cents: field(7) as f32,
+        supersaw_mix_center: field(5) as f32 / 31.0,
+    }
+}
+
+/// Maps `value` in `0.0..=max` onto the integer steps `0..=steps`.
+fn quantize(value: f32, max: f32, steps: u64) -> u64 {
+    ((value / max).clamp(0.0, 1.0) * steps as f32).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn any_preset() -> impl Strategy<Value = Preset> {
+        let sub_interval = prop_oneof![
+            Just(None),
+            Just(Some(SubInterval::OneOctaveDown)),
+            Just(Some(SubInterval::TwoOctavesDown)),
+            Just(Some(SubInterval::FifthDown)),
+        ];
+        (
+            prop_oneof![Just(PatchVoice::WaveTable), Just(PatchVoice::SuperSaw), Just(PatchVoice::Fm)],
+            0..BUILTIN_FM_PRESETS.len(),
+            (sub_interval, 0.0f32..=1.0),
+            any::<bool>(),
+            (1.0f32..30000.0, 0.0f32..=1.0),
+            (0.0f32..200.0, 0.0f32..=1.0),
+        )
+            .prop_map(|(voice, fm, (interval, mix), filter_enabled, (cutoff, resonance), (detune, center))| Preset {
+                name: "Any".to_string(),
+                voice,
+                fm_preset: BUILTIN_FM_PRESETS[fm].name.to_string(),
+                sub_oscillator: interval.map(|interval| SubOscillatorMode { interval, mix }),
+                filter_enabled,
+                filter_cutoff_hz: cutoff,
+                filter_resonance: resonance,
+                supersaw_detune_cents: detune,
+                supersaw_mix_center: center,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn bitfield_reencodes_to_itself(preset in any_preset()) {
+            let bits = preset_to_bitfield(&preset);
+            prop_assert_eq!(preset_to_bitfield(&preset_from_bitfield(bits)), bits);
+        }
+    }
+}
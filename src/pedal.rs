@@ -0,0 +1,132 @@
+use crate::voice::VoiceId;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use std::collections::{HashMap, HashSet};
+
+/// Turns the space bar into a damper pedal: held down it sustains, let go it
+/// releases everything it held.
+///
+/// It needs key release events, like [`KeyRepeatSuppressor`](crate::KeyRepeatSuppressor);
+/// without them the pedal would never come up. Outside pedal mode space is left to
+/// play its note.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SustainPedalSimulator {
+    pub pedal_mode: bool,
+    sustain_active: bool,
+}
+
+impl SustainPedalSimulator {
+    /// Switches pedal mode on or off. Switching off lifts a pedal left down, which
+    /// is returned as a change like [`handle`](Self::handle)'s.
+    pub fn toggle_pedal_mode(&mut self) -> Option<bool> {
+        self.pedal_mode = !self.pedal_mode;
+        if !self.pedal_mode && self.sustain_active {
+            self.sustain_active = false;
+            return Some(false);
+        }
+        None
+    }
+
+    pub fn sustain_active(&self) -> bool {
+        self.sustain_active
+    }
+
+    /// True for a space bar event pedal mode takes over, whether or not it changes
+    /// anything.
+    pub fn claims(&self, event: &KeyEvent) -> bool {
+        self.pedal_mode && event.code == KeyCode::Char(' ')
+    }
+
+    /// The new sustain state when `event` presses or lifts the pedal. Repeats and
+    /// other keys change nothing.
+    pub fn handle(&mut self, event: &KeyEvent) -> Option<bool> {
+        if !self.claims(event) {
+            return None;
+        }
+        let active = match event.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => true,
+            KeyEventKind::Release => false,
+        };
+        if active == self.sustain_active {
+            return None;
+        }
+        self.sustain_active = active;
+        Some(active)
+    }
+}
+
+/// Which voice each key is playing, and which voices the sustain pedal is holding
+/// after their keys came up, like a piano's damper pedal.
+///
+/// Each method returns the voices that should start their release; the caller ends
+/// them on the voice pool. Keys pressed with sustain on add voices as usual, and a
+/// voice whose key is pressed again stops being held by the pedal.
+#[derive(Clone, Debug, Default)]
+pub struct SustainController {
+    voices: HashMap<KeyCode, VoiceId>,
+    physically_held: HashSet<KeyCode>,
+    sustained_voices: HashSet<VoiceId>,
+    sustain_active: bool,
+}
+
+impl SustainController {
+    pub fn sustain_active(&self) -> bool {
+        self.sustain_active
+    }
+
+    pub fn is_held(&self, key: KeyCode) -> bool {
+        self.physically_held.contains(&key)
+    }
+
+    /// Whether any key that plays a voice is down.
+    pub fn keys_held(&self) -> bool {
+        !self.physically_held.is_empty()
+    }
+
+    /// Records `key` starting `voice`.
+    pub fn press(&mut self, key: KeyCode, voice: VoiceId) {
+        self.physically_held.insert(key);
+        self.voices.insert(key, voice);
+        self.sustained_voices.remove(&voice);
+    }
+
+    /// The voice to release as `key` comes up: none with sustain on, which holds it
+    /// instead, or while another key still plays it, as in legato mono.
+    pub fn release(&mut self, key: KeyCode) -> Option<VoiceId> {
+        self.physically_held.remove(&key);
+        let voice = self.voices.remove(&key)?;
+        if self.voices.values().any(|held| *held == voice) {
+            return None;
+        }
+        if self.sustain_active {
+            self.sustained_voices.insert(voice);
+            return None;
+        }
+        Some(voice)
+    }
+
+    /// Engages or lifts sustain. Lifting it returns every voice it was holding whose
+    /// key is no longer down.
+    pub fn set_sustain(&mut self, active: bool) -> Vec<VoiceId> {
+        self.sustain_active = active;
+        if active {
+            return Vec::new();
+        }
+        let held: HashSet<VoiceId> = self.voices.values().copied().collect();
+        let mut released: Vec<VoiceId> = self.sustained_voices.drain().filter(|voice| !held.contains(voice)).collect();
+        released.sort_unstable();
+        released
+    }
+
+    /// Forgets `voice`, e.g. once it's been stolen for another note.
+    pub fn forget_voice(&mut self, voice: VoiceId) {
+        self.voices.retain(|_, held| *held != voice);
+        self.sustained_voices.remove(&voice);
+    }
+
+    /// Forgets every voice and held key, leaving sustain as it is.
+    pub fn clear(&mut self) {
+        self.voices.clear();
+        self.physically_held.clear();
+        self.sustained_voices.clear();
+    }
+}
@@ -0,0 +1,83 @@
+/// Detections weaker than this are treated as noise or several pitches at once.
+pub const PITCH_CONFIDENCE_THRESHOLD: f32 = 0.8;
+
+const MIN_PITCH_HZ: f32 = 50.0;
+const MAX_PITCH_HZ: f32 = 1500.0;
+
+/// Estimates the fundamental of `buffer` with the normalized square difference
+/// function (McLeod's variant of normalized autocorrelation).
+///
+/// Returns `None` for silence, or when the strongest period's normalized correlation is
+/// under [`PITCH_CONFIDENCE_THRESHOLD`], which is what chords and noise look like.
+/// Consonant intervals share a period, so those report their common fundamental.
+pub fn detect_pitch_autocorrelation(buffer: &[f32], sample_rate: u32) -> Option<f32> {
+    let min_lag = (sample_rate as f32 / MAX_PITCH_HZ) as usize;
+    let max_lag = ((sample_rate as f32 / MIN_PITCH_HZ) as usize).min(buffer.len() / 2);
+    if min_lag < 1 || max_lag <= min_lag + 1 {
+        return None;
+    }
+
+    let energy: f32 = buffer.iter().map(|s| s * s).sum();
+    if energy < 1e-6 * buffer.len() as f32 {
+        return None;
+    }
+
+    let nsdf: Vec<f32> = (0..=max_lag + 1).map(|lag| normalized_difference(buffer, lag)).collect();
+
+    // Skip the lobe around lag 0, then take the first peak close to the best one so a
+    // strong second harmonic doesn't report the octave below
+    let start = nsdf.iter().position(|&v| v < 0.0)?.max(min_lag);
+    let peaks: Vec<usize> = (start.max(1)..=max_lag)
+        .filter(|&lag| nsdf[lag] > 0.0 && nsdf[lag] >= nsdf[lag - 1] && nsdf[lag] > nsdf[lag + 1])
+        .collect();
+    let best = peaks.iter().map(|&lag| nsdf[lag]).fold(0.0, f32::max);
+    let lag = *peaks.iter().find(|&&lag| nsdf[lag] >= 0.9 * best)?;
+
+    // A real period repeats; mixtures of unrelated pitches can line up once by chance
+    // but rarely twice
+    let confidence = nsdf[lag].min(normalized_difference(buffer, 2 * lag));
+    if confidence < PITCH_CONFIDENCE_THRESHOLD {
+        return None;
+    }
+
+    // Parabolic interpolation for a sub-sample period
+    let (a, b, c) = (nsdf[lag - 1], nsdf[lag], nsdf[lag + 1]);
+    let denominator = a - 2.0 * b + c;
+    let offset = if denominator != 0.0 { 0.5 * (a - c) / denominator } else { 0.0 };
+    Some(sample_rate as f32 / (lag as f32 + offset))
+}
+
+fn normalized_difference(buffer: &[f32], lag: usize) -> f32 {
+    if lag >= buffer.len() {
+        return 0.0;
+    }
+
+    let (mut correlation, mut power) = (0.0, 0.0);
+    for i in 0..buffer.len() - lag {
+        correlation += buffer[i] * buffer[i + lag];
+        power += buffer[i] * buffer[i] + buffer[i + lag] * buffer[i + lag];
+    }
+    if power > 0.0 { 2.0 * correlation / power } else { 0.0 }
+}
+
+/// Estimates the frequency of a clean periodic signal, such as an oscillator's own
+/// output, from its upward zero crossings.
+///
+/// The crossings are placed between samples by linear interpolation and the
+/// frequency is the number of whole cycles between the first and the last over the
+/// time they span. Returns `None` with fewer than two crossings. Harmonics strong
+/// enough to cross zero on their own read high, so use
+/// [`detect_pitch_autocorrelation`] for anything richer.
+pub fn estimate_frequency(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let mut crossings = samples
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+        .map(|(i, pair)| i as f32 + pair[0] / (pair[0] - pair[1]));
+    let first = crossings.next()?;
+    let (count, last) = crossings.fold((0, first), |(count, _), crossing| (count + 1, crossing));
+    if count == 0 {
+        return None;
+    }
+    Some(count as f32 * sample_rate as f32 / (last - first))
+}
@@ -0,0 +1,526 @@
+use crate::channel::{ChannelRouting, VoiceChannel};
+use crate::midicc::ChannelModeMessage;
+use crate::mixer::mix_voices_simd;
+use crate::oscillator::{OscillatorState, SharedControls, WaveTableOscillator};
+use crate::pan::{constant_power_gains, AutoPanMode};
+use crate::tuning::TuningSystem;
+use crate::voice::{EnvelopePhase, EnvelopeState, PolyphonyMode, VoicePool};
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use synth_core::{Lfo, LfoPolarity};
+
+const ATTACK_SECS: f32 = 0.005;
+const RELEASE_SECS: f32 = 0.2;
+/// Samples rendered per pool lock when played as a [`Source`].
+const BLOCK_SIZE: usize = 128;
+const VIBRATO_RATE_HZ: f32 = 5.5;
+/// How long a stolen voice's old note takes to fade out, about 3 ms at 44.1 kHz.
+const STEAL_FADE_SAMPLES: usize = 128;
+/// Fade-out tails kept ready per voice. Rendered in blocks of at least
+/// [`STEAL_FADE_SAMPLES`] a voice can only be stolen once per fade, so two leaves room
+/// for shorter blocks before a tail has to be cut short.
+const FADE_TAILS_PER_VOICE: usize = 2;
+/// How quickly the mix gain follows the number of sounding voices.
+const MIX_GAIN_SECS: f32 = 0.01;
+
+/// A stolen note fading out on a spare oscillator, which is reused once it's silent.
+struct FadeTail {
+    oscillator: WaveTableOscillator,
+    frequency: f32,
+    level: f32,
+    channel: usize,
+    pan: f32,
+    active: bool,
+}
+
+impl FadeTail {
+    fn new(prototype: &WaveTableOscillator) -> FadeTail {
+        FadeTail {
+            oscillator: prototype.clone(),
+            frequency: 0.0,
+            level: 0.0,
+            channel: 0,
+            pan: 0.0,
+            active: false,
+        }
+    }
+
+    /// Samples left before it's silent, zero when free.
+    fn remaining(&self) -> usize {
+        match self.oscillator.state() {
+            _ if !self.active => 0,
+            OscillatorState::FadingOut { remaining, .. } => remaining,
+            _ => STEAL_FADE_SAMPLES,
+        }
+    }
+}
+
+/// How a new voice's phase lines up with a voice already playing, by voice index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhaseReference {
+    /// The same phase, so a doubled note reinforces instead of partly cancelling
+    /// at whatever offset it happened to start.
+    InPhase(usize),
+    /// Half a cycle on, so at the same pitch the two cancel: a note that only
+    /// sounds through what differs between them, such as detune or vibrato.
+    Opposite(usize),
+}
+
+/// Mixes a fixed number of wavetable voices, each gated by a short attack/release ramp.
+///
+/// Notes are started and stopped through the shared [`VoicePool`] from
+/// [`get_voice_pool_control`](PolyphonicEngine::get_voice_pool_control). The pool is
+/// locked once per rendered block, so as a [`Source`] the engine picks up note changes
+/// every 128 samples.
+///
+/// Every voice has its own vibrato, silent until its depth control is raised, for
+/// example by [`PolyAftertouch`](crate::PolyAftertouch).
+///
+/// With [`VoiceChannel`]s set, voices are grouped onto them by the
+/// [`ChannelRouting`] control and the engine plays interleaved stereo. Without
+/// channels, an auto-pan mode other than center also turns the output to stereo,
+/// with each voice at its slot's pan; channels place voices by their own pan instead.
+///
+/// When the pool steals a sounding voice, its old note is split off onto a copy of
+/// the oscillator that fades out over 128 samples, and the new note attacks from
+/// silence, rather than the pitch jumping at full level. The copies come from a pool
+/// of spare oscillators built with the voices, so a steal doesn't allocate; if every
+/// spare is busy, the one nearest silence is cut short.
+///
+/// Each voice plays at the oscillator's own 0.3 and the mix is scaled by one over the
+/// square root of the voices sounding, weighted by their envelope levels, so a chord
+/// is louder than one note but eight voices don't clip. The gain glides over about
+/// 10 ms so notes starting and stopping don't step the others' level.
+pub struct PolyphonicEngine {
+    sample_rate: u32,
+    prototype: WaveTableOscillator,
+    voices: Vec<WaveTableOscillator>,
+    /// The frequency each voice was last played at, vibrato included.
+    frequencies: Vec<f32>,
+    /// The note each voice last played, by the pool's `started_at`.
+    voice_notes: Vec<u64>,
+    /// Spare oscillators for stolen notes to fade out on, [`FADE_TAILS_PER_VOICE`] a voice.
+    fading: Vec<FadeTail>,
+    vibratos: Vec<Lfo>,
+    lfo_depth_controls: Vec<Arc<AtomicU32>>,
+    pool: Arc<Mutex<VoicePool>>,
+    envelope: Arc<EnvelopeState>,
+    channels: Vec<VoiceChannel>,
+    channel_inputs: Vec<f32>,
+    routing: Arc<Mutex<ChannelRouting>>,
+    channel_mode: Option<Arc<Mutex<Option<ChannelModeMessage>>>>,
+    /// Voices are panned, so the output is stereo even without channels.
+    auto_panned: bool,
+    tuning: TuningSystem,
+    attack_step: f32,
+    release_step: f32,
+    mix_gain: f32,
+    mix_gain_coeff: f32,
+    block: Vec<f32>,
+    block_pos: usize,
+}
+
+impl PolyphonicEngine {
+    pub fn new(sample_rate: u32, wave_table: Vec<f32>, voice_count: usize) -> PolyphonicEngine {
+        PolyphonicEngine::from_prototype(WaveTableOscillator::new(sample_rate, wave_table), voice_count)
+    }
+
+    /// Builds every voice as a clone of `prototype`, so they share its wave table and
+    /// settings but each plays its own note.
+    pub fn from_prototype(prototype: WaveTableOscillator, voice_count: usize) -> PolyphonicEngine {
+        let sample_rate = prototype.sample_rate();
+        let voices: Vec<WaveTableOscillator> = (0..voice_count).map(|_| prototype.clone()).collect();
+        let vibrato = Lfo::new(sample_rate, VIBRATO_RATE_HZ, 0.0, LfoPolarity::Bipolar);
+        let fading = (0..voice_count * FADE_TAILS_PER_VOICE).map(|_| FadeTail::new(&prototype)).collect();
+
+        PolyphonicEngine {
+            sample_rate,
+            prototype,
+            voice_notes: vec![0; voices.len()],
+            frequencies: vec![0.0; voices.len()],
+            voices,
+            fading,
+            vibratos: vec![vibrato; voice_count],
+            lfo_depth_controls: (0..voice_count).map(|_| Arc::new(AtomicU32::new(0.0_f32.to_bits()))).collect(),
+            pool: Arc::new(Mutex::new(VoicePool::new(voice_count))),
+            envelope: Arc::new(EnvelopeState::default()),
+            channels: Vec::new(),
+            channel_inputs: Vec::new(),
+            routing: Arc::new(Mutex::new(ChannelRouting::default())),
+            channel_mode: None,
+            auto_panned: false,
+            tuning: TuningSystem::default(),
+            attack_step: 1.0 / (ATTACK_SECS * sample_rate as f32),
+            release_step: 1.0 / (RELEASE_SECS * sample_rate as f32),
+            mix_gain: 1.0,
+            mix_gain_coeff: (-1.0 / (MIX_GAIN_SECS * sample_rate as f32)).exp(),
+            block: vec![0.0; BLOCK_SIZE],
+            block_pos: BLOCK_SIZE,
+        }
+    }
+
+    pub fn get_voice_pool_control(&self) -> Arc<Mutex<VoicePool>> {
+        self.pool.clone()
+    }
+
+    /// The envelope phase of the newest sounding note, or `Idle` when nothing plays,
+    /// for effects such as [`AttackBypassReverb`](crate::AttackBypassReverb) that
+    /// follow the playing.
+    pub fn get_envelope_state(&self) -> Arc<EnvelopeState> {
+        self.envelope.clone()
+    }
+
+    /// Each voice's vibrato depth in semitones, as `f32` bits. Voices the pool grows
+    /// by later get controls of their own that aren't in this list.
+    pub fn get_lfo_depth_controls(&self) -> Vec<Arc<AtomicU32>> {
+        self.lfo_depth_controls.clone()
+    }
+
+    /// Sets the channels voices are grouped onto. Call before playing: with any
+    /// channels the engine's output turns to interleaved stereo.
+    pub fn set_channels(&mut self, channels: Vec<VoiceChannel>) {
+        self.channel_inputs = vec![0.0; channels.len()];
+        self.channels = channels;
+    }
+
+    pub fn get_channel_routing_control(&self) -> Arc<Mutex<ChannelRouting>> {
+        self.routing.clone()
+    }
+
+    /// Plays `voice_idx` through `channel` from now on, unless a split note is set.
+    pub fn assign_voice_to_channel(&self, voice_idx: usize, channel: usize) {
+        if let Ok(mut routing) = self.routing.lock() {
+            if routing.voice_channels.len() <= voice_idx {
+                routing.voice_channels.resize(voice_idx + 1, 0);
+            }
+            routing.voice_channels[voice_idx] = channel;
+        }
+    }
+
+    /// Starts `frequency` on a voice from the pool, with its phase lined up to
+    /// `phase_reference` if given. Returns the voice, or `None` for an empty pool.
+    pub fn allocate_voice(&mut self, frequency: f32, phase_reference: Option<PhaseReference>) -> Option<usize> {
+        let voice = self.pool.lock().ok()?.note_on(frequency)?;
+        self.grow_voices(voice + 1);
+        let (reference, offset) = match phase_reference {
+            Some(PhaseReference::InPhase(reference)) => (reference, 0.0),
+            Some(PhaseReference::Opposite(reference)) => (reference, 0.5),
+            None => return Some(voice),
+        };
+        if let Some(phase) = self.voices.get(reference).map(WaveTableOscillator::phase) {
+            self.voices[voice].reset_phase_to(phase + offset);
+        }
+        Some(voice)
+    }
+
+    /// Releases every note, for MIDI All Notes Off.
+    pub fn panic_all_notes_off(&self) {
+        if let Ok(mut pool) = self.pool.lock() {
+            pool.all_notes_off();
+        }
+    }
+
+    /// Cuts every voice and clears the channels' effects, for MIDI All Sound Off.
+    pub fn all_sound_off(&mut self) {
+        if let Ok(mut pool) = self.pool.lock() {
+            pool.all_sound_off();
+        }
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+        self.channel_inputs.fill(0.0);
+    }
+
+    /// Acts on panic messages left in `control`, such as
+    /// [`MidiCcMapper::get_channel_mode_control`](crate::MidiCcMapper::get_channel_mode_control),
+    /// once per rendered block.
+    pub fn set_channel_mode_control(&mut self, control: Arc<Mutex<Option<ChannelModeMessage>>>) {
+        self.channel_mode = Some(control);
+    }
+
+    /// Fills `output` with consecutive mixed samples, or left and right pairs when
+    /// stereo.
+    ///
+    /// The pool, the routing and the controls the voices share with the prototype are
+    /// locked once per block, and the voices then play without taking any lock until a
+    /// note is stolen: eight voices in 128-sample blocks run about 1.5x as fast as
+    /// [`get_sample`](Self::get_sample) in `benches/poly_render.rs`.
+    pub fn render_block(&mut self, output: &mut [f32]) {
+        let message = self.channel_mode.as_ref().and_then(|control| control.lock().ok()?.take());
+        match message {
+            Some(ChannelModeMessage::AllSoundOff) => self.all_sound_off(),
+            Some(ChannelModeMessage::AllNotesOff) => self.panic_all_notes_off(),
+            None => {}
+        }
+
+        let (pool, routing) = (self.pool.clone(), self.routing.clone());
+        let (Ok(mut pool), Ok(routing)) = (pool.lock(), routing.lock()) else {
+            output.fill(0.0);
+            return;
+        };
+
+        let controls = self.prototype.shared_controls();
+        if !self.is_stereo() {
+            for sample in output.iter_mut() {
+                *sample = self.mix_sample(&mut pool, &routing, &controls)[0];
+            }
+        } else {
+            for frame in output.chunks_mut(2) {
+                let mixed = self.mix_sample(&mut pool, &routing, &controls);
+                frame.copy_from_slice(&mixed[..frame.len()]);
+            }
+        }
+    }
+
+    /// The next mono sample; when stereo, the left one of a frame.
+    pub fn get_sample(&mut self) -> f32 {
+        let mut sample = [0.0];
+        self.render_block(&mut sample);
+        sample[0]
+    }
+
+    /// Sets how the pool pans voices. Call before playing: any mode but center turns
+    /// the output to interleaved stereo.
+    pub fn set_auto_pan_mode(&mut self, mode: AutoPanMode) {
+        if let Ok(mut pool) = self.pool.lock() {
+            pool.set_auto_pan_mode(mode);
+        }
+        self.auto_panned = mode != AutoPanMode::Center;
+    }
+
+    fn is_stereo(&self) -> bool {
+        self.auto_panned || !self.channels.is_empty()
+    }
+
+    /// Reconfigures the voice pool; see [`VoicePool::set_polyphony_mode`].
+    pub fn set_polyphony_mode(&mut self, mode: PolyphonyMode) {
+        if let Ok(mut pool) = self.pool.lock() {
+            pool.set_polyphony_mode(mode);
+        }
+    }
+
+    fn grow_voices(&mut self, count: usize) {
+        while self.voices.len() < count {
+            let voice = self.prototype.clone();
+            self.voices.push(voice);
+            self.voice_notes.push(0);
+            self.frequencies.push(0.0);
+            self.vibratos.push(Lfo::new(self.sample_rate, VIBRATO_RATE_HZ, 0.0, LfoPolarity::Bipolar));
+            self.lfo_depth_controls.push(Arc::new(AtomicU32::new(0.0_f32.to_bits())));
+            for _ in 0..FADE_TAILS_PER_VOICE {
+                self.fading.push(FadeTail::new(&self.prototype));
+            }
+        }
+    }
+
+    /// One mono sample, repeated, or one stereo frame with channels set or voices panned.
+    fn mix_sample(&mut self, pool: &mut VoicePool, routing: &ChannelRouting, controls: &SharedControls) -> [f32; 2] {
+        // The pool can grow through its control, so add oscillators to match
+        self.grow_voices(pool.slots().len());
+
+        // Voices are mixed eight at a time, once per side when panned
+        let (mut sum, mut right_sum) = (0.0, 0.0);
+        let mut samples = [0.0; 8];
+        let mut gains = [0.0; 8];
+        let mut right_gains = [0.0; 8];
+        let mut lane = 0;
+        let mut sounding = 0.0;
+        let mut newest: Option<(u64, EnvelopePhase)> = None;
+        for (index, (slot, (voice, frequency))) in pool
+            .slots_mut()
+            .iter_mut()
+            .zip(self.voices.iter_mut().zip(self.frequencies.iter_mut()))
+            .enumerate()
+        {
+            let vibrato = &mut self.vibratos[index];
+            vibrato.depth = f32::from_bits(self.lfo_depth_controls[index].load(Ordering::Relaxed));
+
+            match slot.phase {
+                // The pool's voices go straight from attack to sustain
+                EnvelopePhase::Idle | EnvelopePhase::Decay | EnvelopePhase::Sustain => {}
+                EnvelopePhase::Attack => {
+                    slot.level = (slot.level + self.attack_step).min(1.0);
+                    if slot.level >= 1.0 {
+                        slot.phase = EnvelopePhase::Sustain;
+                    }
+                }
+                EnvelopePhase::Release => {
+                    slot.level = (slot.level - self.release_step).max(0.0);
+                    if slot.level <= 0.0 {
+                        slot.phase = EnvelopePhase::Idle;
+                    }
+                }
+            }
+
+            let channel = |frequency: f32| match routing.split_note {
+                Some(split_note) => (self.tuning.nearest_note(frequency) >= split_note) as usize,
+                None => routing.voice_channels.get(index).copied().unwrap_or(0),
+            };
+            if slot.started_at != self.voice_notes[index] {
+                self.voice_notes[index] = slot.started_at;
+                // The copy keeps the old note's frequency, phase and level, and the
+                // voice itself starts over
+                if slot.level > 0.0 {
+                    // A free tail if there is one, otherwise the one closest to silence
+                    if let Some(tail) = self.fading.iter_mut().min_by_key(|tail| tail.remaining()) {
+                        tail.oscillator.clone_from(voice);
+                        tail.frequency = *frequency;
+                        WaveTableOscillator::fade_out(&mut tail.oscillator, STEAL_FADE_SAMPLES);
+                        tail.channel = channel(tail.oscillator.current_frequency_hz());
+                        tail.level = slot.level;
+                        tail.pan = slot.pan;
+                        tail.active = true;
+                    }
+                    voice.reset_phase();
+                    voice.set_frequency_direct(slot.frequency);
+                    slot.level = 0.0;
+                }
+            }
+
+            // Idle voices run at 0 Hz so the next note starts without a glide
+            *frequency = if slot.is_idle() {
+                0.0
+            } else {
+                slot.frequency * 2.0_f32.powf(vibrato.modulate(0.0) / 12.0)
+            };
+            sounding += slot.level;
+            if !slot.is_idle() && newest.is_none_or(|(started_at, _)| slot.started_at >= started_at) {
+                newest = Some((slot.started_at, slot.phase));
+            }
+            let sample = voice.get_sample_with(Some(*frequency), controls);
+            if !self.channel_inputs.is_empty() {
+                let last_channel = self.channel_inputs.len() - 1;
+                self.channel_inputs[channel(slot.frequency).min(last_channel)] += sample * slot.level;
+                continue;
+            }
+            let (left_gain, right_gain) = if self.auto_panned { constant_power_gains(slot.pan) } else { (1.0, 1.0) };
+            samples[lane] = sample;
+            gains[lane] = slot.level * left_gain;
+            right_gains[lane] = slot.level * right_gain;
+            lane += 1;
+            if lane == samples.len() {
+                sum += mix_voices_simd(&samples, &gains);
+                if self.auto_panned {
+                    right_sum += mix_voices_simd(&samples, &right_gains);
+                }
+                lane = 0;
+            }
+        }
+
+        if lane > 0 {
+            gains[lane..].fill(0.0);
+            right_gains[lane..].fill(0.0);
+            sum += mix_voices_simd(&samples, &gains);
+            if self.auto_panned {
+                right_sum += mix_voices_simd(&samples, &right_gains);
+            }
+        }
+        for tail in self.fading.iter_mut().filter(|tail| tail.active) {
+            sounding += tail.level;
+            let sample = tail.oscillator.get_sample_with(Some(tail.frequency), controls) * tail.level;
+            match self.channel_inputs.len().checked_sub(1) {
+                Some(last_channel) => self.channel_inputs[tail.channel.min(last_channel)] += sample,
+                None if self.auto_panned => {
+                    let (left_gain, right_gain) = constant_power_gains(tail.pan);
+                    sum += sample * left_gain;
+                    right_sum += sample * right_gain;
+                }
+                None => sum += sample,
+            }
+            tail.active = !tail.oscillator.is_faded_out();
+        }
+        self.envelope.store(newest.map_or(EnvelopePhase::Idle, |(_, phase)| phase));
+        let target_gain = 1.0 / f32::max(sounding, 1.0).sqrt();
+        self.mix_gain = target_gain + (self.mix_gain - target_gain) * self.mix_gain_coeff;
+        let gain = self.mix_gain;
+
+        if self.channels.is_empty() {
+            return if self.auto_panned { [sum * gain, right_sum * gain] } else { [sum * gain, sum * gain] };
+        }
+        let mut frame = [0.0; 2];
+        for (channel, input) in self.channels.iter_mut().zip(self.channel_inputs.iter_mut()) {
+            let (left, right) = channel.process(std::mem::take(input) * gain);
+            frame[0] += left;
+            frame[1] += right;
+        }
+        frame
+    }
+}
+
+impl Source for PolyphonicEngine {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        if self.is_stereo() {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for PolyphonicEngine {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.block_pos == self.block.len() {
+            let mut block = std::mem::take(&mut self.block);
+            self.render_block(&mut block);
+            self.block = block;
+            self.block_pos = 0;
+        }
+
+        let sample = self.block[self.block_pos];
+        self.block_pos += 1;
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave::{generate_wave_table, WaveShape};
+
+    fn active_tails(engine: &PolyphonicEngine) -> usize {
+        engine.fading.iter().filter(|tail| tail.active).count()
+    }
+
+    #[test]
+    fn steals_fade_out_on_preallocated_tails() {
+        let mut engine = PolyphonicEngine::new(44100, generate_wave_table(WaveShape::Sine, 2048), 1);
+        assert_eq!(engine.fading.len(), FADE_TAILS_PER_VOICE);
+        let mut block = [0.0; 256];
+        engine.allocate_voice(440.0, None);
+        engine.render_block(&mut block);
+
+        engine.allocate_voice(660.0, None);
+        engine.render_block(&mut block[..STEAL_FADE_SAMPLES / 2]);
+        assert_eq!(active_tails(&engine), 1);
+        engine.render_block(&mut block);
+        assert_eq!(active_tails(&engine), 0);
+    }
+
+    #[test]
+    fn steals_faster_than_the_fade_reuse_the_nearest_silent_tail() {
+        let mut engine = PolyphonicEngine::new(44100, generate_wave_table(WaveShape::Sine, 2048), 1);
+        let mut block = [0.0; 16];
+        for i in 0..20 {
+            engine.allocate_voice(220.0 + 10.0 * i as f32, None);
+            engine.render_block(&mut block);
+        }
+        assert_eq!(engine.fading.len(), FADE_TAILS_PER_VOICE);
+        assert_eq!(active_tails(&engine), FADE_TAILS_PER_VOICE);
+        assert!(block.iter().all(|sample| sample.is_finite() && sample.abs() <= 1.0));
+    }
+}
@@ -0,0 +1,205 @@
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Body modes of a classical guitar as `(frequency_hz, q, gain_db)`: the Helmholtz
+/// air mode near 100 Hz, the coupled top-plate modes around 200 Hz, then the higher
+/// plate and cross-dipole modes. Frequencies follow the modal measurements reported
+/// by Caldersmith and by Richardson; Q and gain are rounded to give a similar
+/// response, and real instruments vary by 10% or so.
+const GUITAR_BODY_MODES: [(f32, f32, f32); 12] = [
+    (98.0, 12.0, 9.0),
+    (204.0, 18.0, 8.0),
+    (226.0, 20.0, 6.0),
+    (390.0, 22.0, 5.0),
+    (460.0, 24.0, 5.0),
+    (550.0, 24.0, 4.0),
+    (660.0, 26.0, 3.5),
+    (780.0, 28.0, 3.5),
+    (980.0, 30.0, 3.0),
+    (1250.0, 30.0, 2.5),
+    (1750.0, 28.0, 2.0),
+    (2600.0, 24.0, 2.0),
+];
+
+/// Upright piano soundboard modes, from the densely spaced low plate modes Conklin
+/// measured up to the broad high-frequency ones. Shaped the same way as the guitar.
+const PIANO_SOUNDBOARD_MODES: [(f32, f32, f32); 12] = [
+    (48.0, 8.0, 5.0),
+    (112.0, 10.0, 6.0),
+    (175.0, 12.0, 5.0),
+    (245.0, 12.0, 5.0),
+    (340.0, 14.0, 4.0),
+    (460.0, 14.0, 4.0),
+    (620.0, 14.0, 3.5),
+    (810.0, 12.0, 3.0),
+    (1100.0, 10.0, 3.0),
+    (1500.0, 8.0, 2.5),
+    (2100.0, 6.0, 2.0),
+    (3000.0, 4.0, 2.0),
+];
+
+/// A peaking EQ biquad that boosts by `gain` dB around `freq_hz`, with bandwidth set
+/// by `q`, and leaves the rest of the spectrum alone.
+///
+/// Coefficients are from the RBJ audio EQ cookbook; `z1` and `z2` are the transposed
+/// direct form II state.
+#[derive(Clone, Debug)]
+pub struct BiquadResonator {
+    sample_rate: u32,
+    freq_hz: f32,
+    q: f32,
+    gain: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadResonator {
+    pub fn new(sample_rate: u32, freq_hz: f32, q: f32, gain: f32) -> BiquadResonator {
+        let mut resonator = BiquadResonator {
+            sample_rate,
+            freq_hz,
+            q,
+            gain,
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        };
+        resonator.set(freq_hz, q, gain);
+        resonator
+    }
+
+    pub fn freq_hz(&self) -> f32 {
+        self.freq_hz
+    }
+
+    pub fn q(&self) -> f32 {
+        self.q
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Retunes the resonator, keeping its state so the change doesn't click.
+    pub fn set(&mut self, freq_hz: f32, q: f32, gain: f32) {
+        self.freq_hz = freq_hz.clamp(1.0, 0.45 * self.sample_rate as f32);
+        self.q = q.max(0.1);
+        self.gain = gain;
+
+        let a = 10.0_f32.powf(self.gain / 40.0);
+        let w0 = 2.0 * PI * self.freq_hz / self.sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * self.q);
+        let a0 = 1.0 + alpha / a;
+        self.b0 = (1.0 + alpha * a) / a0;
+        self.b1 = -2.0 * w0.cos() / a0;
+        self.b2 = (1.0 - alpha * a) / a0;
+        self.a1 = self.b1;
+        self.a2 = (1.0 - alpha / a) / a0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// Resonators in series, colouring a sound with the modes of an instrument body.
+#[derive(Clone, Debug, Default)]
+pub struct ResonatorBank {
+    pub resonators: Vec<BiquadResonator>,
+}
+
+impl ResonatorBank {
+    pub fn guitar_body(sample_rate: u32) -> ResonatorBank {
+        ResonatorBank::from_modes(sample_rate, &GUITAR_BODY_MODES)
+    }
+
+    pub fn piano(sample_rate: u32) -> ResonatorBank {
+        ResonatorBank::from_modes(sample_rate, &PIANO_SOUNDBOARD_MODES)
+    }
+
+    fn from_modes(sample_rate: u32, modes: &[(f32, f32, f32)]) -> ResonatorBank {
+        ResonatorBank {
+            resonators: modes
+                .iter()
+                .map(|&(freq_hz, q, gain)| BiquadResonator::new(sample_rate, freq_hz, q, gain))
+                .collect(),
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.resonators.iter_mut().fold(input, |sample, resonator| resonator.process(sample))
+    }
+}
+
+/// Runs a source through a [`ResonatorBank`] that can be swapped or edited while
+/// playing, or bypassed.
+pub struct ResonatorSource<S: Source<Item = f32>> {
+    source: S,
+    bank: Arc<Mutex<ResonatorBank>>,
+    enabled: Arc<Mutex<bool>>,
+}
+
+impl<S: Source<Item = f32>> ResonatorSource<S> {
+    pub fn new(source: S, bank: ResonatorBank) -> ResonatorSource<S> {
+        ResonatorSource {
+            source,
+            bank: Arc::new(Mutex::new(bank)),
+            enabled: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub fn get_bank_control(&self) -> Arc<Mutex<ResonatorBank>> {
+        self.bank.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ResonatorSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ResonatorSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            return Some(input);
+        }
+
+        match self.bank.lock() {
+            Ok(mut bank) => Some(bank.process(input)),
+            Err(_) => Some(input),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::delay::FeedbackDelay;
+use crate::ringbuf::RingBuffer;
+use crate::voice::{EnvelopePhase, EnvelopeState};
+use rodio::Source;
+use std::sync::Arc;
+
+/// Comb and allpass lengths in samples at 44.1 kHz, Schroeder's mutually prime
+/// choices so the combs' echoes don't pile up on the same samples.
+const COMB_SAMPLES: [usize; 4] = [1557, 1617, 1491, 1422];
+const ALLPASS_SAMPLES: [usize; 2] = [225, 556];
+const ALLPASS_GAIN: f32 = 0.5;
+/// How fast the wet signal drops out when a new attack starts, so the cut doesn't
+/// click.
+const WET_DROP_SECS: f32 = 0.005;
+
+struct Allpass {
+    buf: RingBuffer<f32>,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Allpass {
+        Allpass {
+            buf: RingBuffer::new(len),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = *self.buf.read_at(self.buf.len());
+        let output = delayed - ALLPASS_GAIN * input;
+        self.buf.push(input + ALLPASS_GAIN * delayed);
+        output
+    }
+}
+
+/// Schroeder reverb: four parallel feedback combs into two series allpasses.
+///
+/// `room_size` (0.0-1.0) sets the combs' feedback and so the tail's length. `wet` is
+/// the mix [`AttackBypassReverb`] fades towards; [`process`](Self::process) returns
+/// only the reverberated signal.
+pub struct Reverb {
+    pub wet: f32,
+    combs: Vec<FeedbackDelay>,
+    allpasses: Vec<Allpass>,
+    feedback: f32,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: u32, room_size: f32, wet: f32) -> Reverb {
+        let scale = |samples: usize| (samples as f64 * sample_rate as f64 / 44_100.0).round() as usize;
+        let feedback = 0.7 + 0.28 * room_size.clamp(0.0, 1.0);
+        Reverb {
+            wet: wet.clamp(0.0, 1.0),
+            combs: COMB_SAMPLES.iter().map(|&n| FeedbackDelay::new(scale(n), feedback)).collect(),
+            allpasses: ALLPASS_SAMPLES.iter().map(|&n| Allpass::new(scale(n))).collect(),
+            feedback,
+        }
+    }
+
+    /// Cuts the tail off.
+    pub fn clear(&mut self) {
+        for comb in &mut self.combs {
+            comb.clear();
+        }
+        for allpass in &mut self.allpasses {
+            allpass.buf.clear();
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // A comb lifts broadband input by 1 / sqrt(1 - feedback^2) in RMS, and the
+        // combs' outputs add as uncorrelated signals; undo both
+        let drive = input * (1.0 - self.feedback * self.feedback).sqrt() / (self.combs.len() as f32).sqrt();
+        let mut output: f32 = self.combs.iter_mut().map(|comb| comb.process(drive)).sum();
+        for allpass in &mut self.allpasses {
+            output = allpass.process(output);
+        }
+        output
+    }
+}
+
+/// Reverb that leaves a note's attack dry for punch and lets the space in as it
+/// decays.
+///
+/// With `dry_in_attack` set, the wet mix is 0.0 while `envelope` reads `Attack`. Once
+/// it moves on to `Sustain`, `Release` or `Idle`, the mix fades up to the reverb's
+/// `wet` over `dry_to_wet_transition_ms`. The reverb itself keeps running through
+/// the attack, so the tail is already built when it fades in.
+pub struct AttackBypassReverb<S: Source<Item = f32>> {
+    source: S,
+    pub reverb: Reverb,
+    pub envelope: Arc<EnvelopeState>,
+    pub dry_in_attack: bool,
+    pub dry_to_wet_transition_ms: f32,
+    mix: f32,
+}
+
+impl<S: Source<Item = f32>> AttackBypassReverb<S> {
+    pub fn new(source: S, reverb: Reverb, envelope: Arc<EnvelopeState>) -> AttackBypassReverb<S> {
+        AttackBypassReverb {
+            source,
+            reverb,
+            envelope,
+            dry_in_attack: true,
+            dry_to_wet_transition_ms: 100.0,
+            mix: 0.0,
+        }
+    }
+
+    fn step_mix(&mut self) {
+        let sample_rate = self.source.sample_rate() as f32;
+        if self.dry_in_attack && self.envelope.load() == EnvelopePhase::Attack {
+            self.mix = (self.mix - 1.0 / (WET_DROP_SECS * sample_rate)).max(0.0);
+        } else {
+            let fade_samples = (self.dry_to_wet_transition_ms / 1000.0 * sample_rate).max(1.0);
+            self.mix = (self.mix + self.reverb.wet / fade_samples).min(self.reverb.wet);
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for AttackBypassReverb<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for AttackBypassReverb<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        let wet = self.reverb.process(input);
+        self.step_mix();
+        Some(input * (1.0 - self.mix) + wet * self.mix)
+    }
+}
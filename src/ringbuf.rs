@@ -0,0 +1,90 @@
+/// A fixed-capacity history of the last values pushed, read back by how long ago
+/// they arrived. The delay lines and FFT input windows are built on it.
+#[derive(Clone, Debug)]
+pub struct RingBuffer<T: Default + Clone> {
+    buf: Vec<T>,
+    write_pos: usize,
+}
+
+impl<T: Default + Clone> RingBuffer<T> {
+    /// A buffer holding `capacity` values, at least one, all starting at the default.
+    pub fn new(capacity: usize) -> RingBuffer<T> {
+        RingBuffer {
+            buf: vec![T::default(); capacity.max(1)],
+            write_pos: 0,
+        }
+    }
+
+    /// Stores `value`, overwriting the oldest one.
+    pub fn push(&mut self, value: T) {
+        self.buf[self.write_pos] = value;
+        self.write_pos = (self.write_pos + 1) % self.buf.len();
+    }
+
+    /// The value pushed `offset` pushes ago: 1 is the newest and `len()` the oldest,
+    /// the next to be overwritten. Offsets wrap around the capacity.
+    pub fn read_at(&self, offset: usize) -> &T {
+        let len = self.buf.len();
+        &self.buf[(self.write_pos + len - offset % len) % len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Always false, since the capacity is at least one.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Resets every value to the default.
+    pub fn clear(&mut self) {
+        self.buf.fill(T::default());
+    }
+}
+
+impl RingBuffer<f32> {
+    /// [`read_at`](Self::read_at) at a fractional offset, interpolating linearly
+    /// between the two values either side.
+    pub fn read_interpolated(&self, offset: f32) -> f32 {
+        let whole = offset.floor();
+        let frac = offset - whole;
+        let whole = whole as usize;
+        self.read_at(whole) * (1.0 - frac) + self.read_at(whole + 1) * frac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_at_one_is_the_previous_push() {
+        let mut ring = RingBuffer::new(4);
+        ring.push(1.0);
+        assert_eq!(*ring.read_at(1), 1.0);
+        ring.push(2.0);
+        assert_eq!(*ring.read_at(1), 2.0);
+        assert_eq!(*ring.read_at(2), 1.0);
+    }
+
+    #[test]
+    fn read_at_capacity_is_the_oldest_after_wrapping() {
+        let mut ring = RingBuffer::new(4);
+        for value in 1..=6 {
+            ring.push(value);
+        }
+        // 1 and 2 have been overwritten
+        assert_eq!(*ring.read_at(ring.len()), 3);
+        assert_eq!(*ring.read_at(1), 6);
+        assert_eq!(*ring.read_at(ring.len() + 1), 6);
+    }
+
+    #[test]
+    fn read_interpolated_blends_neighbours() {
+        let mut ring = RingBuffer::new(4);
+        ring.push(0.0);
+        ring.push(1.0);
+        assert_eq!(ring.read_interpolated(1.25), 0.75);
+    }
+}
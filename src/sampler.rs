@@ -0,0 +1,258 @@
+use rodio::Source;
+
+/// How a [`SamplePlayer`] loops. Positions are sample indices with `end` exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Plays through once; [`SamplePlayer::release`] stops it straight away.
+    None,
+    Forward(usize, usize),
+    /// Forward, with the last `crossfade_samples` of the loop body blended into the
+    /// lead-in to `start`, so the wrap back to `start` has no jump.
+    ForwardWithCrossfade(usize, usize, usize),
+    /// Back and forth between the two points.
+    PingPong(usize, usize),
+    /// Plays through once and ignores release, for drum hits.
+    OneShot,
+}
+
+/// A stored loop, for switching between several with [`SamplePlayer::use_loop_point`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoopPoint {
+    pub start: usize,
+    pub end: usize,
+    pub crossfade_samples: usize,
+}
+
+/// Plays a recorded mono sample at its own pitch, looping while held. After
+/// [`release`](Self::release) a loop plays on past its end to the end of the sample.
+pub struct SamplePlayer {
+    sample_rate: u32,
+    samples: Vec<f32>,
+    loop_mode: LoopMode,
+    pub loop_points: Vec<LoopPoint>,
+    /// The crossfaded end of the loop body for [`LoopMode::ForwardWithCrossfade`].
+    crossfade_tail: Vec<f32>,
+    position: usize,
+    reverse: bool,
+    released: bool,
+    finished: bool,
+}
+
+impl SamplePlayer {
+    pub fn new(samples: Vec<f32>, sample_rate: u32) -> SamplePlayer {
+        SamplePlayer {
+            sample_rate,
+            samples,
+            loop_mode: LoopMode::None,
+            loop_points: Vec::new(),
+            crossfade_tail: Vec::new(),
+            position: 0,
+            reverse: false,
+            released: false,
+            finished: false,
+        }
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    /// Loop points past the end of the sample are pulled in, and the crossfade is
+    /// shortened to fit both the loop and the audio before it. Returns false, leaving
+    /// the mode alone, if no loop is left.
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) -> bool {
+        let len = self.samples.len();
+        let fit = |start: usize, end: usize| {
+            let end = end.min(len);
+            (start < end).then_some((start, end))
+        };
+        let loop_mode = match loop_mode {
+            LoopMode::Forward(start, end) => match fit(start, end) {
+                Some((start, end)) => LoopMode::Forward(start, end),
+                None => return false,
+            },
+            LoopMode::PingPong(start, end) => match fit(start, end) {
+                Some((start, end)) => LoopMode::PingPong(start, end),
+                None => return false,
+            },
+            LoopMode::ForwardWithCrossfade(start, end, crossfade_samples) => match fit(start, end) {
+                Some((start, end)) => {
+                    let crossfade_samples = crossfade_samples.min(start).min(end - start);
+                    LoopMode::ForwardWithCrossfade(start, end, crossfade_samples)
+                }
+                None => return false,
+            },
+            other => other,
+        };
+
+        self.crossfade_tail.clear();
+        if let LoopMode::ForwardWithCrossfade(start, end, crossfade_samples) = loop_mode {
+            // Fade the body's tail out and the audio leading up to `start` in. The last
+            // sample before the wrap is all lead-in, so it runs straight on into `start`
+            let tail_start = end - crossfade_samples;
+            let lead_in_start = start - crossfade_samples;
+            self.crossfade_tail = (0..crossfade_samples)
+                .map(|i| {
+                    let fade_in = (i + 1) as f32 / crossfade_samples as f32;
+                    self.samples[tail_start + i] * (1.0 - fade_in) + self.samples[lead_in_start + i] * fade_in
+                })
+                .collect();
+        }
+        self.loop_mode = loop_mode;
+        true
+    }
+
+    pub fn add_loop_point(&mut self, loop_point: LoopPoint) {
+        self.loop_points.push(loop_point);
+    }
+
+    /// Loops over stored point `index`, crossfading when it has a crossfade length.
+    pub fn use_loop_point(&mut self, index: usize) -> bool {
+        let Some(&LoopPoint { start, end, crossfade_samples }) = self.loop_points.get(index) else {
+            return false;
+        };
+        if crossfade_samples == 0 {
+            self.set_loop_mode(LoopMode::Forward(start, end))
+        } else {
+            self.set_loop_mode(LoopMode::ForwardWithCrossfade(start, end, crossfade_samples))
+        }
+    }
+
+    /// Starts again from the top, held.
+    pub fn retrigger(&mut self) {
+        self.position = 0;
+        self.reverse = false;
+        self.released = false;
+        self.finished = false;
+    }
+
+    pub fn release(&mut self) {
+        match self.loop_mode {
+            LoopMode::None => self.finished = true,
+            LoopMode::OneShot => {}
+            _ => self.released = true,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn next_sample(&mut self) -> Option<f32> {
+        if self.finished || self.position >= self.samples.len() {
+            self.finished = true;
+            return None;
+        }
+
+        let position = self.position;
+        let sample = match self.loop_mode {
+            LoopMode::Forward(start, end) if !self.released => {
+                self.position = if position + 1 == end { start } else { position + 1 };
+                self.samples[position]
+            }
+            LoopMode::ForwardWithCrossfade(start, end, crossfade_samples) if !self.released => {
+                self.position = if position + 1 == end { start } else { position + 1 };
+                match position.checked_sub(end - crossfade_samples) {
+                    Some(i) if position < end => self.crossfade_tail[i],
+                    _ => self.samples[position],
+                }
+            }
+            LoopMode::PingPong(start, end) if !self.released && (start..end).contains(&position) => {
+                if self.reverse && position == start {
+                    self.reverse = false;
+                } else if !self.reverse && position + 1 == end {
+                    self.reverse = true;
+                }
+                // A one-sample loop just holds
+                self.position = if end - start == 1 {
+                    position
+                } else if self.reverse {
+                    position - 1
+                } else {
+                    position + 1
+                };
+                self.samples[position]
+            }
+            _ => {
+                self.position += 1;
+                self.samples[position]
+            }
+        };
+        Some(sample)
+    }
+}
+
+impl Source for SamplePlayer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for SamplePlayer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const START: usize = 1000;
+    const END: usize = 1537;
+
+    /// A 441 Hz sine, 100 samples a cycle, so the loop is not a whole number of cycles.
+    fn sine_player() -> SamplePlayer {
+        let samples = (0..4000).map(|i| (TAU * i as f32 / 100.0).sin()).collect();
+        SamplePlayer::new(samples, 44100)
+    }
+
+    #[test]
+    fn forward_with_crossfade_wraps_without_a_jump() {
+        let mut player = sine_player();
+        assert!(player.set_loop_mode(LoopMode::ForwardWithCrossfade(START, END, 200)));
+        let played: Vec<f32> = player.by_ref().take(END + 1).collect();
+        // The last sample of the body is the one that leads into `start`
+        let before_wrap = played[END - 1];
+        let after_wrap = played[END];
+        assert!((before_wrap - player.samples()[START - 1]).abs() < 1e-4, "{before_wrap}");
+        assert_eq!(after_wrap, player.samples()[START]);
+        let largest_step = TAU / 100.0;
+        assert!((after_wrap - before_wrap).abs() <= largest_step);
+    }
+
+    #[test]
+    fn plain_forward_loop_jumps_at_the_same_points() {
+        let mut player = sine_player();
+        assert!(player.set_loop_mode(LoopMode::Forward(START, END)));
+        let played: Vec<f32> = player.take(END + 1).collect();
+        assert!((played[END] - played[END - 1]).abs() > 0.5);
+    }
+
+    #[test]
+    fn ping_pong_reverses_at_each_end() {
+        let mut player = SamplePlayer::new((0..8).map(|i| i as f32).collect(), 44100);
+        assert!(player.set_loop_mode(LoopMode::PingPong(2, 5)));
+        let played: Vec<f32> = player.take(12).collect();
+        assert_eq!(played, [0.0, 1.0, 2.0, 3.0, 4.0, 3.0, 2.0, 3.0, 4.0, 3.0, 2.0, 3.0]);
+    }
+}
@@ -0,0 +1,315 @@
+use crossterm::style::Color;
+use rand::Rng;
+
+/// Predefined scales, each a 12-bit mask of semitones above the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    Dorian,
+    Mixolydian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+
+impl Scale {
+    /// Bit `n` is set when the note `n` semitones above the root is in the scale.
+    pub fn mask(self) -> u16 {
+        let degrees: &[u8] = match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+        };
+        degrees.iter().fold(0, |mask, &degree| mask | 1 << degree)
+    }
+}
+
+/// Mask with bit `n` set for each degree `n`, as used by [`Scale::mask`].
+const fn degrees(degrees: &[u8]) -> u16 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < degrees.len() {
+        mask |= 1 << degrees[i];
+        i += 1;
+    }
+    mask
+}
+
+/// Named scales as 12-bit masks in the same layout as [`Scale::mask`], where bit 0 is
+/// the root. Ragas and maqamat are their nearest twelve-tone approximations; several
+/// go by more than one name, and some appear under each tradition's name.
+pub const SCALE_LIBRARY: &[(&str, u16)] = &[
+    // Western modes and minors
+    ("Chromatic", degrees(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11])),
+    ("Major", degrees(&[0, 2, 4, 5, 7, 9, 11])),
+    ("Natural Minor", degrees(&[0, 2, 3, 5, 7, 8, 10])),
+    ("Harmonic Minor", degrees(&[0, 2, 3, 5, 7, 8, 11])),
+    ("Melodic Minor", degrees(&[0, 2, 3, 5, 7, 9, 11])),
+    ("Dorian", degrees(&[0, 2, 3, 5, 7, 9, 10])),
+    ("Phrygian", degrees(&[0, 1, 3, 5, 7, 8, 10])),
+    ("Lydian", degrees(&[0, 2, 4, 6, 7, 9, 11])),
+    ("Mixolydian", degrees(&[0, 2, 4, 5, 7, 9, 10])),
+    ("Locrian", degrees(&[0, 1, 3, 5, 6, 8, 10])),
+    ("Harmonic Major", degrees(&[0, 2, 4, 5, 7, 8, 11])),
+    ("Phrygian Dominant", degrees(&[0, 1, 4, 5, 7, 8, 10])),
+    ("Lydian Dominant", degrees(&[0, 2, 4, 6, 7, 9, 10])),
+    ("Lydian Augmented", degrees(&[0, 2, 4, 6, 8, 9, 11])),
+    ("Mixolydian b6", degrees(&[0, 2, 4, 5, 7, 8, 10])),
+    ("Dorian b2", degrees(&[0, 1, 3, 5, 7, 9, 10])),
+    ("Locrian #2", degrees(&[0, 2, 3, 5, 6, 8, 10])),
+    ("Altered", degrees(&[0, 1, 3, 4, 6, 8, 10])),
+    // Pentatonics, blues and jazz
+    ("Major Pentatonic", degrees(&[0, 2, 4, 7, 9])),
+    ("Minor Pentatonic", degrees(&[0, 3, 5, 7, 10])),
+    ("Blues", degrees(&[0, 3, 5, 6, 7, 10])),
+    ("Major Blues", degrees(&[0, 2, 3, 4, 7, 9])),
+    ("Bebop Dominant", degrees(&[0, 2, 4, 5, 7, 9, 10, 11])),
+    ("Bebop Major", degrees(&[0, 2, 4, 5, 7, 8, 9, 11])),
+    // Symmetric
+    ("Whole Tone", degrees(&[0, 2, 4, 6, 8, 10])),
+    ("Diminished (Whole-Half)", degrees(&[0, 2, 3, 5, 6, 8, 9, 11])),
+    ("Diminished (Half-Whole)", degrees(&[0, 1, 3, 4, 6, 7, 9, 10])),
+    ("Augmented", degrees(&[0, 3, 4, 7, 8, 11])),
+    ("Tritone", degrees(&[0, 1, 4, 6, 7, 10])),
+    // European folk and exotic
+    ("Hungarian Minor", degrees(&[0, 2, 3, 6, 7, 8, 11])),
+    ("Hungarian Major", degrees(&[0, 3, 4, 6, 7, 9, 10])),
+    ("Gypsy", degrees(&[0, 2, 3, 6, 7, 8, 10])),
+    ("Neapolitan Major", degrees(&[0, 1, 3, 5, 7, 9, 11])),
+    ("Neapolitan Minor", degrees(&[0, 1, 3, 5, 7, 8, 11])),
+    ("Enigmatic", degrees(&[0, 1, 4, 6, 8, 10, 11])),
+    ("Prometheus", degrees(&[0, 2, 4, 6, 9, 10])),
+    ("Spanish Eight-Tone", degrees(&[0, 1, 3, 4, 5, 6, 8, 10])),
+    // Indian ragas and thaats
+    ("Bilawal", degrees(&[0, 2, 4, 5, 7, 9, 11])),
+    ("Kalyan (Yaman)", degrees(&[0, 2, 4, 6, 7, 9, 11])),
+    ("Khamaj", degrees(&[0, 2, 4, 5, 7, 9, 10])),
+    ("Kafi", degrees(&[0, 2, 3, 5, 7, 9, 10])),
+    ("Asavari", degrees(&[0, 2, 3, 5, 7, 8, 10])),
+    ("Bhairavi", degrees(&[0, 1, 3, 5, 7, 8, 10])),
+    ("Bhairav", degrees(&[0, 1, 4, 5, 7, 8, 11])),
+    ("Todi", degrees(&[0, 1, 3, 6, 7, 8, 11])),
+    ("Purvi", degrees(&[0, 1, 4, 6, 7, 8, 11])),
+    ("Marwa", degrees(&[0, 1, 4, 6, 7, 9, 11])),
+    ("Bhupali", degrees(&[0, 2, 4, 7, 9])),
+    ("Durga", degrees(&[0, 2, 5, 7, 9])),
+    ("Malkauns", degrees(&[0, 3, 5, 8, 10])),
+    ("Hindol", degrees(&[0, 4, 6, 9, 11])),
+    // Middle Eastern maqamat
+    ("Ajam", degrees(&[0, 2, 4, 5, 7, 9, 11])),
+    ("Nahawand", degrees(&[0, 2, 3, 5, 7, 8, 11])),
+    ("Kurd", degrees(&[0, 1, 3, 5, 7, 8, 10])),
+    ("Hijaz", degrees(&[0, 1, 4, 5, 7, 8, 10])),
+    ("Nikriz", degrees(&[0, 2, 3, 6, 7, 9, 10])),
+    ("Double Harmonic (Byzantine)", degrees(&[0, 1, 4, 5, 7, 8, 11])),
+    ("Persian", degrees(&[0, 1, 4, 5, 6, 8, 11])),
+    ("Arabic", degrees(&[0, 2, 4, 5, 6, 8, 10])),
+    // Japanese and East Asian
+    ("Hirajoshi", degrees(&[0, 2, 3, 7, 8])),
+    ("In", degrees(&[0, 1, 5, 7, 8])),
+    ("Insen", degrees(&[0, 1, 5, 7, 10])),
+    ("Iwato", degrees(&[0, 1, 5, 6, 10])),
+    ("Yo", degrees(&[0, 2, 5, 7, 9])),
+    ("Kumoi", degrees(&[0, 2, 3, 7, 9])),
+    ("Ryukyu", degrees(&[0, 4, 5, 7, 11])),
+    ("Pelog", degrees(&[0, 1, 3, 7, 8])),
+    ("Chinese", degrees(&[0, 4, 6, 7, 11])),
+    ("Egyptian", degrees(&[0, 2, 5, 7, 10])),
+];
+
+/// Scales whose name contains `query`, ignoring case, in library order.
+pub fn search_scales(query: &str) -> Vec<(&'static str, u16)> {
+    let query = query.to_lowercase();
+    SCALE_LIBRARY
+        .iter()
+        .copied()
+        .filter(|(name, _)| name.to_lowercase().contains(&query))
+        .collect()
+}
+
+/// Mask of the library scale named `name`, ignoring case. A name that isn't exact
+/// falls back to the first scale containing it, so "harm" finds Harmonic Minor.
+pub fn find_scale(name: &str) -> Option<u16> {
+    SCALE_LIBRARY
+        .iter()
+        .find(|(scale, _)| scale.eq_ignore_ascii_case(name))
+        .map(|&(_, mask)| mask)
+        .or_else(|| search_scales(name).first().map(|&(_, mask)| mask))
+}
+
+/// Incremental search over [`SCALE_LIBRARY`]: typed characters narrow the list and
+/// the selection moves through what's left.
+#[derive(Clone, Debug, Default)]
+pub struct ScaleChooser {
+    query: String,
+    selected: usize,
+}
+
+impl ScaleChooser {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn matches(&self) -> Vec<(&'static str, u16)> {
+        search_scales(&self.query)
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the selection by `offset`, wrapping around the current matches.
+    pub fn move_selection(&mut self, offset: isize) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected as isize + offset).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    pub fn selected(&self) -> Option<(&'static str, u16)> {
+        self.matches().get(self.selected).copied()
+    }
+}
+
+/// Snaps MIDI notes onto a scale stored as a 12-bit mask, so any scale fits in a `u16`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoteQuantizer {
+    pub scale_mask: u16,
+    /// Pitch class of the scale's root, 0 = C.
+    pub root: u8,
+}
+
+impl NoteQuantizer {
+    /// `scale` rooted on C.
+    pub fn from_scale(scale: Scale) -> NoteQuantizer {
+        NoteQuantizer::custom(scale.mask(), 0)
+    }
+
+    pub fn custom(mask: u16, root: u8) -> NoteQuantizer {
+        NoteQuantizer {
+            scale_mask: mask & 0x0fff,
+            root: root % 12,
+        }
+    }
+
+    pub fn contains(&self, midi_note: u8) -> bool {
+        let degree = (midi_note as i32 - self.root as i32).rem_euclid(12);
+        self.scale_mask & 1 << degree != 0
+    }
+
+    /// The nearest in-scale note, taking the one above when two are equally close.
+    /// An empty mask leaves notes untouched.
+    pub fn quantize_note(&self, midi_note: u8) -> u8 {
+        if self.scale_mask == 0 {
+            return midi_note;
+        }
+
+        // Past 6 semitones only happens when the nearer side is off the MIDI range
+        for distance in 0..12 {
+            for candidate in [midi_note as i32 + distance, midi_note as i32 - distance] {
+                if (0..=127).contains(&candidate) && self.contains(candidate as u8) {
+                    return candidate as u8;
+                }
+            }
+        }
+        midi_note
+    }
+}
+
+/// Where a note falls in a [`ScaleHighlighter`]'s scale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleMembership {
+    Root,
+    InScale,
+    OutOfScale,
+}
+
+/// Colours the keyboard layout by a scale: the root in an accent, the rest of the
+/// scale highlighted and everything else dim. Bit `n` of the mask marks the note `n`
+/// semitones above the root as in the scale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScaleHighlighter {
+    pub quantizer: NoteQuantizer,
+    pub in_scale_color: Color,
+    pub root_color: Color,
+}
+
+impl ScaleHighlighter {
+    /// Green for the scale and amber for the root, readable on light and dark
+    /// backgrounds alike; set the fields to match a theme.
+    pub fn new(quantizer: NoteQuantizer) -> ScaleHighlighter {
+        ScaleHighlighter {
+            quantizer,
+            in_scale_color: Color::Rgb { r: 80, g: 190, b: 110 },
+            root_color: Color::Rgb { r: 240, g: 160, b: 40 },
+        }
+    }
+
+    pub fn membership(&self, midi_note: u8) -> ScaleMembership {
+        if (midi_note % 12) == self.quantizer.root {
+            ScaleMembership::Root
+        } else if self.quantizer.contains(midi_note) {
+            ScaleMembership::InScale
+        } else {
+            ScaleMembership::OutOfScale
+        }
+    }
+
+    /// The colour `midi_note` is drawn in, or `None` for an out-of-scale note, which is
+    /// drawn dim instead.
+    pub fn color(&self, midi_note: u8) -> Option<Color> {
+        match self.membership(midi_note) {
+            ScaleMembership::Root => Some(self.root_color),
+            ScaleMembership::InScale => Some(self.in_scale_color),
+            ScaleMembership::OutOfScale => None,
+        }
+    }
+}
+
+/// Plays a random in-scale note whatever key is pressed, for playing around without
+/// learning the layout, or for installations where any touch should make music.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomPitchMode {
+    pub quantizer: NoteQuantizer,
+    /// Lowest and highest MIDI notes it picks from, inclusive.
+    pub low_note: u8,
+    pub high_note: u8,
+}
+
+impl RandomPitchMode {
+    pub fn new(quantizer: NoteQuantizer, low_note: u8, high_note: u8) -> RandomPitchMode {
+        RandomPitchMode {
+            quantizer,
+            low_note: low_note.min(high_note),
+            high_note: high_note.max(low_note),
+        }
+    }
+
+    /// A note drawn evenly from the in-scale notes of the range; with none in range,
+    /// a random note of the range snapped to the scale.
+    pub fn pick(&self, rng: &mut impl Rng) -> u8 {
+        let notes: Vec<u8> = (self.low_note..=self.high_note)
+            .filter(|&note| self.quantizer.scale_mask == 0 || self.quantizer.contains(note))
+            .collect();
+        if notes.is_empty() {
+            self.quantizer.quantize_note(rng.gen_range(self.low_note..=self.high_note))
+        } else {
+            notes[rng.gen_range(0..notes.len())]
+        }
+    }
+}
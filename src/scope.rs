@@ -0,0 +1,321 @@
+use crossterm::style::Attribute;
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Where an [`Oscilloscope`] frame starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerMode {
+    /// Shows the newest samples, so the trace drifts from frame to frame.
+    Free,
+    /// Starts where the signal first rises through the threshold.
+    RisingEdge(f32),
+    /// Starts where the signal first falls through the threshold.
+    FallingEdge(f32),
+}
+
+/// Draws recent samples as a one-line trace for the status line.
+pub struct Oscilloscope {
+    pub trigger_mode: TriggerMode,
+    pub width: usize,
+    pub samples_per_column: usize,
+    samples: Arc<Mutex<VecDeque<f32>>>,
+}
+
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+impl Oscilloscope {
+    pub fn new(samples: Arc<Mutex<VecDeque<f32>>>, trigger_mode: TriggerMode) -> Oscilloscope {
+        Oscilloscope {
+            trigger_mode,
+            width: 64,
+            samples_per_column: 4,
+            samples,
+        }
+    }
+
+    /// The samples one frame shows, taken from `buffer` (oldest first). Triggered
+    /// modes start at the first matching edge, searching everything but the last
+    /// frame's worth; with no edge found they show the newest samples like `Free`.
+    pub fn frame<'a>(&self, buffer: &'a [f32]) -> &'a [f32] {
+        let len = (self.width * self.samples_per_column).min(buffer.len());
+        let latest = buffer.len() - len;
+
+        let crosses = |prev: f32, current: f32| match self.trigger_mode {
+            TriggerMode::Free => false,
+            TriggerMode::RisingEdge(threshold) => prev < threshold && current >= threshold,
+            TriggerMode::FallingEdge(threshold) => prev > threshold && current <= threshold,
+        };
+        let start = (1..=latest)
+            .find(|&i| crosses(buffer[i - 1], buffer[i]))
+            .unwrap_or(latest);
+
+        &buffer[start..start + len]
+    }
+
+    /// Renders the current frame, with -1.0 at the bottom block and 1.0 at the top.
+    pub fn render(&self) -> String {
+        let buffer: Vec<f32> = match self.samples.lock() {
+            Ok(samples) => samples.iter().copied().collect(),
+            Err(_) => return String::new(),
+        };
+
+        let mut line: String = self
+            .frame(&buffer)
+            .iter()
+            .step_by(self.samples_per_column.max(1))
+            .map(|&sample| {
+                let level = ((sample.clamp(-1.0, 1.0) + 1.0) / 2.0 * (LEVELS.len() - 1) as f32).round();
+                LEVELS[level as usize]
+            })
+            .collect();
+        while line.chars().count() < self.width {
+            line.push(' ');
+        }
+        line
+    }
+}
+
+/// How many `(left, right)` pairs a [`LissajousDisplay`] keeps.
+pub const LISSAJOUS_HISTORY: usize = 512;
+
+/// Plots left against right as an X-Y figure, showing how correlated the channels
+/// are: mono is the rising diagonal, opposite polarity the falling one, and
+/// anything wider an ellipse or a Lissajous figure. The older half of the history
+/// is drawn dim so the figure fades as it moves.
+pub struct LissajousDisplay {
+    history: VecDeque<(f32, f32)>,
+    width: u16,
+    height: u16,
+}
+
+impl LissajousDisplay {
+    pub fn new(width: u16, height: u16) -> LissajousDisplay {
+        LissajousDisplay {
+            history: VecDeque::with_capacity(LISSAJOUS_HISTORY),
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Adds pairs to the history, newest last, dropping the oldest past
+    /// [`LISSAJOUS_HISTORY`].
+    pub fn extend(&mut self, pairs: impl IntoIterator<Item = (f32, f32)>) {
+        for pair in pairs {
+            if self.history.len() == LISSAJOUS_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(pair);
+        }
+    }
+
+    /// Where a pair lands: left runs across and right up, -1.0 to 1.0 each.
+    pub fn position(&self, (left, right): (f32, f32)) -> (u16, u16) {
+        let max_x = self.width - 1;
+        let max_y = self.height - 1;
+        let x = ((left.clamp(-1.0, 1.0) + 1.0) * self.width as f32 / 2.0) as u16;
+        let y = ((1.0 - right.clamp(-1.0, 1.0)) * self.height as f32 / 2.0) as u16;
+        (x.min(max_x), y.min(max_y))
+    }
+
+    /// The figure as `height` rows of `width` columns, joined with `\r\n` for raw mode.
+    pub fn render(&self) -> String {
+        // 0 is empty, 1 a faded point and 2 a recent one, so recent points win
+        let mut grid = vec![vec![0u8; self.width as usize]; self.height as usize];
+        let faded = self.history.len().saturating_sub(LISSAJOUS_HISTORY / 2);
+        for (i, &pair) in self.history.iter().enumerate() {
+            let (x, y) = self.position(pair);
+            let cell = &mut grid[y as usize][x as usize];
+            *cell = (*cell).max(if i < faded { 1 } else { 2 });
+        }
+
+        let rows: Vec<String> = grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        0 => " ".to_string(),
+                        // Not Stylize's dim(), whose full reset would drop the theme's colors
+                        1 => format!("{}*{}", Attribute::Dim, Attribute::NormalIntensity),
+                        _ => "*".to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        rows.join("\r\n")
+    }
+}
+
+/// Plots a whole wave table as bars up and down from a zero line, one column per
+/// stretch of the table: each column shows the sample at its start.
+pub struct WaveformPreview {
+    width: u16,
+    height: u16,
+}
+
+impl WaveformPreview {
+    /// An even `height` is rounded up, so the zero line has a row of its own.
+    pub fn new(width: u16, height: u16) -> WaveformPreview {
+        WaveformPreview {
+            width: width.max(1),
+            height: height.max(1) | 1,
+        }
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// `height` rows joined with `\r\n` for raw mode, 1.0 at the top and -1.0 at the
+    /// bottom.
+    pub fn render(&self, table: &[f32]) -> String {
+        let width = self.width as usize;
+        let center = self.height as usize / 2;
+        let row_of = |sample: f32| ((1.0 - sample.clamp(-1.0, 1.0)) * center as f32).round() as usize;
+        let rows_by_column: Vec<usize> = (0..width)
+            .map(|x| table.get(x * table.len() / width).map_or(center, |&sample| row_of(sample)))
+            .collect();
+
+        let rows: Vec<String> = (0..self.height as usize)
+            .map(|y| {
+                rows_by_column
+                    .iter()
+                    .map(|&row| match y {
+                        _ if (row.min(center)..=row.max(center)).contains(&y) && row != center => '█',
+                        _ if y == center => '─',
+                        _ => ' ',
+                    })
+                    .collect()
+            })
+            .collect();
+        rows.join("\r\n")
+    }
+}
+
+/// Passes a stereo source through unchanged while keeping its most recent
+/// `(left, right)` frames for a [`LissajousDisplay`].
+pub struct StereoTap<S: Source<Item = f32>> {
+    source: S,
+    frames: Arc<Mutex<VecDeque<(f32, f32)>>>,
+    capacity: usize,
+    pending_left: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> StereoTap<S> {
+    pub fn new(source: S, capacity: usize) -> StereoTap<S> {
+        StereoTap {
+            source,
+            frames: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            pending_left: None,
+        }
+    }
+
+    pub fn get_frames_control(&self) -> Arc<Mutex<VecDeque<(f32, f32)>>> {
+        self.frames.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for StereoTap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for StereoTap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        // A mono source has nothing to pair, so it plots as its own diagonal
+        let frame = if self.source.channels() < 2 {
+            Some((sample, sample))
+        } else {
+            match self.pending_left.take() {
+                Some(left) => Some((left, sample)),
+                None => {
+                    self.pending_left = Some(sample);
+                    None
+                }
+            }
+        };
+        if let (Some(frame), Ok(mut frames)) = (frame, self.frames.lock()) {
+            if frames.len() == self.capacity {
+                frames.pop_front();
+            }
+            frames.push_back(frame);
+        }
+        Some(sample)
+    }
+}
+
+/// Passes a source through unchanged while keeping its most recent samples for an
+/// [`Oscilloscope`].
+pub struct ScopeTap<S: Source<Item = f32>> {
+    source: S,
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    capacity: usize,
+}
+
+impl<S: Source<Item = f32>> ScopeTap<S> {
+    pub fn new(source: S, capacity: usize) -> ScopeTap<S> {
+        ScopeTap {
+            source,
+            samples: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn get_samples_control(&self) -> Arc<Mutex<VecDeque<f32>>> {
+        self.samples.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ScopeTap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ScopeTap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() == self.capacity {
+                samples.pop_front();
+            }
+            samples.push_back(sample);
+        }
+        Some(sample)
+    }
+}
@@ -0,0 +1,531 @@
+use crate::fm::FmPreset;
+use crate::oscillator::WaveTableOscillator;
+use crate::scale::Scale;
+use rand::Rng;
+use rodio::Source;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// One sequencer step: a frequency in Hz, or `None` for a rest.
+pub type SequencerStep = Option<f32>;
+
+/// A note captured while recording, timed from the start of the take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedNote {
+    pub time_secs: f32,
+    pub frequency: f32,
+}
+
+/// Number of steps in a [`StepSequencer`] pattern.
+pub const PATTERN_STEPS: usize = 16;
+
+/// A note in one of the pattern's steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PatternStep {
+    pub midi_note: u8,
+    pub velocity: u8,
+}
+
+/// Parameter values a step sets when it starts; `None` leaves that parameter alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StepAutomation {
+    pub fm_index_override: Option<f32>,
+    pub filter_cutoff_override: Option<f32>,
+    pub volume_override: Option<f32>,
+}
+
+impl StepAutomation {
+    pub fn is_empty(&self) -> bool {
+        *self == StepAutomation::default()
+    }
+}
+
+/// How an automated parameter moves from one step's override to the next.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AutomationCurve {
+    /// Jumps at the start of the step.
+    #[default]
+    Snap,
+    /// Slides linearly over the sequencer's crossfade time, like the pitch does.
+    Glide,
+}
+
+/// The parameter handles automation writes to. A missing handle ignores its lane.
+#[derive(Clone, Default)]
+pub struct AutomationTargets {
+    pub fm_preset: Option<Arc<Mutex<FmPreset>>>,
+    pub filter_cutoff: Option<Arc<Mutex<f32>>>,
+}
+
+pub struct StepSequencer {
+    bpm: f32,
+    events: Vec<RecordedNote>,
+    quantize_strength: f32,
+    pattern: [Option<PatternStep>; PATTERN_STEPS],
+    automation: [StepAutomation; PATTERN_STEPS],
+    /// Lowest octave notes are picked from, in MIDI numbering where C4 is note 60.
+    low_octave: u8,
+    octaves: u8,
+}
+
+impl StepSequencer {
+    pub fn new(bpm: f32) -> StepSequencer {
+        StepSequencer {
+            bpm,
+            events: Vec::new(),
+            quantize_strength: 1.0,
+            pattern: [None; PATTERN_STEPS],
+            automation: [StepAutomation::default(); PATTERN_STEPS],
+            low_octave: 4,
+            octaves: 1,
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.bpm
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm.max(1.0);
+    }
+
+    pub fn events(&self) -> &[RecordedNote] {
+        &self.events
+    }
+
+    pub fn record(&mut self, time_secs: f32, frequency: f32) {
+        self.events.push(RecordedNote { time_secs, frequency });
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// How far quantizing pulls notes onto the grid: 0.0 leaves them alone, 1.0 snaps
+    /// them exactly, and values in between keep some of the played feel.
+    pub fn set_quantize_strength(&mut self, strength: f32) {
+        self.quantize_strength = strength.clamp(0.0, 1.0);
+    }
+
+    /// Moves `time_secs` toward the nearest `1 / resolution` of a beat, by the
+    /// quantize strength.
+    pub fn quantize_to_grid(&self, time_secs: f32, resolution: u8) -> f32 {
+        let resolution = resolution.max(1) as f32;
+        let grid_pos = (time_secs * self.bpm / 60.0 * resolution).round();
+        let grid_time = grid_pos * 60.0 / (self.bpm * resolution);
+        time_secs + (grid_time - time_secs) * self.quantize_strength
+    }
+
+    pub fn quantize_in_place(&mut self, resolution: u8) {
+        for i in 0..self.events.len() {
+            self.events[i].time_secs = self.quantize_to_grid(self.events[i].time_secs, resolution);
+        }
+    }
+
+    pub fn pattern(&self) -> &[Option<PatternStep>; PATTERN_STEPS] {
+        &self.pattern
+    }
+
+    pub fn automation(&self) -> &[StepAutomation; PATTERN_STEPS] {
+        &self.automation
+    }
+
+    /// Sets the overrides of `step`, wrapping around the pattern length.
+    pub fn set_automation(&mut self, step: usize, automation: StepAutomation) {
+        self.automation[step % PATTERN_STEPS] = automation;
+    }
+
+    /// The pattern as MML, one sixteenth note per step. A step's automation comes
+    /// before its note as `FM=`, `CUTOFF=` and `VOL=` commands, e.g. `FM=3.5 o4c`.
+    pub fn to_mml(&self) -> String {
+        const MML_NOTES: [&str; 12] = ["c", "c+", "d", "d+", "e", "f", "f+", "g", "g+", "a", "a+", "b"];
+
+        let mut mml = format!("t{} l16", self.bpm.round());
+        let mut octave = None;
+        let mut velocity = None;
+        for (step, automation) in self.pattern.iter().zip(&self.automation) {
+            mml.push(' ');
+            if let Some(index) = automation.fm_index_override {
+                let _ = write!(mml, "FM={index} ");
+            }
+            if let Some(cutoff) = automation.filter_cutoff_override {
+                let _ = write!(mml, "CUTOFF={cutoff} ");
+            }
+            if let Some(volume) = automation.volume_override {
+                let _ = write!(mml, "VOL={volume} ");
+            }
+
+            let Some(step) = step else {
+                mml.push('r');
+                continue;
+            };
+            // MML velocity runs 0-15
+            let step_velocity = step.velocity / 8;
+            if velocity != Some(step_velocity) {
+                let _ = write!(mml, "v{step_velocity}");
+                velocity = Some(step_velocity);
+            }
+            let step_octave = step.midi_note / 12 - 1;
+            if octave != Some(step_octave) {
+                let _ = write!(mml, "o{step_octave}");
+                octave = Some(step_octave);
+            }
+            mml.push_str(MML_NOTES[step.midi_note as usize % 12]);
+        }
+        mml
+    }
+
+    /// Sets the octaves [`StepSequencer::randomize`] picks notes from: `octaves` of
+    /// them starting at the C of `low_octave`.
+    pub fn set_octave_range(&mut self, low_octave: u8, octaves: u8) {
+        self.low_octave = low_octave.min(9);
+        self.octaves = octaves.max(1);
+    }
+
+    /// Fills the pattern with a new random one. Each step sounds with probability
+    /// `density`, on a random note of `scale` (rooted on C) within the octave range and
+    /// a random velocity in `velocity_range`. The same `rng` state gives the same pattern.
+    pub fn randomize(&mut self, scale: Scale, density: f32, velocity_range: (u8, u8), rng: &mut impl Rng) {
+        let mask = scale.mask();
+        let degrees: Vec<u8> = (0..12).filter(|degree| mask & 1 << degree != 0).collect();
+        let density = density.clamp(0.0, 1.0) as f64;
+        let (low_velocity, high_velocity) = if velocity_range.0 <= velocity_range.1 {
+            velocity_range
+        } else {
+            (velocity_range.1, velocity_range.0)
+        };
+
+        for step in self.pattern.iter_mut() {
+            if !rng.gen_bool(density) {
+                *step = None;
+                continue;
+            }
+
+            let octave = self.low_octave + rng.gen_range(0..self.octaves);
+            let degree = degrees[rng.gen_range(0..degrees.len())];
+            let midi_note = ((octave as u32 + 1) * 12 + degree as u32).min(127) as u8;
+            *step = Some(PatternStep {
+                midi_note,
+                velocity: rng.gen_range(low_velocity..=high_velocity).min(127),
+            });
+        }
+    }
+}
+
+/// A loop of notes and rests played live, `steps_per_beat` steps to the beat, by
+/// [`spawn_note_sequencer`]. Nothing plays while `playing` is off or there are no
+/// steps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoteSequencer {
+    pub steps: Vec<SequencerStep>,
+    pub bpm: f32,
+    pub steps_per_beat: u8,
+    pub playing: bool,
+}
+
+impl NoteSequencer {
+    pub fn new(bpm: f32, steps_per_beat: u8) -> NoteSequencer {
+        NoteSequencer {
+            steps: Vec::new(),
+            bpm,
+            steps_per_beat,
+            playing: false,
+        }
+    }
+
+    pub fn step_duration(&self) -> Duration {
+        Duration::from_secs_f32(60.0 / (self.bpm.max(1.0) * self.steps_per_beat.max(1) as f32))
+    }
+}
+
+/// Plays `sequencer` on a background thread for as long as the program runs,
+/// writing each step's frequency, or 0.0 for a rest, to `frequency`.
+///
+/// Steps are timed against an [`Instant`] deadline that moves on by one step each
+/// time; the thread sleeps until the deadline rather than for a step's length, so
+/// the time spent working never adds up into drift. A change of tempo or steps
+/// applies from the next step.
+pub fn spawn_note_sequencer(sequencer: Arc<Mutex<NoteSequencer>>, frequency: Arc<Mutex<f32>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut index = 0;
+        let mut deadline = Instant::now();
+        let mut sounding = false;
+        loop {
+            let Ok(state) = sequencer.lock() else {
+                return;
+            };
+            let step = (state.playing && !state.steps.is_empty()).then(|| state.steps[index % state.steps.len()]);
+            let step_duration = state.step_duration();
+            drop(state);
+
+            let Some(step) = step else {
+                if std::mem::take(&mut sounding) {
+                    if let Ok(mut freq) = frequency.lock() {
+                        *freq = 0.0;
+                    }
+                }
+                // Start from the top, on time, whenever playing resumes
+                index = 0;
+                thread::sleep(Duration::from_millis(5));
+                deadline = Instant::now();
+                continue;
+            };
+            if let Ok(mut freq) = frequency.lock() {
+                *freq = step.unwrap_or(0.0);
+            }
+            sounding = true;
+            index += 1;
+            deadline += step_duration;
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            } else {
+                // Too far behind to catch up without a burst of steps
+                deadline = now;
+            }
+        }
+    })
+}
+
+/// Tempo waypoints as `(beat, bpm)`, with the tempo interpolated linearly between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    events: Vec<(f64, f32)>,
+}
+
+impl TempoMap {
+    /// Waypoints are sorted by beat; an empty list plays at 120 BPM.
+    pub fn new(mut events: Vec<(f64, f32)>) -> TempoMap {
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+        TempoMap { events }
+    }
+
+    pub fn constant(bpm: f32) -> TempoMap {
+        TempoMap::new(vec![(0.0, bpm)])
+    }
+
+    /// Ramps from `start_bpm` at beat 0 to `end_bpm` at `duration_beats`, then holds.
+    pub fn accelerando(start_bpm: f32, end_bpm: f32, duration_beats: f64) -> TempoMap {
+        TempoMap::new(vec![(0.0, start_bpm), (duration_beats, end_bpm)])
+    }
+
+    pub fn tempo_at(&self, beat: f64) -> f32 {
+        let next = self.events.partition_point(|&(b, _)| b <= beat);
+        let bpm = match (next.checked_sub(1).map(|i| self.events[i]), self.events.get(next)) {
+            (Some((b0, bpm0)), Some(&(b1, bpm1))) => {
+                bpm0 + (bpm1 - bpm0) * ((beat - b0) / (b1 - b0)) as f32
+            }
+            (Some((_, bpm)), None) | (None, Some(&(_, bpm))) => bpm,
+            (None, None) => 120.0,
+        };
+        bpm.max(1.0)
+    }
+
+    pub fn samples_per_beat(&self, beat: f64, sample_rate: u32) -> f32 {
+        60.0 * sample_rate as f32 / self.tempo_at(beat)
+    }
+}
+
+/// Loops `steps` sample-accurately, gliding linearly between sounding steps over the
+/// first `crossfade_samples` of each step instead of jumping. Rests start and end
+/// without a glide. Each step lasts as long as the tempo map says at the beat it starts.
+///
+/// Steps can also carry [`StepAutomation`], written to the [`AutomationTargets`] as they
+/// play; the volume lane scales the sequencer's own output.
+pub struct CrossfadeSequencer {
+    steps: Vec<SequencerStep>,
+    automation: Vec<StepAutomation>,
+    automation_curve: AutomationCurve,
+    targets: AutomationTargets,
+    volume: f32,
+    crossfade_samples: usize,
+    tempo: TempoMap,
+    steps_per_beat: u8,
+    step: usize,
+    step_offset: usize,
+    step_samples: usize,
+    beat: f64,
+    oscillator: WaveTableOscillator,
+    frequency: Arc<Mutex<f32>>,
+}
+
+impl CrossfadeSequencer {
+    pub fn new(
+        mut oscillator: WaveTableOscillator,
+        steps: Vec<SequencerStep>,
+        tempo: TempoMap,
+        steps_per_beat: u8,
+        crossfade_ms: f32,
+    ) -> CrossfadeSequencer {
+        let sample_rate = oscillator.sample_rate() as f32;
+        // The sequencer shapes pitch changes itself, so keep the oscillator's own
+        // smoothing out of the way
+        oscillator.set_smoothing_hz(sample_rate);
+
+        let mut sequencer = CrossfadeSequencer {
+            steps,
+            automation: Vec::new(),
+            automation_curve: AutomationCurve::Snap,
+            targets: AutomationTargets::default(),
+            volume: 1.0,
+            crossfade_samples: (sample_rate * crossfade_ms / 1000.0) as usize,
+            tempo,
+            steps_per_beat: steps_per_beat.max(1),
+            step: 0,
+            step_offset: 0,
+            step_samples: 1,
+            beat: 0.0,
+            frequency: oscillator.get_frequency_control(),
+            oscillator,
+        };
+        sequencer.step_samples = sequencer.current_step_samples();
+        sequencer
+    }
+
+    fn current_step_samples(&self) -> usize {
+        let samples_per_beat = self.tempo.samples_per_beat(self.beat, self.oscillator.sample_rate());
+        ((samples_per_beat / self.steps_per_beat as f32) as usize).max(1)
+    }
+
+    /// Per-step overrides, lined up with the steps; missing entries override nothing.
+    pub fn set_automation(&mut self, automation: Vec<StepAutomation>, curve: AutomationCurve) {
+        self.automation = automation;
+        self.automation_curve = curve;
+    }
+
+    pub fn set_automation_targets(&mut self, targets: AutomationTargets) {
+        self.targets = targets;
+    }
+
+    /// The current value of one automation lane, or `None` while it holds still.
+    fn automated_value(&self, lane: impl Fn(&StepAutomation) -> Option<f32>) -> Option<f32> {
+        let automation_at = |step: usize| self.automation.get(step).and_then(&lane);
+        let current = automation_at(self.step)?;
+        let previous = automation_at((self.step + self.steps.len().max(1) - 1) % self.steps.len().max(1));
+
+        match (self.automation_curve, previous) {
+            (AutomationCurve::Glide, Some(from)) if self.step_offset < self.crossfade_samples => {
+                Some(from + (current - from) * self.step_offset as f32 / self.crossfade_samples as f32)
+            }
+            // Past the glide the value is already set
+            _ if self.step_offset > 0 => None,
+            _ => Some(current),
+        }
+    }
+
+    fn apply_automation(&mut self) {
+        if let Some(index) = self.automated_value(|automation| automation.fm_index_override) {
+            if let Some(Ok(mut preset)) = self.targets.fm_preset.as_ref().map(|preset| preset.lock()) {
+                preset.mod_index = index;
+            }
+        }
+        if let Some(cutoff) = self.automated_value(|automation| automation.filter_cutoff_override) {
+            if let Some(Ok(mut target)) = self.targets.filter_cutoff.as_ref().map(|target| target.lock()) {
+                *target = cutoff;
+            }
+        }
+        if let Some(volume) = self.automated_value(|automation| automation.volume_override) {
+            self.volume = volume;
+        }
+    }
+
+    fn current_frequency(&self) -> f32 {
+        if self.steps.is_empty() {
+            return 0.0;
+        }
+
+        let current = self.steps[self.step];
+        let previous = self.steps[(self.step + self.steps.len() - 1) % self.steps.len()];
+
+        match (previous, current) {
+            (Some(from), Some(to)) if self.step_offset < self.crossfade_samples => {
+                from + (to - from) * self.step_offset as f32 / self.crossfade_samples as f32
+            }
+            _ => current.unwrap_or(0.0),
+        }
+    }
+
+    /// Advances one sample, moving to the next step at the end of the current one.
+    pub fn tick(&mut self) {
+        self.step_offset += 1;
+        if self.step_offset < self.step_samples {
+            return;
+        }
+
+        self.step_offset = 0;
+        self.step = (self.step + 1) % self.steps.len().max(1);
+        self.beat += 1.0 / self.steps_per_beat as f64;
+        self.step_samples = self.current_step_samples();
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        if let Ok(mut freq) = self.frequency.lock() {
+            *freq = self.current_frequency();
+        }
+        if !self.automation.is_empty() {
+            self.apply_automation();
+        }
+        self.tick();
+        self.oscillator.get_sample() * self.volume
+    }
+}
+
+impl Source for CrossfadeSequencer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.oscillator.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for CrossfadeSequencer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave::{generate_wave_table, WaveShape};
+
+    #[test]
+    fn tempo_is_interpolated_at_the_midpoint() {
+        let tempo = TempoMap::accelerando(100.0, 200.0, 4.0);
+        assert_eq!(tempo.tempo_at(2.0), 150.0);
+        assert_eq!(tempo.samples_per_beat(2.0, 48000), 19200.0);
+        // Held either side of the ramp
+        assert_eq!(tempo.tempo_at(-1.0), 100.0);
+        assert_eq!(tempo.tempo_at(10.0), 200.0);
+        assert_eq!(TempoMap::constant(90.0).tempo_at(123.0), 90.0);
+    }
+
+    #[test]
+    fn sequencer_steps_follow_the_tempo_map() {
+        let oscillator = WaveTableOscillator::new(48000, generate_wave_table(WaveShape::Sine, 256));
+        let tempo = TempoMap::accelerando(100.0, 200.0, 4.0);
+        let mut sequencer = CrossfadeSequencer::new(oscillator, vec![Some(440.0); 4], tempo, 1, 0.0);
+        // One step a beat: 100 BPM at beat 0, then 125 and 150 BPM
+        for (step, samples) in [(0, 28800), (1, 23040), (2, 19200)] {
+            assert_eq!((sequencer.step, sequencer.step_samples), (step, samples));
+            for _ in 0..samples {
+                sequencer.tick();
+            }
+        }
+        assert_eq!(sequencer.step, 3);
+    }
+}
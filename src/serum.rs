@@ -0,0 +1,69 @@
+use hound::{SampleFormat, WavReader};
+use std::fmt;
+use std::path::Path;
+
+/// Samples in each frame of a Serum wavetable `.wav`; the frames sit back to back.
+pub const SERUM_FRAME_SIZE: usize = 2048;
+
+#[derive(Debug)]
+pub enum SerumWavetableError {
+    Wav(hound::Error),
+    FrameOutOfRange { frame_index: usize, frame_count: usize },
+}
+
+impl fmt::Display for SerumWavetableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerumWavetableError::Wav(error) => write!(f, "wavetable error: {error}"),
+            SerumWavetableError::FrameOutOfRange {
+                frame_index,
+                frame_count,
+            } => write!(f, "wavetable frame {frame_index} out of range; the file has {frame_count}"),
+        }
+    }
+}
+
+impl std::error::Error for SerumWavetableError {}
+
+impl From<hound::Error> for SerumWavetableError {
+    fn from(error: hound::Error) -> Self {
+        SerumWavetableError::Wav(error)
+    }
+}
+
+/// How many whole frames a Serum wavetable holds.
+pub fn serum_frame_count(path: &Path) -> Result<usize, SerumWavetableError> {
+    let reader = WavReader::open(path)?;
+    Ok(reader.duration() as usize / SERUM_FRAME_SIZE)
+}
+
+/// Reads frame `frame_index` of a Serum wavetable as one cycle of
+/// [`SERUM_FRAME_SIZE`] samples. Integer files are scaled to -1.0-1.0, and only the
+/// first channel of a multichannel file is used.
+pub fn read_serum_frame(path: &Path, frame_index: usize) -> Result<Vec<f32>, SerumWavetableError> {
+    let mut reader = WavReader::open(path)?;
+    let frame_count = reader.duration() as usize / SERUM_FRAME_SIZE;
+    if frame_index >= frame_count {
+        return Err(SerumWavetableError::FrameOutOfRange {
+            frame_index,
+            frame_count,
+        });
+    }
+    reader.seek((frame_index * SERUM_FRAME_SIZE) as u32).map_err(hound::Error::IoError)?;
+
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let wanted = SERUM_FRAME_SIZE * channels;
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().take(wanted).collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let full_scale = (1_i64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .take(wanted)
+                .map(|sample| sample.map(|sample| sample as f32 / full_scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    Ok(samples.into_iter().step_by(channels).collect())
+}
@@ -0,0 +1,258 @@
+use super::Session;
+use crate::update_dashboard;
+use crossterm::cursor::{MoveTo, MoveUp};
+use crossterm::event::KeyCode;
+use crossterm::terminal::{Clear, ClearType};
+use exposrog::{
+    find_spectral_peaks, magnitude_spectrum, KeyFrequencyTable, NoteQuantizer, ScaleHighlighter, SynthError, Theme,
+    TuningSystem, WaveformPreview,
+};
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// Clears the screen for the keyboard layout guide, with `highlight` just played.
+fn draw_layout(
+    key_frequencies: &KeyFrequencyTable,
+    theme: &Theme,
+    highlight: Option<KeyCode>,
+    scale_lock: Option<NoteQuantizer>,
+) -> std::io::Result<()> {
+    print!("{}{}", Clear(ClearType::All), MoveTo(0, 0));
+    print!("Keyboard layout: press keys to hear them, ? or Esc to go back to playing\r\n\r\n");
+    let scale = scale_lock.map(ScaleHighlighter::new);
+    for row in key_frequencies.render_layout(&TuningSystem::default(), theme, highlight, scale.as_ref()) {
+        print!("{row}\r\n\r\n");
+    }
+    std::io::stdout().flush()
+}
+
+impl Session {
+    pub(crate) fn open_layout(&mut self) -> Result<(), SynthError> {
+        self.show_layout = true;
+        draw_layout(&self.key_frequencies, &self.theme, None, self.scale_lock)?;
+        Ok(())
+    }
+
+    /// Plays and lights the keys pressed on the layout guide until ? or Esc closes it.
+    pub(crate) fn press_layout_key(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        if let KeyCode::Char('?') | KeyCode::Esc = code {
+            self.show_layout = false;
+            print!("{}{}Back to playing\r\n", Clear(ClearType::All), MoveTo(0, 0));
+            return Ok(());
+        }
+        let Some(mut frequency) = self.key_frequencies.get(&code) else {
+            return Ok(());
+        };
+        if let Some(quantizer) = self.scale_lock {
+            let tuning = TuningSystem::default();
+            let note = tuning.nearest_note(frequency);
+            if !quantizer.contains(note) {
+                // Flash the key, then put the layout back
+                self.layout_flash = Some(Instant::now());
+            }
+            frequency = tuning.frequency(quantizer.quantize_note(note));
+        }
+        if let Ok(mut freq) = self.frequency_control.lock() {
+            *freq = frequency;
+        }
+        draw_layout(&self.key_frequencies, &self.theme, Some(code), self.scale_lock)?;
+        Ok(())
+    }
+
+    pub(crate) fn print_partials(&mut self) {
+        let samples: Vec<f32> = match self.scope_samples.lock() {
+            Ok(samples) => samples.iter().copied().collect(),
+            Err(_) => Vec::new(),
+        };
+        let peaks = find_spectral_peaks(&magnitude_spectrum(&samples), 44100, samples.len(), 5);
+        let partials: Vec<String> = ["1st", "2nd", "3rd", "4th", "5th"]
+            .iter()
+            .zip(&peaks)
+            .map(|(ordinal, (freq, db))| format!("{ordinal}: {freq:.1} Hz ({db:.1} dB)"))
+            .collect();
+        if partials.is_empty() {
+            print!("Partials: none\r\n");
+        } else {
+            print!("Partials: {}\r\n", partials.join(", "));
+        }
+    }
+
+    pub(crate) fn toggle_meter(&mut self) {
+        self.show_meter = !self.show_meter;
+        if !self.show_meter && !self.show_scope {
+            print!("\r\n");
+        }
+    }
+
+    pub(crate) fn toggle_scope(&mut self) {
+        self.show_scope = !self.show_scope;
+        if !self.show_meter && !self.show_scope {
+            print!("\r\n");
+        }
+    }
+
+    pub(crate) fn toggle_lissajous(&mut self) {
+        self.show_lissajous = !self.show_lissajous;
+        // All three draw below the status line
+        self.show_waveform = false;
+        self.show_tonnetz = false;
+        if !self.show_lissajous {
+            // Wipe the figure, which sits below the cursor
+            print!("{}", Clear(ClearType::FromCursorDown));
+        }
+    }
+
+    pub(crate) fn toggle_waveform_preview(&mut self) {
+        self.show_waveform = !self.show_waveform;
+        self.show_lissajous = false;
+        self.show_tonnetz = false;
+        self.waveform_version = None;
+        if !self.show_waveform {
+            print!("{}", Clear(ClearType::FromCursorDown));
+        }
+    }
+
+    pub(crate) fn toggle_tonnetz(&mut self) {
+        self.show_tonnetz = !self.show_tonnetz;
+        self.show_lissajous = false;
+        self.show_waveform = false;
+        self.tonnetz_dirty = true;
+        if !self.show_tonnetz {
+            print!("{}", Clear(ClearType::FromCursorDown));
+        }
+    }
+
+    pub(crate) fn scroll_tonnetz(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Left => self.tonnetz.shift(-1, 0),
+            KeyCode::Right => self.tonnetz.shift(1, 0),
+            KeyCode::Up => self.tonnetz.shift(0, 1),
+            _ => self.tonnetz.shift(0, -1),
+        }
+        self.tonnetz_dirty = true;
+    }
+
+    /// Lights the note at `frequency` on the lattice.
+    pub(crate) fn light_tonnetz(&mut self, frequency: f32) {
+        // The lattice centres on the scale's root, C without one
+        let root = self.scale_lock.map_or(0, |quantizer| quantizer.root);
+        if root != self.tonnetz.root() {
+            self.tonnetz.set_root(root);
+        }
+        self.tonnetz.set_lit([TuningSystem::default().nearest_note(frequency) % 12]);
+        self.tonnetz_dirty = true;
+    }
+
+    /// Refreshes the peak meter and scope at roughly 60 fps, following the microphone
+    /// and applying controller moves on the same beat.
+    pub(crate) fn refresh_meter(&mut self) -> Result<(), SynthError> {
+        let elapsed = self.last_meter_update.elapsed();
+        if elapsed < Duration::from_millis(16) {
+            return Ok(());
+        }
+        self.last_meter_update = Instant::now();
+        let peak = self.peak_reader.poll(elapsed);
+        let mic_level = self.mic_level();
+        self.follow_microphone(mic_level);
+        self.apply_control_changes();
+        let clip_count = self.clip_counter.load(Ordering::Relaxed);
+        let clipping = clip_count != self.last_clip_count;
+        self.last_clip_count = clip_count;
+        if self.show_layout || self.dashboard.is_some() {
+            return Ok(());
+        }
+        let theme = &self.theme;
+        if self.show_meter {
+            let width = 40;
+            let filled = ((peak.min(1.0) * width as f32) as usize).min(width);
+            let db = 20.0 * peak.max(1e-5).log10();
+            let clip = theme.paint(if clipping { " CLIP" } else { "     " }, theme.error);
+            // The bar turns to the warning color above -6 dB
+            let bar_color = if peak >= 0.5 { theme.warning } else { theme.highlight };
+            let bar = theme.paint(&"#".repeat(filled), bar_color);
+            print!("\rPeak [{}{}] {:6.1} dB{}", bar, " ".repeat(width - filled), db, clip);
+            // Past 80% the callback is close to missing its deadline
+            let cpu_load = self.cpu_monitor.cpu_load_percent();
+            let cpu_color = if cpu_load > 80.0 { theme.error } else { theme.secondary };
+            print!("  {}", theme.paint(&format!("CPU: {:3.0}%", cpu_load), cpu_color));
+            if let Some(level) = mic_level {
+                let mic_width = 20;
+                let mic_filled = ((level.min(1.0) * mic_width as f32) as usize).min(mic_width);
+                let mic_bar = theme.paint(&"#".repeat(mic_filled), theme.secondary);
+                print!("  Mic [{}{}]", mic_bar, " ".repeat(mic_width - mic_filled));
+            }
+            if self.playing_midi_file {
+                let chord = self.midi_chord.lock().ok().and_then(|chord| *chord);
+                print!("  Chord [{:<9}]", chord.map_or(String::new(), |chord| chord.to_string()));
+            }
+        }
+        if self.show_scope {
+            if !self.show_meter {
+                print!("\r");
+            }
+            print!("  Scope [{}]", theme.paint(&self.oscilloscope.render(), theme.secondary));
+        }
+        if self.show_meter || self.show_scope {
+            std::io::stdout().flush()?;
+        }
+        Ok(())
+    }
+
+    /// Redraws whichever figure sits below the status line, and the dashboard's wave
+    /// table, when it has changed.
+    pub(crate) fn redraw_displays(&mut self) -> Result<(), SynthError> {
+        if self.layout_flash.is_some_and(|flashed| flashed.elapsed() >= Duration::from_millis(150)) {
+            self.layout_flash = None;
+            if self.show_layout {
+                draw_layout(&self.key_frequencies, &self.theme, None, self.scale_lock)?;
+            }
+        }
+        let drawing = !self.show_layout && self.dashboard.is_none();
+
+        // Redraw the X-Y figure below the status line at 30 fps, then return to it
+        let lissajous_due = self.last_lissajous_update.elapsed() >= Duration::from_millis(33);
+        if self.show_lissajous && drawing && lissajous_due {
+            self.last_lissajous_update = Instant::now();
+            if let Ok(frames) = self.stereo_frames.lock() {
+                self.lissajous.extend(frames.iter().copied());
+            }
+            print!("\r\n{}{}\r", self.lissajous.render(), MoveUp(self.lissajous.height()));
+            std::io::stdout().flush()?;
+        }
+
+        // Plot the oscillator's wave table below the status line whenever it changes
+        let version = self.wave_table_version.load(Ordering::Acquire);
+        if self.dashboard.is_some() && self.dashboard_table_version != Some(version) {
+            self.dashboard_table_version = Some(version);
+            let table = self.dynamic_table.current().read().map(|table| table.clone()).unwrap_or_default();
+            update_dashboard(|state| state.wave_table = table);
+        }
+        if self.show_waveform && drawing && self.waveform_version != Some(version) {
+            self.waveform_version = Some(version);
+            let (columns, _) = crossterm::terminal::size().unwrap_or((80, 24));
+            let preview = WaveformPreview::new(columns.saturating_sub(1), 11);
+            let table = self.dynamic_table.current().read().map(|table| table.clone()).unwrap_or_default();
+            print!("\r\n{}{}\r", preview.render(&table), MoveUp(preview.height()));
+            std::io::stdout().flush()?;
+        }
+
+        // A chord from the MIDI file takes over the lattice while it sounds
+        if self.show_tonnetz && self.playing_midi_file {
+            let chord = self.midi_chord.lock().ok().and_then(|chord| *chord);
+            if chord != self.tonnetz_chord {
+                self.tonnetz_chord = chord;
+                if let Some(chord) = chord {
+                    self.tonnetz.show_chord(chord);
+                    self.tonnetz_dirty = true;
+                }
+            }
+        }
+        if self.show_tonnetz && drawing && self.tonnetz_dirty {
+            self.tonnetz_dirty = false;
+            print!("\r\n{}{}\r", self.tonnetz.render(), MoveUp(self.tonnetz.height()));
+            std::io::stdout().flush()?;
+        }
+        Ok(())
+    }
+}
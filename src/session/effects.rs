@@ -0,0 +1,139 @@
+use super::{toggle, Session};
+use crossterm::event::KeyCode;
+use exposrog::{DelayTime, EffectType, HarmonyPreset, ShaperPreset};
+use std::sync::atomic::Ordering;
+
+impl Session {
+    pub(crate) fn toggle_effect(&mut self, effect: EffectType) {
+        let control = match effect {
+            EffectType::Filter => &self.filter_enabled_control,
+            EffectType::Echo => &self.echo_control,
+            EffectType::Chorus => &self.chorus_control,
+            EffectType::Widener => &self.widener_control,
+            EffectType::BusCompressor => &self.bus_compressor_control,
+            EffectType::Gate => &self.gate_enabled_control,
+            EffectType::Freeze => &self.freeze_control,
+            EffectType::TapeStop => &self.tape_stop_control,
+            EffectType::LowPass => &self.low_pass_enabled_control,
+            EffectType::HighPass => &self.high_pass_enabled_control,
+        };
+        toggle(control, effect.name());
+    }
+
+    pub(crate) fn toggle_tremolo_sync(&mut self) {
+        if let Ok(mut synced) = self.tremolo_synced_control.lock() {
+            *synced = !*synced;
+            print!("Tremolo: {}\r\n", if *synced { "synced to eighth notes" } else { "free at 5 Hz" });
+        }
+    }
+
+    pub(crate) fn toggle_tape_stop(&mut self) {
+        if let Ok(mut engaged) = self.tape_stop_control.lock() {
+            *engaged = !*engaged;
+            print!("Tape: {}\r\n", if *engaged { "stopping" } else { "starting" });
+        }
+    }
+
+    pub(crate) fn trigger_stutter(&mut self) {
+        if let Ok(mut trigger) = self.stutter_trigger.lock() {
+            *trigger = true;
+        }
+    }
+
+    pub(crate) fn cycle_harmonizer(&mut self) {
+        if let Ok(mut preset) = self.harmony_control.lock() {
+            *preset = HarmonyPreset::cycle(*preset);
+            print!("Harmonizer: {}\r\n", preset.map_or("off", |preset| preset.name()));
+        }
+    }
+
+    pub(crate) fn step_echo_time(&mut self) {
+        // Steps 300 ms -> each standard synced time -> 300 ms
+        self.echo_time_index = match self.echo_time_index {
+            None => Some(0),
+            Some(i) if i + 1 < DelayTime::STANDARD_SYNCED.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        let time = self
+            .echo_time_index
+            .map_or(DelayTime::Milliseconds(300.0), |i| DelayTime::STANDARD_SYNCED[i]);
+        if let Ok(mut echo_time) = self.echo_time_control.lock() {
+            *echo_time = time;
+        }
+        print!("Echo time: {time}\r\n");
+    }
+
+    pub(crate) fn adjust_echo_time(&mut self, step: f32) {
+        if let Ok(mut echo_time) = self.echo_time_control.lock() {
+            // A synced time steps on from the default as a fixed one
+            let ms = match *echo_time {
+                DelayTime::Milliseconds(ms) => ms,
+                DelayTime::Synced { .. } => 300.0,
+            };
+            *echo_time = DelayTime::Milliseconds((ms + step).clamp(25.0, 2000.0));
+            self.echo_time_index = None;
+            print!("Echo time: {}\r\n", *echo_time);
+        }
+    }
+
+    pub(crate) fn adjust_echo_feedback(&mut self, step: f32) {
+        if let Ok(mut feedback) = self.echo_feedback_control.lock() {
+            *feedback = (*feedback + step).clamp(0.0, 0.95);
+            print!("Echo feedback: {:.0}%\r\n", *feedback * 100.0);
+        }
+    }
+
+    pub(crate) fn cycle_shaper(&mut self) {
+        self.shaper_index = match self.shaper_index {
+            Some(index) if index + 1 < self.shaper_controls.len() => Some(index + 1),
+            Some(_) => None,
+            None => Some(0),
+        };
+        for (index, (enabled, _)) in self.shaper_controls.iter().enumerate() {
+            if let Ok(mut enabled) = enabled.lock() {
+                *enabled = self.shaper_index == Some(index);
+            }
+        }
+        match self.shaper_index {
+            Some(index) => {
+                let drive = self.shaper_controls[index].1.lock().map_or(1.0, |drive| *drive);
+                print!("Waveshaper: {} at drive {drive:.2}\r\n", ShaperPreset::ALL[index]);
+            }
+            None => print!("Waveshaper: off\r\n"),
+        }
+    }
+
+    pub(crate) fn scale_shaper_drive(&mut self, ratio: f32) {
+        match self.shaper_index {
+            Some(index) => {
+                if let Ok(mut drive) = self.shaper_controls[index].1.lock() {
+                    *drive = (*drive * ratio).clamp(0.1, 20.0);
+                    print!("Waveshaper drive: {:.2}\r\n", *drive);
+                }
+            }
+            None => print!("Waveshaper is off; pick a preset first\r\n"),
+        }
+    }
+
+    /// Alt+Left pans left and Alt+Right right, taking over from register panning.
+    pub(crate) fn pan(&mut self, code: KeyCode) {
+        let step = if code == KeyCode::Left { -0.1 } else { 0.1 };
+        let pan = (f32::from_bits(self.pan_control.load(Ordering::Relaxed)) + step).clamp(-1.0, 1.0);
+        self.pan_control.store(pan.to_bits(), Ordering::Relaxed);
+        let overridden = std::mem::take(&mut self.register_panning);
+        print!("Pan: {pan:+.1}{}\r\n", if overridden { ", register pan off" } else { "" });
+    }
+
+    pub(crate) fn toggle_register_pan(&mut self) {
+        self.register_panning = !self.register_panning;
+        let layout = if self.register_panning { "bass left, treble right" } else { "off" };
+        print!("Register pan: {layout}\r\n");
+    }
+
+    pub(crate) fn balance(&mut self, code: KeyCode) {
+        let step = if code == KeyCode::Char('<') { -0.1 } else { 0.1 };
+        let balance = (f32::from_bits(self.balance_control.load(Ordering::Relaxed)) + step).clamp(-1.0, 1.0);
+        self.balance_control.store(balance.to_bits(), Ordering::Relaxed);
+        print!("Balance: {balance:+.1}\r\n");
+    }
+}
@@ -0,0 +1,118 @@
+use super::Session;
+use exposrog::{
+    Lfo, LfoPolarity, LfoShape, OvertoneFilter, OvertonePreset, ResonatorBank, SELF_OSCILLATION_THRESHOLD,
+};
+use rand::Rng;
+use std::sync::Mutex;
+
+/// Multiplies a filter's cutoff by `ratio`, keeping it in the audible range.
+fn scale_cutoff(cutoff: &Mutex<f32>, ratio: f32, name: &str) {
+    if let Ok(mut cutoff) = cutoff.lock() {
+        *cutoff = (*cutoff * ratio).clamp(20.0, 20000.0);
+        print!("{name} cutoff: {:.0} Hz\r\n", *cutoff);
+    }
+}
+
+impl Session {
+    pub(crate) fn step_resonance(&mut self) {
+        if let Ok(mut resonance) = self.filter_resonance_control.lock() {
+            *resonance = if *resonance >= SELF_OSCILLATION_THRESHOLD {
+                0.0
+            } else {
+                (*resonance + 0.1).min(1.0)
+            };
+            let status = if *resonance >= SELF_OSCILLATION_THRESHOLD { " [SELF-OSC]" } else { "" };
+            print!("Filter resonance: {:.1}{}\r\n", *resonance, status);
+        }
+    }
+
+    pub(crate) fn cycle_filter_tracking(&mut self) {
+        if let Ok(mut tracking) = self.filter_tracking_control.lock() {
+            tracking.keyboard_tracking = match tracking.keyboard_tracking {
+                t if t < 0.25 => 0.5,
+                t if t < 0.75 => 1.0,
+                _ => 0.0,
+            };
+            print!("Filter keyboard tracking: {:.0}%\r\n", tracking.keyboard_tracking * 100.0);
+        }
+    }
+
+    pub(crate) fn cycle_filter_lfo(&mut self) {
+        // Off, then a slow 2 kHz sweep in each polarity
+        if let Ok(mut lfo) = self.filter_lfo_control.lock() {
+            let polarity = match lfo.map(|lfo| lfo.polarity) {
+                None => Some(LfoPolarity::Bipolar),
+                Some(LfoPolarity::Bipolar) => Some(LfoPolarity::Unipolar),
+                Some(LfoPolarity::Unipolar) => Some(LfoPolarity::InvertedUnipolar),
+                Some(LfoPolarity::InvertedUnipolar) => None,
+            };
+            *lfo = polarity.map(|polarity| {
+                let mut lfo = Lfo::new(44100, 0.5, 2000.0, polarity);
+                lfo.shape = self.filter_lfo_shape;
+                lfo.set_seed(self.rng.gen());
+                lfo
+            });
+            match lfo.as_ref() {
+                Some(lfo) => print!("Filter LFO: {:?} {:?}\r\n", lfo.polarity, lfo.shape),
+                None => print!("Filter LFO: off\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn cycle_filter_lfo_shape(&mut self) {
+        self.filter_lfo_shape = match self.filter_lfo_shape {
+            LfoShape::Sine => LfoShape::SampleAndHold,
+            LfoShape::SampleAndHold => LfoShape::SmoothSampleAndHold,
+            LfoShape::SmoothSampleAndHold => LfoShape::Sine,
+        };
+        if let Ok(mut lfo) = self.filter_lfo_control.lock() {
+            if let Some(lfo) = lfo.as_mut() {
+                lfo.shape = self.filter_lfo_shape;
+            }
+        }
+        print!("Filter LFO shape: {:?}\r\n", self.filter_lfo_shape);
+    }
+
+    pub(crate) fn cycle_overtone_filter(&mut self) {
+        // Off, then each preset in turn
+        self.overtone_preset = match self.overtone_preset {
+            None => Some(0),
+            Some(i) if i + 1 < OvertonePreset::ALL.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        let preset = self.overtone_preset.map(|i| OvertonePreset::ALL[i]);
+        if let Ok(mut filter) = self.overtone_control.lock() {
+            *filter = preset.map(OvertoneFilter::from);
+        }
+        match preset {
+            Some(preset) => print!("Overtone filter: {preset:?} {:?}\r\n", preset.harmonics()),
+            None => print!("Overtone filter: off\r\n"),
+        }
+    }
+
+    pub(crate) fn cycle_body_resonance(&mut self) {
+        self.resonator_body = match self.resonator_body {
+            "off" => "guitar",
+            "guitar" => "piano",
+            _ => "off",
+        };
+        if let (Ok(mut enabled), Ok(mut bank)) =
+            (self.resonator_enabled_control.lock(), self.resonator_bank_control.lock())
+        {
+            *enabled = self.resonator_body != "off";
+            *bank = match self.resonator_body {
+                "piano" => ResonatorBank::piano(44100),
+                _ => ResonatorBank::guitar_body(44100),
+            };
+        }
+        print!("Body resonance: {}\r\n", self.resonator_body);
+    }
+
+    pub(crate) fn scale_low_pass_cutoff(&mut self, ratio: f32) {
+        scale_cutoff(&self.low_pass_cutoff_control, ratio, "Low-pass");
+    }
+
+    pub(crate) fn scale_high_pass_cutoff(&mut self, ratio: f32) {
+        scale_cutoff(&self.high_pass_cutoff_control, ratio, "High-pass");
+    }
+}
@@ -0,0 +1,40 @@
+use super::Session;
+use exposrog::{detect_pitch_autocorrelation, ModulationSource};
+
+impl Session {
+    pub(crate) fn toggle_mic_cutoff(&mut self) {
+        self.cutoff_modulation = match self.cutoff_modulation {
+            ModulationSource::Off => ModulationSource::Microphone,
+            ModulationSource::Microphone => ModulationSource::Off,
+        };
+        print!("Filter cutoff modulation: {:?}\r\n", self.cutoff_modulation);
+    }
+
+    pub(crate) fn toggle_auto_follow(&mut self) {
+        self.auto_follow = !self.auto_follow;
+        print!("Auto-follow: {}\r\n", if self.auto_follow { "on" } else { "off" });
+    }
+
+    /// The microphone's recent level, if there is one.
+    pub(crate) fn mic_level(&self) -> Option<f32> {
+        self.microphone.as_ref().map(|mic| mic.rms(mic.sample_rate as usize / 20))
+    }
+
+    /// Moves the filter cutoff with `mic_level` and the note with the sung pitch,
+    /// whichever of them are on.
+    pub(crate) fn follow_microphone(&mut self, mic_level: Option<f32>) {
+        if let (ModulationSource::Microphone, Some(level)) = (self.cutoff_modulation, mic_level) {
+            if let Ok(mut cutoff) = self.filter_cutoff_control.lock() {
+                *cutoff = 200.0 + level.min(1.0) * 8000.0;
+            }
+        }
+        if let (true, Some(mic)) = (self.auto_follow, self.microphone.as_ref()) {
+            // Hold the last pitch through gaps and chords instead of dropping out
+            if let Some(pitch) = detect_pitch_autocorrelation(&mic.latest(4096), mic.sample_rate) {
+                if let Ok(mut freq) = self.frequency_control.lock() {
+                    *freq = pitch;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::Session;
+use exposrog::{freq_to_midi_note, CcTarget, MidiFileEvent, MidiFileRecorder, MidiOutput, MidiPort, SUSTAIN_CC};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Sends a played note or pedal event to the MIDI recording and the MIDI output
+/// port, whichever are open. A port that stops taking messages is closed rather than
+/// stopping the synth.
+pub(crate) fn send_midi_event(
+    recorder: Option<&Mutex<MidiFileRecorder>>,
+    port: &mut Option<MidiPort>,
+    event: MidiFileEvent,
+) -> std::io::Result<()> {
+    if let Some(connection) = port.as_mut() {
+        if let Err(error) = connection.send(&event.message()) {
+            print!("MIDI output to {} failed, closing it: {error}\r\n", connection.name());
+            *port = None;
+        }
+    }
+    if let Some(Ok(mut recorder)) = recorder.map(|recorder| recorder.lock()) {
+        recorder.record(event)?;
+    }
+    Ok(())
+}
+
+impl Session {
+    pub(crate) fn send_midi(&mut self, event: MidiFileEvent) -> std::io::Result<()> {
+        send_midi_event(self.midi_recorder.as_deref(), &mut self.midi_port, event)
+    }
+
+    /// The pedal goes out over MIDI as CC 64 on channel 1.
+    pub(crate) fn send_pedal(&mut self, active: bool) -> std::io::Result<()> {
+        self.send_midi(MidiFileEvent::ControlChange {
+            controller: SUSTAIN_CC,
+            value: if active { 127 } else { 0 },
+        })
+    }
+
+    /// Sends the mono note played at `frequency`, ending the one before it.
+    pub(crate) fn send_mono_note(&mut self, frequency: f32, velocity: u8) -> std::io::Result<()> {
+        if self.midi_recorder.is_none() && self.midi_port.is_none() {
+            return Ok(());
+        }
+        // Without release events each note ends when the next begins
+        let note = freq_to_midi_note(frequency as f64);
+        if let Some(previous) = self.midi_note.replace(note) {
+            self.send_midi(MidiFileEvent::NoteOff { note: previous })?;
+        }
+        self.send_midi(MidiFileEvent::NoteOn { note, velocity })
+    }
+
+    /// Applies the controller moves that came in since the last call.
+    pub(crate) fn apply_control_changes(&mut self) {
+        let mut volume = None;
+        for &(target, ref control) in &self.cc_controls {
+            let value = f32::from_bits(control.swap(f32::NAN.to_bits(), Ordering::Relaxed));
+            if value.is_nan() {
+                continue;
+            }
+            match target {
+                CcTarget::Volume => volume = Some(value),
+                CcTarget::FilterCutoff => {
+                    if let Ok(mut cutoff) = self.filter_cutoff_control.lock() {
+                        *cutoff = value;
+                    }
+                }
+                CcTarget::FilterResonance => {
+                    if let Ok(mut resonance) = self.filter_resonance_control.lock() {
+                        *resonance = value;
+                    }
+                }
+                CcTarget::EnvAttack | CcTarget::EnvDecay | CcTarget::EnvSustain | CcTarget::EnvRelease => {
+                    if let Ok(mut envelope) = self.envelope_control.lock() {
+                        match target {
+                            CcTarget::EnvAttack => envelope.attack_secs = value,
+                            CcTarget::EnvDecay => envelope.decay_secs = value,
+                            CcTarget::EnvSustain => envelope.sustain_level = value,
+                            _ => envelope.release_secs = value,
+                        }
+                    }
+                }
+                CcTarget::LfoRate | CcTarget::LfoDepth => {
+                    if let Ok(mut lfo) = self.lfo_control.lock() {
+                        match target {
+                            CcTarget::LfoRate => lfo.rate_hz = value,
+                            _ => lfo.depth = value,
+                        }
+                    }
+                }
+                CcTarget::ModIndex => {
+                    if let Ok(mut preset) = self.fm_preset_control.lock() {
+                        preset.mod_index = value;
+                    }
+                }
+                CcTarget::PulseWidth => {
+                    if let Ok(mut params) = self.wave_params_control.lock() {
+                        params.pulse_width = value;
+                        self.dynamic_table.mark_dirty();
+                    }
+                }
+                CcTarget::GlideTime => {
+                    // Turning the knob to zero leaves the toggle's last glide alone
+                    let ms = (value * 1000.0).round() as u32;
+                    if ms > 0 {
+                        self.portamento_ms = ms;
+                    }
+                    if let Ok(mut glide) = self.portamento_control.lock() {
+                        *glide = ms;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(volume) = volume {
+            self.set_master_volume(volume);
+        }
+    }
+}
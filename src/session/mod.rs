@@ -0,0 +1,687 @@
+mod display;
+mod effects;
+mod filter;
+mod microphone;
+mod midi;
+mod notes;
+mod oscillator;
+mod prompts;
+mod recording;
+mod sequencing;
+mod shortcuts;
+mod voices;
+
+pub(crate) use midi::send_midi_event;
+pub(crate) use prompts::RemapState;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind};
+use exposrog::{
+    AbComparison, AdsrEnvelope, Arpeggiator, AudioInput, CcTarget, ChordName, CpuMonitor, Dashboard, DelayTime,
+    DynamicWaveTable, FilterTrackingMode, FmPreset, Gate, HarmonyPreset, IntervalTrainer, KeyFrequencyTable,
+    KeyRepeatSuppressor, Lfo, LfoShape, LfoTarget, LissajousDisplay, LiveLooper, MacroBank, MacroPlayer,
+    MacroRecorder, MidiFileRecorder, MidiPort, ModulationSource, NoteQuantizer, NoteSequencer, NoteVelocityMapper,
+    NumpadKey, Oscilloscope, OvertoneFilter, PatchControls, PatchMemory, PeakReader, PercKind, PolyAftertouch,
+    PolyphonyMode, ResonatorBank, ScaleChooser, ShortcutLayer, StepSequencer, SubOscillatorMode, SustainController,
+    SustainPedalSimulator, SynthError, TempoTapper, Theme, TonnetzDisplay, VoicePool, WavSessionRecorder,
+    WaveParams, WaveShape,
+};
+use rand::rngs::StdRng;
+use rodio::Sink;
+use std::collections::{HashSet, VecDeque};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, AtomicUsize};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A waveshaper's on/off and drive controls.
+type ShaperControls = (Arc<Mutex<bool>>, Arc<Mutex<f32>>);
+
+/// Everything the keys act on while the synth plays: the controls into the audio chain
+/// and the sinks `main` sets up, and the state of the session around them.
+pub(crate) struct Session {
+    // The main voice
+    pub(crate) sink: Sink,
+    pub(crate) frequency_control: Arc<Mutex<f32>>,
+    pub(crate) amplitude_control: Arc<Mutex<f32>>,
+    pub(crate) sub_oscillator_control: Arc<Mutex<Option<SubOscillatorMode>>>,
+    pub(crate) drift_control: Arc<Mutex<bool>>,
+    pub(crate) lfo_control: Arc<Mutex<Lfo>>,
+    pub(crate) lfo_target_control: Arc<Mutex<Option<LfoTarget>>>,
+    pub(crate) portamento_control: Arc<Mutex<u32>>,
+    /// The glide Alt+R turns back on, changed by the glide time CC
+    pub(crate) portamento_ms: u32,
+    pub(crate) unison_voices_control: Arc<Mutex<usize>>,
+    pub(crate) envelope_control: Arc<Mutex<AdsrEnvelope>>,
+    pub(crate) envelope_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) dynamic_table: DynamicWaveTable,
+    pub(crate) wave_params_control: Arc<Mutex<WaveParams>>,
+    pub(crate) wave_table_version: Arc<AtomicUsize>,
+    /// The `--serum-wavetable` file and how many frames it holds
+    pub(crate) serum_frames: Option<(PathBuf, usize)>,
+    pub(crate) wavetable_mode: bool,
+
+    // Filters and shapers
+    pub(crate) resonator_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) resonator_bank_control: Arc<Mutex<ResonatorBank>>,
+    pub(crate) resonator_body: &'static str,
+    pub(crate) filter_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) filter_cutoff_control: Arc<Mutex<f32>>,
+    pub(crate) filter_resonance_control: Arc<Mutex<f32>>,
+    pub(crate) filter_tracking_control: Arc<Mutex<FilterTrackingMode>>,
+    pub(crate) filter_lfo_control: Arc<Mutex<Option<Lfo>>>,
+    pub(crate) filter_lfo_shape: LfoShape,
+    pub(crate) overtone_control: Arc<Mutex<Option<OvertoneFilter>>>,
+    pub(crate) overtone_preset: Option<usize>,
+    /// One shaper for each of `ShaperPreset::ALL`, with at most one of them on
+    pub(crate) shaper_controls: [ShaperControls; 3],
+    pub(crate) shaper_index: Option<usize>,
+    pub(crate) low_pass_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) low_pass_cutoff_control: Arc<Mutex<f32>>,
+    pub(crate) high_pass_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) high_pass_cutoff_control: Arc<Mutex<f32>>,
+
+    // Effects
+    pub(crate) harmony_control: Arc<Mutex<Option<HarmonyPreset>>>,
+    pub(crate) microphone: Option<AudioInput>,
+    pub(crate) mic_through_control: Arc<Mutex<bool>>,
+    pub(crate) vocoder_control: Arc<Mutex<bool>>,
+    pub(crate) cutoff_modulation: ModulationSource,
+    pub(crate) auto_follow: bool,
+    pub(crate) looper_control: Arc<Mutex<LiveLooper>>,
+    pub(crate) freeze_control: Arc<Mutex<bool>>,
+    pub(crate) echo_control: Arc<Mutex<bool>>,
+    pub(crate) echo_time_control: Arc<Mutex<DelayTime>>,
+    pub(crate) echo_feedback_control: Arc<Mutex<f32>>,
+    pub(crate) echo_bpm_control: Arc<Mutex<f32>>,
+    pub(crate) echo_time_index: Option<usize>,
+    pub(crate) tape_stop_control: Arc<Mutex<bool>>,
+    pub(crate) stutter_trigger: Arc<Mutex<bool>>,
+    pub(crate) stutter_bpm_control: Arc<Mutex<f32>>,
+    pub(crate) tremolo_control: Arc<Mutex<bool>>,
+    pub(crate) tremolo_synced_control: Arc<Mutex<bool>>,
+    pub(crate) gate_enabled_control: Arc<Mutex<bool>>,
+    pub(crate) gate_control: Arc<Mutex<Gate>>,
+    /// Pan and balance, -1.0 to 1.0 in f32 bits
+    pub(crate) pan_control: Arc<AtomicU32>,
+    pub(crate) balance_control: Arc<AtomicU32>,
+    /// While on, each mono note pans itself by pitch until the pan is set by hand
+    pub(crate) register_panning: bool,
+    pub(crate) chorus_control: Arc<Mutex<bool>>,
+    pub(crate) widener_control: Arc<Mutex<bool>>,
+    pub(crate) bus_compressor_control: Arc<Mutex<bool>>,
+    pub(crate) wav_recorder: WavSessionRecorder,
+
+    // Meters and displays
+    pub(crate) theme: Theme,
+    pub(crate) scope_samples: Arc<Mutex<VecDeque<f32>>>,
+    pub(crate) oscilloscope: Oscilloscope,
+    pub(crate) stereo_frames: Arc<Mutex<VecDeque<(f32, f32)>>>,
+    pub(crate) lissajous: LissajousDisplay,
+    pub(crate) peak_reader: PeakReader,
+    pub(crate) cpu_monitor: CpuMonitor,
+    pub(crate) clip_counter: Arc<AtomicUsize>,
+    pub(crate) last_clip_count: usize,
+    pub(crate) dashboard: Option<Dashboard>,
+    /// The wave table version the dashboard last got
+    pub(crate) dashboard_table_version: Option<usize>,
+    pub(crate) show_meter: bool,
+    pub(crate) show_scope: bool,
+    pub(crate) show_lissajous: bool,
+    pub(crate) show_waveform: bool,
+    /// The wave table version last plotted, so the preview only redraws on a change
+    pub(crate) waveform_version: Option<usize>,
+    pub(crate) show_layout: bool,
+    /// When an out-of-scale key was last flashed on the layout
+    pub(crate) layout_flash: Option<Instant>,
+    pub(crate) show_tonnetz: bool,
+    pub(crate) tonnetz: TonnetzDisplay,
+    pub(crate) tonnetz_dirty: bool,
+    /// The file's chord last drawn, so a held chord doesn't redraw every frame
+    pub(crate) tonnetz_chord: Option<ChordName>,
+    pub(crate) last_meter_update: Instant,
+    pub(crate) last_lissajous_update: Instant,
+
+    // The other voices, each on a sink of its own
+    pub(crate) supersaw_sink: Sink,
+    pub(crate) supersaw_detune_control: Arc<Mutex<f32>>,
+    pub(crate) supersaw_mix_control: Arc<Mutex<f32>>,
+    pub(crate) fm_sink: Sink,
+    pub(crate) fm_preset_control: Arc<Mutex<FmPreset>>,
+    pub(crate) fm_preset_index: Option<usize>,
+    pub(crate) string_sink: Sink,
+    pub(crate) string_loss_control: Arc<Mutex<f32>>,
+    pub(crate) additive_sink: Sink,
+    pub(crate) additive_partials_control: Arc<Mutex<Vec<f32>>>,
+    pub(crate) additive_preset_index: Option<usize>,
+    pub(crate) poly_sink: Sink,
+    /// The polyphonic voices' clones share this level
+    pub(crate) poly_amplitude_control: Arc<Mutex<f32>>,
+    pub(crate) voice_pool_control: Arc<Mutex<VoicePool>>,
+    pub(crate) aftertouch: PolyAftertouch,
+    pub(crate) poly_mode: Option<PolyphonyMode>,
+    pub(crate) drum_triggers: Arc<Mutex<Vec<PercKind>>>,
+    pub(crate) drum_mode: bool,
+    pub(crate) patch_memory: PatchMemory,
+    pub(crate) patch_controls: PatchControls,
+    pub(crate) ab_comparison: Option<AbComparison>,
+
+    // Sequencing
+    pub(crate) step_sequencer: StepSequencer,
+    pub(crate) arpeggiator: Arpeggiator,
+    pub(crate) arp_enabled: bool,
+    pub(crate) last_arp_tick: Instant,
+    pub(crate) tempo_tapper: TempoTapper,
+    pub(crate) note_sequencer: Arc<Mutex<NoteSequencer>>,
+    /// The sequencer's voice and the drums, mixed
+    pub(crate) backing_sink: Sink,
+    pub(crate) sequencer_recording: bool,
+
+    // Playing notes
+    pub(crate) key_frequencies: KeyFrequencyTable,
+    /// Octaves the key map is transposed by, -4 to 4
+    pub(crate) octave_offset: i32,
+    pub(crate) scale_lock: Option<NoteQuantizer>,
+    pub(crate) random_pitch: bool,
+    /// Played notes set the level through their guessed velocity, under the CC volume
+    pub(crate) velocity_mapper: NoteVelocityMapper,
+    pub(crate) master_volume: f32,
+    pub(crate) note_amplitude: f32,
+    /// The voice each held key plays, so its release ends the right note, and those
+    /// that sustain holds on after their keys
+    pub(crate) sustain: SustainController,
+    pub(crate) sustain_pedal: SustainPedalSimulator,
+    pub(crate) sustain_key: KeyCode,
+    /// Keys holding the mono note, which releases its envelope once they're all up
+    pub(crate) held_notes: HashSet<KeyCode>,
+    /// The mono note outlasting its keys while the pedal is down
+    pub(crate) pedal_held_note: bool,
+    pub(crate) key_repeats: Option<KeyRepeatSuppressor>,
+    pub(crate) keyboard_enhanced: bool,
+    /// Xorshift state for the frequencies of unmapped keys, never zero
+    pub(crate) unmapped_rng: u64,
+    pub(crate) rng: StdRng,
+    /// Macros send no releases, so each of their notes ends when the next begins
+    pub(crate) macro_voice: Option<usize>,
+    /// The newest note, for the mouse wheel's aftertouch
+    pub(crate) last_voice: Option<usize>,
+    /// Frequencies to set at given times, for tones the app plays by itself
+    pub(crate) scheduled_tones: VecDeque<(Instant, f32)>,
+
+    // Prompts that take over the keyboard while they're open
+    pub(crate) remap_state: RemapState,
+    pub(crate) scale_chooser: Option<ScaleChooser>,
+    pub(crate) patch_input: Option<String>,
+    pub(crate) gate_pattern_input: Option<String>,
+    pub(crate) interval_trainer: Option<IntervalTrainer>,
+    pub(crate) trainer_guess: String,
+    pub(crate) trainer_intervals: Vec<u8>,
+
+    // Macros and shortcut layers
+    pub(crate) shortcut_layer: ShortcutLayer,
+    pub(crate) macro_bank: MacroBank,
+    pub(crate) macro_recorder: MacroRecorder,
+    pub(crate) macro_player: MacroPlayer,
+    pub(crate) macro_mode: bool,
+    /// The slot Ctrl+M records into and Ctrl+Shift+M plays
+    pub(crate) macro_slot: usize,
+
+    // MIDI in and out
+    /// Controller moves, to apply with the meter refresh
+    pub(crate) cc_controls: Vec<(CcTarget, Arc<AtomicU32>)>,
+    /// The chord the `--midi-file` notes held together name
+    pub(crate) midi_chord: Arc<Mutex<Option<ChordName>>>,
+    pub(crate) playing_midi_file: bool,
+    pub(crate) midi_port: Option<MidiPort>,
+    pub(crate) midi_recorder: Option<Arc<Mutex<MidiFileRecorder>>>,
+    /// The mono note last sent over MIDI
+    pub(crate) midi_note: Option<u8>,
+}
+
+impl Session {
+    /// Runs whatever `key_event` is bound to, from the keyboard or a macro, breaking on
+    /// Esc. The bindings are tried in order, so a mode or prompt shadows those below it.
+    pub(crate) fn handle_key(&mut self, key_event: KeyEvent, from_macro: bool) -> Result<ControlFlow<()>, SynthError> {
+        let KeyEvent { code, modifiers, kind, .. } = key_event;
+        let fresh_press = from_macro || self.key_repeats.as_mut().is_none_or(|repeats| repeats.handle(&key_event));
+        let numpad = NumpadKey::from_key_event(&key_event);
+        // Ctrl and Alt keep their bindings on every layer, and macros only play notes
+        let layer_action = match from_macro
+            || numpad.is_some()
+            || modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            true => None,
+            false => self.shortcut_layer.action(code),
+        };
+        let text_entry = !matches!(self.remap_state, RemapState::Idle)
+            || self.scale_chooser.is_some()
+            || self.patch_input.is_some()
+            || self.interval_trainer.is_some()
+            || self.gate_pattern_input.is_some();
+        let control = modifiers.contains(KeyModifiers::CONTROL);
+        let alt = modifiers.contains(KeyModifiers::ALT);
+        let microphone = self.microphone.is_some();
+        match code {
+            // A pedal let go while typing still comes up, or it would stick
+            _ if !from_macro
+                && self.sustain_pedal.claims(&key_event)
+                && (kind == KeyEventKind::Release || !text_entry) =>
+            {
+                self.press_pedal(&key_event)?
+            }
+            _ if code == self.sustain_key && kind != KeyEventKind::Release && !text_entry && !from_macro => {
+                if fresh_press {
+                    self.change_sustain(!self.sustain.sustain_active(), code)?;
+                }
+            }
+            // Only presses act, but a held note ends with its key, the mono note
+            // with the last of its keys; controls still auto-repeat, notes don't
+            _ if kind == KeyEventKind::Release => self.release_key(code)?,
+            _ if !matches!(self.remap_state, RemapState::Idle) => self.remap_key(code),
+            _ if self.scale_chooser.is_some() => self.choose_scale(code),
+            _ if self.patch_input.is_some() => self.enter_patch_number(code)?,
+            _ if self.interval_trainer.is_some() => self.answer_interval(code)?,
+            _ if self.gate_pattern_input.is_some() => self.enter_gate_pattern(code)?,
+            _ if self.show_layout => self.press_layout_key(code)?,
+            KeyCode::Char('m' | 'M') if control && modifiers.contains(KeyModifiers::SHIFT) => self.play_macro(),
+            KeyCode::Char('m') if control => self.record_macro(),
+            KeyCode::Char('m') if alt => self.toggle_macro_mode(),
+            KeyCode::F(n @ 1..=8) if self.macro_mode && !from_macro => self.pick_macro(n),
+            _ if code == self.shortcut_layer.cycle_key && fresh_press && !from_macro => self.cycle_layer(),
+            _ if layer_action.is_some() => {
+                if let Some(action) = layer_action.filter(|_| fresh_press) {
+                    self.run_layer_action(action);
+                }
+            }
+            // The numpad's operators stand in for their own characters
+            _ if numpad.is_some_and(|key| !matches!(key, NumpadKey::Digit(_))) => {
+                self.press_numpad_operator(numpad, control)
+            }
+            KeyCode::Esc => return Ok(ControlFlow::Break(())),
+            KeyCode::Char('?') => self.open_layout()?,
+            KeyCode::Char('a') if alt => self.toggle_ab_comparison(),
+            // While comparing, these shadow the a and b notes and Alt+S's super saw mix
+            KeyCode::Char(c)
+                if self.ab_comparison.is_some()
+                    && matches!((c, modifiers), ('a' | 'b', KeyModifiers::NONE) | ('c' | 's', KeyModifiers::ALT)) =>
+            {
+                self.compare_ab(c)?
+            }
+            KeyCode::Char('p') if control => self.start_patch_select()?,
+            KeyCode::Char('g') if control => self.randomize_pattern(),
+            KeyCode::Char('l') if control => self.clear_loop(),
+            KeyCode::Char('t') if control => self.toggle_tremolo_sync(),
+            KeyCode::Char('t') if alt => toggle(&self.tremolo_control, "Tremolo"),
+            KeyCode::Char('k') if control => self.start_remap(),
+            KeyCode::Char('k') if alt => self.reset_key_map(),
+            KeyCode::Char('U') => self.cycle_sub_oscillator(),
+            KeyCode::Char('F') => toggle(&self.filter_enabled_control, "Filter"),
+            KeyCode::Char('f') if alt => self.cycle_filter_tracking(),
+            KeyCode::Char('o') if alt => self.cycle_overtone_filter(),
+            KeyCode::Char('l') if alt => self.cycle_filter_lfo(),
+            KeyCode::Char('X') => self.cycle_filter_lfo_shape(),
+            KeyCode::Char('Z') => toggle(&self.freeze_control, "Spectral freeze"),
+            KeyCode::Char('L') => self.press_looper(),
+            KeyCode::Char('"') => self.trigger_stutter(),
+            KeyCode::Char('T') => self.toggle_tape_stop(),
+            KeyCode::Char('P') => self.print_partials(),
+            KeyCode::Char('H') => self.cycle_harmonizer(),
+            KeyCode::Char('B') => self.cycle_body_resonance(),
+            KeyCode::Char('V') => self.cycle_waveform(),
+            KeyCode::Char(c @ '1'..='4') if control => self.select_waveform(WaveShape::ALL[c as usize - '1' as usize]),
+            KeyCode::Char('v') if alt => self.step_pulse_width(),
+            KeyCode::Char('Q') => self.start_ear_training()?,
+            KeyCode::Char('K') => self.tap_tempo(),
+            KeyCode::Char('G') => toggle(&self.gate_enabled_control, "Gate"),
+            KeyCode::Char('g') if alt => self.start_gate_pattern()?,
+            KeyCode::Char('C') => self.start_scale_chooser(),
+            KeyCode::Char('D') => self.toggle_drum_mode(),
+            KeyCode::Char('N') => self.toggle_wavetable_mode(),
+            KeyCode::Char(c @ '0'..='9') if self.wavetable_mode => self.pick_wavetable_frame(c),
+            KeyCode::Char(c) if self.drum_mode && PercKind::for_key(c).is_some() => self.hit_drum(c),
+            KeyCode::Char('E') => toggle(&self.echo_control, "Echo"),
+            KeyCode::Char('e') if alt => self.step_echo_time(),
+            KeyCode::Char('R') => self.step_resonance(),
+            KeyCode::Left | KeyCode::Right if alt => self.pan(code),
+            KeyCode::Char('h') if alt => self.toggle_register_pan(),
+            KeyCode::Char('<') | KeyCode::Char('>') => self.balance(code),
+            KeyCode::Char('I') if microphone => self.toggle_mic_cutoff(),
+            KeyCode::Char('J') if microphone => toggle(&self.mic_through_control, "Microphone through"),
+            KeyCode::Char('j') if microphone && alt => toggle(&self.vocoder_control, "Vocoder"),
+            KeyCode::Char('A') if microphone => self.toggle_auto_follow(),
+            KeyCode::Char('M') => self.toggle_meter(),
+            KeyCode::Char('O') => self.toggle_scope(),
+            KeyCode::Char('o') if control => self.toggle_lissajous(),
+            KeyCode::Char('Y') => self.toggle_waveform_preview(),
+            KeyCode::Char('n') if alt => self.toggle_tonnetz(),
+            KeyCode::Up | KeyCode::Down if control => self.transpose(code),
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down if alt => self.adjust_lfo(code),
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down if self.show_tonnetz => {
+                self.scroll_tonnetz(code)
+            }
+            KeyCode::Char('p') if alt && !self.keyboard_enhanced => {
+                print!("Polyphony needs key release events, which this terminal doesn't send\r\n")
+            }
+            KeyCode::Char('p') if alt => self.cycle_polyphony()?,
+            KeyCode::Char('v') if control => self.list_voices(),
+            KeyCode::Char('W') => self.toggle_string()?,
+            KeyCode::Char('w') if control => self.toggle_string_mode(),
+            KeyCode::Char('S') => self.toggle_supersaw()?,
+            KeyCode::Char('s') if control => self.step_supersaw_detune(),
+            KeyCode::Char('s') if alt => self.step_supersaw_mix(),
+            KeyCode::Char('f') if control => self.cycle_fm_preset()?,
+            KeyCode::Char('x') if alt => self.cycle_additive_preset()?,
+            KeyCode::Char('u') if control => self.step_sub_oscillator_mix(),
+            KeyCode::Char('r') if control => self.toggle_random_pitch(),
+            KeyCode::Char('z') if control => self.panic()?,
+            KeyCode::Char('a') if control => self.toggle_arpeggiator(),
+            KeyCode::Char('n') if control => self.next_arp_pattern(),
+            KeyCode::Char('y') if control => self.toggle_arp_latch(),
+            KeyCode::Char('d') if control => toggle(&self.drift_control, "Analog drift"),
+            KeyCode::F(5) if self.key_repeats.is_none() => {
+                print!("Pedal mode needs key release events, which this terminal doesn't send\r\n")
+            }
+            KeyCode::F(5) => self.toggle_pedal_mode(code)?,
+            KeyCode::Char('e') if control => toggle(&self.chorus_control, "Thick chorus"),
+            KeyCode::Char('w') if alt => toggle(&self.widener_control, "Stereo widener"),
+            KeyCode::Char('+') | KeyCode::Char('_') => self.adjust_level(code),
+            KeyCode::Char('x') if control => self.toggle_wav_recording()?,
+            KeyCode::Char('r') if alt => self.toggle_portamento(),
+            KeyCode::Char('u') if alt => self.cycle_unison(),
+            KeyCode::Char('i') if alt => self.cycle_lfo_target(),
+            KeyCode::Char('d') if alt => self.toggle_envelope(),
+            KeyCode::Char('q') if alt => self.toggle_sequencer_recording(),
+            KeyCode::Char('z') if alt => self.clear_sequencer(),
+            KeyCode::Enter if self.sequencer_recording && fresh_press => self.add_sequencer_rest(),
+            KeyCode::Char('b') if control => toggle(&self.bus_compressor_control, "Bus compressor"),
+            _ if !fresh_press => {}
+            key => self.play_key(key, numpad, from_macro)?,
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Only captured in polyphony, where the wheel presses the newest note harder.
+    pub(crate) fn handle_mouse(&mut self, MouseEvent { kind, .. }: MouseEvent) {
+        let Some(voice) = self.last_voice else {
+            return;
+        };
+        let notches = match kind {
+            MouseEventKind::ScrollUp => 1,
+            MouseEventKind::ScrollDown => -1,
+            _ => 0,
+        };
+        if notches != 0 && self.aftertouch.scroll(notches, voice) != 0.0 {
+            print!("Aftertouch: voice {} vibrato {:.2} st\r\n", voice + 1, self.aftertouch.depth(voice));
+        }
+    }
+}
+
+/// Flips an on/off control and reports which it's now.
+fn toggle(control: &Mutex<bool>, name: &str) {
+    if let Ok(mut enabled) = control.lock() {
+        *enabled = !*enabled;
+        print!("{name}: {}\r\n", if *enabled { "on" } else { "off" });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RemapState, Session};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use exposrog::{
+        pan_control, AdsrEnvelope, Arpeggiator, CpuMonitor, DelayTime, DynamicWaveTable, FilterTrackingMode, Gate,
+        KeyFrequencyTable, Lfo, LfoPolarity, LfoShape, LissajousDisplay, LiveLooper, MacroBank, MacroPlayer,
+        MacroRecorder, MasterClock, ModulationSource, NoteSequencer, NoteVelocityMapper, Oscilloscope, PatchControls,
+        PatchMemory, PeakReader, PolyAftertouch, ResonatorBank, ShortcutLayer, StepSequencer, SustainController,
+        SustainPedalSimulator, TempoTapper, Theme, TonnetzDisplay, TriggerMode, VoicePool, WavSessionRecorder,
+        WaveParams, WaveShape, BUILTIN_FM_PRESETS,
+    };
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rodio::Sink;
+    use std::collections::{HashSet, VecDeque};
+    use std::sync::atomic::{AtomicU32, AtomicUsize};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn control<T>(value: T) -> Arc<Mutex<T>> {
+        Arc::new(Mutex::new(value))
+    }
+
+    fn idle_sink() -> Sink {
+        Sink::new_idle().0
+    }
+
+    /// A session on idle sinks, with the controls wired to nothing, as main sets it up
+    /// before the first key.
+    fn session() -> Session {
+        let dynamic_table = DynamicWaveTable::new(WaveParams::new(WaveShape::Sine, 2048));
+        let filter_enabled_control = control(false);
+        let filter_cutoff_control = control(1000.0);
+        let filter_resonance_control = control(0.0);
+        let sub_oscillator_control = control(None);
+        let fm_preset_control = control(BUILTIN_FM_PRESETS[0]);
+        let supersaw_detune_control = control(0.0);
+        let supersaw_mix_control = control(0.5);
+        let scope_samples = control(VecDeque::new());
+        let step_sequencer = StepSequencer::new(120.0);
+        Session {
+            sink: idle_sink(),
+            frequency_control: control(440.0),
+            amplitude_control: control(1.0),
+            sub_oscillator_control: sub_oscillator_control.clone(),
+            drift_control: control(false),
+            lfo_control: control(Lfo::new(44100, 5.0, 0.0, LfoPolarity::Bipolar)),
+            lfo_target_control: control(None),
+            portamento_control: control(0),
+            portamento_ms: 100,
+            unison_voices_control: control(1),
+            envelope_control: control(AdsrEnvelope::new(44100, 0.01, 0.2, 0.7, 0.5)),
+            envelope_enabled_control: control(false),
+            wave_params_control: dynamic_table.get_params_control(),
+            wave_table_version: Arc::new(AtomicUsize::new(0)),
+            dynamic_table,
+            serum_frames: None,
+            wavetable_mode: false,
+
+            resonator_enabled_control: control(false),
+            resonator_bank_control: control(ResonatorBank::guitar_body(44100)),
+            resonator_body: "off",
+            filter_enabled_control: filter_enabled_control.clone(),
+            filter_cutoff_control: filter_cutoff_control.clone(),
+            filter_resonance_control: filter_resonance_control.clone(),
+            filter_tracking_control: control(FilterTrackingMode::default()),
+            filter_lfo_control: control(None),
+            filter_lfo_shape: LfoShape::Sine,
+            overtone_control: control(None),
+            overtone_preset: None,
+            shaper_controls: [0, 1, 2].map(|_| (control(false), control(1.0))),
+            shaper_index: None,
+            low_pass_enabled_control: control(false),
+            low_pass_cutoff_control: control(4000.0),
+            high_pass_enabled_control: control(false),
+            high_pass_cutoff_control: control(80.0),
+
+            harmony_control: control(None),
+            microphone: None,
+            mic_through_control: control(false),
+            vocoder_control: control(false),
+            cutoff_modulation: ModulationSource::Off,
+            auto_follow: false,
+            looper_control: control(LiveLooper::new(44100, 2.0)),
+            freeze_control: control(false),
+            echo_control: control(false),
+            echo_time_control: control(DelayTime::Milliseconds(300.0)),
+            echo_feedback_control: control(0.4),
+            echo_bpm_control: control(step_sequencer.bpm()),
+            echo_time_index: None,
+            tape_stop_control: control(false),
+            stutter_trigger: control(false),
+            stutter_bpm_control: control(step_sequencer.bpm()),
+            tremolo_control: control(false),
+            tremolo_synced_control: control(false),
+            gate_enabled_control: control(false),
+            gate_control: control(Gate::trance(step_sequencer.bpm(), MasterClock::new(44100))),
+            pan_control: pan_control(0.0),
+            balance_control: pan_control(0.0),
+            register_panning: false,
+            chorus_control: control(false),
+            widener_control: control(false),
+            bus_compressor_control: control(false),
+            wav_recorder: WavSessionRecorder::new(44100),
+
+            theme: Theme::default(),
+            oscilloscope: Oscilloscope::new(scope_samples.clone(), TriggerMode::Free),
+            scope_samples,
+            stereo_frames: control(VecDeque::new()),
+            lissajous: LissajousDisplay::new(33, 16),
+            peak_reader: PeakReader::new(Arc::new(AtomicU32::new(0)), 0.05),
+            cpu_monitor: CpuMonitor::new(Duration::from_millis(500)),
+            clip_counter: Arc::new(AtomicUsize::new(0)),
+            last_clip_count: 0,
+            dashboard: None,
+            dashboard_table_version: None,
+            show_meter: false,
+            show_scope: false,
+            show_lissajous: false,
+            show_waveform: false,
+            waveform_version: None,
+            show_layout: false,
+            layout_flash: None,
+            show_tonnetz: false,
+            tonnetz: TonnetzDisplay::new(20, 5),
+            tonnetz_dirty: false,
+            tonnetz_chord: None,
+            last_meter_update: Instant::now(),
+            last_lissajous_update: Instant::now(),
+
+            supersaw_sink: idle_sink(),
+            supersaw_detune_control: supersaw_detune_control.clone(),
+            supersaw_mix_control: supersaw_mix_control.clone(),
+            fm_sink: idle_sink(),
+            fm_preset_control: fm_preset_control.clone(),
+            fm_preset_index: None,
+            string_sink: idle_sink(),
+            string_loss_control: control(0.996),
+            additive_sink: idle_sink(),
+            additive_partials_control: control(vec![1.0]),
+            additive_preset_index: None,
+            poly_sink: idle_sink(),
+            poly_amplitude_control: control(1.0),
+            voice_pool_control: control(VoicePool::new(8)),
+            aftertouch: PolyAftertouch::new(Vec::new()),
+            poly_mode: None,
+            drum_triggers: control(Vec::new()),
+            drum_mode: false,
+            patch_memory: PatchMemory::default(),
+            patch_controls: PatchControls {
+                filter_enabled: filter_enabled_control,
+                filter_cutoff_hz: filter_cutoff_control,
+                filter_resonance: filter_resonance_control,
+                sub_oscillator: sub_oscillator_control,
+                fm_preset: fm_preset_control,
+                supersaw_detune_cents: supersaw_detune_control,
+                supersaw_mix_center: supersaw_mix_control,
+            },
+            ab_comparison: None,
+
+            arpeggiator: Arpeggiator::new(step_sequencer.bpm(), 4),
+            arp_enabled: false,
+            last_arp_tick: Instant::now(),
+            tempo_tapper: TempoTapper::fixed_bpm(step_sequencer.bpm()),
+            note_sequencer: control(NoteSequencer::new(step_sequencer.bpm(), 4)),
+            step_sequencer,
+            backing_sink: idle_sink(),
+            sequencer_recording: false,
+
+            key_frequencies: KeyFrequencyTable::default(),
+            octave_offset: 0,
+            scale_lock: None,
+            random_pitch: false,
+            velocity_mapper: NoteVelocityMapper::default(),
+            master_volume: 1.0,
+            note_amplitude: 1.0,
+            sustain: SustainController::default(),
+            sustain_pedal: SustainPedalSimulator::default(),
+            sustain_key: KeyCode::Char(' '),
+            held_notes: HashSet::new(),
+            pedal_held_note: false,
+            key_repeats: None,
+            keyboard_enhanced: false,
+            unmapped_rng: 1,
+            rng: StdRng::seed_from_u64(0),
+            macro_voice: None,
+            last_voice: None,
+            scheduled_tones: VecDeque::new(),
+
+            remap_state: RemapState::Idle,
+            scale_chooser: None,
+            patch_input: None,
+            gate_pattern_input: None,
+            interval_trainer: None,
+            trainer_guess: String::new(),
+            trainer_intervals: Vec::new(),
+
+            shortcut_layer: ShortcutLayer::default(),
+            macro_bank: MacroBank::default(),
+            macro_recorder: MacroRecorder::default(),
+            macro_player: MacroPlayer::default(),
+            macro_mode: false,
+            macro_slot: 0,
+
+            cc_controls: Vec::new(),
+            midi_chord: control(None),
+            playing_midi_file: false,
+            midi_port: None,
+            midi_recorder: None,
+            midi_note: None,
+        }
+    }
+
+    fn press(session: &mut Session, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        session.handle_key(KeyEvent::new(code, modifiers), false).unwrap().is_break()
+    }
+
+    fn frequency(session: &Session) -> f32 {
+        *session.frequency_control.lock().unwrap()
+    }
+
+    #[test]
+    fn note_key_sets_the_mapped_frequency() {
+        let mut session = session();
+        let expected = session.key_frequencies.get(&KeyCode::Char('a')).unwrap();
+        assert!(!press(&mut session, KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(frequency(&session), expected);
+    }
+
+    #[test]
+    fn ctrl_up_plays_notes_an_octave_higher() {
+        let mut session = session();
+        let base = session.key_frequencies.get(&KeyCode::Char('a')).unwrap();
+        press(&mut session, KeyCode::Up, KeyModifiers::CONTROL);
+        assert_eq!(session.octave_offset, 1);
+        press(&mut session, KeyCode::Char('a'), KeyModifiers::NONE);
+        assert_eq!(frequency(&session), base * 2.0);
+        press(&mut session, KeyCode::Down, KeyModifiers::CONTROL);
+        press(&mut session, KeyCode::Down, KeyModifiers::CONTROL);
+        assert_eq!(session.octave_offset, -1);
+    }
+
+    #[test]
+    fn shift_v_cycles_the_waveform() {
+        let mut session = session();
+        let shapes = WaveShape::ALL;
+        press(&mut session, KeyCode::Char('V'), KeyModifiers::SHIFT);
+        assert_eq!(session.wave_params_control.lock().unwrap().shape, shapes[1 % shapes.len()]);
+        for _ in 1..shapes.len() {
+            press(&mut session, KeyCode::Char('V'), KeyModifiers::SHIFT);
+        }
+        assert_eq!(session.wave_params_control.lock().unwrap().shape, WaveShape::Sine);
+    }
+
+    #[test]
+    fn esc_quits() {
+        let mut session = session();
+        assert!(press(&mut session, KeyCode::Esc, KeyModifiers::NONE));
+    }
+}
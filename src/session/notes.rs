@@ -0,0 +1,293 @@
+use super::Session;
+use crate::update_dashboard;
+use crossterm::event::{KeyCode, KeyEvent};
+use exposrog::{
+    midi_panic, register_pan, MidiFileEvent, NoteQuantizer, NoteVelocityMapper, NumpadKey, PercKind, RandomPitchMode,
+    Scale, SynthError, TuningSystem,
+};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+/// A frequency for a key with no note, uniform over 100-2000 Hz, from a xorshift64
+/// generator whose state is `rng`, which must not be zero.
+fn next_unmapped_freq(rng: &mut u64) -> f32 {
+    *rng ^= *rng << 13;
+    *rng ^= *rng >> 7;
+    *rng ^= *rng << 17;
+    // The top 24 bits, as many as an f32 holds exactly
+    let unit = (*rng >> 40) as f32 / (1_u32 << 24) as f32;
+    100.0 + unit * 1900.0
+}
+
+impl Session {
+    /// Plays the note `key` is mapped to on whichever voice is active, or a random
+    /// frequency for a key with none.
+    pub(crate) fn play_key(
+        &mut self,
+        key: KeyCode,
+        numpad: Option<NumpadKey>,
+        from_macro: bool,
+    ) -> Result<(), SynthError> {
+        let mut played = match numpad {
+            Some(NumpadKey::Digit(digit)) => self.key_frequencies.numpad(digit),
+            // The numpad has its own octave; the key map shifts by the offset
+            _ => self.key_frequencies.get(&key).map(|freq| freq * 2.0_f32.powi(self.octave_offset)),
+        };
+        if self.random_pitch {
+            // Any key: an in-scale note from the range the keyboard covers
+            let tuning = TuningSystem::default();
+            let mut mapped = self.key_frequencies.iter_sorted_by_frequency().map(|(_, freq)| freq);
+            let low = mapped.next().map_or(48, |freq| tuning.nearest_note(freq));
+            let high = mapped.last().map_or(84, |freq| tuning.nearest_note(freq));
+            let quantizer = self.scale_lock.unwrap_or(NoteQuantizer::from_scale(Scale::Major));
+            let note = RandomPitchMode::new(quantizer, low, high).pick(&mut self.rng);
+            played = Some(tuning.frequency(note));
+        }
+        let Some(mut frequency) = played else {
+            // For any unmapped key, assign a random frequency
+            if let Ok(mut freq) = self.frequency_control.lock() {
+                *freq = next_unmapped_freq(&mut self.unmapped_rng);
+            }
+            return Ok(());
+        };
+        if !from_macro {
+            self.macro_recorder.record(key);
+        }
+        if let Some(quantizer) = self.scale_lock {
+            let tuning = TuningSystem::default();
+            frequency = tuning.frequency(quantizer.quantize_note(tuning.nearest_note(frequency)));
+        }
+        update_dashboard(|state| state.frequency = Some(frequency));
+        if self.poly_mode.is_some() {
+            self.play_poly_note(key, frequency, from_macro);
+        } else if self.arp_enabled && !from_macro {
+            self.arpeggiator.press(key, frequency);
+        } else {
+            self.trigger_mono_note(frequency);
+            if !from_macro {
+                self.held_notes.insert(key);
+            }
+            self.pedal_held_note = false;
+        }
+        self.light_tonnetz(frequency);
+        if let (true, Ok(mut sequencer)) = (self.sequencer_recording, self.note_sequencer.lock()) {
+            sequencer.steps.push(Some(frequency));
+            print!("Step {}: {frequency:.1} Hz\r\n", sequencer.steps.len());
+        }
+        let velocity = self.velocity_mapper.note_on(key);
+        self.note_amplitude = NoteVelocityMapper::amplitude(velocity);
+        self.apply_note_volume();
+        self.send_mono_note(frequency, velocity)?;
+        Ok(())
+    }
+
+    fn play_poly_note(&mut self, key: KeyCode, frequency: f32, from_macro: bool) {
+        let Ok(mut pool) = self.voice_pool_control.lock() else {
+            return;
+        };
+        if let Some(voice) = self.macro_voice.take() {
+            pool.note_off(voice);
+        }
+        if let Some(voice) = pool.note_on(frequency) {
+            // A stolen voice's old key no longer owns it
+            self.sustain.forget_voice(voice);
+            if from_macro {
+                self.macro_voice = Some(voice);
+            } else {
+                self.sustain.press(key, voice);
+            }
+            self.aftertouch.note_on(voice);
+            self.last_voice = Some(voice);
+        }
+    }
+
+    /// Retunes the mono voice to `frequency` and starts its envelope again.
+    pub(crate) fn trigger_mono_note(&self, frequency: f32) {
+        if let Ok(mut freq) = self.frequency_control.lock() {
+            *freq = frequency;
+        }
+        if let Ok(mut envelope) = self.envelope_control.lock() {
+            envelope.note_on();
+        }
+        if self.register_panning {
+            self.pan_control.store(register_pan(frequency).to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Ends the mono note once its keys are up: through the envelope's release when it's
+    /// on, otherwise at once.
+    pub(crate) fn release_mono_note(&self) {
+        if self.envelope_enabled_control.lock().is_ok_and(|enabled| *enabled) {
+            if let Ok(mut envelope) = self.envelope_control.lock() {
+                envelope.note_off();
+            }
+        } else if let Ok(mut freq) = self.frequency_control.lock() {
+            *freq = 0.0;
+        }
+    }
+
+    /// Releases the mono note, clearing it from the dashboard and ending it over MIDI.
+    fn end_mono_note(&mut self) -> Result<(), SynthError> {
+        self.release_mono_note();
+        update_dashboard(|state| state.frequency = None);
+        if let Some(note) = self.midi_note.take() {
+            self.send_midi(MidiFileEvent::NoteOff { note })?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn release_key(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        if self.held_notes.remove(&code) && self.held_notes.is_empty() {
+            if self.sustain.sustain_active() {
+                self.pedal_held_note = true;
+            } else {
+                self.end_mono_note()?;
+            }
+        }
+        if self.arp_enabled && !self.arpeggiator.is_empty() {
+            self.arpeggiator.release(code);
+            if self.arpeggiator.is_empty() {
+                self.release_mono_note();
+                update_dashboard(|state| state.frequency = None);
+            }
+        }
+        if let Some(voice) = self.sustain.release(code) {
+            if let Ok(mut pool) = self.voice_pool_control.lock() {
+                pool.note_off(voice);
+            }
+            if !self.sustain.keys_held() {
+                update_dashboard(|state| state.frequency = None);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn press_pedal(&mut self, key_event: &KeyEvent) -> Result<(), SynthError> {
+        match self.sustain_pedal.handle(key_event) {
+            Some(active) => self.change_sustain(active, key_event.code),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn toggle_pedal_mode(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        let sustain_change = self.sustain_pedal.toggle_pedal_mode();
+        let mode = if self.sustain_pedal.pedal_mode { "space is the sustain pedal" } else { "off" };
+        print!("Pedal mode: {mode}\r\n");
+        match sustain_change {
+            Some(active) => self.change_sustain(active, code),
+            None => Ok(()),
+        }
+    }
+
+    /// Engages or lifts sustain from the pedal or the sustain key `code`, letting go of
+    /// the notes it was holding when it lifts.
+    pub(crate) fn change_sustain(&mut self, active: bool, code: KeyCode) -> Result<(), SynthError> {
+        if active == self.sustain.sustain_active() {
+            return Ok(());
+        }
+        self.send_pedal(active)?;
+        let released = self.sustain.set_sustain(active);
+        if let Ok(mut pool) = self.voice_pool_control.lock() {
+            for voice in released {
+                pool.note_off(voice);
+            }
+        }
+        if !active && std::mem::take(&mut self.pedal_held_note) {
+            self.end_mono_note()?;
+        }
+        if code == self.sustain_key {
+            print!("Sustain: {}\r\n", if active { "latched" } else { "off" });
+        }
+        Ok(())
+    }
+
+    /// Stops the note and anything queued, then tells MIDI the same.
+    pub(crate) fn panic(&mut self) -> Result<(), SynthError> {
+        self.scheduled_tones.clear();
+        if let Ok(mut freq) = self.frequency_control.lock() {
+            *freq = 0.0;
+        }
+        if let Ok(mut pool) = self.voice_pool_control.lock() {
+            pool.all_notes_off();
+        }
+        self.sustain.clear();
+        self.macro_voice = None;
+        if let Ok(mut envelope) = self.envelope_control.lock() {
+            envelope.note_off();
+        }
+        self.held_notes.clear();
+        self.pedal_held_note = false;
+        self.arpeggiator.clear();
+        if let Some(Ok(mut recorder)) = self.midi_recorder.as_ref().map(|r| r.lock()) {
+            midi_panic(&mut *recorder)?;
+        }
+        if let Some(port) = self.midi_port.as_mut() {
+            // The panic is also for a port that has gone wrong
+            let _ = midi_panic(port);
+        }
+        self.midi_note = None;
+        update_dashboard(|state| state.frequency = None);
+        print!("Panic: all notes off\r\n");
+        Ok(())
+    }
+
+    /// Ctrl+Up transposes the keyboard an octave up and Ctrl+Down one down.
+    pub(crate) fn transpose(&mut self, code: KeyCode) {
+        let step = if code == KeyCode::Up { 1 } else { -1 };
+        self.octave_offset = (self.octave_offset + step).clamp(-4, 4);
+        let octave_offset = self.octave_offset;
+        update_dashboard(|state| state.octave_offset = octave_offset);
+        print!("Octave: {octave_offset:+}\r\n");
+    }
+
+    pub(crate) fn toggle_random_pitch(&mut self) {
+        self.random_pitch = !self.random_pitch;
+        print!("Random pitch: {}\r\n", if self.random_pitch { "on" } else { "off" });
+    }
+
+    /// The numpad's + and - change the volume, or the tempo with Ctrl, and * and /
+    /// move its digits up and down an octave.
+    pub(crate) fn press_numpad_operator(&mut self, numpad: Option<NumpadKey>, control: bool) {
+        match numpad {
+            Some(NumpadKey::Add | NumpadKey::Subtract) if control => {
+                self.nudge_tempo(if numpad == Some(NumpadKey::Add) { 5.0 } else { -5.0 });
+            }
+            Some(NumpadKey::Add | NumpadKey::Subtract) => {
+                self.adjust_volume(if numpad == Some(NumpadKey::Add) { 0.1 } else { -0.1 });
+            }
+            _ => {
+                let octave = self.key_frequencies.numpad_octave();
+                let octave = match numpad {
+                    Some(NumpadKey::Multiply) => octave.saturating_add(1),
+                    _ => octave.saturating_sub(1),
+                };
+                self.key_frequencies.set_numpad_octave(octave);
+                print!("Numpad octave: {}\r\n", self.key_frequencies.numpad_octave());
+            }
+        }
+    }
+
+    pub(crate) fn toggle_drum_mode(&mut self) {
+        self.drum_mode = !self.drum_mode;
+        print!("Mode: {}\r\n", if self.drum_mode { "drums" } else { "melodic" });
+    }
+
+    pub(crate) fn hit_drum(&mut self, c: char) {
+        if let (Some(kind), Ok(mut triggers)) = (PercKind::for_key(c), self.drum_triggers.lock()) {
+            triggers.push(kind);
+        }
+    }
+
+    /// Sets the frequencies of the tones the app plays by itself as they come due.
+    pub(crate) fn play_scheduled_tones(&mut self) {
+        while let Some(&(due, frequency)) = self.scheduled_tones.front() {
+            if due > Instant::now() {
+                break;
+            }
+            if let Ok(mut freq) = self.frequency_control.lock() {
+                *freq = frequency;
+            }
+            self.scheduled_tones.pop_front();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use super::Session;
+use crossterm::event::KeyCode;
+use exposrog::{read_serum_frame, LfoTarget, SubOscillatorMode, WaveShape, MAX_UNISON_VOICES};
+
+impl Session {
+    /// Plays `shape` from the wave table worker, dropping any loaded table.
+    pub(crate) fn select_waveform(&mut self, shape: WaveShape) {
+        if let Ok(mut params) = self.dynamic_table.get_params_control().lock() {
+            params.custom_table = None;
+            params.shape = shape;
+            self.dynamic_table.mark_dirty();
+            print!("Waveform: {shape}\r\n");
+        }
+    }
+
+    pub(crate) fn cycle_waveform(&mut self) {
+        if let Ok(mut params) = self.wave_params_control.lock() {
+            params.custom_table = None;
+            let shapes = WaveShape::ALL;
+            let next = shapes.iter().position(|shape| *shape == params.shape).map_or(0, |i| i + 1);
+            params.shape = shapes[next % shapes.len()];
+            self.dynamic_table.mark_dirty();
+            print!("Waveform: {}\r\n", params.shape);
+        }
+    }
+
+    pub(crate) fn step_pulse_width(&mut self) {
+        if let Ok(mut params) = self.wave_params_control.lock() {
+            let width = params.pulse_width;
+            params.pulse_width = if width >= 0.85 { 0.1 } else { width + 0.1 };
+            self.dynamic_table.mark_dirty();
+            print!("Pulse width: {:.0}%\r\n", params.pulse_width * 100.0);
+        }
+    }
+
+    pub(crate) fn toggle_wavetable_mode(&mut self) {
+        match &self.serum_frames {
+            Some((_, frame_count)) => {
+                self.wavetable_mode = !self.wavetable_mode;
+                let state = if self.wavetable_mode { "digits pick frames" } else { "off" };
+                print!("Wavetable mode ({frame_count} frames): {state}\r\n");
+            }
+            None => print!("Wavetable mode needs --serum-wavetable <file.wav>\r\n"),
+        }
+    }
+
+    pub(crate) fn pick_wavetable_frame(&mut self, digit: char) {
+        if let Some((path, frame_count)) = &self.serum_frames {
+            let frame_index = digit as usize - '0' as usize;
+            match read_serum_frame(path, frame_index) {
+                Ok(frame) => {
+                    if let Ok(mut params) = self.wave_params_control.lock() {
+                        params.custom_table = Some(frame);
+                        self.dynamic_table.mark_dirty();
+                    }
+                    print!("Wavetable frame {frame_index} of {frame_count}\r\n");
+                }
+                Err(error) => print!("{error}\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn cycle_sub_oscillator(&mut self) {
+        if let Ok(mut mode) = self.sub_oscillator_control.lock() {
+            *mode = SubOscillatorMode::cycle(*mode);
+            match *mode {
+                Some(m) => print!("Sub-oscillator: {} (mix {:.1})\r\n", m.interval.name(), m.mix),
+                None => print!("Sub-oscillator: off\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn step_sub_oscillator_mix(&mut self) {
+        if let Ok(mut mode) = self.sub_oscillator_control.lock() {
+            if let Some(m) = mode.as_mut() {
+                // Step the mix up and wrap back around past fully-sub
+                m.mix = if m.mix >= 0.95 { 0.1 } else { m.mix + 0.1 };
+                print!("Sub-oscillator mix: {:.1}\r\n", m.mix);
+            }
+        }
+    }
+
+    /// `+` raises the main and polyphonic oscillators' level and `_` lowers it.
+    pub(crate) fn adjust_level(&mut self, code: KeyCode) {
+        let step = if code == KeyCode::Char('+') { 0.05 } else { -0.05 };
+        let level = self.amplitude_control.lock().map_or(0.3, |level| *level);
+        let level = (level + step).clamp(0.0, 1.0);
+        for control in [&self.amplitude_control, &self.poly_amplitude_control] {
+            if let Ok(mut amplitude) = control.lock() {
+                *amplitude = level;
+            }
+        }
+        print!("Oscillator level: {level:.2}\r\n");
+    }
+
+    pub(crate) fn toggle_portamento(&mut self) {
+        if let Ok(mut ms) = self.portamento_control.lock() {
+            *ms = if *ms == 0 { self.portamento_ms } else { 0 };
+            match *ms {
+                0 => print!("Portamento: off\r\n"),
+                ms => print!("Portamento: {ms} ms glide\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn cycle_unison(&mut self) {
+        if let Ok(mut voices) = self.unison_voices_control.lock() {
+            // 1 -> 3 -> 5 -> 7 -> 1
+            *voices = if *voices >= MAX_UNISON_VOICES { 1 } else { (*voices + 1) | 1 };
+            match *voices {
+                1 => print!("Unison: off\r\n"),
+                voices => print!("Unison: {voices} voices\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn cycle_lfo_target(&mut self) {
+        if let Ok(mut target) = self.lfo_target_control.lock() {
+            *target = match *target {
+                None => Some(LfoTarget::Pitch),
+                Some(LfoTarget::Pitch) => Some(LfoTarget::Amplitude),
+                Some(LfoTarget::Amplitude) => None,
+            };
+            match *target {
+                Some(target) => print!("LFO: {target}\r\n"),
+                None => print!("LFO: off\r\n"),
+            }
+        }
+    }
+
+    /// Left and Right change the LFO's rate, Up and Down its depth.
+    pub(crate) fn adjust_lfo(&mut self, code: KeyCode) {
+        if let Ok(mut lfo) = self.lfo_control.lock() {
+            match code {
+                KeyCode::Left => lfo.rate_hz = (lfo.rate_hz / 1.25).max(0.1),
+                KeyCode::Right => lfo.rate_hz = (lfo.rate_hz * 1.25).min(20.0),
+                KeyCode::Up => lfo.depth = (lfo.depth + 0.01).min(1.0),
+                _ => lfo.depth = (lfo.depth - 0.01).max(0.0),
+            }
+            print!("LFO: {:.2} Hz, depth {:.2}\r\n", lfo.rate_hz, lfo.depth);
+        }
+    }
+
+    pub(crate) fn toggle_envelope(&mut self) {
+        if let Ok(mut enabled) = self.envelope_enabled_control.lock() {
+            *enabled = !*enabled;
+            print!("ADSR envelope: {}\r\n", if *enabled { "on" } else { "off" });
+            if *enabled && !self.keyboard_enhanced {
+                // Without release events the sustain lasts until the next note
+                print!("  (this terminal doesn't report key releases, so notes won't release)\r\n");
+            }
+        }
+    }
+}
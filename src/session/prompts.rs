@@ -0,0 +1,282 @@
+use super::Session;
+use crossterm::event::KeyCode;
+use exposrog::{
+    parse_gate_pattern, IntervalQuestion, IntervalTrainer, KeyFrequencyTable, NoteQuantizer, ScaleChooser,
+    SynthError, TuningSystem, TRANCE_GATE_PATTERN,
+};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Progress through the Ctrl+K key remapping prompt.
+pub(crate) enum RemapState {
+    Idle,
+    AwaitingKey,
+    AwaitingFrequency { key: KeyCode, input: String },
+}
+
+fn step_remap(
+    state: RemapState,
+    code: KeyCode,
+    key_frequencies: &mut KeyFrequencyTable,
+) -> RemapState {
+    match (state, code) {
+        (_, KeyCode::Esc) => {
+            print!("\r\nRemap cancelled\r\n");
+            RemapState::Idle
+        }
+        (RemapState::AwaitingKey, key) => {
+            print!("Remapping {key:?}: type a frequency in Hz and press Enter (empty unmaps the key)\r\n");
+            RemapState::AwaitingFrequency { key, input: String::new() }
+        }
+        (RemapState::AwaitingFrequency { key, mut input }, KeyCode::Char(c))
+            if c.is_ascii_digit() || c == '.' =>
+        {
+            input.push(c);
+            print!("{c}");
+            let _ = std::io::stdout().flush();
+            RemapState::AwaitingFrequency { key, input }
+        }
+        (RemapState::AwaitingFrequency { key, mut input }, KeyCode::Backspace) => {
+            if input.pop().is_some() {
+                print!("\u{8} \u{8}");
+                let _ = std::io::stdout().flush();
+            }
+            RemapState::AwaitingFrequency { key, input }
+        }
+        (RemapState::AwaitingFrequency { key, input }, KeyCode::Enter) => {
+            if input.is_empty() {
+                key_frequencies.unmap(key);
+                print!("\r\n{key:?} unmapped\r\n");
+            } else {
+                match input.parse::<f32>() {
+                    Ok(freq) if freq > 0.0 => {
+                        key_frequencies.remap(key, freq);
+                        print!("\r\n{key:?} -> {freq} Hz\r\n");
+                    }
+                    _ => print!("\r\nInvalid frequency '{input}'\r\n"),
+                }
+            }
+            RemapState::Idle
+        }
+        (state, _) => state,
+    }
+}
+
+/// One status line of the scale chooser: the search text and the matches from the
+/// selected one on.
+fn print_scale_matches(chooser: &ScaleChooser) {
+    let matches = chooser.matches();
+    let selected = chooser.selected().map_or(0, |(name, _)| {
+        matches.iter().position(|&(candidate, _)| candidate == name).unwrap_or(0)
+    });
+    let shown: Vec<&str> = matches.iter().skip(selected).take(5).map(|&(name, _)| name).collect();
+    let more = matches.len().saturating_sub(selected + shown.len());
+    print!("Scale '{}': ", chooser.query());
+    match shown.split_first() {
+        None => print!("no matches"),
+        Some((first, rest)) => {
+            print!("> {first}");
+            for name in rest {
+                print!(", {name}");
+            }
+            if more > 0 {
+                print!(" (+{more} more)");
+            }
+        }
+    }
+    print!("\r\n");
+}
+
+/// When to play each half of an ear training question: the low note, the high
+/// note 700 ms later, then silence.
+fn interval_tones(question: IntervalQuestion, start: Instant) -> VecDeque<(Instant, f32)> {
+    let tuning = TuningSystem::default();
+    let gap = Duration::from_millis(700);
+    VecDeque::from([
+        (start, tuning.frequency(question.low_note)),
+        (start + gap, tuning.frequency(question.high_note())),
+        (start + 2 * gap, 0.0),
+    ])
+}
+
+impl Session {
+    pub(crate) fn start_remap(&mut self) {
+        self.remap_state = RemapState::AwaitingKey;
+        print!("Press the key to remap (Esc cancels)\r\n");
+    }
+
+    pub(crate) fn remap_key(&mut self, code: KeyCode) {
+        let state = std::mem::replace(&mut self.remap_state, RemapState::Idle);
+        self.remap_state = step_remap(state, code, &mut self.key_frequencies);
+    }
+
+    pub(crate) fn reset_key_map(&mut self) {
+        self.key_frequencies.reset_to_default();
+        print!("Key map restored to defaults\r\n");
+    }
+
+    pub(crate) fn start_scale_chooser(&mut self) {
+        let chooser = ScaleChooser::default();
+        print_scale_matches(&chooser);
+        self.scale_chooser = Some(chooser);
+    }
+
+    pub(crate) fn choose_scale(&mut self, code: KeyCode) {
+        let chooser = self.scale_chooser.get_or_insert_with(ScaleChooser::default);
+        match code {
+            KeyCode::Char(c) => chooser.push(c),
+            KeyCode::Backspace => chooser.pop(),
+            KeyCode::Up => chooser.move_selection(-1),
+            KeyCode::Down => chooser.move_selection(1),
+            KeyCode::Enter => {
+                if let Some((name, mask)) = chooser.selected() {
+                    self.scale_lock = Some(NoteQuantizer::custom(mask, 0));
+                    self.scale_chooser = None;
+                    print!("Scale lock: {name}\r\n");
+                }
+            }
+            KeyCode::Esc => {
+                self.scale_chooser = None;
+                print!("Scale selection cancelled\r\n");
+            }
+            _ => {}
+        }
+        if let Some(chooser) = &self.scale_chooser {
+            print_scale_matches(chooser);
+        }
+    }
+
+    pub(crate) fn start_patch_select(&mut self) -> Result<(), SynthError> {
+        self.patch_input = Some(String::new());
+        print!("Patch number (Enter selects, Esc cancels): ");
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn enter_patch_number(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        let input = self.patch_input.get_or_insert_with(String::new);
+        match code {
+            KeyCode::Char(c) if c.is_ascii_digit() && input.len() < 3 => {
+                input.push(c);
+                print!("{c}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Backspace if input.pop().is_some() => {
+                print!("\u{8} \u{8}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Enter => {
+                let program = input.parse::<usize>().ok();
+                self.patch_input = None;
+                let activated = program.and_then(|program| {
+                    let preset = self.patch_memory.activate(program, &self.patch_controls)?;
+                    Some((program, preset.name.clone(), preset.voice, preset.fm_preset.clone()))
+                });
+                match activated {
+                    Some((program, name, voice, fm_preset)) => {
+                        self.play_patch_voice(voice, &fm_preset)?;
+                        print!("\r\nPatch {program}: {name}\r\n");
+                    }
+                    None => print!("\r\nNo patch stored there\r\n"),
+                }
+            }
+            KeyCode::Esc => {
+                self.patch_input = None;
+                print!("\r\nPatch select cancelled\r\n");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub(crate) fn start_ear_training(&mut self) -> Result<(), SynthError> {
+        let mut trainer = IntervalTrainer::new(self.trainer_intervals.clone());
+        let question = trainer.next_question(&mut self.rng);
+        self.scheduled_tones = interval_tones(question, Instant::now());
+        self.interval_trainer = Some(trainer);
+        self.trainer_guess.clear();
+        print!("Ear training: type an interval (m2, M2, m3, M3, P4, TT, P5, m6, M6, m7, M7, P8), ");
+        print!("Enter to answer, Space to replay, Esc to stop\r\nWhat interval is this? ");
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn answer_interval(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        let trainer = self.interval_trainer.get_or_insert_with(IntervalTrainer::default);
+        match code {
+            KeyCode::Esc | KeyCode::Char('Q') => {
+                print!("\r\nEar training over: {}\r\n", trainer.score());
+                self.interval_trainer = None;
+                self.scheduled_tones = VecDeque::from([(Instant::now(), 0.0)]);
+            }
+            KeyCode::Char(c) if "mMPT0123456789".contains(c) && self.trainer_guess.len() < 3 => {
+                self.trainer_guess.push(c);
+                print!("{c}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Backspace if self.trainer_guess.pop().is_some() => {
+                print!("\u{8} \u{8}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Char(' ') => {
+                if let Some(question) = trainer.current() {
+                    self.scheduled_tones = interval_tones(question, Instant::now());
+                }
+            }
+            KeyCode::Enter => {
+                let guess = std::mem::take(&mut self.trainer_guess);
+                if let Some((question, correct)) = trainer.answer(&guess) {
+                    let verdict = if correct { "Right!" } else { "Wrong." };
+                    let answer = question.describe(&TuningSystem::default());
+                    print!("\r\n{verdict} {answer} ({})\r\n", trainer.score());
+                }
+                let question = trainer.next_question(&mut self.rng);
+                self.scheduled_tones = interval_tones(question, Instant::now() + Duration::from_millis(500));
+                print!("What interval is this? ");
+                std::io::stdout().flush()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub(crate) fn start_gate_pattern(&mut self) -> Result<(), SynthError> {
+        self.gate_pattern_input = Some(String::new());
+        print!("Gate pattern (letter open, space/dot closed, Enter sets, empty for trance): ");
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn enter_gate_pattern(&mut self, code: KeyCode) -> Result<(), SynthError> {
+        let input = self.gate_pattern_input.get_or_insert_with(String::new);
+        match code {
+            KeyCode::Char(c) if (c == ' ' || c == '.' || c.is_alphabetic()) && input.len() < 32 => {
+                input.push(c);
+                print!("{c}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Backspace if input.pop().is_some() => {
+                print!("\u{8} \u{8}");
+                std::io::stdout().flush()?;
+            }
+            KeyCode::Enter => {
+                // An empty pattern brings back the trance gate
+                let text = match self.gate_pattern_input.take() {
+                    Some(text) if !text.is_empty() => text,
+                    _ => TRANCE_GATE_PATTERN.to_string(),
+                };
+                if let Ok(mut gate) = self.gate_control.lock() {
+                    gate.pattern = parse_gate_pattern(&text);
+                    print!("\r\nGate pattern: {}\r\n", gate.pattern_string());
+                }
+            }
+            KeyCode::Esc => {
+                self.gate_pattern_input = None;
+                print!("\r\nGate pattern unchanged\r\n");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use super::Session;
+use exposrog::SynthError;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl Session {
+    /// Starts recording the output to a WAV file named for the time, or stops the one
+    /// running.
+    pub(crate) fn toggle_wav_recording(&mut self) -> Result<(), SynthError> {
+        if self.wav_recorder.is_recording() {
+            if let Some(recording) = self.wav_recorder.stop()? {
+                print!(
+                    "Recording stopped: {} ({} samples, {:.1} s)\r\n",
+                    recording.path.display(),
+                    recording.sample_count,
+                    recording.duration_secs()
+                );
+            }
+        } else {
+            let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+            let path = PathBuf::from(format!("recording_{stamp}.wav"));
+            match self.wav_recorder.start(&path) {
+                Ok(()) => print!("Recording to {}\r\n", path.display()),
+                Err(error) => print!("Can't record: {error}\r\n"),
+            }
+        }
+        Ok(())
+    }
+}
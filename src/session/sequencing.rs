@@ -0,0 +1,142 @@
+use super::Session;
+use crate::update_dashboard;
+use exposrog::{LiveLooper, Scale};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+/// Steps the looper on from Shift+L or a layer key and reports where it is.
+fn press_looper(looper: &mut LiveLooper) {
+    looper.press();
+    let state = match (looper.is_recording(), looper.is_playing()) {
+        (true, false) => "recording",
+        (false, true) => "playing",
+        (true, true) => "overdubbing",
+        (false, false) => "stopped",
+    };
+    print!("Looper ({:.2} s): {state}\r\n", looper.length_secs());
+}
+
+impl Session {
+    pub(crate) fn press_looper(&mut self) {
+        if let Ok(mut looper) = self.looper_control.lock() {
+            press_looper(&mut looper);
+        }
+    }
+
+    pub(crate) fn clear_loop(&mut self) {
+        if let Ok(mut looper) = self.looper_control.lock() {
+            looper.clear();
+            print!("Loop cleared\r\n");
+        }
+    }
+
+    pub(crate) fn randomize_pattern(&mut self) {
+        self.step_sequencer.randomize(Scale::MinorPentatonic, 0.6, (70, 120), &mut self.rng);
+        let steps: Vec<String> = self
+            .step_sequencer
+            .pattern()
+            .iter()
+            .map(|step| step.map_or("--".to_string(), |step| step.midi_note.to_string()))
+            .collect();
+        print!("Pattern: {}\r\n", steps.join(" "));
+    }
+
+    pub(crate) fn tap_tempo(&mut self) {
+        let previous = self.tempo_tapper.bpm();
+        self.tempo_tapper.tap();
+        if self.tempo_tapper.bpm() != previous {
+            self.change_tempo(self.tempo_tapper.bpm());
+        }
+    }
+
+    pub(crate) fn nudge_tempo(&mut self, step: f32) {
+        self.change_tempo((self.tempo_tapper.bpm() + step).clamp(20.0, 300.0));
+    }
+
+    /// Moves everything that keeps time to `bpm`.
+    fn change_tempo(&mut self, bpm: f32) {
+        self.tempo_tapper.get_bpm_control().store(bpm.to_bits(), Ordering::Relaxed);
+        self.step_sequencer.set_bpm(bpm);
+        self.arpeggiator.bpm = bpm;
+        if let Ok(mut sequencer) = self.note_sequencer.lock() {
+            sequencer.bpm = bpm;
+        }
+        if let Ok(mut echo_bpm) = self.echo_bpm_control.lock() {
+            *echo_bpm = bpm;
+        }
+        if let Ok(mut gate) = self.gate_control.lock() {
+            gate.bpm = bpm;
+        }
+        if let Ok(mut stutter_bpm) = self.stutter_bpm_control.lock() {
+            *stutter_bpm = bpm;
+        }
+        print!("Tempo: {bpm:.1} BPM\r\n");
+    }
+
+    pub(crate) fn toggle_arpeggiator(&mut self) {
+        self.arp_enabled = !self.arp_enabled;
+        if !self.arp_enabled && !self.arpeggiator.is_empty() {
+            self.arpeggiator.clear();
+            self.release_mono_note();
+        }
+        match (self.arp_enabled, self.poly_mode) {
+            (false, _) => print!("Arpeggiator: off\r\n"),
+            (true, Some(_)) => print!("Arpeggiator: on, for the mono voice once polyphony is off\r\n"),
+            (true, None) => print!(
+                "Arpeggiator: {}{} at {:.0} BPM\r\n",
+                self.arpeggiator.pattern,
+                if self.arpeggiator.latch() { ", latched" } else { "" },
+                self.arpeggiator.bpm
+            ),
+        }
+    }
+
+    pub(crate) fn next_arp_pattern(&mut self) {
+        self.arpeggiator.pattern = self.arpeggiator.pattern.next();
+        print!("Arpeggiator pattern: {}\r\n", self.arpeggiator.pattern);
+    }
+
+    pub(crate) fn toggle_arp_latch(&mut self) {
+        self.arpeggiator.set_latch(!self.arpeggiator.latch());
+        if self.arp_enabled && self.arpeggiator.is_empty() {
+            self.release_mono_note();
+        }
+        print!("Arpeggiator latch: {}\r\n", if self.arpeggiator.latch() { "on" } else { "off" });
+    }
+
+    /// The arpeggiator retriggers the main voice on its own beat.
+    pub(crate) fn tick_arpeggiator(&mut self) {
+        let elapsed = self.last_arp_tick.elapsed();
+        self.last_arp_tick = Instant::now();
+        if let Some(frequency) = self.arpeggiator.tick(elapsed).filter(|_| self.arp_enabled) {
+            self.trigger_mono_note(frequency);
+            update_dashboard(|state| state.frequency = Some(frequency));
+        }
+    }
+
+    pub(crate) fn toggle_sequencer_recording(&mut self) {
+        self.sequencer_recording = !self.sequencer_recording;
+        if let Ok(mut sequencer) = self.note_sequencer.lock() {
+            sequencer.playing = !self.sequencer_recording;
+            match (self.sequencer_recording, sequencer.steps.len()) {
+                (true, _) => print!("Sequencer: recording, notes add steps and Enter a rest\r\n"),
+                (false, 0) => print!("Sequencer: nothing recorded\r\n"),
+                (false, steps) => print!("Sequencer: looping {steps} steps\r\n"),
+            }
+        }
+    }
+
+    pub(crate) fn clear_sequencer(&mut self) {
+        if let Ok(mut sequencer) = self.note_sequencer.lock() {
+            sequencer.steps.clear();
+            print!("Sequencer cleared\r\n");
+        }
+    }
+
+    pub(crate) fn add_sequencer_rest(&mut self) {
+        if let Ok(mut sequencer) = self.note_sequencer.lock() {
+            sequencer.steps.push(None);
+            print!("Step {}: rest\r\n", sequencer.steps.len());
+        }
+    }
+}
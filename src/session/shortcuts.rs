@@ -0,0 +1,76 @@
+use super::Session;
+use exposrog::Action;
+use std::time::Instant;
+
+impl Session {
+    pub(crate) fn play_macro(&mut self) {
+        match &self.macro_bank.slots[self.macro_slot] {
+            Some(keyboard_macro) if !keyboard_macro.is_empty() => {
+                self.macro_player.play(keyboard_macro, Instant::now());
+                print!("Macro {}: playing\r\n", self.macro_slot + 1);
+            }
+            _ => print!("Macro {}: empty\r\n", self.macro_slot + 1),
+        }
+    }
+
+    pub(crate) fn record_macro(&mut self) {
+        if self.macro_recorder.recording {
+            let keyboard_macro = self.macro_recorder.stop();
+            let (notes, secs) = (keyboard_macro.events.len(), keyboard_macro.duration().as_secs_f32());
+            print!("Macro {}: {notes} notes over {secs:.1} s\r\n", self.macro_slot + 1);
+            self.macro_bank.slots[self.macro_slot] = Some(keyboard_macro);
+        } else {
+            self.macro_recorder.start();
+            print!("Macro {}: recording, Ctrl+M to stop\r\n", self.macro_slot + 1);
+        }
+    }
+
+    pub(crate) fn toggle_macro_mode(&mut self) {
+        self.macro_mode = !self.macro_mode;
+        if self.macro_mode {
+            print!("Macro mode: F1-F8 pick and play a macro, Alt+M to leave\r\n");
+        } else {
+            print!("Macro mode off\r\n");
+        }
+    }
+
+    /// In macro mode F`n` picks slot `n` and plays what it holds.
+    pub(crate) fn pick_macro(&mut self, n: u8) {
+        self.macro_slot = n as usize - 1;
+        match &self.macro_bank.slots[self.macro_slot] {
+            Some(keyboard_macro) if !keyboard_macro.is_empty() => {
+                self.macro_player.play(keyboard_macro, Instant::now());
+                print!("Macro {n}: playing\r\n");
+            }
+            _ => print!("Macro {n}: empty, Ctrl+M records into it\r\n"),
+        }
+    }
+
+    pub(crate) fn cycle_layer(&mut self) {
+        self.shortcut_layer.cycle();
+        match self.shortcut_layer.active_layer {
+            0 => print!("Layer 0: notes\r\n"),
+            layer => {
+                let (name, keys) = (self.shortcut_layer.layer_name(), self.shortcut_layer.describe());
+                print!("Layer {layer} ({name}): {keys}\r\n");
+            }
+        }
+    }
+
+    pub(crate) fn run_layer_action(&mut self, action: Action) {
+        match action {
+            Action::SetWaveform(shape) => self.select_waveform(shape),
+            Action::AdjustVolume(step) => self.adjust_volume(step),
+            Action::AdjustTempo(step) => self.nudge_tempo(step),
+            Action::AdjustEchoTime(step) => self.adjust_echo_time(step),
+            Action::AdjustEchoFeedback(step) => self.adjust_echo_feedback(step),
+            Action::CycleShaper => self.cycle_shaper(),
+            Action::ScaleShaperDrive(ratio) => self.scale_shaper_drive(ratio),
+            Action::ScaleLowPassCutoff(ratio) => self.scale_low_pass_cutoff(ratio),
+            Action::ScaleHighPassCutoff(ratio) => self.scale_high_pass_cutoff(ratio),
+            Action::ToggleEffect(effect) => self.toggle_effect(effect),
+            Action::TriggerStutter => self.trigger_stutter(),
+            Action::PressLooper => self.press_looper(),
+        }
+    }
+}
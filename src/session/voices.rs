@@ -0,0 +1,279 @@
+use super::Session;
+use crate::POLY_VOICES;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use exposrog::{
+    capture_preset, AbComparison, AbSlot, AdditivePreset, PatchVoice, PolyphonyMode, Preset, SynthError,
+    BUILTIN_FM_PRESETS, SUSTAIN_LOSS,
+};
+use rodio::Sink;
+
+/// Pauses every voice's sink except the one `voice` plays through.
+fn select_voice_sink(voice: PatchVoice, wave_table: &Sink, supersaw: &Sink, fm: &Sink) {
+    for (sink_voice, sink) in [
+        (PatchVoice::WaveTable, wave_table),
+        (PatchVoice::SuperSaw, supersaw),
+        (PatchVoice::Fm, fm),
+    ] {
+        if sink_voice == voice {
+            sink.play();
+        } else {
+            sink.pause();
+        }
+    }
+}
+
+/// The voice whose sink is playing, for capturing the current sound into a preset.
+fn playing_voice(supersaw: &Sink, fm: &Sink) -> PatchVoice {
+    if !supersaw.is_paused() {
+        PatchVoice::SuperSaw
+    } else if !fm.is_paused() {
+        PatchVoice::Fm
+    } else {
+        PatchVoice::WaveTable
+    }
+}
+
+/// Switches the polyphonic sink off for another voice, giving the mouse back.
+fn leave_polyphony(poly_mode: &mut Option<PolyphonyMode>, poly_sink: &Sink) -> std::io::Result<()> {
+    poly_sink.pause();
+    if poly_mode.take().is_some() {
+        execute!(std::io::stdout(), DisableMouseCapture)?;
+    }
+    Ok(())
+}
+
+/// Alt+P's steps: eight-voice poly, duophony, a legato mono voice, and back to the
+/// monophonic chain.
+fn next_polyphony_mode(mode: Option<PolyphonyMode>) -> Option<PolyphonyMode> {
+    match mode {
+        None => Some(PolyphonyMode::Poly(POLY_VOICES)),
+        Some(PolyphonyMode::Poly(_)) => Some(PolyphonyMode::Duophony),
+        Some(PolyphonyMode::Duophony) => Some(PolyphonyMode::Mono { legato: true }),
+        Some(PolyphonyMode::Mono { .. }) => None,
+    }
+}
+
+impl Session {
+    /// Every sink a note can play on, which the volume controls all follow.
+    fn voice_sinks(&self) -> [&Sink; 6] {
+        [&self.sink, &self.supersaw_sink, &self.fm_sink, &self.string_sink, &self.additive_sink, &self.poly_sink]
+    }
+
+    /// Sets the voices to the master volume scaled by the last note's velocity.
+    pub(crate) fn apply_note_volume(&self) {
+        for voice_sink in self.voice_sinks() {
+            voice_sink.set_volume(self.master_volume * self.note_amplitude);
+        }
+    }
+
+    pub(crate) fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume;
+        self.apply_note_volume();
+        self.backing_sink.set_volume(self.master_volume);
+    }
+
+    pub(crate) fn adjust_volume(&mut self, step: f32) {
+        self.set_master_volume((self.master_volume + step).clamp(0.0, 1.0));
+        print!("Volume: {:.0}%\r\n", self.master_volume * 100.0);
+    }
+
+    /// Switches to the voice a patch or A/B config plays, on its FM preset for FM.
+    pub(crate) fn play_patch_voice(&mut self, voice: PatchVoice, fm_preset: &str) -> Result<(), SynthError> {
+        self.string_sink.pause();
+        self.additive_sink.pause();
+        self.additive_preset_index = None;
+        leave_polyphony(&mut self.poly_mode, &self.poly_sink)?;
+        select_voice_sink(voice, &self.sink, &self.supersaw_sink, &self.fm_sink);
+        self.fm_preset_index = (voice == PatchVoice::Fm)
+            .then(|| BUILTIN_FM_PRESETS.iter().position(|p| p.name == fm_preset))
+            .flatten();
+        Ok(())
+    }
+
+    pub(crate) fn toggle_ab_comparison(&mut self) {
+        if self.ab_comparison.take().is_some() {
+            print!("A/B comparison off\r\n");
+        } else {
+            let voice = playing_voice(&self.supersaw_sink, &self.fm_sink);
+            self.ab_comparison = Some(AbComparison::new(capture_preset("Current", voice, &self.patch_controls)));
+            print!("A/B comparison: A and B hold the current sound, A is playing\r\n");
+        }
+    }
+
+    /// A and B pick a config, Alt+C copies A to B and Alt+S swaps them.
+    pub(crate) fn compare_ab(&mut self, c: char) -> Result<(), SynthError> {
+        let voice = playing_voice(&self.supersaw_sink, &self.fm_sink);
+        let comparison = self.ab_comparison.get_or_insert_with(|| AbComparison::new(Preset::init()));
+        let (action, preset) = match c {
+            'a' => ("Config A", comparison.select(AbSlot::A, voice, &self.patch_controls)),
+            'b' => ("Config B", comparison.select(AbSlot::B, voice, &self.patch_controls)),
+            'c' => ("Copied A to B", comparison.copy_a_to_b(voice, &self.patch_controls)),
+            _ => ("Swapped A and B", comparison.swap(voice, &self.patch_controls)),
+        };
+        let (name, voice, fm_preset) = (preset.name.clone(), preset.voice, preset.fm_preset.clone());
+        self.play_patch_voice(voice, &fm_preset)?;
+        print!("{action}, playing {name}\r\n");
+        Ok(())
+    }
+
+    pub(crate) fn cycle_polyphony(&mut self) -> Result<(), SynthError> {
+        self.poly_mode = next_polyphony_mode(self.poly_mode);
+        if let Ok(mut pool) = self.voice_pool_control.lock() {
+            pool.all_notes_off();
+            if let Some(mode) = self.poly_mode {
+                pool.set_polyphony_mode(mode);
+            }
+        }
+        self.sustain.clear();
+        (self.macro_voice, self.last_voice) = (None, None);
+        match self.poly_mode {
+            Some(mode) => {
+                if self.poly_sink.is_paused() {
+                    // The polyphonic sink comes last
+                    let [mono_sinks @ .., _] = self.voice_sinks();
+                    for voice_sink in mono_sinks {
+                        voice_sink.pause();
+                    }
+                    (self.fm_preset_index, self.additive_preset_index) = (None, None);
+                    self.poly_sink.play();
+                    // The wheel is the voices' aftertouch
+                    execute!(std::io::stdout(), EnableMouseCapture)?;
+                }
+                print!("Polyphony: {mode}\r\n");
+            }
+            None => {
+                self.poly_sink.pause();
+                self.sink.play();
+                execute!(std::io::stdout(), DisableMouseCapture)?;
+                print!("Polyphony: off\r\n");
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn list_voices(&self) {
+        if let Ok(pool) = self.voice_pool_control.lock() {
+            for line in self.aftertouch.render_voice_list(&pool) {
+                print!("{line}\r\n");
+            }
+        }
+    }
+
+    pub(crate) fn toggle_string(&mut self) -> Result<(), SynthError> {
+        if self.string_sink.is_paused() {
+            self.sink.pause();
+            self.supersaw_sink.pause();
+            self.fm_sink.pause();
+            self.additive_sink.pause();
+            leave_polyphony(&mut self.poly_mode, &self.poly_sink)?;
+            (self.fm_preset_index, self.additive_preset_index) = (None, None);
+            self.string_sink.play();
+            print!("Waveguide string: on\r\n");
+        } else {
+            self.string_sink.pause();
+            self.sink.play();
+            print!("Waveguide string: off\r\n");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn toggle_string_mode(&mut self) {
+        if let Ok(mut loss) = self.string_loss_control.lock() {
+            let pizzicato = *loss >= SUSTAIN_LOSS;
+            *loss = if pizzicato { 0.95 } else { SUSTAIN_LOSS };
+            print!("Waveguide string: {}\r\n", if pizzicato { "pizzicato" } else { "sustained" });
+        }
+    }
+
+    pub(crate) fn toggle_supersaw(&mut self) -> Result<(), SynthError> {
+        if self.supersaw_sink.is_paused() {
+            self.sink.pause();
+            self.fm_sink.pause();
+            self.string_sink.pause();
+            self.additive_sink.pause();
+            leave_polyphony(&mut self.poly_mode, &self.poly_sink)?;
+            (self.fm_preset_index, self.additive_preset_index) = (None, None);
+            self.supersaw_sink.play();
+            print!("Super saw: on\r\n");
+        } else {
+            self.supersaw_sink.pause();
+            self.sink.play();
+            print!("Super saw: off\r\n");
+        }
+        Ok(())
+    }
+
+    pub(crate) fn step_supersaw_detune(&mut self) {
+        if let Ok(mut detune) = self.supersaw_detune_control.lock() {
+            *detune = if *detune >= 100.0 { 0.0 } else { *detune + 10.0 };
+            print!("Super saw detune: {:.0} cents\r\n", *detune);
+        }
+    }
+
+    pub(crate) fn step_supersaw_mix(&mut self) {
+        if let Ok(mut mix) = self.supersaw_mix_control.lock() {
+            *mix = if *mix >= 0.95 { 0.0 } else { *mix + 0.1 };
+            print!("Super saw center mix: {:.1}\r\n", *mix);
+        }
+    }
+
+    pub(crate) fn cycle_fm_preset(&mut self) -> Result<(), SynthError> {
+        // Steps off -> each built-in preset -> off, like the sub-oscillator
+        self.fm_preset_index = match self.fm_preset_index {
+            None => Some(0),
+            Some(i) if i + 1 < BUILTIN_FM_PRESETS.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        match self.fm_preset_index {
+            Some(i) => {
+                if let Ok(mut preset) = self.fm_preset_control.lock() {
+                    *preset = BUILTIN_FM_PRESETS[i];
+                }
+                self.sink.pause();
+                self.supersaw_sink.pause();
+                self.string_sink.pause();
+                self.additive_sink.pause();
+                self.additive_preset_index = None;
+                leave_polyphony(&mut self.poly_mode, &self.poly_sink)?;
+                self.fm_sink.play();
+                print!("FM preset: {}\r\n", BUILTIN_FM_PRESETS[i].name);
+            }
+            None => {
+                self.fm_sink.pause();
+                self.sink.play();
+                print!("FM: off\r\n");
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn cycle_additive_preset(&mut self) -> Result<(), SynthError> {
+        self.additive_preset_index = match self.additive_preset_index {
+            None => Some(0),
+            Some(i) if i + 1 < AdditivePreset::ALL.len() => Some(i + 1),
+            Some(_) => None,
+        };
+        match self.additive_preset_index {
+            Some(i) => {
+                if let Ok(mut partials) = self.additive_partials_control.lock() {
+                    *partials = AdditivePreset::ALL[i].partials();
+                }
+                self.sink.pause();
+                self.supersaw_sink.pause();
+                self.fm_sink.pause();
+                self.string_sink.pause();
+                leave_polyphony(&mut self.poly_mode, &self.poly_sink)?;
+                self.fm_preset_index = None;
+                self.additive_sink.play();
+                print!("Additive preset: {}\r\n", AdditivePreset::ALL[i]);
+            }
+            None => {
+                self.additive_sink.pause();
+                self.sink.play();
+                print!("Additive: off\r\n");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::keymap::keycode_display;
+use crate::wave::WaveShape;
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Names of the default layers, by number.
+const LAYER_NAMES: [&str; 3] = ["notes", "sound design", "performance"];
+
+/// How far one press of a cutoff key moves a one-pole filter: a quarter of an octave.
+const CUTOFF_STEP: f32 = 1.189_207;
+
+/// How far one press of a drive key moves the waveshaper: 1.5 dB.
+const DRIVE_STEP: f32 = 1.188_502;
+
+/// An effect a layer key can switch on and off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EffectType {
+    Filter,
+    Echo,
+    Chorus,
+    Widener,
+    BusCompressor,
+    Gate,
+    Freeze,
+    TapeStop,
+    LowPass,
+    HighPass,
+}
+
+impl EffectType {
+    pub fn name(self) -> &'static str {
+        match self {
+            EffectType::Filter => "filter",
+            EffectType::Echo => "echo",
+            EffectType::Chorus => "thick chorus",
+            EffectType::Widener => "stereo widener",
+            EffectType::BusCompressor => "bus compressor",
+            EffectType::Gate => "gate",
+            EffectType::Freeze => "spectral freeze",
+            EffectType::TapeStop => "tape stop",
+            EffectType::LowPass => "low-pass",
+            EffectType::HighPass => "high-pass",
+        }
+    }
+}
+
+/// What a key does on a layer other than the notes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    SetWaveform(WaveShape),
+    /// Steps the master volume by this much.
+    AdjustVolume(f32),
+    /// Steps the tempo by this many BPM.
+    AdjustTempo(f32),
+    /// Multiplies the one-pole low-pass cutoff by this.
+    ScaleLowPassCutoff(f32),
+    /// Multiplies the one-pole high-pass cutoff by this.
+    ScaleHighPassCutoff(f32),
+    /// Steps the echo's delay time by this many milliseconds.
+    AdjustEchoTime(f32),
+    /// Steps the echo's feedback by this much.
+    AdjustEchoFeedback(f32),
+    /// Moves to the next waveshaper preset, or off after the last.
+    CycleShaper,
+    /// Multiplies the active waveshaper's drive by this.
+    ScaleShaperDrive(f32),
+    ToggleEffect(EffectType),
+    TriggerStutter,
+    /// Records, plays or overdubs, as the looper's own key does.
+    PressLooper,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::SetWaveform(shape) => write!(f, "{shape}"),
+            Action::AdjustVolume(step) => write!(f, "volume {step:+.1}"),
+            Action::AdjustTempo(step) => write!(f, "tempo {step:+.0}"),
+            Action::ScaleLowPassCutoff(ratio) => write!(f, "low-pass cutoff x{ratio:.2}"),
+            Action::ScaleHighPassCutoff(ratio) => write!(f, "high-pass cutoff x{ratio:.2}"),
+            Action::AdjustEchoTime(step) => write!(f, "echo time {step:+.0} ms"),
+            Action::AdjustEchoFeedback(step) => write!(f, "echo feedback {step:+.2}"),
+            Action::CycleShaper => write!(f, "waveshaper"),
+            Action::ScaleShaperDrive(ratio) => write!(f, "drive x{ratio:.2}"),
+            Action::ToggleEffect(effect) => write!(f, "{}", effect.name()),
+            Action::TriggerStutter => write!(f, "stutter"),
+            Action::PressLooper => write!(f, "looper"),
+        }
+    }
+}
+
+/// Layers of key bindings, so the same keys reach more controls than modifiers
+/// alone leave room for. Layer 0 plays notes; by default layer 1 holds the sound
+/// design controls and layer 2 the performance effects. [`cycle_key`](Self::cycle_key),
+/// Tab unless changed, moves to the next layer.
+///
+/// `key_actions` is keyed by layer and key, and [`bind`](Self::bind) changes it. A key
+/// with no action on the active layer does what it does on layer 0.
+#[derive(Clone, Debug)]
+pub struct ShortcutLayer {
+    pub active_layer: u8,
+    pub layer_count: u8,
+    pub key_actions: HashMap<(u8, KeyCode), Action>,
+    pub cycle_key: KeyCode,
+}
+
+impl Default for ShortcutLayer {
+    fn default() -> Self {
+        let mut layers = ShortcutLayer {
+            active_layer: 0,
+            layer_count: LAYER_NAMES.len() as u8,
+            key_actions: HashMap::new(),
+            cycle_key: KeyCode::Tab,
+        };
+        for (key, shape) in ['1', '2', '3', '4'].into_iter().zip(WaveShape::ALL) {
+            layers.bind(1, KeyCode::Char(key), Action::SetWaveform(shape));
+        }
+        let sound_design = [
+            ('f', Action::ToggleEffect(EffectType::Filter)),
+            ('e', Action::ToggleEffect(EffectType::Echo)),
+            (',', Action::AdjustEchoTime(-25.0)),
+            ('.', Action::AdjustEchoTime(25.0)),
+            ('n', Action::AdjustEchoFeedback(-0.05)),
+            ('m', Action::AdjustEchoFeedback(0.05)),
+            ('d', Action::CycleShaper),
+            ('9', Action::ScaleShaperDrive(1.0 / DRIVE_STEP)),
+            ('0', Action::ScaleShaperDrive(DRIVE_STEP)),
+            ('c', Action::ToggleEffect(EffectType::Chorus)),
+            ('w', Action::ToggleEffect(EffectType::Widener)),
+            ('b', Action::ToggleEffect(EffectType::BusCompressor)),
+            ('l', Action::ToggleEffect(EffectType::LowPass)),
+            ('[', Action::ScaleLowPassCutoff(1.0 / CUTOFF_STEP)),
+            (']', Action::ScaleLowPassCutoff(CUTOFF_STEP)),
+            ('h', Action::ToggleEffect(EffectType::HighPass)),
+            (';', Action::ScaleHighPassCutoff(1.0 / CUTOFF_STEP)),
+            ('\'', Action::ScaleHighPassCutoff(CUTOFF_STEP)),
+            ('-', Action::AdjustVolume(-0.1)),
+            ('=', Action::AdjustVolume(0.1)),
+        ];
+        let performance = [
+            ('g', Action::ToggleEffect(EffectType::Gate)),
+            ('z', Action::ToggleEffect(EffectType::Freeze)),
+            ('t', Action::ToggleEffect(EffectType::TapeStop)),
+            ('s', Action::TriggerStutter),
+            ('l', Action::PressLooper),
+            ('-', Action::AdjustTempo(-5.0)),
+            ('=', Action::AdjustTempo(5.0)),
+        ];
+        for (key, action) in sound_design {
+            layers.bind(1, KeyCode::Char(key), action);
+        }
+        for (key, action) in performance {
+            layers.bind(2, KeyCode::Char(key), action);
+        }
+        layers
+    }
+}
+
+impl ShortcutLayer {
+    /// Binds `key` on `layer`, replacing what it did there. Layers past the count
+    /// add to it.
+    pub fn bind(&mut self, layer: u8, key: KeyCode, action: Action) {
+        self.layer_count = self.layer_count.max(layer + 1);
+        self.key_actions.insert((layer, key), action);
+    }
+
+    pub fn unbind(&mut self, layer: u8, key: KeyCode) {
+        self.key_actions.remove(&(layer, key));
+    }
+
+    /// Moves to the next layer, back to the notes after the last, and returns it.
+    pub fn cycle(&mut self) -> u8 {
+        self.active_layer = (self.active_layer + 1) % self.layer_count.max(1);
+        self.active_layer
+    }
+
+    /// What `key` does on the active layer, if it isn't a note there.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.key_actions.get(&(self.active_layer, key)).copied()
+    }
+
+    pub fn layer_name(&self) -> String {
+        match LAYER_NAMES.get(self.active_layer as usize) {
+            Some(name) => name.to_string(),
+            None => format!("layer {}", self.active_layer),
+        }
+    }
+
+    /// The active layer's bindings in key order, like "1 sine, 2 square".
+    pub fn describe(&self) -> String {
+        let mut bindings: Vec<(String, Action)> = self
+            .key_actions
+            .iter()
+            .filter(|((layer, _), _)| *layer == self.active_layer)
+            .map(|(&(_, key), &action)| (keycode_display(key), action))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        let bindings: Vec<String> = bindings.iter().map(|(key, action)| format!("{key} {action}")).collect();
+        bindings.join(", ")
+    }
+}
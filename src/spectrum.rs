@@ -0,0 +1,133 @@
+use crate::oscillator::WaveTableOscillator;
+use crate::window::FftWindow;
+use rodio::Source;
+use rustfft::num_complex::Complex32;
+use rustfft::FftPlanner;
+
+/// Peaks further than this below the strongest one are window leakage or noise, not
+/// partials.
+pub const PEAK_FLOOR_DB: f32 = -60.0;
+
+/// Samples [`measure_thd`] renders and transforms.
+pub const THD_FFT_SIZE: usize = 4096;
+
+/// Bins either side of a harmonic counted toward its power: the Blackman-Harris
+/// main lobe, so a partial between bins is still caught whole.
+const THD_LOBE_BINS: usize = 4;
+
+/// Magnitudes of bins `0..=len / 2` of `samples` under a Hann window, scaled so a
+/// full-scale sine centred on a bin reads 1.0.
+pub fn magnitude_spectrum(samples: &[f32]) -> Vec<f32> {
+    let window = FftWindow::Hann.coefficients(samples.len());
+    let window_sum: f32 = window.iter().sum();
+    if window_sum == 0.0 {
+        return vec![0.0; samples.len() / 2 + 1];
+    }
+
+    let mut spectrum: Vec<Complex32> = samples
+        .iter()
+        .zip(&window)
+        .map(|(sample, coefficient)| Complex32::new(sample * coefficient, 0.0))
+        .collect();
+    FftPlanner::new().plan_fft_forward(samples.len()).process(&mut spectrum);
+
+    spectrum[..=samples.len() / 2]
+        .iter()
+        .map(|bin| bin.norm() * 2.0 / window_sum)
+        .collect()
+}
+
+/// The `count` strongest local maxima of `magnitudes` as `(frequency_hz,
+/// magnitude_db)`, strongest first. Each partial counts once however many bins it
+/// spreads over, and its frequency and level are refined by a parabola through the
+/// peak bin and its neighbours in dB. Peaks below [`PEAK_FLOOR_DB`] relative to the
+/// strongest are left out.
+pub fn find_spectral_peaks(magnitudes: &[f32], sample_rate: u32, fft_size: usize, count: usize) -> Vec<(f32, f32)> {
+    let db: Vec<f32> = magnitudes.iter().map(|&m| 20.0 * m.max(1e-10).log10()).collect();
+    let bin_hz = sample_rate as f32 / fft_size.max(1) as f32;
+
+    let mut peaks: Vec<(f32, f32)> = (1..db.len().saturating_sub(1))
+        .filter(|&bin| db[bin] > db[bin - 1] && db[bin] >= db[bin + 1])
+        .map(|bin| {
+            let (left, centre, right) = (db[bin - 1], db[bin], db[bin + 1]);
+            let denominator = left - 2.0 * centre + right;
+            let offset = if denominator == 0.0 { 0.0 } else { 0.5 * (left - right) / denominator };
+            ((bin as f32 + offset) * bin_hz, centre - 0.25 * (left - right) * offset)
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    if let Some(&(_, strongest)) = peaks.first() {
+        peaks.retain(|&(_, level)| level >= strongest + PEAK_FLOOR_DB);
+    }
+    peaks.truncate(count);
+    peaks
+}
+
+/// Total harmonic distortion of `osc` playing `fundamental_hz`: the RMS of
+/// harmonics 2 through `n_harmonics` relative to the fundamental, so 0.001 is 0.1%.
+/// Harmonics at or above Nyquist are skipped.
+///
+/// Renders [`THD_FFT_SIZE`] samples under a Blackman-Harris window, whose sidelobes
+/// sit low enough that leakage from the fundamental doesn't swamp a clean sine's
+/// harmonics the way Hann's would.
+pub fn measure_thd(osc: &mut WaveTableOscillator, fundamental_hz: f32, n_harmonics: usize) -> f32 {
+    osc.set_frequency_direct(fundamental_hz);
+    let window = FftWindow::BlackmanHarris.coefficients(THD_FFT_SIZE);
+    let mut spectrum: Vec<Complex32> = window
+        .iter()
+        .map(|coefficient| Complex32::new(osc.get_sample() * coefficient, 0.0))
+        .collect();
+    FftPlanner::new().plan_fft_forward(THD_FFT_SIZE).process(&mut spectrum);
+
+    let nyquist_bin = THD_FFT_SIZE / 2;
+    let bin_hz = osc.sample_rate() as f32 / THD_FFT_SIZE as f32;
+    let power_near = |hz: f32| -> f32 {
+        let centre = (hz / bin_hz).round() as usize;
+        let low = centre.saturating_sub(THD_LOBE_BINS).max(1);
+        let high = (centre + THD_LOBE_BINS).min(nyquist_bin);
+        spectrum[low..=high].iter().map(|bin| bin.norm_sqr()).sum()
+    };
+
+    let fundamental_power = power_near(fundamental_hz);
+    if fundamental_power == 0.0 {
+        return 0.0;
+    }
+    let harmonic_power: f32 = (2..=n_harmonics)
+        .map(|n| n as f32 * fundamental_hz)
+        .take_while(|&hz| hz < bin_hz * nyquist_bin as f32)
+        .map(power_near)
+        .sum();
+    (harmonic_power / fundamental_power).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave::{generate_wave_table, WaveShape};
+    use std::f32::consts::TAU;
+
+    #[test]
+    fn pure_sine_has_one_peak_at_its_frequency() {
+        let sine: Vec<f32> = (0..4096).map(|i| (TAU * 440.0 * i as f32 / 44100.0).sin()).collect();
+        let peaks = find_spectral_peaks(&magnitude_spectrum(&sine), 44100, sine.len(), 5);
+        assert_eq!(peaks.len(), 1, "{peaks:?}");
+        let (freq, level) = peaks[0];
+        assert!((freq - 440.0).abs() <= 1.0, "{freq} Hz");
+        assert!(level.abs() < 1.5, "{level} dB");
+    }
+
+    #[test]
+    fn small_sine_table_has_low_distortion() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Sine, 64));
+        let thd = measure_thd(&mut osc, 440.0, 10);
+        assert!(thd < 0.0005, "THD {:.4}%", thd * 100.0);
+    }
+
+    #[test]
+    fn square_wave_is_heavily_distorted() {
+        let mut osc = WaveTableOscillator::new(44100, generate_wave_table(WaveShape::Square, 2048));
+        // Odd harmonics at 1/n: 3rd to 9th alone come to about 43%
+        assert!(measure_thd(&mut osc, 440.0, 10) > 0.4);
+    }
+}
@@ -0,0 +1,143 @@
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Captures a short slice of the signal and repeats it, the glitch-style stutter.
+///
+/// A trigger records the next `capture_len` samples while the live signal carries on,
+/// then plays the slice back `repeat_count` times before returning to the live signal.
+/// Capture and playback never overlap: a retrigger during playback drops the old slice
+/// and starts a fresh capture.
+pub struct StutterEffect {
+    capture_buf: Vec<f32>,
+    capture_len: usize,
+    repeat_count: u8,
+    current_repeat: u8,
+    playback_pos: usize,
+    capturing: bool,
+}
+
+impl StutterEffect {
+    pub fn new(capture_len: usize, repeat_count: u8) -> StutterEffect {
+        StutterEffect {
+            capture_buf: Vec::with_capacity(capture_len),
+            capture_len: capture_len.max(1),
+            repeat_count,
+            current_repeat: repeat_count,
+            playback_pos: 0,
+            capturing: false,
+        }
+    }
+
+    /// The slice length for the next trigger; one already under way keeps its own.
+    pub fn set_capture_len(&mut self, capture_len: usize) {
+        self.capture_len = capture_len.max(1);
+    }
+
+    pub fn trigger(&mut self) {
+        self.capture_buf.clear();
+        self.capturing = true;
+        self.current_repeat = 0;
+        self.playback_pos = 0;
+    }
+
+    /// True while capturing or repeating.
+    pub fn is_active(&self) -> bool {
+        self.capturing || self.current_repeat < self.repeat_count
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if self.capturing {
+            self.capture_buf.push(input);
+            if self.capture_buf.len() >= self.capture_len {
+                self.capturing = false;
+            }
+            return input;
+        }
+        if self.current_repeat >= self.repeat_count || self.capture_buf.is_empty() {
+            return input;
+        }
+
+        let sample = self.capture_buf[self.playback_pos];
+        self.playback_pos += 1;
+        if self.playback_pos == self.capture_buf.len() {
+            self.playback_pos = 0;
+            self.current_repeat += 1;
+        }
+        sample
+    }
+}
+
+/// Runs a source through a [`StutterEffect`] whose slice is one `subdivision` note
+/// (8 an eighth, 16 a sixteenth) at the tempo in the bpm control.
+pub struct StutterSource<S: Source<Item = f32>> {
+    source: S,
+    stutter: StutterEffect,
+    trigger: Arc<Mutex<bool>>,
+    bpm: Arc<Mutex<f32>>,
+    subdivision: u8,
+}
+
+impl<S: Source<Item = f32>> StutterSource<S> {
+    /// An eighth note slice repeated `repeat_count` times.
+    pub fn new(source: S, bpm: f32, repeat_count: u8) -> StutterSource<S> {
+        StutterSource {
+            source,
+            stutter: StutterEffect::new(1, repeat_count),
+            trigger: Arc::new(Mutex::new(false)),
+            bpm: Arc::new(Mutex::new(bpm)),
+            subdivision: 8,
+        }
+    }
+
+    /// Set to true to start a stutter; the source clears it once it has started.
+    pub fn get_trigger_control(&self) -> Arc<Mutex<bool>> {
+        self.trigger.clone()
+    }
+
+    pub fn get_bpm_control(&self) -> Arc<Mutex<f32>> {
+        self.bpm.clone()
+    }
+
+    pub fn set_subdivision(&mut self, subdivision: u8) {
+        self.subdivision = subdivision.max(1);
+    }
+
+    fn capture_len(&self) -> usize {
+        let bpm = self.bpm.lock().map_or(120.0, |bpm| *bpm).max(1.0);
+        let beat_samples = 60.0 * self.source.sample_rate() as f32 / bpm;
+        (beat_samples * 4.0 / self.subdivision as f32) as usize * self.source.channels() as usize
+    }
+}
+
+impl<S: Source<Item = f32>> Source for StutterSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for StutterSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        let triggered = self.trigger.lock().is_ok_and(|mut trigger| std::mem::take(&mut *trigger));
+        if triggered {
+            let capture_len = self.capture_len();
+            self.stutter.set_capture_len(capture_len);
+            self.stutter.trigger();
+        }
+        Some(self.stutter.process(input))
+    }
+}
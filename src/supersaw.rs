@@ -0,0 +1,124 @@
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Naive sawtooth with a PolyBLEP correction at the wrap point.
+struct SawOscillator {
+    phase: f32,
+    phase_increment: f32,
+}
+
+impl SawOscillator {
+    fn new(phase: f32) -> SawOscillator {
+        SawOscillator {
+            phase,
+            phase_increment: 0.0,
+        }
+    }
+
+    fn get_sample(&mut self) -> f32 {
+        let t = self.phase;
+        let dt = self.phase_increment;
+        let mut sample = 2.0 * t - 1.0;
+
+        // Smooth the discontinuity to keep aliasing down at high pitches
+        if dt > 0.0 {
+            if t < dt {
+                let x = t / dt;
+                sample -= x + x - x * x - 1.0;
+            } else if t > 1.0 - dt {
+                let x = (t - 1.0) / dt;
+                sample -= x * x + x + x + 1.0;
+            }
+        }
+
+        self.phase += dt;
+        self.phase -= self.phase.floor();
+        sample
+    }
+}
+
+/// Seven detuned saws mixed like the JP-8000: a louder center voice and
+/// three symmetric pairs sharing the remaining level.
+pub struct SuperSaw {
+    sample_rate: u32,
+    oscillators: [SawOscillator; 7],
+    detune_cents: Arc<Mutex<f32>>,
+    mix_center: Arc<Mutex<f32>>,
+    frequency: Arc<Mutex<f32>>,
+}
+
+impl SuperSaw {
+    pub fn new(sample_rate: u32, frequency: Arc<Mutex<f32>>) -> SuperSaw {
+        SuperSaw {
+            sample_rate,
+            // Staggered start phases so the voices don't start in lockstep
+            oscillators: std::array::from_fn(|i| SawOscillator::new(i as f32 / 7.0)),
+            detune_cents: Arc::new(Mutex::new(25.0)),
+            mix_center: Arc::new(Mutex::new(0.4)),
+            frequency,
+        }
+    }
+
+    pub fn get_detune_control(&self) -> Arc<Mutex<f32>> {
+        self.detune_cents.clone()
+    }
+
+    pub fn get_mix_center_control(&self) -> Arc<Mutex<f32>> {
+        self.mix_center.clone()
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let freq = self.frequency.lock().map_or(0.0, |f| *f);
+        if freq == 0.0 {
+            return 0.0;
+        }
+
+        let detune = self.detune_cents.lock().map_or(0.0, |d| d.clamp(0.0, 100.0));
+        let mix_center = self.mix_center.lock().map_or(0.5, |m| m.clamp(0.0, 1.0));
+        let side_gain = (1.0 - mix_center) / 6.0;
+
+        let mut sample = 0.0;
+        for (i, osc) in self.oscillators.iter_mut().enumerate() {
+            // 0 is the center voice, then +/- pairs at 1/3, 2/3 and the full detune
+            let cents = match i {
+                0 => 0.0,
+                _ => {
+                    let pair = i.div_ceil(2) as f32;
+                    let sign = if i % 2 == 1 { 1.0 } else { -1.0 };
+                    sign * detune * pair / 3.0
+                }
+            };
+            osc.phase_increment = freq * 2.0_f32.powf(cents / 1200.0) / self.sample_rate as f32;
+            let gain = if i == 0 { mix_center } else { side_gain };
+            sample += osc.get_sample() * gain;
+        }
+
+        sample * 0.3
+    }
+}
+
+impl Source for SuperSaw {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for SuperSaw {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
@@ -0,0 +1,184 @@
+use crate::fm::BUILTIN_FM_PRESETS;
+use crate::oscillator::{SubInterval, SubOscillatorMode};
+use crate::patch::{apply_preset, PatchControls, PatchVoice, Preset, BITFIELD_CUTOFF_RANGE};
+use std::fmt;
+
+/// The non-commercial / educational manufacturer ID.
+pub const SYSEX_MANUFACTURER_ID: u8 = 0x7d;
+pub const SYSEX_PATCH_LOAD: u8 = 0x01;
+
+/// Parameter IDs of the patch-load message. Every value is one 7-bit data byte.
+pub mod sysex_param {
+    /// 0 wave table, 1 super saw, 2 FM.
+    pub const VOICE: u8 = 0x00;
+    /// Index into the built-in FM presets.
+    pub const FM_PRESET: u8 = 0x01;
+    /// 0 off, 1 one octave down, 2 two octaves down, 3 a fifth down.
+    pub const SUB_INTERVAL: u8 = 0x02;
+    /// 0-127 for 0.0-1.0.
+    pub const SUB_MIX: u8 = 0x03;
+    /// 0 off, 1 on.
+    pub const FILTER_ENABLED: u8 = 0x04;
+    /// 0-127 for 20 Hz-20 kHz on a log scale.
+    pub const FILTER_CUTOFF: u8 = 0x05;
+    /// 0-127 for 0.0-1.0.
+    pub const FILTER_RESONANCE: u8 = 0x06;
+    /// Whole cents.
+    pub const SUPERSAW_DETUNE: u8 = 0x07;
+    /// 0-127 for 0.0-1.0.
+    pub const SUPERSAW_MIX_CENTER: u8 = 0x08;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SysexError {
+    /// Doesn't start with 0xF0 and end with 0xF7.
+    NotSysex,
+    /// Another manufacturer's message; not an error as such, just not ours.
+    OtherManufacturer(u8),
+    UnknownCommand(u8),
+    /// A parameter ID without its value.
+    MissingValue(u8),
+    UnknownParameter(u8),
+    InvalidValue { parameter: u8, value: u8 },
+}
+
+impl fmt::Display for SysexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SysexError::NotSysex => write!(f, "not a complete SysEx message"),
+            SysexError::OtherManufacturer(id) => write!(f, "SysEx for manufacturer {id:#04x}"),
+            SysexError::UnknownCommand(command) => write!(f, "unknown SysEx command {command:#04x}"),
+            SysexError::MissingValue(parameter) => write!(f, "SysEx parameter {parameter:#04x} has no value"),
+            SysexError::UnknownParameter(parameter) => write!(f, "unknown SysEx parameter {parameter:#04x}"),
+            SysexError::InvalidValue { parameter, value } => {
+                write!(f, "invalid value {value} for SysEx parameter {parameter:#04x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SysexError {}
+
+/// Decodes and applies a patch-load message, returning the preset so the caller can
+/// switch to its voice.
+///
+/// The format is `[0xF0, 0x7D, 0x01, (parameter, value)..., 0xF7]`; see
+/// [`sysex_param`]. Parameters left out keep their [`Preset::init`] values.
+pub fn handle_sysex(data: &[u8], controls: &PatchControls) -> Result<Preset, SysexError> {
+    let preset = parse_sysex_preset(data)?;
+    apply_preset(&preset, controls);
+    Ok(preset)
+}
+
+/// The decoding half of [`handle_sysex`].
+pub fn parse_sysex_preset(data: &[u8]) -> Result<Preset, SysexError> {
+    let [0xf0, body @ .., 0xf7] = data else {
+        return Err(SysexError::NotSysex);
+    };
+    match body {
+        [SYSEX_MANUFACTURER_ID, SYSEX_PATCH_LOAD, ..] => {}
+        [SYSEX_MANUFACTURER_ID, command, ..] => return Err(SysexError::UnknownCommand(*command)),
+        [manufacturer, ..] => return Err(SysexError::OtherManufacturer(*manufacturer)),
+        [] => return Err(SysexError::NotSysex),
+    }
+
+    let mut preset = Preset {
+        name: "SysEx".to_string(),
+        ..Preset::init()
+    };
+    let mut sub_interval = None;
+    let mut sub_mix = 0.5;
+    for pair in body[2..].chunks(2) {
+        let &[parameter, value] = pair else {
+            return Err(SysexError::MissingValue(pair[0]));
+        };
+        let invalid = SysexError::InvalidValue { parameter, value };
+        if value > 0x7f {
+            return Err(invalid);
+        }
+        let fraction = value as f32 / 127.0;
+
+        match parameter {
+            sysex_param::VOICE => {
+                preset.voice = match value {
+                    0 => PatchVoice::WaveTable,
+                    1 => PatchVoice::SuperSaw,
+                    2 => PatchVoice::Fm,
+                    _ => return Err(invalid),
+                }
+            }
+            sysex_param::FM_PRESET => {
+                preset.fm_preset = BUILTIN_FM_PRESETS.get(value as usize).ok_or(invalid)?.name.to_string()
+            }
+            sysex_param::SUB_INTERVAL => {
+                sub_interval = match value {
+                    0 => None,
+                    1 => Some(SubInterval::OneOctaveDown),
+                    2 => Some(SubInterval::TwoOctavesDown),
+                    3 => Some(SubInterval::FifthDown),
+                    _ => return Err(invalid),
+                }
+            }
+            sysex_param::SUB_MIX => sub_mix = fraction,
+            sysex_param::FILTER_ENABLED => preset.filter_enabled = value != 0,
+            sysex_param::FILTER_CUTOFF => {
+                let (low, high) = BITFIELD_CUTOFF_RANGE;
+                preset.filter_cutoff_hz = low * (high / low).powf(fraction);
+            }
+            sysex_param::FILTER_RESONANCE => preset.filter_resonance = fraction,
+            sysex_param::SUPERSAW_DETUNE => preset.supersaw_detune_cents = value as f32,
+            sysex_param::SUPERSAW_MIX_CENTER => preset.supersaw_mix_center = fraction,
+            _ => return Err(SysexError::UnknownParameter(parameter)),
+        }
+    }
+    preset.sub_oscillator = sub_interval.map(|interval| SubOscillatorMode { interval, mix: sub_mix });
+
+    Ok(preset)
+}
+
+/// Encodes `preset` as a patch-load message with every parameter set, for sending a
+/// patch to another instance. Values are rounded to 7 bits and the name isn't sent.
+pub fn preset_to_sysex(preset: &Preset) -> Vec<u8> {
+    let seven_bit = |fraction: f32| (fraction.clamp(0.0, 1.0) * 127.0).round() as u8;
+    let voice = match preset.voice {
+        PatchVoice::WaveTable => 0,
+        PatchVoice::SuperSaw => 1,
+        PatchVoice::Fm => 2,
+    };
+    let fm_preset = BUILTIN_FM_PRESETS
+        .iter()
+        .position(|fm| fm.name.eq_ignore_ascii_case(&preset.fm_preset))
+        .unwrap_or(0) as u8;
+    let (sub_interval, sub_mix) = match preset.sub_oscillator {
+        None => (0, 0.5),
+        Some(mode) => {
+            let interval = match mode.interval {
+                SubInterval::OneOctaveDown => 1,
+                SubInterval::TwoOctavesDown => 2,
+                SubInterval::FifthDown => 3,
+            };
+            (interval, mode.mix)
+        }
+    };
+    let (low, high) = BITFIELD_CUTOFF_RANGE;
+    let cutoff_position = (preset.filter_cutoff_hz.clamp(low, high) / low).ln() / (high / low).ln();
+
+    let parameters = [
+        (sysex_param::VOICE, voice),
+        (sysex_param::FM_PRESET, fm_preset),
+        (sysex_param::SUB_INTERVAL, sub_interval),
+        (sysex_param::SUB_MIX, seven_bit(sub_mix)),
+        (sysex_param::FILTER_ENABLED, preset.filter_enabled as u8),
+        (sysex_param::FILTER_CUTOFF, seven_bit(cutoff_position)),
+        (sysex_param::FILTER_RESONANCE, seven_bit(preset.filter_resonance)),
+        (sysex_param::SUPERSAW_DETUNE, preset.supersaw_detune_cents.clamp(0.0, 127.0).round() as u8),
+        (sysex_param::SUPERSAW_MIX_CENTER, seven_bit(preset.supersaw_mix_center)),
+    ];
+
+    let mut message = vec![0xf0, SYSEX_MANUFACTURER_ID, SYSEX_PATCH_LOAD];
+    for (parameter, value) in parameters {
+        message.extend([parameter, value]);
+    }
+    message.push(0xf7);
+    message
+}
@@ -0,0 +1,139 @@
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// How long the catch-up crossfade back to the live signal takes after a tape start.
+const CATCH_UP_SECS: f32 = 0.01;
+
+/// Slows the signal to a halt like a tape deck losing power, and spins it back up.
+///
+/// Input goes into a ring buffer at normal speed while the read position advances by
+/// `speed` samples per output sample, so slowing down drops the pitch and falls
+/// further behind the newest input, by `lag` samples. At a standstill the read position rejoins the
+/// newest input. The spin-up leaves the read position behind again, and once back at
+/// full speed it crossfades over to the live signal to drop that lag.
+pub struct TapeStop {
+    deceleration_rate: f32,
+    speed: f32,
+    buf: RingBuffer<f32>,
+    lag: f64,
+    engaged: bool,
+    catch_up: f32,
+    catch_up_step: f32,
+}
+
+impl TapeStop {
+    /// A tape stop that takes `stop_secs` to go from full speed to a standstill.
+    pub fn new(sample_rate: u32, stop_secs: f32) -> TapeStop {
+        let stop_samples = (stop_secs * sample_rate as f32).max(1.0);
+        // A stop or a start each fall half the ramp length behind, and one can start
+        // before the other has caught up
+        let len = stop_samples.ceil() as usize + 4;
+
+        TapeStop {
+            deceleration_rate: 1.0 / stop_samples,
+            speed: 1.0,
+            buf: RingBuffer::new(len),
+            lag: 0.0,
+            engaged: false,
+            catch_up: 0.0,
+            catch_up_step: 1.0 / (CATCH_UP_SECS * sample_rate as f32).max(1.0),
+        }
+    }
+
+    /// `true` powers the tape down, `false` spins it back up.
+    pub fn set_engaged(&mut self, engaged: bool) {
+        self.engaged = engaged;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.buf.push(input);
+
+        if self.engaged {
+            self.speed = (self.speed - self.deceleration_rate).max(0.0);
+        } else {
+            self.speed = (self.speed + self.deceleration_rate).min(1.0);
+        }
+
+        // Stopped, the read position stays on this input, so the next one is a sample ahead
+        if self.speed == 0.0 {
+            self.lag = 1.0;
+            return 0.0;
+        }
+
+        let lag = self.lag;
+        let tape = self.buf.read_interpolated(1.0 + lag as f32);
+        // The input moves on a sample while the read position moves on `speed`. Speed
+        // never exceeds 1, so this never passes the next input to be written
+        self.lag += 1.0 - self.speed as f64;
+
+        if self.engaged || self.speed < 1.0 || lag < 0.5 {
+            self.catch_up = 0.0;
+            return tape;
+        }
+
+        self.catch_up += self.catch_up_step;
+        if self.catch_up >= 1.0 {
+            self.catch_up = 0.0;
+            self.lag = 0.0;
+            return input;
+        }
+        tape * (1.0 - self.catch_up) + input * self.catch_up
+    }
+}
+
+/// Runs a source through a [`TapeStop`] with a shared engage control.
+pub struct TapeStopSource<S: Source<Item = f32>> {
+    source: S,
+    tape_stop: TapeStop,
+    engaged: Arc<Mutex<bool>>,
+}
+
+impl<S: Source<Item = f32>> TapeStopSource<S> {
+    pub fn new(source: S, stop_secs: f32) -> TapeStopSource<S> {
+        let tape_stop = TapeStop::new(source.sample_rate(), stop_secs);
+        TapeStopSource {
+            source,
+            tape_stop,
+            engaged: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub fn get_engaged_control(&self) -> Arc<Mutex<bool>> {
+        self.engaged.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TapeStopSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TapeStopSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+        if let Ok(engaged) = self.engaged.lock() {
+            self.tape_stop.set_engaged(*engaged);
+        }
+        Some(self.tape_stop.process(input))
+    }
+}
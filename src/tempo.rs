@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Taps further back than this are forgotten, so a pause starts a new count.
+const TAP_WINDOW: Duration = Duration::from_secs(3);
+
+/// Tap tempo: the BPM follows the average gap between recent taps.
+///
+/// The tempo is shared as `f32` bits in an `AtomicU32`, for anything that should
+/// follow it to read.
+#[derive(Debug)]
+pub struct TempoTapper {
+    taps: VecDeque<Instant>,
+    current_bpm: Arc<AtomicU32>,
+}
+
+impl TempoTapper {
+    /// A tapper holding `bpm` until it is tapped; also all headless or MIDI file
+    /// playback needs.
+    pub fn fixed_bpm(bpm: f32) -> TempoTapper {
+        TempoTapper {
+            taps: VecDeque::new(),
+            current_bpm: Arc::new(AtomicU32::new(bpm.max(1.0).to_bits())),
+        }
+    }
+
+    pub fn get_bpm_control(&self) -> Arc<AtomicU32> {
+        self.current_bpm.clone()
+    }
+
+    pub fn bpm(&self) -> f32 {
+        f32::from_bits(self.current_bpm.load(Ordering::Relaxed))
+    }
+
+    pub fn tap(&mut self) {
+        self.tap_at(Instant::now());
+    }
+
+    /// [`tap`](Self::tap) at a given time. The tempo changes from the second tap in
+    /// a window on; a lone tap only starts the count.
+    pub fn tap_at(&mut self, now: Instant) {
+        while self.taps.front().is_some_and(|&tap| now.duration_since(tap) > TAP_WINDOW) {
+            self.taps.pop_front();
+        }
+        self.taps.push_back(now);
+
+        if let (Some(&first), Some(&last), true) = (self.taps.front(), self.taps.back(), self.taps.len() > 1) {
+            let beat_secs = last.duration_since(first).as_secs_f32() / (self.taps.len() - 1) as f32;
+            if beat_secs > 0.0 {
+                self.current_bpm.store((60.0 / beat_secs).to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::SynthError;
+use crossterm::style::{Color, SetForegroundColor};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Names `--theme` accepts for the built-in themes.
+pub const THEME_NAMES: [&str; 5] = ["dark", "light", "high-contrast", "solarized", "nord"];
+
+/// Colors for everything the synth draws in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    pub background: Color,
+    /// Ordinary text and meter bars.
+    pub primary: Color,
+    pub secondary: Color,
+    pub highlight: Color,
+    /// Levels getting close to clipping.
+    pub warning: Color,
+    pub error: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+/// `theme.toml`: each color a crossterm color name like `dark_blue`, `reset` for the
+/// terminal's own, or a `#rrggbb` hex value. Missing colors come from `base`, a
+/// built-in theme name, or Dark.
+#[derive(Deserialize)]
+struct ThemeFile {
+    base: Option<String>,
+    background: Option<String>,
+    primary: Option<String>,
+    secondary: Option<String>,
+    highlight: Option<String>,
+    warning: Option<String>,
+    error: Option<String>,
+}
+
+impl Theme {
+    /// The terminal's own colors, with the keyboard layout's original octave colors.
+    pub fn dark() -> Theme {
+        Theme {
+            background: Color::Reset,
+            primary: Color::Reset,
+            secondary: Color::Blue,
+            highlight: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            background: Color::White,
+            primary: Color::Black,
+            secondary: Color::DarkBlue,
+            highlight: Color::DarkGreen,
+            warning: Color::DarkYellow,
+            error: Color::DarkRed,
+        }
+    }
+
+    /// Black, white and a single yellow accent.
+    pub fn high_contrast() -> Theme {
+        Theme {
+            background: Color::Black,
+            primary: Color::White,
+            secondary: Color::White,
+            highlight: Color::Yellow,
+            warning: Color::Yellow,
+            error: Color::Yellow,
+        }
+    }
+
+    pub fn solarized() -> Theme {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        Theme {
+            background: rgb(0, 43, 54),
+            primary: rgb(131, 148, 150),
+            secondary: rgb(38, 139, 210),
+            highlight: rgb(133, 153, 0),
+            warning: rgb(181, 137, 0),
+            error: rgb(220, 50, 47),
+        }
+    }
+
+    pub fn nord() -> Theme {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        Theme {
+            background: rgb(46, 52, 64),
+            primary: rgb(216, 222, 233),
+            secondary: rgb(129, 161, 193),
+            highlight: rgb(163, 190, 140),
+            warning: rgb(235, 203, 139),
+            error: rgb(191, 97, 106),
+        }
+    }
+
+    /// One of [`THEME_NAMES`], ignoring case and with a space allowed for the dash.
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name.trim().to_ascii_lowercase().replace(' ', "-").as_str() {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "solarized" => Some(Theme::solarized()),
+            "nord" => Some(Theme::nord()),
+            _ => None,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Theme, SynthError> {
+        let file: ThemeFile = toml::from_str(&std::fs::read_to_string(path)?)?;
+        let mut theme = match &file.base {
+            Some(name) => Theme::builtin(name)
+                .ok_or_else(|| SynthError::invalid_parameter("theme base", name, "not a built-in theme"))?,
+            None => Theme::dark(),
+        };
+
+        for (name, value, color) in [
+            ("background", &file.background, &mut theme.background),
+            ("primary", &file.primary, &mut theme.primary),
+            ("secondary", &file.secondary, &mut theme.secondary),
+            ("highlight", &file.highlight, &mut theme.highlight),
+            ("warning", &file.warning, &mut theme.warning),
+            ("error", &file.error, &mut theme.error),
+        ] {
+            if let Some(value) = value {
+                *color = parse_color(value)
+                    .ok_or_else(|| SynthError::invalid_parameter(name, value, "not a color name or #rrggbb"))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    /// `text` in `color`, going back to the primary color after it rather than
+    /// resetting, so the background stays.
+    pub fn paint(&self, text: &str, color: Color) -> String {
+        format!("{}{text}{}", SetForegroundColor(color), SetForegroundColor(self.primary))
+    }
+
+    /// Colors for successive octaves on the keyboard layout, low to high.
+    pub fn octave_colors(&self) -> [Color; 4] {
+        [self.secondary, self.highlight, self.warning, self.error]
+    }
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    match value.to_ascii_lowercase().as_str() {
+        // The terminal's own color
+        "reset" | "default" => Some(Color::Reset),
+        // Color's FromStr falls back to white; try_from turns unknown names down
+        name => Color::try_from(name).ok(),
+    }
+}
@@ -0,0 +1,160 @@
+use crate::chord::{ChordName, NoteName};
+use crossterm::style::Attribute;
+
+/// Characters from one node to the next along a row.
+const CELL_WIDTH: usize = 4;
+
+/// A window onto the Tonnetz, the lattice of pitch classes where a step right is a
+/// fifth up and a step to the upper right a major third up, so the upper left is a
+/// minor third down.
+///
+/// Nodes sit on staggered rows, every other row half a cell in, and the lines
+/// between them show the lattice's triangles: major triads point up, minor ones
+/// down, and a lit chord draws its shape. The window centres on the root until
+/// [`shift`](Self::shift) moves it. Pitch classes repeat across the lattice, so a
+/// lit note lights every copy in view.
+pub struct TonnetzDisplay {
+    columns: u16,
+    rows: u16,
+    root: u8,
+    /// Fifths and major thirds the window has moved from the root.
+    offset: (i32, i32),
+    /// One bit per lit pitch class.
+    lit: u16,
+}
+
+impl TonnetzDisplay {
+    /// `columns` nodes across and `rows` down.
+    pub fn new(columns: u16, rows: u16) -> TonnetzDisplay {
+        TonnetzDisplay {
+            columns: columns.max(1),
+            rows: rows.max(1),
+            root: 0,
+            offset: (0, 0),
+            lit: 0,
+        }
+    }
+
+    /// Lines of text [`render`](Self::render) produces: the rows plus the links
+    /// between them.
+    pub fn height(&self) -> u16 {
+        self.rows * 2 - 1
+    }
+
+    /// Centres the window on `root`, a pitch class, and drops any shift.
+    pub fn set_root(&mut self, root: u8) {
+        self.root = root % 12;
+        self.offset = (0, 0);
+    }
+
+    pub fn root(&self) -> u8 {
+        self.root
+    }
+
+    /// Moves the window `fifths` to the right and `thirds` up the lattice.
+    pub fn shift(&mut self, fifths: i32, thirds: i32) {
+        self.offset = (self.offset.0 + fifths, self.offset.1 + thirds);
+    }
+
+    /// Lights exactly `pitch_classes`.
+    pub fn set_lit(&mut self, pitch_classes: impl IntoIterator<Item = u8>) {
+        self.lit = pitch_classes.into_iter().fold(0, |mask, pitch_class| mask | 1 << (pitch_class % 12));
+    }
+
+    /// Centres on `chord`'s root and lights its tones.
+    pub fn show_chord(&mut self, chord: ChordName) {
+        self.set_root(chord.root.0);
+        self.set_lit(chord.quality.intervals().iter().map(|interval| chord.root.0 + interval));
+    }
+
+    pub fn is_lit(&self, pitch_class: u8) -> bool {
+        self.lit & 1 << (pitch_class % 12) != 0
+    }
+
+    /// The pitch class at node `x` of row `y`, counting from the bottom left.
+    pub fn pitch_class_at(&self, x: u16, y: u16) -> u8 {
+        let (fifths, thirds) = self.lattice_position(x, y);
+        let (center_fifths, center_thirds) = self.lattice_position(self.columns / 2, self.rows / 2);
+        let steps = 7 * (fifths - center_fifths + self.offset.0) + 4 * (thirds - center_thirds + self.offset.1);
+        (self.root as i32 + steps).rem_euclid(12) as u8
+    }
+
+    /// Odd rows are drawn half a cell in, so going up a row alternately keeps the
+    /// column and moves one right; the lattice's fifths count has to step back to
+    /// match.
+    fn lattice_position(&self, x: u16, y: u16) -> (i32, i32) {
+        (x as i32 - (y as i32) / 2, y as i32)
+    }
+
+    /// The lattice as text rows joined with `\r\n` for raw mode, top row first. Lit
+    /// nodes are drawn in reverse video and the root in bold; a link between two lit
+    /// nodes is drawn heavy.
+    pub fn render(&self) -> String {
+        let lines: Vec<String> = (0..self.rows)
+            .rev()
+            .flat_map(|y| {
+                let nodes = self.render_nodes(y);
+                match y {
+                    0 => vec![nodes],
+                    _ => vec![nodes, self.render_links(y - 1)],
+                }
+            })
+            .collect();
+        lines.join("\r\n")
+    }
+
+    fn indent(y: u16) -> usize {
+        (y as usize % 2) * CELL_WIDTH / 2
+    }
+
+    /// Row `y`'s note names with the fifths between neighbours.
+    fn render_nodes(&self, y: u16) -> String {
+        let mut line = " ".repeat(Self::indent(y));
+        for x in 0..self.columns {
+            let pitch_class = self.pitch_class_at(x, y);
+            // Not Stylize's reverse() or bold(), whose full reset would drop the theme's colors
+            let mut name = format!("{:<2}", NoteName(pitch_class).to_string());
+            if self.is_lit(pitch_class) {
+                name = format!("{}{name}{}", Attribute::Reverse, Attribute::NoReverse);
+            }
+            if pitch_class == self.root {
+                name = format!("{}{name}{}", Attribute::Bold, Attribute::NormalIntensity);
+            }
+            line.push_str(&name);
+            if x + 1 < self.columns {
+                let linked = self.is_lit(pitch_class) && self.is_lit(self.pitch_class_at(x + 1, y));
+                line.push_str(if linked { "══" } else { "──" });
+            }
+        }
+        line
+    }
+
+    /// The thirds between row `y` and the row above: `/` up to the major third and
+    /// `\` up to the minor third below.
+    fn render_links(&self, y: u16) -> String {
+        let width = Self::indent(1) + self.columns as usize * CELL_WIDTH;
+        let mut cells = vec![' '; width];
+        for x in 0..self.columns {
+            let pitch_class = self.pitch_class_at(x, y);
+            let position = Self::indent(y) + x as usize * CELL_WIDTH;
+            let major_third = (pitch_class + 4) % 12;
+            let minor_third_down = (pitch_class + 9) % 12;
+            // The node up and to the right is `x` on an even row and `x + 1` on an
+            // odd one; it only exists if it fits in the window
+            let even = y.is_multiple_of(2);
+            let right = if even { x } else { x + 1 };
+            if right < self.columns {
+                let lit = self.is_lit(pitch_class) && self.is_lit(major_third);
+                cells[position + 1] = if lit { '▞' } else { '/' };
+            }
+            let left = if even { x.checked_sub(1) } else { Some(x) };
+            if let Some(left) = left {
+                if left < self.columns && position > 0 {
+                    let lit = self.is_lit(pitch_class) && self.is_lit(minor_third_down);
+                    cells[position - 1] = if lit { '▚' } else { '\\' };
+                }
+            }
+        }
+        cells.into_iter().collect::<String>().trim_end().to_string()
+    }
+}
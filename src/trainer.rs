@@ -0,0 +1,101 @@
+use crate::tuning::{cents, note_name, TuningSystem};
+use rand::Rng;
+
+/// Short names of the intervals from a unison to an octave, indexed by semitones.
+pub const INTERVAL_NAMES: [&str; 13] = ["P1", "m2", "M2", "m3", "M3", "P4", "TT", "P5", "m6", "M6", "m7", "M7", "P8"];
+
+/// Semitones in the interval called `name`, as in [`INTERVAL_NAMES`]. Case matters:
+/// `m3` is a minor third and `M3` a major one.
+pub fn parse_interval(name: &str) -> Option<u8> {
+    INTERVAL_NAMES.iter().position(|&known| known == name.trim()).map(|semitones| semitones as u8)
+}
+
+/// Two notes to tell apart, played low then high.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntervalQuestion {
+    pub low_note: u8,
+    pub semitones: u8,
+}
+
+impl IntervalQuestion {
+    pub fn high_note(&self) -> u8 {
+        self.low_note + self.semitones
+    }
+
+    /// What the answer reveals, e.g. `M3: C4 -> E4, 400 cents`.
+    pub fn describe(&self, tuning: &TuningSystem) -> String {
+        let (low, high) = (tuning.frequency(self.low_note), tuning.frequency(self.high_note()));
+        format!(
+            "{}: {} -> {}, {:.0} cents",
+            INTERVAL_NAMES[self.semitones as usize],
+            note_name(self.low_note),
+            note_name(self.high_note()),
+            cents(low, high)
+        )
+    }
+}
+
+/// Ear training: asks for the interval between two notes and keeps score.
+///
+/// Questions are drawn from `intervals`, in semitones, with the lower note anywhere
+/// from `low_note` up to an octave above it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntervalTrainer {
+    pub intervals: Vec<u8>,
+    pub low_note: u8,
+    pub right_count: u32,
+    pub wrong_count: u32,
+    current: Option<IntervalQuestion>,
+}
+
+impl IntervalTrainer {
+    pub fn new(intervals: Vec<u8>) -> IntervalTrainer {
+        IntervalTrainer {
+            intervals: intervals.into_iter().filter(|&semitones| semitones <= 12).collect(),
+            low_note: 60,
+            right_count: 0,
+            wrong_count: 0,
+            current: None,
+        }
+    }
+
+    /// The question waiting for an answer.
+    pub fn current(&self) -> Option<IntervalQuestion> {
+        self.current
+    }
+
+    /// Draws and remembers a new question. An empty interval set asks only octaves.
+    pub fn next_question(&mut self, rng: &mut impl Rng) -> IntervalQuestion {
+        let semitones = match self.intervals.len() {
+            0 => 12,
+            len => self.intervals[rng.gen_range(0..len)],
+        };
+        let low_note = self.low_note.min(127 - 24) + rng.gen_range(0..=12);
+        let question = IntervalQuestion { low_note, semitones };
+        self.current = Some(question);
+        question
+    }
+
+    /// Marks `guess` against the current question and counts it, returning the
+    /// question and whether the guess was right. `None` when nothing was asked.
+    pub fn answer(&mut self, guess: &str) -> Option<(IntervalQuestion, bool)> {
+        let question = self.current.take()?;
+        let correct = parse_interval(guess) == Some(question.semitones);
+        if correct {
+            self.right_count += 1;
+        } else {
+            self.wrong_count += 1;
+        }
+        Some((question, correct))
+    }
+
+    pub fn score(&self) -> String {
+        format!("{} right, {} wrong", self.right_count, self.wrong_count)
+    }
+}
+
+impl Default for IntervalTrainer {
+    fn default() -> IntervalTrainer {
+        IntervalTrainer::new((1..=12).collect())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::clock::MasterClock;
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+const MODE_CROSSFADE_SECS: f32 = 0.1;
+
+/// Beat-locked timing for a [`Tremolo`].
+///
+/// `subdivision` is a note value in 4/4: 1 is a whole note, 2 a half, 4 a quarter
+/// (one beat), 8 an eighth and 16 a sixteenth, and the LFO runs one cycle per note.
+/// Its phase comes straight from the master clock, so it lines up with the beat no
+/// matter when it was switched on.
+#[derive(Clone, Debug)]
+pub struct TremoloSync {
+    pub subdivision: u8,
+    pub master_clock: MasterClock,
+    pub bpm: f32,
+}
+
+impl TremoloSync {
+    pub fn cycle_samples(&self) -> f64 {
+        let samples_per_beat = 60.0 * self.master_clock.sample_rate() as f64 / self.bpm.max(1.0) as f64;
+        samples_per_beat * 4.0 / self.subdivision.max(1) as f64
+    }
+
+    /// Position in the current cycle, 0.0-1.0.
+    pub fn phase(&self) -> f32 {
+        let cycle = self.cycle_samples().max(1.0);
+        (self.master_clock.sample_count() as f64 % cycle / cycle) as f32
+    }
+}
+
+/// Amplitude modulation by a raised-cosine LFO, running freely at `rate_hz` or
+/// locked to the beat through a [`TremoloSync`].
+///
+/// Switching between the two crossfades the LFO over 100 ms, so the level doesn't
+/// jump when their phases disagree.
+pub struct Tremolo<S: Source<Item = f32>> {
+    source: S,
+    rate_hz: f32,
+    depth: f32,
+    phase: f32,
+    sync: TremoloSync,
+    enabled: Arc<Mutex<bool>>,
+    synced: Arc<Mutex<bool>>,
+    sync_mix: f32,
+    mix_step: f32,
+}
+
+impl<S: Source<Item = f32>> Tremolo<S> {
+    pub fn new(source: S, rate_hz: f32, depth: f32, sync: TremoloSync) -> Tremolo<S> {
+        let mix_step = 1.0 / (MODE_CROSSFADE_SECS * source.sample_rate() as f32);
+        Tremolo {
+            source,
+            rate_hz,
+            depth: depth.clamp(0.0, 1.0),
+            phase: 0.0,
+            sync,
+            enabled: Arc::new(Mutex::new(false)),
+            synced: Arc::new(Mutex::new(false)),
+            sync_mix: 0.0,
+            mix_step,
+        }
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+
+    /// `true` locks the LFO to the master clock, `false` lets it run at `rate_hz`.
+    pub fn get_synced_control(&self) -> Arc<Mutex<bool>> {
+        self.synced.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Tremolo<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Tremolo<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.source.next()?;
+
+        // The free LFO keeps running while unused, so switching back picks it up
+        // mid-cycle instead of restarting it
+        self.phase = (self.phase + self.rate_hz / self.source.sample_rate() as f32).fract();
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            return Some(input);
+        }
+
+        let target = if self.synced.lock().is_ok_and(|synced| *synced) { 1.0 } else { 0.0 };
+        if self.sync_mix < target {
+            self.sync_mix = (self.sync_mix + self.mix_step).min(target);
+        } else if self.sync_mix > target {
+            self.sync_mix = (self.sync_mix - self.mix_step).max(target);
+        }
+
+        let free = 0.5 - 0.5 * (TAU * self.phase).cos();
+        let synced = 0.5 - 0.5 * (TAU * self.sync.phase()).cos();
+        let lfo = free * (1.0 - self.sync_mix) + synced * self.sync_mix;
+        Some(input * (1.0 - self.depth * lfo))
+    }
+}
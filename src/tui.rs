@@ -0,0 +1,218 @@
+use crate::tuning::{freq_to_midi_note, note_name};
+use crossterm::execute;
+use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline};
+use ratatui::Terminal;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the dashboard redraws: about 30 fps.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// How many of the newest samples the VU meter takes its RMS over, about 23 ms.
+const VU_WINDOW: usize = 1024;
+
+/// How many status messages the dashboard keeps.
+const LOG_LINES: usize = 64;
+
+/// The VU meter's floor; anything quieter reads as an empty bar.
+const VU_FLOOR_DB: f32 = -60.0;
+
+/// What the dashboard shows, kept up to date by the key loop.
+#[derive(Clone, Debug, Default)]
+pub struct AppState {
+    /// The note sounding now, in Hz.
+    pub frequency: Option<f32>,
+    /// The keyboard's octave shift.
+    pub octave_offset: i32,
+    pub wave_table: Arc<Vec<f32>>,
+    log: VecDeque<String>,
+    /// The message line still being written, until a newline ends it.
+    partial: String,
+}
+
+impl AppState {
+    pub fn new(wave_table: Arc<Vec<f32>>) -> AppState {
+        AppState {
+            wave_table,
+            ..AppState::default()
+        }
+    }
+
+    /// Takes text meant for the terminal into the message log. Escape sequences are
+    /// dropped, and a carriage return starts its line over, as a terminal would.
+    pub fn log(&mut self, text: &str) {
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // A CSI sequence ends with its first letter
+                '\x1b' if chars.next() == Some('[') => {
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                }
+                '\r' => self.partial.clear(),
+                '\n' => {
+                    let line = std::mem::take(&mut self.partial);
+                    if self.log.len() == LOG_LINES {
+                        self.log.pop_front();
+                    }
+                    self.log.push_back(line);
+                }
+                c if !c.is_control() => self.partial.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    /// The newest `count` messages, oldest first, including one still being written.
+    pub fn recent_messages(&self, count: usize) -> Vec<&str> {
+        let partial = (!self.partial.is_empty()).then_some(self.partial.as_str());
+        let lines: Vec<&str> = self.log.iter().map(String::as_str).chain(partial).collect();
+        lines[lines.len().saturating_sub(count)..].to_vec()
+    }
+}
+
+/// RMS of the newest [`VU_WINDOW`] samples in `samples`, 0 for none.
+pub fn sliding_rms(samples: &VecDeque<f32>) -> f32 {
+    let window = samples.len().min(VU_WINDOW);
+    if window == 0 {
+        return 0.0;
+    }
+    let sum: f32 = samples.iter().rev().take(window).map(|sample| sample * sample).sum();
+    (sum / window as f32).sqrt()
+}
+
+/// A dashboard drawn with `ratatui` on the alternate screen by its own thread, so the
+/// key loop never waits on drawing: the note playing, the wave table as a sparkline,
+/// a VU meter and the latest status messages.
+///
+/// The VU meter reads `samples`, such as a [`ScopeTap`](crate::ScopeTap)'s, and
+/// everything else comes from the [`AppState`]. Dropping it, or [`stop`](Self::stop),
+/// ends the thread and goes back to the normal screen.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Dashboard {
+    /// Starts drawing; the terminal must already be in raw mode.
+    pub fn spawn(state: Arc<Mutex<AppState>>, samples: Arc<Mutex<VecDeque<f32>>>) -> io::Result<Dashboard> {
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => terminal,
+            Err(error) => {
+                let _ = execute!(io::stdout(), LeaveAlternateScreen);
+                return Err(error);
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let drawn = draw_until_stopped(&mut terminal, &state, &samples, &stop);
+                execute!(io::stdout(), LeaveAlternateScreen)?;
+                terminal.show_cursor()?;
+                drawn
+            })
+        };
+        Ok(Dashboard {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Ends the dashboard, returning any error drawing it hit.
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("the dashboard thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn draw_until_stopped(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    state: &Mutex<AppState>,
+    samples: &Mutex<VecDeque<f32>>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    terminal.hide_cursor()?;
+    terminal.clear()?;
+    while !stop.load(Ordering::Relaxed) {
+        let rms = samples.lock().map_or(0.0, |samples| sliding_rms(&samples));
+        // A snapshot, so the key loop isn't held up while a frame is drawn
+        let Ok(state) = state.lock().map(|state| state.clone()) else {
+            break;
+        };
+        terminal.draw(|frame| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(6), Constraint::Length(3), Constraint::Length(8)])
+                .split(frame.size());
+
+            let note = match state.frequency {
+                Some(freq) if freq > 0.0 => {
+                    format!("{}  {freq:.2} Hz", note_name(freq_to_midi_note(freq as f64)))
+                }
+                _ => "-".to_string(),
+            };
+            let top_bar = format!("{note}    octave {:+}", state.octave_offset);
+            frame.render_widget(
+                Paragraph::new(top_bar).block(Block::default().borders(Borders::ALL).title("Note")),
+                rows[0],
+            );
+
+            // One bar per column, from the bottom of the box at -1 to the top at 1
+            let width = rows[1].width.saturating_sub(2).max(1) as usize;
+            let table = &state.wave_table;
+            let bars: Vec<u64> = (0..width)
+                .map(|column| {
+                    let sample = table.get(column * table.len() / width).copied().unwrap_or(0.0);
+                    ((sample.clamp(-1.0, 1.0) + 1.0) * 50.0) as u64
+                })
+                .collect();
+            frame.render_widget(
+                Sparkline::default()
+                    .data(&bars)
+                    .max(100)
+                    .block(Block::default().borders(Borders::ALL).title("Wave table")),
+                rows[1],
+            );
+
+            let db = 20.0 * rms.max(1e-6).log10();
+            let ratio = ((db - VU_FLOOR_DB) / -VU_FLOOR_DB).clamp(0.0, 1.0);
+            frame.render_widget(
+                Gauge::default()
+                    .ratio(ratio as f64)
+                    .label(format!("{db:.1} dB RMS"))
+                    .block(Block::default().borders(Borders::ALL).title("VU")),
+                rows[2],
+            );
+
+            let messages = state.recent_messages(rows[3].height.saturating_sub(2) as usize).join("\n");
+            frame.render_widget(
+                Paragraph::new(messages).block(Block::default().borders(Borders::ALL).title("Messages")),
+                rows[3],
+            );
+        })?;
+        thread::sleep(FRAME_INTERVAL);
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Five-limit just ratios for each semitone above A, as (numerator, denominator).
+const JUST_RATIOS: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (45, 32),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+pub(crate) const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Scientific pitch name of a MIDI note, such as `C4` for 60 or `A#5` for 82.
+pub fn note_name(midi_note: u8) -> String {
+    let octave = midi_note as i32 / 12 - 1;
+    format!("{}{octave}", NOTE_NAMES[midi_note as usize % 12])
+}
+
+/// The equal-tempered MIDI note nearest `freq`, with A4 at 440 Hz, clamped to 0-127.
+pub fn freq_to_midi_note(freq: f64) -> u8 {
+    // Zero and below come out as NaN or -inf, which cast to 0
+    (69.0 + 12.0 * (freq / 440.0).log2()).round().clamp(0.0, 127.0) as u8
+}
+
+/// Deviation from the nearest just ratio beyond which an interval audibly beats.
+pub const BEATING_THRESHOLD_CENTS: f32 = 10.0;
+
+/// How MIDI note numbers map to frequencies, anchored on A4 (note 69).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TuningSystem {
+    /// Twelve-tone equal temperament.
+    Equal { a4_hz: f32 },
+    /// Five-limit just intonation built on A.
+    Just { a4_hz: f32 },
+}
+
+impl Default for TuningSystem {
+    fn default() -> Self {
+        TuningSystem::Equal { a4_hz: 440.0 }
+    }
+}
+
+impl TuningSystem {
+    pub fn frequency(&self, midi_note: u8) -> f32 {
+        let semitones = midi_note as i32 - 69;
+        match *self {
+            TuningSystem::Equal { a4_hz } => a4_hz * 2.0_f32.powf(semitones as f32 / 12.0),
+            TuningSystem::Just { a4_hz } => {
+                let octave = semitones.div_euclid(12);
+                let (numerator, denominator) = JUST_RATIOS[semitones.rem_euclid(12) as usize];
+                a4_hz * numerator as f32 / denominator as f32 * 2.0_f32.powi(octave)
+            }
+        }
+    }
+
+    /// The MIDI note whose frequency in this tuning is closest to `frequency`.
+    pub fn nearest_note(&self, frequency: f32) -> u8 {
+        (0..=127)
+            .min_by(|&a, &b| {
+                let distance = |note| cents(self.frequency(note), frequency).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(69)
+    }
+}
+
+impl fmt::Display for TuningSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TuningSystem::Equal { a4_hz } => write!(f, "Equal ({a4_hz} Hz)"),
+            TuningSystem::Just { a4_hz } => write!(f, "Just (A={a4_hz})"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseTuningSystemError(String);
+
+impl fmt::Display for ParseTuningSystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown tuning system '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseTuningSystemError {}
+
+/// Accepts the `Display` forms as well as `equal`, `just`, `equal:432` and `just:432`.
+impl FromStr for TuningSystem {
+    type Err = ParseTuningSystemError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseTuningSystemError(s.to_string());
+        let lower = s.trim().to_ascii_lowercase();
+
+        let (name, reference) = match lower.split_once(['(', ':']) {
+            Some((name, rest)) => {
+                let digits = rest
+                    .trim_end_matches(')')
+                    .trim()
+                    .trim_start_matches("a=")
+                    .trim_end_matches("hz")
+                    .trim();
+                (name.trim(), Some(digits.parse::<f32>().map_err(|_| error())?))
+            }
+            None => (lower.as_str(), None),
+        };
+
+        let a4_hz = reference.unwrap_or(440.0);
+        match name {
+            "equal" => Ok(TuningSystem::Equal { a4_hz }),
+            "just" => Ok(TuningSystem::Just { a4_hz }),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Size of the interval from `from_hz` up to `to_hz` in cents.
+pub fn cents(from_hz: f32, to_hz: f32) -> f32 {
+    1200.0 * (to_hz / from_hz).log2()
+}
+
+/// How one pair of notes in a chord sits against just intonation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntervalAnalysis {
+    /// The lower note, as the nearest MIDI note in the analysed tuning.
+    pub note_a: u8,
+    pub note_b: u8,
+    pub cents: f32,
+    /// Nearest five-limit ratio, octaves included (3/1 is a twelfth).
+    pub just_ratio: (u32, u32),
+    /// How far the interval is from `just_ratio`; positive means wider.
+    pub deviation_cents: f32,
+    /// The deviation exceeds [`BEATING_THRESHOLD_CENTS`].
+    pub beating_detected: bool,
+}
+
+/// Compares every pair of `freqs` with the nearest just ratio.
+pub fn analyze_chord(freqs: &[f32], tuning: &TuningSystem) -> Vec<IntervalAnalysis> {
+    let mut analyses = Vec::new();
+    for (i, &a) in freqs.iter().enumerate() {
+        for &b in &freqs[i + 1..] {
+            let (low, high) = if a <= b { (a, b) } else { (b, a) };
+            let interval = cents(low, high);
+
+            let octaves = (interval / 1200.0).floor().max(0.0) as u32;
+            let (numerator, denominator, just_cents) = JUST_RATIOS
+                .iter()
+                .chain(std::iter::once(&(2, 1)))
+                .map(|&(n, d)| {
+                    let n = n << octaves;
+                    let divisor = gcd(n, d);
+                    (n / divisor, d / divisor, cents(d as f32, n as f32))
+                })
+                .min_by(|x, y| (x.2 - interval).abs().total_cmp(&(y.2 - interval).abs()))
+                .unwrap_or((1, 1, 0.0));
+
+            let deviation_cents = interval - just_cents;
+            analyses.push(IntervalAnalysis {
+                note_a: tuning.nearest_note(low),
+                note_b: tuning.nearest_note(high),
+                cents: interval,
+                just_ratio: (numerator, denominator),
+                deviation_cents,
+                beating_detected: deviation_cents.abs() > BEATING_THRESHOLD_CENTS,
+            });
+        }
+    }
+    analyses
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuning_system_names_parse_back() {
+        for tuning in [
+            TuningSystem::Equal { a4_hz: 440.0 },
+            TuningSystem::Just { a4_hz: 440.0 },
+            TuningSystem::Equal { a4_hz: 432.0 },
+            TuningSystem::Just { a4_hz: 415.3 },
+        ] {
+            assert_eq!(tuning.to_string().parse::<TuningSystem>(), Ok(tuning));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::oscillator::WaveTableOscillator;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+pub const MAX_UNISON_VOICES: usize = 7;
+
+/// Copies of one [`WaveTableOscillator`] stacked on the same note and spread evenly
+/// across ±`detune_cents`, the outer voices at the full detune, then averaged: the
+/// thick, beating sound of a unison synth.
+///
+/// The first voice is the oscillator it was made from, so it keeps any master clock;
+/// the rest follow its frequency control. Added voices start at staggered phases so
+/// they don't cancel. With one voice, or no detune, it plays exactly as the
+/// oscillator alone would.
+pub struct UnisonOscillator {
+    voices: Vec<WaveTableOscillator>,
+    num_voices: Arc<Mutex<usize>>,
+    detune_cents: Arc<Mutex<f32>>,
+    /// The detune the voices are tuned to now.
+    applied_detune: f32,
+}
+
+impl UnisonOscillator {
+    pub fn new(oscillator: WaveTableOscillator) -> UnisonOscillator {
+        UnisonOscillator {
+            voices: vec![oscillator],
+            num_voices: Arc::new(Mutex::new(1)),
+            detune_cents: Arc::new(Mutex::new(0.0)),
+            applied_detune: 0.0,
+        }
+    }
+
+    /// 1 to [`MAX_UNISON_VOICES`].
+    pub fn set_num_voices(&mut self, num_voices: usize) {
+        if let Ok(mut shared) = self.num_voices.lock() {
+            *shared = num_voices.clamp(1, MAX_UNISON_VOICES);
+        }
+    }
+
+    pub fn set_detune_cents(&mut self, detune_cents: f32) {
+        if let Ok(mut shared) = self.detune_cents.lock() {
+            *shared = detune_cents;
+        }
+    }
+
+    pub fn get_voices_control(&self) -> Arc<Mutex<usize>> {
+        self.num_voices.clone()
+    }
+
+    pub fn get_detune_control(&self) -> Arc<Mutex<f32>> {
+        self.detune_cents.clone()
+    }
+
+    /// Adds or drops voices and retunes them when the controls have changed.
+    fn follow_controls(&mut self) {
+        let num_voices = self.num_voices.lock().map_or(1, |n| n.clamp(1, MAX_UNISON_VOICES));
+        let detune = self.detune_cents.lock().map_or(0.0, |cents| *cents);
+        if num_voices == self.voices.len() && detune == self.applied_detune {
+            return;
+        }
+        self.voices.truncate(num_voices);
+        let phase = self.voices[0].phase();
+        for i in self.voices.len()..num_voices {
+            let mut voice = self.voices[0].unison_voice();
+            voice.reset_phase_to((phase + i as f32 / num_voices as f32).fract());
+            self.voices.push(voice);
+        }
+        for (i, voice) in self.voices.iter_mut().enumerate() {
+            let spread = match num_voices {
+                1 => 0.0,
+                n => 2.0 * i as f32 / (n - 1) as f32 - 1.0,
+            };
+            voice.set_detune_cents(spread * detune);
+        }
+        self.applied_detune = detune;
+    }
+}
+
+impl Source for UnisonOscillator {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.voices[0].sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for UnisonOscillator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.follow_controls();
+        let (first, rest) = self.voices.split_first_mut()?;
+        let mut sum = first.next()?;
+        for voice in rest {
+            sum += voice.get_sample();
+        }
+        Some(sum / self.voices.len() as f32)
+    }
+}
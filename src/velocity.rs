@@ -0,0 +1,59 @@
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Notes this close together are the quietest, played staccato.
+const FASTEST_GAP: Duration = Duration::from_millis(80);
+/// Notes at least this far apart get full velocity.
+const SLOWEST_GAP: Duration = Duration::from_millis(600);
+/// A key pressed again within this long is the terminal's auto-repeat, not a new note.
+const AUTO_REPEAT_GAP: Duration = Duration::from_millis(100);
+const MIN_VELOCITY: u8 = 40;
+
+/// Guesses note velocities for a keyboard that has none, from the time since the
+/// previous note: quick runs come out soft and detached, notes played after a pause
+/// land hard. A held key's auto-repeats keep its first velocity.
+///
+/// Only an approximation, but it varies the level more naturally than a fixed
+/// velocity.
+#[derive(Clone, Debug, Default)]
+pub struct NoteVelocityMapper {
+    note_on_times: HashMap<KeyCode, Instant>,
+    last_note_on: Option<Instant>,
+    last_velocity: u8,
+}
+
+impl NoteVelocityMapper {
+    pub fn note_on(&mut self, key: KeyCode) -> u8 {
+        self.note_on_at(key, Instant::now())
+    }
+
+    /// [`note_on`](Self::note_on) for a press at `now`.
+    pub fn note_on_at(&mut self, key: KeyCode, now: Instant) -> u8 {
+        let repeated = self
+            .note_on_times
+            .insert(key, now)
+            .is_some_and(|previous| now.saturating_duration_since(previous) < AUTO_REPEAT_GAP);
+        if repeated && self.last_velocity > 0 {
+            self.last_note_on = Some(now);
+            return self.last_velocity;
+        }
+
+        let velocity = match self.last_note_on {
+            None => 127,
+            Some(previous) => {
+                let gap = now.saturating_duration_since(previous).clamp(FASTEST_GAP, SLOWEST_GAP);
+                let t = (gap - FASTEST_GAP).as_secs_f32() / (SLOWEST_GAP - FASTEST_GAP).as_secs_f32();
+                (MIN_VELOCITY as f32 + t * (127 - MIN_VELOCITY) as f32).round() as u8
+            }
+        };
+        self.last_note_on = Some(now);
+        self.last_velocity = velocity;
+        velocity
+    }
+
+    /// Output level for `velocity`, from 0.3 at 1 up to 1.0 at 127.
+    pub fn amplitude(velocity: u8) -> f32 {
+        0.3 + 0.7 * (velocity.clamp(1, 127) - 1) as f32 / 126.0
+    }
+}
@@ -0,0 +1,99 @@
+use std::f32::consts::PI;
+
+/// Lowest and highest band centres of a [`Vocoder`]. Together they cover the formants
+/// and most of the sibilance that make speech intelligible.
+const LOWEST_BAND_HZ: f32 = 150.0;
+const HIGHEST_BAND_HZ: f32 = 7000.0;
+/// Window the band levels are measured over.
+const ENVELOPE_SECS: f32 = 0.03;
+
+/// A constant-gain bandpass biquad: unity at `freq_hz`, with bandwidth set by `q`.
+///
+/// Coefficients are from the RBJ audio EQ cookbook, in the same transposed direct
+/// form II as [`BiquadResonator`](crate::BiquadResonator).
+#[derive(Clone, Debug)]
+pub struct BandpassFilter {
+    b0: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl BandpassFilter {
+    pub fn new(sample_rate: u32, freq_hz: f32, q: f32) -> BandpassFilter {
+        let w0 = 2.0 * PI * freq_hz.clamp(1.0, 0.45 * sample_rate as f32) / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * q.max(0.1));
+        let a0 = 1.0 + alpha;
+        BandpassFilter {
+            b0: alpha / a0,
+            b2: -alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = -self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// A channel vocoder: the carrier, usually the synth, is split into bands and each
+/// band is scaled so its level follows the same band of the modulator, usually a
+/// voice. A bright, sustained carrier such as a saw then talks.
+///
+/// `band_filters` holds a carrier filter and a modulator filter per band, and the
+/// envelopes are each band's RMS level over about 30 ms.
+pub struct Vocoder {
+    pub n_bands: usize,
+    band_filters: Vec<(BandpassFilter, BandpassFilter)>,
+    mic_envelope: Vec<f32>,
+    synth_envelope: Vec<f32>,
+    /// One-pole coefficient of the mean-square smoothing.
+    envelope_coeff: f32,
+}
+
+impl Vocoder {
+    /// `n_bands` bands, at least one, spaced evenly in pitch from 150 Hz to 7 kHz,
+    /// each as wide as the gap to its neighbours.
+    pub fn new(sample_rate: u32, n_bands: usize) -> Vocoder {
+        let n_bands = n_bands.max(1);
+        let highest = HIGHEST_BAND_HZ.min(0.45 * sample_rate as f32);
+        let ratio = (highest / LOWEST_BAND_HZ).powf(1.0 / (n_bands.max(2) - 1) as f32);
+        let q = 1.0 / (ratio.sqrt() - 1.0 / ratio.sqrt());
+        let band_filters = (0..n_bands)
+            .map(|i| {
+                let freq_hz = LOWEST_BAND_HZ * ratio.powi(i as i32);
+                (BandpassFilter::new(sample_rate, freq_hz, q), BandpassFilter::new(sample_rate, freq_hz, q))
+            })
+            .collect();
+        Vocoder {
+            n_bands,
+            band_filters,
+            mic_envelope: vec![0.0; n_bands],
+            synth_envelope: vec![0.0; n_bands],
+            envelope_coeff: (-1.0 / (ENVELOPE_SECS * sample_rate as f32)).exp(),
+        }
+    }
+
+    /// One sample of `carrier` shaped by `modulator`.
+    pub fn process(&mut self, carrier: f32, modulator: f32) -> f32 {
+        let coeff = self.envelope_coeff;
+        let mut output = 0.0;
+        for (i, (synth_filter, mic_filter)) in self.band_filters.iter_mut().enumerate() {
+            let synth = synth_filter.process(carrier);
+            let mic = mic_filter.process(modulator);
+            // The envelopes hold mean squares; the gain takes their roots
+            self.synth_envelope[i] = coeff * self.synth_envelope[i] + (1.0 - coeff) * synth * synth;
+            self.mic_envelope[i] = coeff * self.mic_envelope[i] + (1.0 - coeff) * mic * mic;
+            output += synth * self.mic_envelope[i].sqrt() / (self.synth_envelope[i].sqrt() + 1e-10);
+        }
+        output
+    }
+}
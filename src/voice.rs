@@ -0,0 +1,355 @@
+use crate::pan::{AutoPan, AutoPanMode};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Where a voice's amplitude envelope currently is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum EnvelopePhase {
+    Idle,
+    Attack,
+    /// Falling from the attack's peak to the sustain level.
+    Decay,
+    Sustain,
+    Release,
+}
+
+impl EnvelopePhase {
+    /// The phase with discriminant `value`; anything out of range reads as `Idle`.
+    pub fn from_u8(value: u8) -> EnvelopePhase {
+        match value {
+            1 => EnvelopePhase::Attack,
+            2 => EnvelopePhase::Decay,
+            3 => EnvelopePhase::Sustain,
+            4 => EnvelopePhase::Release,
+            _ => EnvelopePhase::Idle,
+        }
+    }
+}
+
+/// An [`EnvelopePhase`] shared between threads as its discriminant in an `AtomicU8`.
+#[derive(Debug, Default)]
+pub struct EnvelopeState(AtomicU8);
+
+impl EnvelopeState {
+    pub fn new(phase: EnvelopePhase) -> EnvelopeState {
+        EnvelopeState(AtomicU8::new(phase as u8))
+    }
+
+    pub fn load(&self) -> EnvelopePhase {
+        EnvelopePhase::from_u8(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn store(&self, phase: EnvelopePhase) {
+        self.0.store(phase as u8, Ordering::Relaxed);
+    }
+}
+
+/// Allocation bookkeeping for one voice of a [`VoicePool`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceSlot {
+    pub frequency: f32,
+    pub phase: EnvelopePhase,
+    pub level: f32,
+    /// Note-on order; lower values started earlier.
+    pub started_at: u64,
+    /// Let go while the sustain pedal was down, so it releases when the pedal comes up.
+    pub sustained: bool,
+    /// -1.0 (left) to 1.0 (right), from the pool's [`AutoPan`] as the note started.
+    pub pan: f32,
+}
+
+impl VoiceSlot {
+    pub fn is_idle(&self) -> bool {
+        self.phase == EnvelopePhase::Idle
+    }
+}
+
+impl Default for VoiceSlot {
+    fn default() -> VoiceSlot {
+        VoiceSlot {
+            frequency: 0.0,
+            phase: EnvelopePhase::Idle,
+            level: 0.0,
+            started_at: 0,
+            sustained: false,
+            pan: 0.0,
+        }
+    }
+}
+
+/// Which sounding voice gives way when a note arrives and every voice is busy.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VoiceAllocationStrategy {
+    OldestVoice,
+    /// Takes the quietest voice that is already releasing, so the cut is barely
+    /// audible, and falls back to the oldest voice.
+    #[default]
+    StealByEnvelopePhase,
+}
+
+impl VoiceAllocationStrategy {
+    pub fn choose(self, pool: &[VoiceSlot]) -> Option<usize> {
+        match self {
+            VoiceAllocationStrategy::OldestVoice => steal_oldest_voice(pool),
+            VoiceAllocationStrategy::StealByEnvelopePhase => steal_release_voice(pool),
+        }
+    }
+}
+
+/// Index of the sounding voice that started first.
+pub fn steal_oldest_voice(pool: &[VoiceSlot]) -> Option<usize> {
+    pool.iter()
+        .enumerate()
+        .filter(|(_, slot)| !slot.is_idle())
+        .min_by_key(|(_, slot)| slot.started_at)
+        .map(|(index, _)| index)
+}
+
+/// Index of the release-phase voice with the lowest envelope level, or the oldest
+/// voice if none are releasing.
+pub fn steal_release_voice(pool: &[VoiceSlot]) -> Option<usize> {
+    pool.iter()
+        .enumerate()
+        .filter(|(_, slot)| slot.phase == EnvelopePhase::Release)
+        .min_by(|(_, a), (_, b)| a.level.total_cmp(&b.level))
+        .map(|(index, _)| index)
+        .or_else(|| steal_oldest_voice(pool))
+}
+
+/// How many notes can sound at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolyphonyMode {
+    /// One voice. With `legato`, a note played while another still sounds glides to
+    /// the new pitch without restarting the attack.
+    Mono { legato: bool },
+    /// Two voices that can be addressed separately, e.g. a bass line under a melody
+    /// with [`VoicePool::note_on_voice`].
+    Duophony,
+    Poly(usize),
+}
+
+impl PolyphonyMode {
+    pub fn voice_count(self) -> usize {
+        match self {
+            PolyphonyMode::Mono { .. } => 1,
+            PolyphonyMode::Duophony => 2,
+            PolyphonyMode::Poly(voices) => voices,
+        }
+    }
+
+    /// Mono, mono legato, duophony, then `poly_voices`-voice poly, and round again.
+    pub fn cycle(self, poly_voices: usize) -> PolyphonyMode {
+        match self {
+            PolyphonyMode::Mono { legato: false } => PolyphonyMode::Mono { legato: true },
+            PolyphonyMode::Mono { legato: true } => PolyphonyMode::Duophony,
+            PolyphonyMode::Duophony => PolyphonyMode::Poly(poly_voices),
+            PolyphonyMode::Poly(_) => PolyphonyMode::Mono { legato: false },
+        }
+    }
+}
+
+impl fmt::Display for PolyphonyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolyphonyMode::Mono { legato: false } => write!(f, "Mono"),
+            PolyphonyMode::Mono { legato: true } => write!(f, "Mono (legato)"),
+            PolyphonyMode::Duophony => write!(f, "Duophony"),
+            PolyphonyMode::Poly(voices) => write!(f, "Poly ({voices} voices)"),
+        }
+    }
+}
+
+/// Index of a voice in a [`VoicePool`], as [`VoicePool::note_on`] hands out.
+pub type VoiceId = usize;
+
+/// Fixed set of voice slots handed out to incoming notes.
+pub struct VoicePool {
+    slots: Vec<VoiceSlot>,
+    strategy: VoiceAllocationStrategy,
+    mode: PolyphonyMode,
+    note_counter: u64,
+    sustain_active: bool,
+    auto_pan: AutoPan,
+}
+
+impl VoicePool {
+    pub fn new(voice_count: usize) -> VoicePool {
+        VoicePool {
+            slots: vec![VoiceSlot::default(); voice_count],
+            strategy: VoiceAllocationStrategy::default(),
+            mode: PolyphonyMode::Poly(voice_count),
+            note_counter: 0,
+            sustain_active: false,
+            auto_pan: AutoPan::default(),
+        }
+    }
+
+    pub fn strategy(&self) -> VoiceAllocationStrategy {
+        self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: VoiceAllocationStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn polyphony_mode(&self) -> PolyphonyMode {
+        self.mode
+    }
+
+    /// Resizes the pool for `mode`. Voices beyond the new count are cut off; new ones
+    /// start idle.
+    pub fn set_polyphony_mode(&mut self, mode: PolyphonyMode) {
+        self.mode = mode;
+        self.slots.resize(mode.voice_count(), VoiceSlot::default());
+    }
+
+    pub fn auto_pan_mode(&self) -> AutoPanMode {
+        self.auto_pan.mode
+    }
+
+    /// Sets how voices are panned; a voice already playing keeps its place until its
+    /// next note.
+    pub fn set_auto_pan_mode(&mut self, mode: AutoPanMode) {
+        self.auto_pan.mode = mode;
+    }
+
+    pub fn slots(&self) -> &[VoiceSlot] {
+        &self.slots
+    }
+
+    pub(crate) fn slots_mut(&mut self) -> &mut [VoiceSlot] {
+        &mut self.slots
+    }
+
+    /// Starts a note on an idle voice, stealing one by the pool's strategy if all are
+    /// busy. Returns the voice index, or `None` for an empty pool.
+    pub fn note_on(&mut self, frequency: f32) -> Option<usize> {
+        let index = self
+            .slots
+            .iter()
+            .position(VoiceSlot::is_idle)
+            .or_else(|| self.strategy.choose(&self.slots))?;
+        self.note_on_voice(frequency, index)
+    }
+
+    /// Starts a note on a particular voice, whatever it is playing. Returns `None` if
+    /// there is no such voice.
+    pub fn note_on_voice(&mut self, frequency: f32, voice: usize) -> Option<usize> {
+        let legato = matches!(self.mode, PolyphonyMode::Mono { legato: true });
+        let pan = self.auto_pan.pan_for(voice);
+        let slot = self.slots.get_mut(voice)?;
+        // The key is down again, so the pedal no longer decides when it ends
+        slot.sustained = false;
+        if legato && matches!(slot.phase, EnvelopePhase::Attack | EnvelopePhase::Decay | EnvelopePhase::Sustain) {
+            slot.frequency = frequency;
+            return Some(voice);
+        }
+
+        // A stolen voice attacks from wherever its level is, which avoids a click
+        slot.frequency = frequency;
+        slot.phase = EnvelopePhase::Attack;
+        slot.pan = pan;
+        slot.started_at = self.note_counter;
+        self.note_counter += 1;
+        Some(voice)
+    }
+
+    /// Releases the voice, or with the sustain pedal down holds it until the pedal
+    /// comes up.
+    pub fn note_off(&mut self, voice: usize) {
+        if let Some(slot) = self.slots.get_mut(voice) {
+            if slot.is_idle() {
+                return;
+            }
+            if self.sustain_active {
+                slot.sustained = true;
+            } else {
+                slot.phase = EnvelopePhase::Release;
+            }
+        }
+    }
+
+    pub fn sustain_active(&self) -> bool {
+        self.sustain_active
+    }
+
+    /// Presses or lifts the sustain pedal. Lifting it releases every voice let go
+    /// while it was down.
+    pub fn set_sustain(&mut self, active: bool) {
+        self.sustain_active = active;
+        if active {
+            return;
+        }
+        for slot in &mut self.slots {
+            if std::mem::take(&mut slot.sustained) && !slot.is_idle() {
+                slot.phase = EnvelopePhase::Release;
+            }
+        }
+    }
+
+    /// Releases every sounding voice. It lifts the sustain pedal too, so a panic
+    /// isn't held up by it.
+    pub fn all_notes_off(&mut self) {
+        self.set_sustain(false);
+        for voice in 0..self.slots.len() {
+            self.note_off(voice);
+        }
+    }
+
+    /// Silences every voice at once, skipping the release.
+    pub fn all_sound_off(&mut self) {
+        for slot in &mut self.slots {
+            slot.phase = EnvelopePhase::Idle;
+            slot.level = 0.0;
+            slot.sustained = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(phase: EnvelopePhase, level: f32, started_at: u64) -> VoiceSlot {
+        VoiceSlot {
+            phase,
+            level,
+            started_at,
+            ..VoiceSlot::default()
+        }
+    }
+
+    #[test]
+    fn steals_the_quietest_releasing_voice() {
+        let pool = [
+            slot(EnvelopePhase::Sustain, 0.8, 0),
+            slot(EnvelopePhase::Release, 0.4, 1),
+            slot(EnvelopePhase::Sustain, 0.8, 2),
+            slot(EnvelopePhase::Release, 0.1, 3),
+        ];
+        assert_eq!(steal_release_voice(&pool), Some(3));
+    }
+
+    #[test]
+    fn falls_back_to_the_oldest_voice() {
+        let pool = [
+            slot(EnvelopePhase::Sustain, 0.8, 5),
+            slot(EnvelopePhase::Sustain, 0.8, 2),
+            slot(EnvelopePhase::Attack, 0.3, 7),
+        ];
+        assert_eq!(steal_release_voice(&pool), Some(1));
+    }
+
+    #[test]
+    fn pool_steals_a_releasing_voice_by_default() {
+        let mut pool = VoicePool::new(4);
+        for freq in [220.0, 330.0, 440.0, 550.0] {
+            pool.note_on(freq);
+        }
+        pool.note_off(1);
+        pool.note_off(3);
+        let stolen = pool.note_on(660.0);
+        assert!(matches!(stolen, Some(1 | 3)), "{stolen:?}");
+    }
+}
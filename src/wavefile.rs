@@ -0,0 +1,87 @@
+use hound::{SampleFormat, WavReader};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum WaveLoadError {
+    NotFound(PathBuf),
+    /// Bits per sample the loader doesn't read: integer files must be 8, 16, 24 or
+    /// 32 bits and float files 32.
+    UnsupportedBitDepth(u16),
+    /// The file holds no samples.
+    Empty,
+    Wav(hound::Error),
+}
+
+impl fmt::Display for WaveLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaveLoadError::NotFound(path) => write!(f, "no wave table file at {}", path.display()),
+            WaveLoadError::UnsupportedBitDepth(bits) => write!(f, "{bits}-bit WAV files aren't supported"),
+            WaveLoadError::Empty => write!(f, "the WAV file holds no samples"),
+            WaveLoadError::Wav(error) => write!(f, "wave table error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for WaveLoadError {}
+
+impl From<hound::Error> for WaveLoadError {
+    fn from(error: hound::Error) -> Self {
+        WaveLoadError::Wav(error)
+    }
+}
+
+/// Reads a single-cycle WAV file, as exported by most wavetable synths, as a wave
+/// table of `target_size` samples.
+///
+/// The whole file is taken as one cycle and resampled to `target_size` by linear
+/// interpolation, wrapping around at the end so the loop stays seamless. Only the
+/// first channel of a multichannel file is used, and the table is normalised so its
+/// peak is 1.0.
+pub fn load_wave_table_from_wav(path: &Path, target_size: usize) -> Result<Vec<f32>, WaveLoadError> {
+    let mut reader = WavReader::open(path).map_err(|error| match error {
+        hound::Error::IoError(error) if error.kind() == io::ErrorKind::NotFound => {
+            WaveLoadError::NotFound(path.to_path_buf())
+        }
+        error => WaveLoadError::Wav(error),
+    })?;
+
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let cycle: Vec<f32> = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => reader.samples::<f32>().step_by(channels).collect::<Result<_, _>>()?,
+        (SampleFormat::Int, bits @ (8 | 16 | 24 | 32)) => {
+            let full_scale = (1_i64 << (bits - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .step_by(channels)
+                .map(|sample| sample.map(|sample| sample as f32 / full_scale))
+                .collect::<Result<_, _>>()?
+        }
+        (_, bits) => return Err(WaveLoadError::UnsupportedBitDepth(bits)),
+    };
+    if cycle.is_empty() {
+        return Err(WaveLoadError::Empty);
+    }
+
+    let step = cycle.len() as f32 / target_size as f32;
+    let mut table: Vec<f32> = (0..target_size)
+        .map(|i| {
+            let position = i as f32 * step;
+            let index = position as usize;
+            let frac = position - index as f32;
+            let (a, b) = (cycle[index % cycle.len()], cycle[(index + 1) % cycle.len()]);
+            a + (b - a) * frac
+        })
+        .collect();
+
+    let peak = table.iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    if peak > 0.0 {
+        for sample in &mut table {
+            *sample /= peak;
+        }
+    }
+    Ok(table)
+}
@@ -0,0 +1,164 @@
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+/// Loss per round trip while a note is held; the string rings for several seconds.
+pub const SUSTAIN_LOSS: f32 = 0.998;
+/// Fraction of the loop the excitation burst lasts.
+const EXCITATION_FRACTION: f32 = 0.5;
+
+/// One-pole low-pass, `y[n] = (1 - a) x[n] + a y[n-1]`. Higher `coefficient`
+/// darkens more.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OnePoleFilter {
+    pub coefficient: f32,
+    previous: f32,
+}
+
+impl OnePoleFilter {
+    pub fn new(coefficient: f32) -> OnePoleFilter {
+        OnePoleFilter {
+            coefficient: coefficient.clamp(0.0, 0.999),
+            previous: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.previous = (1.0 - self.coefficient) * input + self.coefficient * self.previous;
+        self.previous
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = 0.0;
+    }
+}
+
+/// A bowed/plucked string as a digital waveguide: two delay lines carrying the
+/// right- and left-going waves, each half the loop, so a round trip lasts
+/// `sample_rate / freq` samples.
+///
+/// The nut reflects with a sign flip; the bridge does the same through the low-pass
+/// `filter` and `loss_factor`, which together stand in for the string's damping, higher
+/// partials dying first. At [`SUSTAIN_LOSS`] a mid-range note rings for seconds; a few
+/// percent more loss gives a pizzicato.
+///
+/// A change of the shared frequency plucks a new note; 0 Hz damps the string.
+pub struct WaveguideString {
+    sample_rate: u32,
+    frequency: Arc<Mutex<f32>>,
+    last_freq: f32,
+    delay_line_l: RingBuffer<f32>,
+    delay_line_r: RingBuffer<f32>,
+    filter: OnePoleFilter,
+    loss_factor: Arc<Mutex<f32>>,
+    excitation_done: bool,
+    excitation_pos: usize,
+    excitation_len: usize,
+}
+
+impl WaveguideString {
+    pub fn new(sample_rate: u32, frequency: Arc<Mutex<f32>>) -> WaveguideString {
+        WaveguideString {
+            sample_rate,
+            frequency,
+            last_freq: 0.0,
+            delay_line_l: RingBuffer::new(1),
+            delay_line_r: RingBuffer::new(1),
+            filter: OnePoleFilter::new(0.3),
+            loss_factor: Arc::new(Mutex::new(SUSTAIN_LOSS)),
+            excitation_done: true,
+            excitation_pos: 0,
+            excitation_len: 0,
+        }
+    }
+
+    pub fn get_loss_factor_control(&self) -> Arc<Mutex<f32>> {
+        self.loss_factor.clone()
+    }
+
+    /// Resizes the string for `freq` and starts a fresh excitation on it.
+    pub fn note_on(&mut self, freq: f32) {
+        let loop_len = (self.sample_rate as f32 / freq.max(20.0)).round().max(2.0) as usize;
+        let half = loop_len / 2;
+        self.delay_line_r = RingBuffer::new(half);
+        self.delay_line_l = RingBuffer::new(loop_len - half);
+        self.filter.reset();
+        self.excitation_len = ((loop_len as f32 * EXCITATION_FRACTION) as usize).max(1);
+        self.excitation_pos = 0;
+        self.excitation_done = false;
+    }
+
+    /// Silences the string at once, for a frequency of 0 Hz.
+    fn damp(&mut self) {
+        self.delay_line_l.clear();
+        self.delay_line_r.clear();
+        self.excitation_done = true;
+    }
+
+    /// Gaussian noise under a Gaussian window, so the burst has no hard edges.
+    fn excitation(&mut self) -> f32 {
+        let t = self.excitation_pos as f32 / self.excitation_len as f32 - 0.5;
+        self.excitation_pos += 1;
+        if self.excitation_pos >= self.excitation_len {
+            self.excitation_done = true;
+        }
+
+        // Box-Muller from two uniforms
+        let (u1, u2) = (rand::random::<f32>().max(1e-7), rand::random::<f32>());
+        let noise = (-2.0 * u1.ln()).sqrt() * (TAU * u2).cos();
+        0.5 * noise * (-t * t / (2.0 * 0.15 * 0.15)).exp()
+    }
+
+    pub fn get_sample(&mut self) -> f32 {
+        let freq = self.frequency.lock().map_or(0.0, |f| *f);
+        if freq != self.last_freq {
+            self.last_freq = freq;
+            if freq > 0.0 {
+                self.note_on(freq);
+            } else {
+                self.damp();
+            }
+        }
+
+        let loss = self.loss_factor.lock().map_or(SUSTAIN_LOSS, |loss| *loss);
+        let at_bridge = *self.delay_line_r.read_at(self.delay_line_r.len());
+        let at_nut = *self.delay_line_l.read_at(self.delay_line_l.len());
+
+        let mut into_right = -at_nut;
+        if !self.excitation_done {
+            into_right += self.excitation();
+        }
+        self.delay_line_r.push(into_right);
+        self.delay_line_l.push(-loss * self.filter.process(at_bridge));
+
+        // The force on the bridge is what a body would hear
+        at_bridge * 0.5
+    }
+}
+
+impl Source for WaveguideString {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl Iterator for WaveguideString {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_sample())
+    }
+}
@@ -0,0 +1,140 @@
+use rodio::Source;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The shapes [`WaveShaper::from_preset`] builds, at settings that suit the main voice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaperPreset {
+    /// Flat tops at ±0.8: buzzy, with strong odd harmonics.
+    HardClip,
+    /// A tanh curve driven 3 times: warm, rounding the peaks off gradually.
+    SoftClip,
+    /// 4 bits: the gritty steps of an early sampler.
+    BitCrush,
+}
+
+impl ShaperPreset {
+    pub const ALL: [ShaperPreset; 3] = [ShaperPreset::HardClip, ShaperPreset::SoftClip, ShaperPreset::BitCrush];
+}
+
+impl fmt::Display for ShaperPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShaperPreset::HardClip => "hard clip",
+            ShaperPreset::SoftClip => "soft clip",
+            ShaperPreset::BitCrush => "bit crush",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Distorts a source through a transfer function, `output_gain * transfer(input_gain
+/// * drive * x)` for each sample `x`.
+///
+/// The gains fit the transfer function to the shape, so [`hard_clip`](Self::hard_clip)
+/// clamps at its threshold with a plain `clamp(-1, 1)`. The drive control pushes the
+/// source harder into the shape while it plays. Switched by the enabled control and
+/// off to begin with.
+pub struct WaveShaper<S: Source<Item = f32>> {
+    source: S,
+    transfer: fn(f32) -> f32,
+    input_gain: f32,
+    output_gain: f32,
+    drive: Arc<Mutex<f32>>,
+    enabled: Arc<Mutex<bool>>,
+}
+
+impl<S: Source<Item = f32>> WaveShaper<S> {
+    /// Shapes `source` through `transfer` at a drive of 1.
+    pub fn new(source: S, transfer: fn(f32) -> f32) -> WaveShaper<S> {
+        WaveShaper {
+            source,
+            transfer,
+            input_gain: 1.0,
+            output_gain: 1.0,
+            drive: Arc::new(Mutex::new(1.0)),
+            enabled: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Clamps the source to ±`threshold`.
+    pub fn hard_clip(source: S, threshold: f32) -> WaveShaper<S> {
+        let threshold = threshold.max(f32::EPSILON);
+        WaveShaper {
+            input_gain: 1.0 / threshold,
+            output_gain: threshold,
+            ..WaveShaper::new(source, |x| x.clamp(-1.0, 1.0))
+        }
+    }
+
+    /// `tanh(drive * x)`, which never goes past ±1.
+    pub fn soft_clip(source: S, drive: f32) -> WaveShaper<S> {
+        let mut shaper = WaveShaper::new(source, f32::tanh);
+        shaper.set_drive(drive);
+        shaper
+    }
+
+    /// Rounds the source to the nearest of the `2^bits` steps across -1 to 1, as a
+    /// `bits`-bit converter would. `bits` is 1 to 24.
+    pub fn bit_crush(source: S, bits: u8) -> WaveShaper<S> {
+        let half_steps = (1_u32 << (bits.clamp(1, 24) - 1)) as f32;
+        WaveShaper {
+            input_gain: half_steps,
+            output_gain: 1.0 / half_steps,
+            ..WaveShaper::new(source, f32::round)
+        }
+    }
+
+    pub fn from_preset(source: S, preset: ShaperPreset) -> WaveShaper<S> {
+        match preset {
+            ShaperPreset::HardClip => WaveShaper::hard_clip(source, 0.8),
+            ShaperPreset::SoftClip => WaveShaper::soft_clip(source, 3.0),
+            ShaperPreset::BitCrush => WaveShaper::bit_crush(source, 4),
+        }
+    }
+
+    pub fn set_drive(&mut self, drive: f32) {
+        if let Ok(mut shared) = self.drive.lock() {
+            *shared = drive;
+        }
+    }
+
+    pub fn get_drive_control(&self) -> Arc<Mutex<f32>> {
+        self.drive.clone()
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for WaveShaper<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for WaveShaper<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        if !self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            return Some(sample);
+        }
+        let drive = self.drive.lock().map_or(1.0, |drive| *drive);
+        Some(self.output_gain * (self.transfer)(self.input_gain * drive * sample))
+    }
+}
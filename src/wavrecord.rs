@@ -0,0 +1,159 @@
+use crate::error::SynthError;
+use rodio::Source;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Where a [`RecordingSource`] sends its samples while recording.
+type RecordingTap = Arc<Mutex<Option<Sender<f32>>>>;
+
+/// A finished recording.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WavRecording {
+    pub path: PathBuf,
+    pub sample_count: u64,
+    pub sample_rate: u32,
+}
+
+impl WavRecording {
+    pub fn duration_secs(&self) -> f32 {
+        self.sample_count as f32 / self.sample_rate as f32
+    }
+}
+
+/// Records what a [`RecordingSource`] plays to a 32-bit float mono WAV file.
+///
+/// The audio thread only hands samples to a channel; a writer thread started by
+/// [`start`](Self::start) drains it into the file, so a slow disk never holds up
+/// playback. [`stop`](Self::stop) closes the channel and waits for the writer to
+/// finish the file.
+pub struct WavSessionRecorder {
+    tap: RecordingTap,
+    sample_rate: u32,
+    writer: Option<(PathBuf, JoinHandle<Result<u64, SynthError>>)>,
+}
+
+impl WavSessionRecorder {
+    pub fn new(sample_rate: u32) -> WavSessionRecorder {
+        WavSessionRecorder {
+            tap: Arc::new(Mutex::new(None)),
+            sample_rate,
+            writer: None,
+        }
+    }
+
+    /// Wraps `source` so this recorder hears it.
+    pub fn tap<S: Source<Item = f32>>(&self, source: S) -> RecordingSource<S> {
+        RecordingSource {
+            source,
+            tap: self.tap.clone(),
+            pending_left: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Starts writing to `path`, ending any recording already going.
+    pub fn start(&mut self, path: &Path) -> Result<(), SynthError> {
+        self.stop()?;
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut sample_count = 0;
+            for sample in receiver {
+                writer.write_sample(sample)?;
+                sample_count += 1;
+            }
+            writer.finalize()?;
+            Ok(sample_count)
+        });
+        if let Ok(mut tap) = self.tap.lock() {
+            *tap = Some(sender);
+        }
+        self.writer = Some((path.to_path_buf(), handle));
+        Ok(())
+    }
+
+    /// Ends the recording and finishes the file, or does nothing if not recording.
+    pub fn stop(&mut self) -> Result<Option<WavRecording>, SynthError> {
+        if let Ok(mut tap) = self.tap.lock() {
+            tap.take();
+        }
+        let Some((path, handle)) = self.writer.take() else {
+            return Ok(None);
+        };
+        let sample_count = handle
+            .join()
+            .map_err(|_| SynthError::IoError(io::Error::other("the WAV writer thread panicked")))??;
+        Ok(Some(WavRecording {
+            path,
+            sample_count,
+            sample_rate: self.sample_rate,
+        }))
+    }
+}
+
+impl Drop for WavSessionRecorder {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Passes a source through untouched while a [`WavSessionRecorder`] is recording it,
+/// mixing stereo down to mono for the file.
+pub struct RecordingSource<S: Source<Item = f32>> {
+    source: S,
+    tap: RecordingTap,
+    pending_left: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> Source for RecordingSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for RecordingSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let mono = match (self.source.channels(), self.pending_left.take()) {
+            (2, Some(left)) => Some((left + sample) / 2.0),
+            (2, None) => {
+                self.pending_left = Some(sample);
+                None
+            }
+            _ => Some(sample),
+        };
+        if let (Some(mono), Ok(tap)) = (mono, self.tap.lock()) {
+            if let Some(sender) = tap.as_ref() {
+                // A writer that failed has hung up; its error comes back from stop
+                let _ = sender.send(mono);
+            }
+        }
+        Some(sample)
+    }
+}
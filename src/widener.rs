@@ -0,0 +1,106 @@
+use crate::ringbuf::RingBuffer;
+use rodio::Source;
+use std::sync::{Arc, Mutex};
+
+/// Longest side-channel delay, past which the Haas effect turns into an echo.
+const MAX_DELAY_MS: f32 = 20.0;
+
+/// Widens a stereo image by mid-side processing: the side signal (what differs
+/// between the channels) is scaled by `width` and delayed by a few milliseconds,
+/// so the ear hears the channels as arriving from further apart.
+///
+/// A width of 0.0 collapses to mono, 1.0 keeps the original spread and above that
+/// widens it; 1.5 with a 10 ms delay is clearly wider on headphones. The output is
+/// clamped to -1.0..=1.0, as a wide side signal can push a channel past full scale.
+pub struct StereoWidener {
+    pub delay_ms: f32,
+    pub width: f32,
+    buf: RingBuffer<f32>,
+    delay_samples: usize,
+}
+
+impl StereoWidener {
+    /// `delay_ms` is clamped to the Haas range of 2 to 20 ms.
+    pub fn new(sample_rate: u32, delay_ms: f32, width: f32) -> StereoWidener {
+        let delay_ms = delay_ms.clamp(2.0, MAX_DELAY_MS);
+        let samples = |ms: f32| (ms * sample_rate as f32 / 1000.0).round() as usize;
+        StereoWidener {
+            delay_ms,
+            width: width.max(0.0),
+            buf: RingBuffer::new(samples(MAX_DELAY_MS) + 1),
+            delay_samples: samples(delay_ms),
+        }
+    }
+
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let mid = (left + right) / 2.0;
+        self.buf.push((left - right) / 2.0);
+        let side = *self.buf.read_at(self.delay_samples + 1) * self.width;
+        ((mid + side).clamp(-1.0, 1.0), (mid - side).clamp(-1.0, 1.0))
+    }
+}
+
+/// Runs an interleaved stereo source through a [`StereoWidener`], switched by the
+/// enabled control and off to begin with. A mono source passes through untouched.
+pub struct StereoWidenerSource<S: Source<Item = f32>> {
+    source: S,
+    widener: StereoWidener,
+    enabled: Arc<Mutex<bool>>,
+    pending_right: Option<f32>,
+}
+
+impl<S: Source<Item = f32>> StereoWidenerSource<S> {
+    pub fn new(source: S, widener: StereoWidener) -> StereoWidenerSource<S> {
+        StereoWidenerSource {
+            source,
+            widener,
+            enabled: Arc::new(Mutex::new(false)),
+            pending_right: None,
+        }
+    }
+
+    pub fn get_enabled_control(&self) -> Arc<Mutex<bool>> {
+        self.enabled.clone()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for StereoWidenerSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for StereoWidenerSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.source.channels() != 2 {
+            return self.source.next();
+        }
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let left = self.source.next()?;
+        let right = self.source.next()?;
+        let (left, right) = if self.enabled.lock().is_ok_and(|enabled| *enabled) {
+            self.widener.process(left, right)
+        } else {
+            (left, right)
+        };
+        self.pending_right = Some(right);
+        Some(left)
+    }
+}
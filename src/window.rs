@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::{LazyLock, Mutex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FftWindow {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+}
+
+type WindowCache = HashMap<(FftWindow, usize), Vec<f32>>;
+
+/// Computed coefficient tables keyed by window and length, since FFT code asks for
+/// the same few sizes over and over.
+static WINDOW_CACHE: LazyLock<Mutex<WindowCache>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+impl FftWindow {
+    /// Symmetric window of `len` points, so tapered windows reach zero (Hann,
+    /// Blackman) or their minimum at both ends.
+    pub fn coefficients(self, len: usize) -> Vec<f32> {
+        if let Ok(mut cache) = WINDOW_CACHE.lock() {
+            cache.entry((self, len)).or_insert_with(|| self.compute(len)).clone()
+        } else {
+            self.compute(len)
+        }
+    }
+
+    fn compute(self, len: usize) -> Vec<f32> {
+        if len < 2 {
+            return vec![1.0; len];
+        }
+
+        // Generalized cosine window: a0 - a1 cos(x) + a2 cos(2x) - a3 cos(3x)
+        let [a0, a1, a2, a3] = match self {
+            FftWindow::Rectangular => [1.0, 0.0, 0.0, 0.0],
+            FftWindow::Hann => [0.5, 0.5, 0.0, 0.0],
+            FftWindow::Hamming => [0.54, 0.46, 0.0, 0.0],
+            FftWindow::Blackman => [0.42, 0.5, 0.08, 0.0],
+            FftWindow::BlackmanHarris => [0.35875, 0.48829, 0.14128, 0.01168],
+        };
+
+        (0..len)
+            .map(|i| {
+                let x = TAU * i as f32 / (len - 1) as f32;
+                // Rounding can dip Blackman a hair below zero at the ends
+                (a0 - a1 * x.cos() + a2 * (2.0 * x).cos() - a3 * (3.0 * x).cos()).max(0.0)
+            })
+            .collect()
+    }
+}
+
+pub fn apply_window(buffer: &mut [f32], window: FftWindow) {
+    if window == FftWindow::Rectangular {
+        return;
+    }
+
+    let coefficients = window.coefficients(buffer.len());
+    for (sample, coefficient) in buffer.iter_mut().zip(coefficients) {
+        *sample *= coefficient;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [FftWindow; 5] = [
+        FftWindow::Rectangular,
+        FftWindow::Hann,
+        FftWindow::Hamming,
+        FftWindow::Blackman,
+        FftWindow::BlackmanHarris,
+    ];
+
+    #[test]
+    fn rectangular_leaves_the_buffer_alone() {
+        let original: Vec<f32> = (0..64).map(|i| (i as f32 * 0.3).sin()).collect();
+        let mut buffer = original.clone();
+        apply_window(&mut buffer, FftWindow::Rectangular);
+        assert_eq!(buffer, original);
+    }
+
+    #[test]
+    fn hann_is_zero_at_both_ends() {
+        let mut buffer = vec![1.0; 64];
+        apply_window(&mut buffer, FftWindow::Hann);
+        assert!(buffer[0].abs() < 1e-6 && buffer[63].abs() < 1e-6);
+        assert!(buffer[31] > 0.99);
+    }
+
+    #[test]
+    fn windows_have_their_expected_energy() {
+        // Only the rectangular window keeps all N of the energy; Hann keeps 0.375 N,
+        // and in general a cosine window keeps a0^2 + (a1^2 + a2^2 + a3^2) / 2 per point
+        let expected = [1.0, 0.375, 0.3974, 0.3046, 0.2580];
+        let len = 4096;
+        for (window, expected) in ALL.into_iter().zip(expected) {
+            let energy: f32 = window.coefficients(len).iter().map(|c| c * c).sum();
+            let per_point = energy / len as f32;
+            assert!((per_point - expected).abs() < 0.01 * expected, "{window:?}: {per_point}");
+        }
+    }
+}
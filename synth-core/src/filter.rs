@@ -0,0 +1,130 @@
+use core::f32::consts::PI;
+
+/// Normalized resonance at and above which the SVF self-oscillates.
+pub const SELF_OSCILLATION_THRESHOLD: f32 = 0.999;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SvfOutput {
+    pub low: f32,
+    pub band: f32,
+    pub high: f32,
+    pub notch: f32,
+}
+
+/// Chamberlin state variable filter.
+///
+/// `resonance` is normalized to 0.0-1.0. At [`SELF_OSCILLATION_THRESHOLD`] and above the
+/// damping goes slightly negative and the filter rings on its own as a sine oscillator at
+/// `cutoff_hz`, even with zero input. The damping grows back with the band amplitude, so
+/// the ringing settles at a steady level instead of blowing up.
+pub struct StateVariableFilter {
+    sample_rate: u32,
+    cutoff_hz: f32,
+    resonance: f32,
+    low: f32,
+    band: f32,
+}
+
+/// Copies the settings but not the integrator state, so a clone starts silent
+/// instead of ringing on with the original's signal.
+impl Clone for StateVariableFilter {
+    fn clone(&self) -> Self {
+        StateVariableFilter::new(self.sample_rate, self.cutoff_hz, self.resonance)
+    }
+}
+
+impl StateVariableFilter {
+    pub fn new(sample_rate: u32, cutoff_hz: f32, resonance: f32) -> StateVariableFilter {
+        StateVariableFilter {
+            sample_rate,
+            cutoff_hz,
+            resonance: resonance.clamp(0.0, 1.0),
+            low: 0.0,
+            band: 0.0,
+        }
+    }
+
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f32) {
+        self.cutoff_hz = cutoff_hz;
+    }
+
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance.clamp(0.0, 1.0);
+    }
+
+    /// Clears the integrators, stopping any ringing.
+    pub fn reset(&mut self) {
+        self.low = 0.0;
+        self.band = 0.0;
+    }
+
+    pub fn is_self_oscillating(&self) -> bool {
+        self.resonance >= SELF_OSCILLATION_THRESHOLD
+    }
+
+    pub fn process(&mut self, input: f32) -> SvfOutput {
+        // The Chamberlin structure goes unstable above roughly a sixth of the sample rate
+        let cutoff = self.cutoff_hz.clamp(1.0, self.sample_rate as f32 / 6.0);
+        let f = 2.0 * libm::sinf(PI * cutoff / self.sample_rate as f32);
+
+        let self_oscillating = self.is_self_oscillating();
+        let damping = if self_oscillating {
+            // Van der Pol style: negative damping at low levels, positive once the ringing
+            // passes roughly the oscillator's own output level
+            -0.1 + 2.2 * self.band * self.band
+        } else {
+            2.0 * (1.0 - self.resonance)
+        };
+
+        // A silent, lossless loop stays silent forever, so give it a nudge to start ringing
+        if self_oscillating && self.low.abs() + self.band.abs() < 1e-6 {
+            self.band = 0.1;
+        }
+
+        let low = self.low + f * self.band;
+        let high = input - low - damping * self.band;
+        let band = self.band + f * high;
+
+        self.low = low;
+        self.band = band;
+
+        SvfOutput {
+            low,
+            band,
+            high,
+            notch: high + low,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_oscillates_at_the_cutoff_with_no_input() {
+        let (sample_rate, cutoff_hz) = (44100, 1000.0);
+        let mut filter = StateVariableFilter::new(sample_rate, cutoff_hz, 1.0);
+        assert!(filter.is_self_oscillating());
+        let mut low = [0.0; 1000];
+        for sample in low.iter_mut() {
+            *sample = filter.process(0.0).low;
+        }
+
+        let peak = low.iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.1, "peak {peak}");
+        // Upward zero crossings count whole cycles
+        let crossings: usize = low.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count();
+        let measured_hz = crossings as f32 * sample_rate as f32 / low.len() as f32;
+        assert!((measured_hz - cutoff_hz).abs() < 0.1 * cutoff_hz, "{measured_hz} Hz");
+    }
+
+    #[test]
+    fn stays_silent_below_the_threshold() {
+        let mut filter = StateVariableFilter::new(44100, 1000.0, 0.9);
+        assert!(!filter.is_self_oscillating());
+        for _ in 0..1000 {
+            assert_eq!(filter.process(0.0).low, 0.0);
+        }
+    }
+}
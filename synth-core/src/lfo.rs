@@ -0,0 +1,177 @@
+use core::f32::consts::TAU;
+
+/// Which way an [`Lfo`]'s output swings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LfoPolarity {
+    /// -1.0 to 1.0, centred on the parameter's base value.
+    #[default]
+    Bipolar,
+    /// 0.0 to 1.0, rising from the base and never going below it.
+    Unipolar,
+    /// 1.0 to 0.0, the unipolar swing upside down: at the base when the raw sine peaks.
+    InvertedUnipolar,
+}
+
+impl LfoPolarity {
+    /// Maps a raw -1.0-1.0 sine value into this polarity's range.
+    pub fn apply(self, raw: f32) -> f32 {
+        match self {
+            LfoPolarity::Bipolar => raw,
+            LfoPolarity::Unipolar => (raw + 1.0) / 2.0,
+            LfoPolarity::InvertedUnipolar => (1.0 - raw) / 2.0,
+        }
+    }
+}
+
+/// An [`Lfo`]'s waveform.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    /// A new random value each cycle, held until the next: the classic stepped
+    /// generative modulation.
+    SampleAndHold,
+    /// Random values like `SampleAndHold`, but gliding in a straight line from one
+    /// to the next over each cycle.
+    SmoothSampleAndHold,
+}
+
+/// A low-frequency oscillator for modulating parameters, a sine unless `shape` says
+/// otherwise.
+///
+/// [`modulate`](Self::modulate) offsets a base value by `depth` times the output, so a
+/// bipolar LFO on a cutoff sweeps `depth` either side of it and a unipolar one from
+/// the base up to `base + depth`.
+///
+/// The random shapes draw from a small xorshift generator, so copies of an LFO step
+/// through the same values unless given their own [`set_seed`](Self::set_seed).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lfo {
+    pub rate_hz: f32,
+    pub depth: f32,
+    pub polarity: LfoPolarity,
+    pub shape: LfoShape,
+    sample_rate: u32,
+    phase: f32,
+    held_value: f32,
+    next_value: f32,
+    rng_state: u32,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: u32, rate_hz: f32, depth: f32, polarity: LfoPolarity) -> Lfo {
+        let mut lfo = Lfo {
+            rate_hz,
+            depth,
+            polarity,
+            shape: LfoShape::Sine,
+            sample_rate,
+            phase: 0.0,
+            held_value: 0.0,
+            next_value: 0.0,
+            rng_state: 0,
+        };
+        lfo.set_seed(0x9E37_79B9);
+        lfo
+    }
+
+    /// Restarts the random sequence from `seed`.
+    pub fn set_seed(&mut self, seed: u32) {
+        // Xorshift never leaves zero
+        self.rng_state = seed.max(1);
+        self.held_value = self.next_random();
+        self.next_value = self.next_random();
+    }
+
+    /// Uniform in -1.0-1.0.
+    fn next_random(&mut self) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+        (x >> 8) as f32 / (1 << 23) as f32 - 1.0
+    }
+
+    /// Position in the cycle, 0.0-1.0; 0.0 is the raw sine's upward zero crossing.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase - libm::floorf(phase);
+    }
+
+    /// The output at the current phase, then advances one sample.
+    pub fn tick(&mut self) -> f32 {
+        let raw = match self.shape {
+            LfoShape::Sine => libm::sinf(TAU * self.phase),
+            LfoShape::SampleAndHold => self.held_value,
+            LfoShape::SmoothSampleAndHold => self.held_value + (self.next_value - self.held_value) * self.phase,
+        };
+        let step = self.rate_hz / self.sample_rate.max(1) as f32;
+        if self.phase + step >= 1.0 {
+            self.held_value = self.next_value;
+            self.next_value = self.next_random();
+        }
+        self.set_phase(self.phase + step);
+        self.polarity.apply(raw)
+    }
+
+    /// `base` moved by this sample's output times `depth`.
+    pub fn modulate(&mut self, base: f32) -> f32 {
+        base + self.depth * self.tick()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of an LFO of `polarity` with the raw sine at `phase`.
+    fn tick_at(polarity: LfoPolarity, phase: f32) -> f32 {
+        let mut lfo = Lfo::new(1000, 1.0, 1.0, polarity);
+        lfo.set_phase(phase);
+        lfo.tick()
+    }
+
+    fn assert_outputs(polarity: LfoPolarity, [peak, zero, trough]: [f32; 3]) {
+        for (phase, expected) in [(0.25, peak), (0.0, zero), (0.75, trough)] {
+            let value = tick_at(polarity, phase);
+            assert!((value - expected).abs() < 1e-5, "{polarity:?} at phase {phase}: {value}");
+        }
+    }
+
+    #[test]
+    fn bipolar_swings_either_side_of_zero() {
+        assert_outputs(LfoPolarity::Bipolar, [1.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn unipolar_rises_from_zero() {
+        assert_outputs(LfoPolarity::Unipolar, [1.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn inverted_unipolar_falls_from_one() {
+        assert_outputs(LfoPolarity::InvertedUnipolar, [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn unipolar_cutoff_never_drops_below_the_base() {
+        let mut unipolar = Lfo::new(1000, 5.0, 500.0, LfoPolarity::Unipolar);
+        let mut bipolar = Lfo::new(1000, 5.0, 500.0, LfoPolarity::Bipolar);
+        let (mut low, mut high) = (f32::MAX, f32::MIN);
+        let (mut bipolar_low, mut bipolar_high) = (f32::MAX, f32::MIN);
+        for _ in 0..1000 {
+            let cutoff = unipolar.modulate(1000.0);
+            low = low.min(cutoff);
+            high = high.max(cutoff);
+            let cutoff = bipolar.modulate(1000.0);
+            bipolar_low = bipolar_low.min(cutoff);
+            bipolar_high = bipolar_high.max(cutoff);
+        }
+        assert!(low >= 1000.0 && high > 1499.0 && high <= 1500.0, "{low}..{high}");
+        assert!(bipolar_low < 501.0 && bipolar_high > 1499.0, "{bipolar_low}..{bipolar_high}");
+    }
+}
@@ -0,0 +1,27 @@
+//! The synth's DSP kernels without the standard library, for embedded and
+//! WebAssembly builds. Controls, audio output and I/O live in the `exposrog` crate,
+//! which wraps these types.
+//!
+//! Everything here works on borrowed or generic buffers; with the `alloc` feature, on
+//! by default, those can be `Vec`s or shared `Arc`s as well as slices and arrays. The
+//! `std` feature adds `CoreSource`, which plays a [`WaveTableCore`] through rodio.
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod filter;
+mod lfo;
+mod oscillator;
+mod smoother;
+#[cfg(feature = "std")]
+mod source;
+
+pub use filter::{StateVariableFilter, SvfOutput, SELF_OSCILLATION_THRESHOLD};
+pub use lfo::{Lfo, LfoPolarity, LfoShape};
+pub use oscillator::{smoothing_coeff, InterpolationMode, WaveTableCore};
+pub use smoother::ParameterSmoother;
+#[cfg(feature = "std")]
+pub use source::CoreSource;
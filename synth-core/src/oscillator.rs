@@ -0,0 +1,287 @@
+use crate::smoother::ParameterSmoother;
+use core::f32::consts::PI;
+use core::fmt;
+
+/// One-pole coefficient for a smoother with its corner at `smoothing_hz`.
+pub fn smoothing_coeff(smoothing_hz: f32, sample_rate: u32) -> f32 {
+    1.0 - libm::expf(-2.0 * PI * smoothing_hz / sample_rate as f32)
+}
+
+/// How a [`WaveTableCore`] reads between table samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InterpolationMode {
+    /// A straight line between the two nearest samples.
+    #[default]
+    Lerp,
+    /// A Catmull-Rom spline through the four nearest samples. Smoother, so small
+    /// tables played high add fewer spurious harmonics, for about twice the work.
+    CubicLerp,
+}
+
+impl InterpolationMode {
+    pub const ALL: [InterpolationMode; 2] = [InterpolationMode::Lerp, InterpolationMode::CubicLerp];
+}
+
+impl fmt::Display for InterpolationMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpolationMode::Lerp => write!(f, "linear"),
+            InterpolationMode::CubicLerp => write!(f, "cubic"),
+        }
+    }
+}
+
+/// The table lookup, phase and pitch smoothing behind a wave table oscillator, with
+/// optional sub-oscillator reading the same table at a lower speed.
+///
+/// `T` is anything holding the samples of one cycle: a `&'static [f32]` or array on
+/// a microcontroller, or with the `alloc` feature a `Vec<f32>` or `Arc<[f32]>`.
+/// Frequencies are set directly rather than read from a shared control, and output
+/// is at full scale.
+#[derive(Clone, Debug)]
+pub struct WaveTableCore<T: AsRef<[f32]>> {
+    sample_rate: u32,
+    wave_table: T,
+    index: f32,
+    sub_index: f32,
+    increment: ParameterSmoother,
+    interpolation: InterpolationMode,
+}
+
+impl<T: AsRef<[f32]>> WaveTableCore<T> {
+    pub fn new(sample_rate: u32, wave_table: T, smoothing_hz: f32) -> WaveTableCore<T> {
+        WaveTableCore {
+            sample_rate,
+            wave_table,
+            index: 0.0,
+            sub_index: 0.0,
+            increment: ParameterSmoother::new(0.0, smoothing_hz, sample_rate),
+            interpolation: InterpolationMode::Lerp,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Swaps in a new table, keeping the phase at the same point of the cycle.
+    pub fn set_wave_table(&mut self, wave_table: T) {
+        let phase = self.phase();
+        let ratio = wave_table.as_ref().len() as f32 / self.len();
+        let target = self.increment.target() * ratio;
+        self.increment.reset(self.increment.current() * ratio);
+        self.increment.set_target(target);
+        self.wave_table = wave_table;
+        self.set_phase(phase);
+    }
+
+    pub fn wave_table(&self) -> &[f32] {
+        self.wave_table.as_ref()
+    }
+
+    /// The table as it was handed in, rather than as a slice.
+    pub fn table(&self) -> &T {
+        &self.wave_table
+    }
+
+    /// The table in place; writes take effect from the next sample.
+    pub fn wave_table_mut(&mut self) -> &mut [f32]
+    where
+        T: AsMut<[f32]>,
+    {
+        self.wave_table.as_mut()
+    }
+
+    fn len(&self) -> f32 {
+        self.wave_table.as_ref().len() as f32
+    }
+
+    /// Read position in the table, in samples.
+    pub fn index(&self) -> f32 {
+        self.index
+    }
+
+    /// Table samples advanced per output sample right now, smoothing included.
+    pub fn increment(&self) -> f32 {
+        self.increment.current()
+    }
+
+    /// Puts the phase and a settled increment back, e.g. from a saved snapshot.
+    pub fn restore(&mut self, index: f32, increment: f32) {
+        self.index = index;
+        self.increment.reset(increment);
+    }
+
+    pub fn interpolation(&self) -> InterpolationMode {
+        self.interpolation
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: InterpolationMode) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn set_smoothing_hz(&mut self, smoothing_hz: f32) {
+        self.increment.set_cutoff_hz(smoothing_hz, self.sample_rate);
+    }
+
+    /// Moves the pitch toward `freq_hz`; the smoother gets there over a few
+    /// milliseconds of [`next_sample`](Self::next_sample) calls.
+    pub fn set_frequency(&mut self, freq_hz: f32) {
+        self.increment.set_target(freq_hz * self.len() / self.sample_rate as f32);
+    }
+
+    /// Jumps the smoother straight to the target pitch.
+    pub fn settle(&mut self) {
+        self.increment.settle();
+    }
+
+    /// Position in the cycle, 0.0-1.0.
+    pub fn phase(&self) -> f32 {
+        self.index / self.len()
+    }
+
+    /// Jumps to `phase_normalized` (0.0-1.0) of the way through the cycle, bringing the
+    /// sub-oscillator along.
+    pub fn set_phase(&mut self, phase_normalized: f32) {
+        let len = self.len();
+        self.index = (phase_normalized - libm::floorf(phase_normalized)) * len;
+        self.index %= len;
+        self.sub_index = self.index;
+    }
+
+    /// The next sample, mixed with a sub-oscillator `sub_ratio` times lower by
+    /// `sub_mix` when `sub` is `Some((sub_ratio, sub_mix))`.
+    pub fn next_sample(&mut self, sub: Option<(f32, f32)>) -> f32 {
+        self.next_sample_phase_shifted(sub, 0.0)
+    }
+
+    /// [`next_sample`](Self::next_sample) read `phase_offset` cycles away from the
+    /// accumulated phase, which carries on unshifted: phase modulation.
+    pub fn next_sample_phase_shifted(&mut self, sub: Option<(f32, f32)>, phase_offset: f32) -> f32 {
+        // Starting from or stopping to silence jumps straight there; only pitch
+        // changes between sounding notes are smoothed
+        if self.increment.current() == 0.0 || self.increment.target() == 0.0 {
+            self.increment.settle();
+        } else {
+            self.increment.tick();
+        }
+
+        if self.increment.current() == 0.0 {
+            return 0.0;
+        }
+
+        let len = self.len();
+        let read_index = if phase_offset == 0.0 {
+            self.index
+        } else {
+            let shifted = self.index + phase_offset * len;
+            (shifted - libm::floorf(shifted / len) * len) % len
+        };
+        let mut sample = self.sample_at(read_index);
+        self.index += self.increment.current();
+        self.index %= len;
+
+        if let Some((ratio, mix)) = sub {
+            let sub_sample = self.sample_at(self.sub_index);
+            self.sub_index += self.increment.current() / ratio;
+            self.sub_index %= len;
+            sample = sample * (1.0 - mix) + sub_sample * mix;
+        }
+
+        sample
+    }
+
+    /// Advances by `n` samples without rendering them, treating the smoother as
+    /// settled. `sub_ratio` moves the sub-oscillator too.
+    pub fn skip(&mut self, n: usize, sub_ratio: Option<f32>) {
+        self.settle();
+        if self.increment.current() == 0.0 {
+            return;
+        }
+
+        // Accumulate in f64 so large skips don't lose the fractional phase
+        let len = self.len() as f64;
+        let advance = self.increment.current() as f64 * n as f64;
+        self.index = ((self.index as f64 + advance) % len) as f32;
+        if let Some(ratio) = sub_ratio {
+            self.sub_index = ((self.sub_index as f64 + advance / ratio as f64) % len) as f32;
+        }
+    }
+
+    fn sample_at(&self, index: f32) -> f32 {
+        match self.interpolation {
+            InterpolationMode::Lerp => self.lerp_at(index),
+            InterpolationMode::CubicLerp => self.cubic_lerp_at(index),
+        }
+    }
+
+    fn lerp_at(&self, index: f32) -> f32 {
+        let table = self.wave_table.as_ref();
+        let truncated_index = index as usize;
+        let next_index = (truncated_index + 1) % table.len();
+
+        let next_index_weight = index - truncated_index as f32;
+        let truncated_index_weight = 1.0 - next_index_weight;
+
+        truncated_index_weight * table[truncated_index] + next_index_weight * table[next_index]
+    }
+
+    /// Catmull-Rom through the samples either side of `index`, wrapping round the
+    /// table at both ends.
+    fn cubic_lerp_at(&self, index: f32) -> f32 {
+        let table = self.wave_table.as_ref();
+        let len = table.len();
+        let n = index as usize;
+        let x = index - n as f32;
+        let y0 = table[(n + len - 1) % len];
+        let y1 = table[n % len];
+        let y2 = table[(n + 1) % len];
+        let y3 = table[(n + 2) % len];
+
+        let c1 = 0.5 * (y2 - y0);
+        let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+        let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+        ((c3 * x + c2) * x + c1) * x + y1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const TABLE: [f32; 4] = [0.0, 1.0, 0.25, -0.5];
+
+    fn core() -> WaveTableCore<[f32; 4]> {
+        WaveTableCore::new(44100, TABLE, 1000.0)
+    }
+
+    #[test]
+    fn lerp_at_integer_index_is_the_table_value() {
+        let core = core();
+        for (i, sample) in TABLE.iter().enumerate() {
+            assert_eq!(core.lerp_at(i as f32), *sample);
+        }
+    }
+
+    #[test]
+    fn lerp_halfway_averages_the_neighbours() {
+        assert_eq!(core().lerp_at(0.5), (TABLE[0] + TABLE[1]) / 2.0);
+    }
+
+    #[test]
+    fn lerp_past_the_last_sample_wraps_to_the_first() {
+        let index = TABLE.len() as f32 - 0.5;
+        assert_eq!(core().lerp_at(index), (TABLE[3] + TABLE[0]) / 2.0);
+    }
+
+    proptest! {
+        #[test]
+        fn lerp_stays_between_its_neighbours(index in 0.0f32..TABLE.len() as f32) {
+            let i = index as usize;
+            let (a, b) = (TABLE[i], TABLE[(i + 1) % TABLE.len()]);
+            let sample = core().lerp_at(index);
+            prop_assert!(sample >= a.min(b) - 1e-6 && sample <= a.max(b) + 1e-6);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::oscillator::smoothing_coeff;
+
+/// One-pole smoothing for a real-time parameter, so a control that jumps reaches the
+/// audio as a short glide instead of a click or zipper noise.
+///
+/// Each [`tick`](Self::tick) moves `current` a fixed fraction `coeff` of the way to
+/// `target`, which is `1 - exp(-2π · cutoff_hz / sample_rate)` for a given corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterSmoother {
+    target: f32,
+    current: f32,
+    coeff: f32,
+}
+
+impl ParameterSmoother {
+    /// A smoother resting at `value`, with its corner at `cutoff_hz`.
+    pub fn new(value: f32, cutoff_hz: f32, sample_rate: u32) -> ParameterSmoother {
+        ParameterSmoother {
+            target: value,
+            current: value,
+            coeff: smoothing_coeff(cutoff_hz, sample_rate),
+        }
+    }
+
+    /// A smoother resting at 0.0 whose time constant is `transition_ms`: after that
+    /// long it has covered about 63% of a jump, and all but 1% by five times it.
+    pub fn new_with_time(transition_ms: f32, sample_rate: u32) -> ParameterSmoother {
+        let samples = transition_ms / 1000.0 * sample_rate as f32;
+        ParameterSmoother {
+            target: 0.0,
+            current: 0.0,
+            coeff: if samples > 0.0 { 1.0 - libm::expf(-1.0 / samples) } else { 1.0 },
+        }
+    }
+
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f32, sample_rate: u32) {
+        self.coeff = smoothing_coeff(cutoff_hz, sample_rate);
+    }
+
+    pub fn set_target(&mut self, value: f32) {
+        self.target = value;
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Advances one sample and returns the smoothed value.
+    pub fn tick(&mut self) -> f32 {
+        self.current += (self.target - self.current) * self.coeff;
+        self.current
+    }
+
+    /// Jumps straight to the target.
+    pub fn settle(&mut self) {
+        self.current = self.target;
+    }
+
+    /// Jumps both the target and the current value to `value`.
+    pub fn reset(&mut self, value: f32) {
+        self.target = value;
+        self.current = value;
+    }
+}
@@ -0,0 +1,55 @@
+use crate::oscillator::WaveTableCore;
+use rodio::Source;
+
+/// A [`WaveTableCore`] played through rodio, with no sub-oscillator.
+///
+/// Its pitch is whatever was last set through [`core_mut`](Self::core_mut), so the
+/// owner keeps it in tune. A wrapper rather than impls on the core itself, whose own
+/// `skip` would otherwise lose out to `Iterator::skip`.
+pub struct CoreSource<T: AsRef<[f32]>> {
+    core: WaveTableCore<T>,
+}
+
+impl<T: AsRef<[f32]>> CoreSource<T> {
+    pub fn new(core: WaveTableCore<T>) -> CoreSource<T> {
+        CoreSource { core }
+    }
+
+    pub fn core(&self) -> &WaveTableCore<T> {
+        &self.core
+    }
+
+    pub fn core_mut(&mut self) -> &mut WaveTableCore<T> {
+        &mut self.core
+    }
+
+    pub fn into_inner(self) -> WaveTableCore<T> {
+        self.core
+    }
+}
+
+impl<T: AsRef<[f32]>> Source for CoreSource<T> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.core.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+impl<T: AsRef<[f32]>> Iterator for CoreSource<T> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.core.next_sample(None))
+    }
+}
@@ -0,0 +1,18 @@
+//! Runs against the crate built without `std`, e.g.
+//! `cargo test -p synth-core --no-default-features`.
+#![cfg(not(feature = "std"))]
+
+use synth_core::WaveTableCore;
+
+static TABLE: [f32; 4] = [0.0, 1.0, 0.0, -1.0];
+
+#[test]
+fn plays_a_static_table() {
+    let table: &'static [f32] = &TABLE;
+    let mut core = WaveTableCore::new(8, table, 1000.0);
+    core.set_frequency(2.0);
+    core.settle();
+    for i in 0..100 {
+        assert_eq!(core.next_sample(None), TABLE[i % TABLE.len()]);
+    }
+}
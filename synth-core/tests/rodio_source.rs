@@ -0,0 +1,19 @@
+//! Needs the `std` feature: `cargo test -p synth-core --features std`.
+use rodio::Source;
+use synth_core::{CoreSource, WaveTableCore};
+
+static TABLE: [f32; 4] = [0.0, 1.0, 0.0, -1.0];
+
+#[test]
+fn core_source_plays_through_rodio() {
+    let mut source = CoreSource::new(WaveTableCore::new(8, &TABLE[..], 1000.0));
+    source.core_mut().set_frequency(2.0);
+    source.core_mut().settle();
+    assert_eq!(source.channels(), 1);
+    assert_eq!(source.sample_rate(), 8);
+    assert_eq!(source.total_duration(), None);
+
+    // Through one of rodio's own adapters, which only sees a Source
+    let samples: Vec<f32> = source.amplify(0.5).take(8).collect();
+    assert_eq!(samples, [0.0, 0.5, 0.0, -0.5, 0.0, 0.5, 0.0, -0.5]);
+}